@@ -1,14 +1,20 @@
 //! Collecting chunks during PDF creation.
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
 
-use pdf_writer::{Chunk, Finish, Name, Pdf, Ref, Str, TextStr};
+use base64::Engine;
+use pdf_writer::{Chunk, Finish, Name, Ref, Str, TextStr};
 use xmp_writer::{RenditionClass, XmpWriter};
 
-use crate::error::KrillaResult;
-use crate::metadata::Metadata;
+#[cfg(feature = "encryption")]
+use crate::encryption::EncryptionAlgorithm;
+use crate::error::{KrillaError, KrillaResult};
+use crate::metadata::{Metadata, Trapped, XmpHook};
+use crate::object::form::FieldFont;
+use crate::output::PdfOutput;
 use crate::serialize::SerializeContext;
-use crate::util::{hash_base64, Deferred};
+use crate::util::{hash_base64, Deferred, NameExt, SipHashable};
 use crate::validation::ValidationError;
 use crate::version::PdfVersion;
 
@@ -45,6 +51,7 @@ pub(crate) struct ChunkContainer {
     pub(crate) page_label_tree: Option<(Ref, Chunk)>,
     pub(crate) page_tree: Option<(Ref, Chunk)>,
     pub(crate) outline: Option<(Ref, Chunk)>,
+    pub(crate) open_action: Option<(Ref, Chunk)>,
     pub(crate) destination_profiles: Option<(Ref, Chunk)>,
     pub(crate) struct_tree_root: Option<(Ref, Chunk)>,
 
@@ -62,8 +69,17 @@ pub(crate) struct ChunkContainer {
     pub(crate) patterns: Vec<Chunk>,
     pub(crate) pages: Vec<Deferred<Chunk>>,
     pub(crate) images: Vec<Deferred<KrillaResult<Chunk>>>,
+    pub(crate) raw_objects: Vec<Chunk>,
+    pub(crate) layers: Vec<Chunk>,
 
     pub(crate) metadata: Option<Metadata>,
+    pub(crate) xmp_hook: Option<XmpHook>,
+    pub(crate) catalog_entries: Vec<(String, Ref)>,
+    pub(crate) need_appearances: bool,
+    pub(crate) form_fields: Vec<Ref>,
+    pub(crate) form_fonts: BTreeSet<FieldFont>,
+    /// The reference of each layer, as well as whether it's visible by default.
+    pub(crate) layer_states: Vec<(Ref, bool)>,
 }
 
 impl ChunkContainer {
@@ -72,24 +88,48 @@ impl ChunkContainer {
     }
 
     // TODO: Split up into multiple methods?
-    pub(crate) fn finish(mut self, sc: &mut SerializeContext) -> KrillaResult<Pdf> {
-        let mut remapped_ref = Ref::new(1);
+    pub(crate) fn finish<W: Write>(
+        mut self,
+        sc: &mut SerializeContext,
+        sink: W,
+        streamed: bool,
+    ) -> KrillaResult<PdfOutput<W>> {
+        // Object numbers reserved by the user come first, so that they remain stable.
+        let mut remapped_ref = Ref::new(1 + sc.reserved_refs());
         let mut remapper = HashMap::new();
 
+        // Objects of duplicate chunks, mapped to the corresponding object they duplicate.
+        // Duplicate chunks are not written at all.
+        let duplicates = if sc.serialize_settings().deduplicate_streams {
+            let mut images = vec![];
+            for chunk in &self.images {
+                images.push(chunk.wait().res()?);
+            }
+
+            find_duplicates(&[
+                self.color_spaces.iter().collect(),
+                self.icc_profiles.iter().collect(),
+                self.ext_g_states.iter().collect(),
+                self.masks.iter().collect(),
+                self.x_objects.iter().collect(),
+                self.shading_functions.iter().collect(),
+                self.patterns.iter().collect(),
+                images,
+            ])
+        } else {
+            HashMap::new()
+        };
+
         // Two utility macros, that basically traverses the fields in the order that we
         // will write them to the PDF and assigns new references as we go.
         // This gives us the advantage that the PDF will be numbered with
         // monotonically increasing numbers, which, while it is not a strict requirement
         // for a valid PDF, makes it a lot cleaner and might make implementing features
         // like object streams easier down the road.
-        //
-        // It also allows us to estimate the capacity we will need for the new PDF.
-        let mut chunks_len = 0;
         macro_rules! remap_field {
             ($remapper:expr, $remapped_ref:expr; $($field:expr),+) => {
                 $(
                     if let Some((original_ref, chunk)) = $field {
-                        chunks_len += chunk.len();
                         for object_ref in chunk.refs() {
                             debug_assert!(!remapper.contains_key(&object_ref));
 
@@ -107,8 +147,11 @@ impl ChunkContainer {
                 $(
                     for chunk in $field {
                         let chunk = chunk.wait().res()?;
-                        chunks_len += chunk.len();
                         for ref_ in chunk.refs() {
+                            if duplicates.contains_key(&ref_) {
+                                continue;
+                            }
+
                             debug_assert!(!remapper.contains_key(&ref_));
 
                             $remapper.insert(ref_, $remapped_ref.bump());
@@ -118,71 +161,91 @@ impl ChunkContainer {
             };
         }
 
-        // Chunk length is not an exact number because the length might change as we renumber,
-        // so we add a bit of a padding by multiplying with 1.1. The 200 is additional padding
-        // for the document catalog. This hopefully allows us to avoid re-alloactions in the general
-        // case, and thus give us better performance.
-        let mut pdf = Pdf::with_capacity((chunks_len as f32 * 1.1 + 200.0) as usize);
-        sc.serialize_settings().pdf_version.set_version(&mut pdf);
-
-        if sc.serialize_settings().ascii_compatible
-            && !sc.serialize_settings().validator.requires_binary_header()
-        {
-            pdf.set_binary_marker(b"AAAA")
-        }
-
-        remap_field!(remapper, remapped_ref; &mut self.page_tree, &mut self.outline,
+        remap_field!(remapper, remapped_ref; &mut self.page_tree, &mut self.outline, &mut self.open_action,
             &mut self.page_label_tree, &mut self.destination_profiles,
         &mut self.struct_tree_root);
         remap_fields!(remapper, remapped_ref; &self.struct_elements, &self.page_labels,
             &self.annotations, &self.fonts, &self.color_spaces, &self.icc_profiles, &self.destinations,
             &self.ext_g_states, &self.masks, &self.x_objects, &self.shading_functions,
-            &self.patterns, &self.pages, &self.images
+            &self.patterns, &self.pages, &self.images, &self.layers, &self.raw_objects
         );
 
+        for duplicate in duplicates.keys() {
+            let original = remapper[&resolve_duplicate(&duplicates, *duplicate)];
+            remapper.insert(*duplicate, original);
+        }
+
+        // The output might be streamed to a writer, so we need to make sure that the
+        // document can be written before writing the first byte.
+        self.preflight(sc, &remapper)?;
+
+        let mut output = PdfOutput::new(
+            sink,
+            sc.serialize_settings().pdf_version,
+            sc.serialize_settings().ascii_compatible
+                && !sc.serialize_settings().validator.requires_binary_header(),
+            streamed,
+        )?;
+
         macro_rules! write_field {
-            ($remapper:expr, $pdf:expr; $($field:expr),+) => {
+            ($remapper:expr, $output:expr; $($field:expr),+) => {
                 $(
                     if let Some((_, chunk)) = $field {
-                        chunk.renumber_into($pdf, |old| *$remapper.get(&old).unwrap());
+                        $output.write_chunk(chunk, |old| *$remapper.get(&old).unwrap())?;
                     }
                 )+
             };
         }
 
+        // Each chunk is dropped as soon as it has been written, so that we don't
+        // hold on to data that already is in the output.
         macro_rules! write_fields {
-            ($remapper:expr, $pdf:expr; $($field:expr),+) => {
+            ($remapper:expr, $output:expr; $($field:expr),+) => {
                 $(
-                    for chunk in $field {
+                    for chunk in std::mem::take($field) {
                         let chunk = chunk.wait().res()?;
-                        chunk.renumber_into($pdf, |old| *$remapper.get(&old).unwrap());
+
+                        if chunk.refs().next().is_some_and(|r| duplicates.contains_key(&r)) {
+                            continue;
+                        }
+
+                        $output.write_chunk(chunk, |old| *$remapper.get(&old).unwrap())?;
                     }
                 )+
             };
         }
 
-        write_field!(remapper, &mut pdf; &self.page_tree, &self.outline,
+        let num_pages = self.pages.len();
+
+        write_field!(remapper, output; &self.page_tree, &self.outline, &self.open_action,
             &self.page_label_tree, &self.destination_profiles,
         &mut self.struct_tree_root);
-        write_fields!(remapper, &mut pdf; &self.struct_elements, &self.page_labels,
-            &self.annotations, &self.fonts, &self.color_spaces, &self.icc_profiles, &self.destinations,
-            &self.ext_g_states, &self.masks, &self.x_objects,
-            &self.shading_functions, &self.patterns, &self.pages, &self.images
+        write_fields!(remapper, output; &mut self.struct_elements, &mut self.page_labels,
+            &mut self.annotations, &mut self.fonts, &mut self.color_spaces, &mut self.icc_profiles,
+            &mut self.destinations, &mut self.ext_g_states, &mut self.masks, &mut self.x_objects,
+            &mut self.shading_functions, &mut self.patterns, &mut self.pages, &mut self.images,
+            &mut self.layers
         );
 
-        // TODO: Replace with `is_none_or` once MSRV allows to.
-        let missing_title = match self.metadata.as_ref() {
-            None => true,
-            Some(m) => m.title.is_none(),
-        };
+        // Objects that were reserved by the user keep their number. References of raw
+        // objects have been checked already.
+        let reserved_refs = sc.reserved_refs();
+        for chunk in std::mem::take(&mut self.raw_objects) {
+            output.write_chunk(&chunk, |old| {
+                if old.get() <= reserved_refs {
+                    return old;
+                }
 
-        if missing_title {
-            sc.register_validation_error(ValidationError::NoDocumentTitle);
+                remapper.get(&old).copied().unwrap_or(old)
+            })?;
         }
 
         // Write the PDF document info metadata.
-        if let Some(metadata) = &self.metadata {
-            metadata.serialize_document_info(&mut remapped_ref, &mut pdf);
+        if let Some(metadata) = self.metadata.as_ref().filter(|m| m.has_document_info()) {
+            let validator = sc.serialize_settings().validator;
+            output.write_document_info(remapped_ref.bump(), |document_info| {
+                metadata.serialize_document_info(document_info, validator)
+            })?;
         }
 
         let mut xmp = XmpWriter::new();
@@ -190,11 +253,19 @@ impl ChunkContainer {
             metadata.serialize_xmp_metadata(&mut xmp);
         }
 
+        // Run the hook before writing any of the properties that are required for
+        // conformance, so that they always reflect the actual document.
+        if let Some(xmp_hook) = &self.xmp_hook {
+            xmp_hook(&mut xmp);
+        }
+
         sc.serialize_settings().validator.write_xmp(&mut xmp);
 
-        let instance_id = hash_base64(pdf.as_bytes());
+        let instance_id = output.instance_id();
 
-        let document_id = if let Some(metadata) = &self.metadata {
+        let document_id = if let Some(document_id) = sc.serialize_settings().document_id {
+            base64::engine::general_purpose::STANDARD.encode(document_id)
+        } else if let Some(metadata) = &self.metadata {
             if let Some(document_id) = &metadata.document_id {
                 hash_base64(&(sc.serialize_settings().pdf_version.as_str(), document_id))
             } else if metadata.title.is_some() && metadata.authors.is_some() {
@@ -210,33 +281,25 @@ impl ChunkContainer {
             instance_id.clone()
         };
 
-        xmp.num_pages(self.pages.len() as u32);
+        xmp.num_pages(num_pages as u32);
         xmp.format("application/pdf");
         xmp.instance_id(&instance_id);
         xmp.document_id(&document_id);
-        pdf.set_file_id((
-            document_id.as_bytes().to_vec(),
-            instance_id.as_bytes().to_vec(),
-        ));
+        output.set_file_id((document_id, instance_id));
 
         xmp.rendition_class(RenditionClass::Proof);
         sc.serialize_settings().pdf_version.write_xmp(&mut xmp);
 
         let named_destinations = sc.global_objects.named_destinations.take();
 
-        // We only write a catalog if a page tree exists. Every valid PDF must have one
-        // and krilla ensures that there always is one, but for snapshot tests, it can be
-        // useful to not write a document catalog if we don't actually need it for the test.
-        if self.page_tree.is_some()
-            || self.outline.is_some()
-            || self.page_label_tree.is_some()
-            || self.destination_profiles.is_some()
-            || self.struct_tree_root.is_some()
-        {
+        if self.has_catalog() {
+            let mut chunk = Chunk::new();
+
             let meta_ref = if sc.serialize_settings().xmp_metadata {
                 let meta_ref = remapped_ref.bump();
                 let xmp_buf = xmp.finish(None);
-                pdf.stream(meta_ref, xmp_buf.as_bytes())
+                chunk
+                    .stream(meta_ref, xmp_buf.as_bytes())
                     .pair(Name(b"Type"), Name(b"Metadata"))
                     .pair(Name(b"Subtype"), Name(b"XML"));
                 Some(meta_ref)
@@ -244,75 +307,399 @@ impl ChunkContainer {
                 None
             };
 
-            let catalog_ref = remapped_ref.bump();
+            output.write_renumbered(&chunk)?;
 
-            let mut catalog = pdf.catalog(catalog_ref);
+            output.write_catalog(remapped_ref.bump(), |mut catalog| {
+                if let Some(pt) = &self.page_tree {
+                    catalog.pages(pt.0);
+                }
 
-            if let Some(pt) = &self.page_tree {
-                catalog.pages(pt.0);
-            }
+                if let Some(meta_ref) = meta_ref {
+                    catalog.metadata(meta_ref);
+                }
 
-            if let Some(meta_ref) = meta_ref {
-                catalog.metadata(meta_ref);
-            }
+                // AES-256 encryption is only part of PDF 2.0, so for PDF 1.7 we need
+                // to declare the Adobe extension that introduced it.
+                #[cfg(feature = "encryption")]
+                let uses_aes256 = sc
+                    .serialize_settings()
+                    .encryption
+                    .as_ref()
+                    .is_some_and(|e| e.algorithm == EncryptionAlgorithm::Aes256);
+                #[cfg(not(feature = "encryption"))]
+                let uses_aes256 = false;
+
+                if uses_aes256 {
+                    let mut extensions = catalog.insert(Name(b"Extensions")).dict();
+                    let mut adbe = extensions.insert(Name(b"ADBE")).dict();
+                    adbe.pair(Name(b"BaseVersion"), Name(b"1.7"));
+                    adbe.pair(Name(b"ExtensionLevel"), 3);
+                    adbe.finish();
+                    extensions.finish();
+                }
 
-            if let Some(pl) = &self.page_label_tree {
-                catalog.pair(Name(b"PageLabels"), pl.0);
-            }
+                if let Some(pl) = &self.page_label_tree {
+                    catalog.pair(Name(b"PageLabels"), pl.0);
+                }
+
+                if let Some(oi) = &self.destination_profiles {
+                    catalog.pair(Name(b"OutputIntents"), oi.0);
+                }
+
+                if let Some(lang) = self.metadata.and_then(|m| m.language).as_ref() {
+                    catalog.lang(TextStr(lang));
+                }
+
+                if let Some(st) = &self.struct_tree_root {
+                    catalog.pair(Name(b"StructTreeRoot"), st.0);
+                    let mut mark_info = catalog.mark_info();
+                    mark_info.marked(true);
+                    if sc.serialize_settings().pdf_version >= PdfVersion::Pdf16 {
+                        // We always set suspects to false because it's required by PDF/UA
+                        mark_info.suspects(false);
+                    }
+                    mark_info.finish();
+                }
+
+                if sc
+                    .serialize_settings()
+                    .validator
+                    .requires_display_doc_title()
+                {
+                    catalog.viewer_preferences().display_doc_title(true);
+                }
+
+                if let Some(ol) = &self.outline {
+                    catalog.outlines(ol.0);
+                }
+
+                if let Some(oa) = &self.open_action {
+                    catalog.pair(Name(b"OpenAction"), oa.0);
+                }
+
+                // Free text annotations can reference fonts of the interactive form
+                // even if there are no form fields.
+                if self.need_appearances
+                    || !self.form_fields.is_empty()
+                    || !self.form_fonts.is_empty()
+                {
+                    let mut acro_form = catalog.insert(Name(b"AcroForm")).dict();
+                    acro_form
+                        .insert(Name(b"Fields"))
+                        .array()
+                        .items(self.form_fields.iter().map(|r| *remapper.get(r).unwrap()));
+
+                    if self.need_appearances {
+                        acro_form.pair(Name(b"NeedAppearances"), true);
+                    }
+
+                    if !self.form_fonts.is_empty() {
+                        let mut resources = acro_form.insert(Name(b"DR")).dict();
+                        let mut fonts = resources.insert(Name(b"Font")).dict();
+
+                        for font in &self.form_fonts {
+                            let mut font_dict =
+                                fonts.insert(Name(font.resource_name().as_bytes())).dict();
+                            font_dict.pair(Name(b"Type"), Name(b"Font"));
+                            font_dict.pair(Name(b"Subtype"), Name(b"Type1"));
+                            font_dict.pair(Name(b"BaseFont"), font.base_font());
+                            font_dict.pair(Name(b"Encoding"), Name(b"WinAnsiEncoding"));
+                            font_dict.finish();
+                        }
+
+                        fonts.finish();
+                        resources.finish();
+                    }
+
+                    acro_form.finish();
+                }
+
+                if !self.layer_states.is_empty() {
+                    let (layer_states, remapper) = (&self.layer_states, &remapper);
+                    let layers = move |visible: Option<bool>| {
+                        layer_states
+                            .iter()
+                            .filter(move |(_, v)| visible.map_or(true, |visible| *v == visible))
+                            .map(move |(r, _)| *remapper.get(r).unwrap())
+                    };
+
+                    let mut oc_properties = catalog.insert(Name(b"OCProperties")).dict();
+                    oc_properties
+                        .insert(Name(b"OCGs"))
+                        .array()
+                        .items(layers(None));
+                    let mut config = oc_properties.insert(Name(b"D")).dict();
+                    // PDF/A2 and PDF/UA require each configuration to have a name.
+                    config.pair(Name(b"Name"), TextStr("Default"));
+                    config.insert(Name(b"Order")).array().items(layers(None));
+                    config.insert(Name(b"ON")).array().items(layers(Some(true)));
+                    config
+                        .insert(Name(b"OFF"))
+                        .array()
+                        .items(layers(Some(false)));
+                    config.finish();
+                    oc_properties.finish();
+                }
+
+                for (key, value) in &self.catalog_entries {
+                    let value = remapper.get(value).ok_or(KrillaError::UserError(
+                        "a catalog entry references an object that doesn't exist".to_string(),
+                    ))?;
+                    catalog.pair(key.to_pdf_name(), *value);
+                }
+
+                if !named_destinations.is_empty() {
+                    // Cannot use pdf-writer API here because it requires Ref's, while
+                    // we write our destinations directly into the array.
+                    let mut names = catalog.names();
+                    let mut name_tree = names.destinations();
+                    let mut name_entries = name_tree.names();
+
+                    // Sort to prevent inconsistent order.
+                    let mut sorted = named_destinations.into_iter().collect::<Vec<_>>();
+                    sorted.sort_by(|a, b| a.1.cmp(&b.1));
+
+                    for (name, dest_ref) in sorted {
+                        name_entries
+                            .insert(Str(name.name.as_bytes()), *remapper.get(&dest_ref).unwrap());
+                    }
+                }
+
+                catalog.finish();
+
+                Ok(())
+            })?;
+        }
+
+        Ok(output)
+    }
+
+    /// Whether a document catalog will be written.
+    ///
+    /// Every valid PDF must have one and krilla ensures that there always is one, but for
+    /// snapshot tests, it can be useful to not write a document catalog if we don't actually
+    /// need it for the test.
+    fn has_catalog(&self) -> bool {
+        self.page_tree.is_some()
+            || self.outline.is_some()
+            || self.page_label_tree.is_some()
+            || self.destination_profiles.is_some()
+            || self.struct_tree_root.is_some()
+    }
 
-            if let Some(oi) = &self.destination_profiles {
-                catalog.pair(Name(b"OutputIntents"), oi.0);
+    /// Check for everything that would make writing the document fail, including
+    /// validation errors, without writing anything yet.
+    fn preflight(
+        &self,
+        sc: &mut SerializeContext,
+        remapper: &HashMap<Ref, Ref>,
+    ) -> KrillaResult<()> {
+        // Raw objects are provided by the user, so they might reference objects that
+        // don't exist.
+        let reserved_refs = sc.reserved_refs();
+        for chunk in &self.raw_objects {
+            let mut invalid_ref = false;
+            chunk.renumber(|old| {
+                invalid_ref |= old.get() > reserved_refs && !remapper.contains_key(&old);
+                old
+            });
+
+            if invalid_ref {
+                return Err(KrillaError::UserError(
+                    "a raw object references an object that doesn't exist".to_string(),
+                ));
             }
+        }
 
-            if let Some(lang) = self.metadata.and_then(|m| m.language).as_ref() {
-                catalog.lang(TextStr(lang));
-            } else {
+        if self
+            .catalog_entries
+            .iter()
+            .any(|(_, value)| !remapper.contains_key(value))
+        {
+            return Err(KrillaError::UserError(
+                "a catalog entry references an object that doesn't exist".to_string(),
+            ));
+        }
+
+        // TODO: Replace with `is_none_or` once MSRV allows to.
+        let missing_title = match self.metadata.as_ref() {
+            None => true,
+            Some(m) => m.title.is_none(),
+        };
+
+        if missing_title {
+            sc.register_validation_error(ValidationError::NoDocumentTitle);
+        }
+
+        if self
+            .metadata
+            .as_ref()
+            .is_some_and(|m| !m.custom_info.is_empty())
+        {
+            sc.register_validation_error(ValidationError::CustomDocumentInfo);
+        }
+
+        let missing_trapping_status = !matches!(
+            self.metadata.as_ref().and_then(|m| m.trapped),
+            Some(Trapped::True | Trapped::False)
+        );
+
+        if missing_trapping_status {
+            sc.register_validation_error(ValidationError::MissingTrappingStatus);
+        }
+
+        let language = self.metadata.as_ref().and_then(|m| m.language.as_ref());
+
+        if self.has_catalog() {
+            if language.is_none() {
                 sc.register_validation_error(ValidationError::NoDocumentLanguage);
             }
 
-            if let Some(st) = &self.struct_tree_root {
-                catalog.pair(Name(b"StructTreeRoot"), st.0);
-                let mut mark_info = catalog.mark_info();
-                mark_info.marked(true);
-                if sc.serialize_settings().pdf_version >= PdfVersion::Pdf16 {
-                    // We always set suspects to false because it's required by PDF/UA
-                    mark_info.suspects(false);
-                }
-                mark_info.finish();
+            if self.need_appearances {
+                sc.register_validation_error(ValidationError::NeedAppearances);
             }
 
-            if sc
-                .serialize_settings()
-                .validator
-                .requires_display_doc_title()
-            {
-                catalog.viewer_preferences().display_doc_title(true);
+            if !self.layer_states.is_empty() {
+                sc.register_validation_error(ValidationError::OptionalContent);
             }
+        }
 
-            if let Some(ol) = &self.outline {
-                catalog.outlines(ol.0);
+        // Register the limits of everything we are going to write. The document info
+        // and the catalog are written to a scratch chunk for that.
+        for (_, chunk) in [
+            &self.page_tree,
+            &self.outline,
+            &self.open_action,
+            &self.page_label_tree,
+            &self.destination_profiles,
+            &self.struct_tree_root,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            sc.register_limits(chunk.limits());
+        }
+
+        for group in [
+            &self.struct_elements,
+            &self.page_labels,
+            &self.annotations,
+            &self.fonts,
+            &self.color_spaces,
+            &self.icc_profiles,
+            &self.destinations,
+            &self.ext_g_states,
+            &self.masks,
+            &self.x_objects,
+            &self.shading_functions,
+            &self.patterns,
+            &self.raw_objects,
+            &self.layers,
+        ] {
+            for chunk in group {
+                sc.register_limits(chunk.limits());
             }
+        }
+
+        for chunk in &self.pages {
+            sc.register_limits(chunk.wait().limits());
+        }
+
+        for chunk in &self.images {
+            sc.register_limits(chunk.wait().res()?.limits());
+        }
+
+        let mut scratch = Chunk::new();
 
-            if !named_destinations.is_empty() {
-                // Cannot use pdf-writer API here because it requires Ref's, while
-                // we write our destinations directly into the array.
-                let mut names = catalog.names();
-                let mut name_tree = names.destinations();
-                let mut name_entries = name_tree.names();
+        if let Some(metadata) = self.metadata.as_ref().filter(|m| m.has_document_info()) {
+            let validator = sc.serialize_settings().validator;
+            metadata.serialize_document_info(scratch.indirect(Ref::new(1)).start(), validator);
+        }
 
-                // Sort to prevent inconsistent order.
-                let mut sorted = named_destinations.into_iter().collect::<Vec<_>>();
-                sorted.sort_by(|a, b| a.1.cmp(&b.1));
+        let mut catalog = scratch.indirect(Ref::new(2)).dict();
+        if let Some(lang) = language {
+            catalog.pair(Name(b"Lang"), TextStr(lang));
+        }
+        for (key, _) in &self.catalog_entries {
+            catalog.pair(key.to_pdf_name(), Ref::new(2));
+        }
+        catalog.finish();
 
-                for (name, dest_ref) in sorted {
-                    name_entries
-                        .insert(Str(name.name.as_bytes()), *remapper.get(&dest_ref).unwrap());
+        sc.register_limits(scratch.limits());
+
+        sc.check_validation()
+    }
+}
+
+/// Find chunks that are identical to an earlier chunk of the same group.
+///
+/// Two chunks are identical if their bytes match once the references to their own
+/// objects are numbered consistently and references to other duplicates are resolved.
+/// Since resolving a duplicate can make the chunks that reference it identical, this
+/// is repeated until no new duplicates are found.
+///
+/// Returns a mapping from each object of a duplicate chunk to the corresponding object
+/// of the chunk it duplicates.
+fn find_duplicates(groups: &[Vec<&Chunk>]) -> HashMap<Ref, Ref> {
+    let groups = groups
+        .iter()
+        .map(|group| {
+            group
+                .iter()
+                .map(|chunk| {
+                    let mut refs = chunk.refs().collect::<Vec<_>>();
+                    refs.sort();
+                    (*chunk, refs)
+                })
+                .filter(|(_, refs)| !refs.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut duplicates = HashMap::new();
+
+    loop {
+        let mut changed = false;
+
+        for group in &groups {
+            let mut originals: HashMap<u128, &[Ref]> = HashMap::new();
+
+            for (chunk, refs) in group {
+                if duplicates.contains_key(&refs[0]) {
+                    continue;
+                }
+
+                let local = refs.len() as i32;
+                let canonical = chunk.renumber(|ref_| match refs.binary_search(&ref_) {
+                    Ok(index) => Ref::new(index as i32 + 1),
+                    Err(_) => Ref::new(local + resolve_duplicate(&duplicates, ref_).get()),
+                });
+
+                let hash = canonical.as_bytes().sip_hash();
+
+                match originals.get(&hash) {
+                    Some(original) => {
+                        duplicates.extend(refs.iter().copied().zip(original.iter().copied()));
+                        changed = true;
+                    }
+                    None => {
+                        originals.insert(hash, refs);
+                    }
                 }
             }
+        }
 
-            catalog.finish();
+        if !changed {
+            return duplicates;
         }
+    }
+}
 
-        Ok(pdf)
+/// Follow a chain of duplicates to the object that is actually written.
+fn resolve_duplicate(duplicates: &HashMap<Ref, Ref>, mut ref_: Ref) -> Ref {
+    while let Some(original) = duplicates.get(&ref_) {
+        ref_ = *original;
     }
+
+    ref_
 }
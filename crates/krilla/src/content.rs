@@ -15,7 +15,8 @@ use tiny_skia_path::Size;
 use tiny_skia_path::{NormalizedF32, Path, PathSegment, Point, Rect, Transform};
 
 use crate::color::{Color, ColorSpace};
-use crate::font::{Font, Glyph, GlyphUnits};
+use crate::document::Layer;
+use crate::font::{ppem, Font, Glyph, GlyphUnits};
 use crate::graphics_state::GraphicsStates;
 #[cfg(feature = "raster-images")]
 use crate::image::Image;
@@ -24,6 +25,8 @@ use crate::object::ext_g_state::ExtGState;
 use crate::object::font::cid_font::CIDFont;
 use crate::object::font::type3_font::{CoveredGlyph, Type3Font};
 use crate::object::font::{FontContainer, FontIdentifier, PDFGlyph, PaintMode};
+#[cfg(feature = "pdf")]
+use crate::object::pdf_page::PdfPage;
 use crate::object::shading_function::{GradientProperties, GradientPropertiesExt, ShadingFunction};
 use crate::object::shading_pattern::ShadingPattern;
 use crate::object::tiling_pattern::TilingPattern;
@@ -38,9 +41,17 @@ use crate::tagging::ContentTag;
 use crate::util::{calculate_stroke_bbox, LineCapExt, LineJoinExt, NameExt, RectExt, TransformExt};
 use crate::validation::ValidationError;
 
+/// Rough estimates of the number of bytes that are written to a content stream
+/// for each operation, path segment and glyph.
+const OPERATION_LEN: usize = 64;
+const SEGMENT_LEN: usize = 48;
+const GLYPH_LEN: usize = 8;
+
 pub(crate) struct ContentBuilder {
     rd_builder: ResourceDictionaryBuilder,
     content: Content,
+    /// An estimate of the number of bytes written to `content` so far.
+    estimated_len: usize,
     validation_errors: HashSet<ValidationError>,
     root_transform: Transform,
     graphics_states: GraphicsStates,
@@ -62,6 +73,7 @@ impl ContentBuilder {
             rd_builder: ResourceDictionaryBuilder::new(),
             validation_errors: HashSet::new(),
             content: Content::new(),
+            estimated_len: 0,
             root_transform,
             graphics_states: GraphicsStates::new(),
             bbox: None,
@@ -69,6 +81,10 @@ impl ContentBuilder {
         }
     }
 
+    pub(crate) fn estimated_len(&self) -> usize {
+        self.estimated_len
+    }
+
     pub(crate) fn content_save_state(&mut self) {
         self.content.save_state();
 
@@ -82,8 +98,11 @@ impl ContentBuilder {
         let buf = self.content.finish();
         sc.register_limits(buf.limits());
 
+        let content = buf.to_bytes();
+        sc.register_content_bytes(content.len());
+
         Stream::new(
-            buf.to_bytes(),
+            content,
             self.bbox
                 .unwrap_or(Rect::from_xywh(0.0, 0.0, 1.0, 1.0).unwrap()),
             self.validation_errors.into_iter().collect(),
@@ -133,6 +152,19 @@ impl ContentBuilder {
         self.active_marked_content = false;
     }
 
+    pub(crate) fn start_layer(&mut self, layer: &Layer) {
+        let name = self
+            .rd_builder
+            .register_resource(resource::Properties::new(layer.ref_()));
+        self.content
+            .begin_marked_content_with_properties(Name(b"OC"))
+            .properties_named(name.to_pdf_name());
+    }
+
+    pub(crate) fn end_layer(&mut self) {
+        self.content.end_marked_content();
+    }
+
     pub(crate) fn concat_transform(&mut self, transform: &Transform) {
         self.graphics_states.transform(*transform);
     }
@@ -257,6 +289,7 @@ impl ContentBuilder {
         sc: &mut SerializeContext,
         fill: Fill,
         glyphs: &[impl Glyph],
+        glyph_paints: &[Option<Paint>],
         font: Font,
         text: &str,
         font_size: f32,
@@ -271,17 +304,22 @@ impl ContentBuilder {
             self.set_fill_opacity(fill.opacity);
         }
 
+        let bbox = get_glyphs_bbox(glyphs, x, y, font_size, font.clone(), glyph_units);
+
         self.fill_stroke_glyph_run(
             x,
             y,
             sc,
             TextRenderingMode::Fill,
             |sb, sc| {
-                let bbox = get_glyphs_bbox(glyphs, x, y, font_size, font.clone(), glyph_units);
                 sb.expand_bbox(bbox);
                 sb.content_set_fill_properties(bbox, &fill, sc)
             },
             glyphs,
+            GlyphPaints {
+                paints: glyph_paints,
+                base: Some((&fill, bbox)),
+            },
             font.clone(),
             PaintMode::Fill(&fill),
             text,
@@ -342,6 +380,7 @@ impl ContentBuilder {
                 )
             },
             glyphs,
+            GlyphPaints::none(),
             font.clone(),
             PaintMode::Stroke(&stroke),
             text,
@@ -352,8 +391,97 @@ impl ContentBuilder {
         self.graphics_states.restore_state();
     }
 
+    /// Draw a sequence of glyphs that are filled and then stroked (text rendering
+    /// mode 2). Type3 fonts can't be drawn like this, since their glyphs already
+    /// contain either the fill or the stroke.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn fill_stroke_glyphs(
+        &mut self,
+        start: Point,
+        sc: &mut SerializeContext,
+        fill: Fill,
+        stroke: Stroke,
+        glyphs: &[impl Glyph],
+        font: Font,
+        text: &str,
+        font_size: f32,
+        glyph_units: GlyphUnits,
+    ) {
+        let (x, y) = (start.x, start.y);
+        self.graphics_states.save_state();
+
+        // PDF viewers don't show patterns with fill/stroke opacities consistently.
+        // Because of this, the opacity is accounted for in the pattern itself.
+        if !matches!(&fill.paint.0, &InnerPaint::Pattern(_)) {
+            self.set_fill_opacity(fill.opacity);
+        }
+
+        if !matches!(&stroke.paint.0, &InnerPaint::Pattern(_)) {
+            self.set_stroke_opacity(stroke.opacity);
+        }
+
+        let bbox = get_glyphs_bbox(glyphs, x, y, font_size, font.clone(), glyph_units);
+
+        self.fill_stroke_glyph_run(
+            x,
+            y,
+            sc,
+            TextRenderingMode::FillStroke,
+            |sb, sc| {
+                sb.expand_bbox(bbox);
+                sb.content_set_fill_properties(bbox, &fill, sc);
+                sb.content_set_stroke_properties(bbox, stroke.clone(), sc);
+            },
+            glyphs,
+            GlyphPaints::none(),
+            font.clone(),
+            PaintMode::Fill(&fill),
+            text,
+            font_size,
+            glyph_units,
+        );
+
+        self.graphics_states.restore_state();
+    }
+
+    /// Draw a sequence of glyphs that are neither filled nor stroked (text rendering
+    /// mode 3), so that the text can only be selected and searched.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn invisible_glyphs(
+        &mut self,
+        start: Point,
+        sc: &mut SerializeContext,
+        glyphs: &[impl Glyph],
+        font: Font,
+        text: &str,
+        font_size: f32,
+        glyph_units: GlyphUnits,
+    ) {
+        let (x, y) = (start.x, start.y);
+        let fill = Fill::default();
+
+        self.fill_stroke_glyph_run(
+            x,
+            y,
+            sc,
+            TextRenderingMode::Invisible,
+            |sb, _| {
+                let bbox = get_glyphs_bbox(glyphs, x, y, font_size, font.clone(), glyph_units);
+                sb.expand_bbox(bbox);
+            },
+            glyphs,
+            GlyphPaints::none(),
+            font.clone(),
+            PaintMode::Fill(&fill),
+            text,
+            font_size,
+            glyph_units,
+        );
+    }
+
     /// Encode a successive sequence of glyphs that share the same properties and
-    /// can be encoded with one text showing operator.
+    /// can be encoded with one text showing operator, unless some glyphs have their
+    /// own paint, in which case the paint is changed in-between the text showing operators.
     #[allow(clippy::too_many_arguments)]
     #[inline(always)]
     fn encode_consecutive_glyph_run(
@@ -366,6 +494,7 @@ impl ContentBuilder {
         size: f32,
         paint_mode: PaintMode,
         glyphs: &[impl Glyph],
+        glyph_paints: GlyphPaints,
         glyph_units: GlyphUnits,
     ) {
         let font_name = self
@@ -376,64 +505,103 @@ impl ContentBuilder {
             Transform::from_row(1.0, 0.0, 0.0, -1.0, *cur_x, cur_y).to_pdf_transform(),
         );
 
-        let mut positioned = self.content.show_positioned();
-        let mut items = positioned.items();
-
         let mut adjustment = 0.0;
-        let mut encoded = vec![];
-
-        for glyph in glyphs {
-            if glyph.glyph_id() == GlyphId::new(0)
-                || pdf_font.font().postscript_name() == Some("LastResort")
-            {
-                sc.register_validation_error(ValidationError::ContainsNotDefGlyph);
+        let mut start = 0;
+        // `None` means that the paint of the whole run is active.
+        let mut cur_paint = None;
+
+        while start < glyphs.len() {
+            // Find the longest sequence of glyphs that share the same paint.
+            let paint = glyph_paints.get(start);
+            let end = (start + 1..glyphs.len())
+                .find(|i| glyph_paints.get(*i) != paint)
+                .unwrap_or(glyphs.len());
+
+            if paint != cur_paint {
+                self.set_glyph_paint(sc, glyph_paints, paint);
+                cur_paint = paint;
             }
 
-            let pdf_glyph = pdf_font
-                .get_gid(CoveredGlyph::new(glyph.glyph_id(), paint_mode))
-                .unwrap();
+            let mut positioned = self.content.show_positioned();
+            let mut items = positioned.items();
+            let mut encoded = vec![];
+
+            for glyph in &glyphs[start..end] {
+                if glyph.glyph_id() == GlyphId::new(0)
+                    || pdf_font.font().postscript_name() == Some("LastResort")
+                {
+                    sc.register_validation_error(ValidationError::ContainsNotDefGlyph);
+                }
+
+                let pdf_glyph = pdf_font
+                    .get_gid(CoveredGlyph::new(glyph.glyph_id(), paint_mode))
+                    .unwrap();
 
-            let normalize =
-                |v| unit_normalize(glyph_units, pdf_font.font().units_per_em(), size, v);
+                let normalize =
+                    |v| unit_normalize(glyph_units, pdf_font.font().units_per_em(), size, v);
 
-            let x_advance = normalize(glyph.x_advance()) * pdf_font.units_per_em();
-            let font_advance = pdf_font
-                .font()
-                .advance_width(glyph.glyph_id())
-                .map(|n| (n / pdf_font.font().units_per_em()) * pdf_font.units_per_em());
-            let x_offset = normalize(glyph.x_offset()) * pdf_font.units_per_em();
+                let x_advance = normalize(glyph.x_advance()) * pdf_font.units_per_em();
+                let font_advance = pdf_font
+                    .font()
+                    .advance_width(glyph.glyph_id())
+                    .map(|n| (n / pdf_font.font().units_per_em()) * pdf_font.units_per_em());
+                let x_offset = normalize(glyph.x_offset()) * pdf_font.units_per_em();
 
-            adjustment += x_offset;
+                adjustment += x_offset;
 
-            // Make sure we don't write miniscule adjustments
-            if !approx_eq!(f32, adjustment, 0.0, epsilon = 0.001) {
-                if !encoded.is_empty() {
-                    items.show(Str(&encoded));
-                    encoded.clear();
+                // Make sure we don't write miniscule adjustments
+                if !approx_eq!(f32, adjustment, 0.0, epsilon = 0.001) {
+                    if !encoded.is_empty() {
+                        items.show(Str(&encoded));
+                        encoded.clear();
+                    }
+
+                    // Adjustment is always in 1000 units, even for Type3 fonts.
+                    items.adjust(-(adjustment / pdf_font.units_per_em() * 1000.0));
+                    adjustment = 0.0;
                 }
 
-                // Adjustment is always in 1000 units, even for Type3 fonts.
-                items.adjust(-(adjustment / pdf_font.units_per_em() * 1000.0));
-                adjustment = 0.0;
-            }
+                pdf_glyph.encode_into(&mut encoded);
 
-            pdf_glyph.encode_into(&mut encoded);
+                if let Some(font_advance) = font_advance {
+                    adjustment += x_advance - font_advance;
+                }
+
+                adjustment -= x_offset;
+                // cur_x/cur_y and glyph metrics are in user space units.
+                *cur_x += normalize(glyph.x_advance()) * size;
+            }
 
-            if let Some(font_advance) = font_advance {
-                adjustment += x_advance - font_advance;
+            if !encoded.is_empty() {
+                items.show(Str(&encoded));
             }
 
-            adjustment -= x_offset;
-            // cur_x/cur_y and glyph metrics are in user space units.
-            *cur_x += normalize(glyph.x_advance()) * size;
+            items.finish();
+            positioned.finish();
+
+            start = end;
         }
 
-        if !encoded.is_empty() {
-            items.show(Str(&encoded));
+        // Restore the paint of the run for the next glyph group.
+        if cur_paint.is_some() {
+            self.set_glyph_paint(sc, glyph_paints, None);
         }
+    }
 
-        items.finish();
-        positioned.finish();
+    fn set_glyph_paint(
+        &mut self,
+        sc: &mut SerializeContext,
+        glyph_paints: GlyphPaints,
+        paint: Option<&Paint>,
+    ) {
+        if let Some((fill, bbox)) = glyph_paints.base {
+            let fill = Fill {
+                paint: paint.unwrap_or(&fill.paint).clone(),
+                ..fill.clone()
+            };
+
+            self.content_set_fill_properties(bbox, &fill, sc);
+        }
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -442,20 +610,27 @@ impl ContentBuilder {
         x: f32,
         ys: f32,
         sc: &mut SerializeContext,
-        fill_render_mode: TextRenderingMode,
+        render_mode: TextRenderingMode,
         action: impl FnOnce(&mut ContentBuilder, &mut SerializeContext),
         glyphs: &[impl Glyph],
+        glyph_paints: GlyphPaints,
         font: Font,
         paint_mode: PaintMode,
         text: &str,
         font_size: f32,
         glyph_units: GlyphUnits,
     ) {
+        self.estimated_len += glyphs.len() * GLYPH_LEN;
+        let ppem = ppem(font_size, self.cur_transform_with_root_transform());
+        let font = sc.font_for_ppem(font, ppem);
+
         self.apply_isolated_op(
             |_, _| {},
             |sb, sc| {
                 let mut cur_x = x;
                 let mut cur_y = ys;
+                // The index of the first glyph of the current glyph group.
+                let mut glyph_index = 0;
 
                 action(sb, sc);
                 sb.content.begin_text();
@@ -502,11 +677,12 @@ impl ContentBuilder {
                             )
                         };
 
-                        if fill_render_mode == TextRenderingMode::Fill || pdf_font.force_fill() {
+                        // Type3 glyphs already contain the stroke, so they always need to be
+                        // filled. Invisible text should stay invisible, though.
+                        if pdf_font.force_fill() && render_mode != TextRenderingMode::Invisible {
                             sb.content.set_text_rendering_mode(TextRenderingMode::Fill);
                         } else {
-                            sb.content
-                                .set_text_rendering_mode(TextRenderingMode::Stroke);
+                            sb.content.set_text_rendering_mode(render_mode);
                         }
 
                         sb.encode_consecutive_glyph_run(
@@ -518,9 +694,12 @@ impl ContentBuilder {
                             font_size,
                             paint_mode,
                             glyph_group.glyphs,
+                            glyph_paints.slice(glyph_index, glyph_group.glyphs.len()),
                             glyph_units,
                         );
 
+                        glyph_index += glyph_group.glyphs.len();
+
                         cur_y -= normalize(glyph_group.y_advance) * font_size;
                     }
 
@@ -559,6 +738,9 @@ impl ContentBuilder {
 
     pub(crate) fn draw_masked(&mut self, sc: &mut SerializeContext, mask: Mask, stream: Stream) {
         let state = ExtGState::new().mask(mask, sc);
+        // The masked group is not isolated, so that blend modes inside of it still
+        // interact with the backdrop. Users who need an isolated group can push one
+        // inside of the mask.
         let x_object = XObject::new(stream, false, true, None);
         self.draw_xobject(sc, x_object, &state);
     }
@@ -576,16 +758,39 @@ impl ContentBuilder {
         self.draw_xobject(sc, x_object, &state);
     }
 
-    pub(crate) fn draw_isolated(&mut self, sc: &mut SerializeContext, stream: Stream) {
+    pub(crate) fn draw_transparency_group(
+        &mut self,
+        sc: &mut SerializeContext,
+        stream: Stream,
+        isolated: bool,
+        knockout: bool,
+    ) {
         let state = ExtGState::new();
-        let x_object = XObject::new(stream, true, false, None);
+        let x_object = XObject::new(stream, isolated, false, None).transparency_group(knockout);
+        self.draw_xobject(sc, x_object, &state);
+    }
+
+    pub(crate) fn draw_stream(&mut self, sc: &mut SerializeContext, stream: Stream) {
+        let state = ExtGState::new();
+        let x_object = XObject::new(stream, false, false, None);
         self.draw_xobject(sc, x_object, &state);
     }
 
     #[cfg(feature = "raster-images")]
-    pub(crate) fn draw_image(&mut self, image: Image, size: Size, sc: &mut SerializeContext) {
+    pub(crate) fn draw_image(
+        &mut self,
+        image: Image,
+        size: Size,
+        opacity: NormalizedF32,
+        blend_mode: pdf_writer::types::BlendMode,
+        sc: &mut SerializeContext,
+    ) {
         self.apply_isolated_op(
             |sb, _| {
+                // Images are painted with the non-stroking alpha.
+                sb.set_fill_opacity(opacity);
+                sb.set_blend_mode(blend_mode);
+
                 // Scale the image from 1x1 to the actual dimensions.
                 let transform =
                     Transform::from_row(size.width(), 0.0, 0.0, -size.height(), 0.0, size.height());
@@ -603,6 +808,71 @@ impl ContentBuilder {
         );
     }
 
+    #[cfg(feature = "raster-images")]
+    pub(crate) fn draw_stencil(
+        &mut self,
+        image: Image,
+        size: Size,
+        fill: Fill,
+        sc: &mut SerializeContext,
+    ) {
+        let bounds = Rect::from_xywh(0.0, 0.0, size.width(), size.height()).unwrap();
+        let has_pattern = matches!(fill.paint.0, InnerPaint::Pattern(_));
+        let fill_opacity = fill.opacity;
+
+        self.apply_isolated_op(
+            |sb, _| {
+                sb.expand_bbox(bounds);
+
+                // See comment in `fill_path`.
+                if !has_pattern {
+                    sb.set_fill_opacity(fill_opacity);
+                }
+            },
+            move |sb, sc| {
+                // The fill needs to be set before scaling the image, so that
+                // gradients and patterns are relative to the actual size of the stencil.
+                sb.content_set_fill_properties(bounds, &fill, sc);
+
+                let image_name = sb
+                    .rd_builder
+                    .register_resource(resource::XObject::new(sc.register_image(image)));
+
+                sb.content.transform(
+                    Transform::from_row(size.width(), 0.0, 0.0, -size.height(), 0.0, size.height())
+                        .to_pdf_transform(),
+                );
+                sb.content.x_object(image_name.to_pdf_name());
+            },
+            sc,
+        );
+    }
+
+    #[cfg(feature = "pdf")]
+    pub(crate) fn draw_pdf_page(
+        &mut self,
+        page: PdfPage,
+        transform: &Transform,
+        sc: &mut SerializeContext,
+    ) {
+        let transform = transform.pre_concat(page.transform());
+        let bbox = page.bbox();
+
+        self.apply_isolated_op(
+            |sb, _| {
+                sb.concat_transform(&transform);
+                sb.expand_bbox(bbox);
+            },
+            move |sb, sc| {
+                let x_object_name = sb
+                    .rd_builder
+                    .register_resource(sc.register_resourceable(page));
+                sb.content.x_object(x_object_name.to_pdf_name());
+            },
+            sc,
+        );
+    }
+
     pub(crate) fn draw_shading(&mut self, shading: &ShadingFunction, sc: &mut SerializeContext) {
         self.apply_isolated_op(
             |_, _| {},
@@ -636,6 +906,7 @@ impl ContentBuilder {
         op: impl FnOnce(&mut Self, &mut SerializeContext),
         sc: &mut SerializeContext,
     ) {
+        self.estimated_len += OPERATION_LEN;
         self.save_graphics_state();
         self.content_save_state();
 
@@ -670,6 +941,7 @@ impl ContentBuilder {
         sc: &mut SerializeContext,
         mut set_pattern_fn: impl FnMut(&mut Content, String),
         mut set_solid_fn: impl FnMut(&mut Content, ContentColorSpace, Color),
+        mut set_named_fn: impl FnMut(&mut Content, String, &[f32]),
     ) {
         let pattern_transform = |transform: Transform| -> Transform {
             transform.post_concat(self.cur_transform_with_root_transform())
@@ -723,6 +995,12 @@ impl ContentBuilder {
                 let color_space_resource = Self::cs_to_content_cs(self, sc, cs);
                 set_solid_fn(&mut self.content, color_space_resource, *c);
             }
+            InnerPaint::Separation(s) => {
+                let color_space = self.rd_builder.register_resource::<resource::ColorSpace>(
+                    sc.register_resourceable(s.color_space()),
+                );
+                set_named_fn(&mut self.content, color_space, &s.to_pdf_color());
+            }
             InnerPaint::LinearGradient(lg) => {
                 let (gradient_props, transform) = lg.clone().gradient_properties(bounds);
                 write_gradient(gradient_props, sc, transform, self);
@@ -735,9 +1013,16 @@ impl ContentBuilder {
                 let (gradient_props, transform) = sg.clone().gradient_properties(bounds);
                 write_gradient(gradient_props, sc, transform, self);
             }
+            InnerPaint::MeshGradient(mg) => {
+                let (gradient_props, transform) = mg.clone().gradient_properties(bounds);
+                write_gradient(gradient_props, sc, transform, self);
+            }
             InnerPaint::Pattern(pat) => {
                 let mut pat = Arc::unwrap_or_clone(pat.clone());
-                pat.transform = pattern_transform(pat.transform);
+                pat.transform = pattern_transform(
+                    pat.transform
+                        .pre_concat(Transform::from_translate(pat.phase.x, pat.phase.y)),
+                );
 
                 let tiling_pattern = TilingPattern::new(
                     pat.stream,
@@ -745,6 +1030,8 @@ impl ContentBuilder {
                     opacity,
                     pat.width,
                     pat.height,
+                    pat.x_step,
+                    pat.y_step,
                     sc,
                 );
 
@@ -796,14 +1083,18 @@ impl ContentBuilder {
                         let comps = c.to_pdf_color();
                         content.set_fill_cmyk(comps[0], comps[1], comps[2], comps[3]);
                     }
+                    // Lab colors always use a named color space.
+                    Color::Lab(_) => unreachable!(),
                 },
-                ContentColorSpace::Named(n) => {
-                    content.set_fill_color_space(n.to_pdf_name());
-                    content.set_fill_color(color.to_pdf_color());
-                }
+                ContentColorSpace::Named(n) => set_named_fn(content, n, &color.to_pdf_color()),
             }
         }
 
+        fn set_named_fn(content: &mut Content, color_space: String, components: &[f32]) {
+            content.set_fill_color_space(color_space.to_pdf_name());
+            content.set_fill_color(components.iter().copied());
+        }
+
         self.content_set_fill_stroke_properties(
             bounds,
             &fill.paint,
@@ -811,6 +1102,7 @@ impl ContentBuilder {
             serializer_context,
             set_pattern_fn,
             set_solid_fn,
+            set_named_fn,
         );
     }
 
@@ -839,14 +1131,18 @@ impl ContentBuilder {
                         let comps = c.to_pdf_color();
                         content.set_stroke_cmyk(comps[0], comps[1], comps[2], comps[3]);
                     }
+                    // Lab colors always use a named color space.
+                    Color::Lab(_) => unreachable!(),
                 },
-                ContentColorSpace::Named(n) => {
-                    content.set_stroke_color_space(n.to_pdf_name());
-                    content.set_stroke_color(color.to_pdf_color());
-                }
+                ContentColorSpace::Named(n) => set_named_fn(content, n, &color.to_pdf_color()),
             }
         }
 
+        fn set_named_fn(content: &mut Content, color_space: String, components: &[f32]) {
+            content.set_stroke_color_space(color_space.to_pdf_name());
+            content.set_stroke_color(components.iter().copied());
+        }
+
         self.content_set_fill_stroke_properties(
             bounds,
             &stroke.paint,
@@ -854,6 +1150,7 @@ impl ContentBuilder {
             serializer_context,
             set_pattern_fn,
             set_solid_fn,
+            set_named_fn,
         );
 
         // Only write if they don't correspond to the default values as defined in the
@@ -862,8 +1159,9 @@ impl ContentBuilder {
             self.content.set_line_width(stroke.width);
         }
 
-        if stroke.miter_limit != 10.0 {
-            self.content.set_miter_limit(stroke.miter_limit);
+        let miter_limit = stroke.valid_miter_limit();
+        if miter_limit != 10.0 {
+            self.content.set_miter_limit(miter_limit);
         }
 
         if stroke.line_cap != LineCap::Butt {
@@ -875,7 +1173,8 @@ impl ContentBuilder {
                 .set_line_join(stroke.line_join.to_pdf_line_join());
         }
 
-        if let Some(stroke_dash) = &stroke.dash {
+        // Invalid dash arrays are treated as a solid line.
+        if let Some(stroke_dash) = stroke.dash.as_ref().filter(|d| d.is_valid()) {
             self.content
                 .set_dash_pattern(stroke_dash.array.iter().copied(), stroke_dash.offset);
         }
@@ -890,6 +1189,8 @@ impl ContentBuilder {
         let mut p_prev = None;
 
         for operation in path_data {
+            self.estimated_len += SEGMENT_LEN;
+
             match operation {
                 PathSegment::MoveTo(p) => {
                     self.content.move_to(p.x, p.y);
@@ -1079,6 +1380,38 @@ where
     }
 }
 
+/// The paints of the individual glyphs of a glyph run, in case they differ from
+/// the paint of the run itself.
+#[derive(Clone, Copy)]
+pub(crate) struct GlyphPaints<'a> {
+    paints: &'a [Option<Paint>],
+    // The fill of the whole run, as well as the bbox of the run.
+    base: Option<(&'a Fill, Rect)>,
+}
+
+impl<'a> GlyphPaints<'a> {
+    fn none() -> Self {
+        Self {
+            paints: &[],
+            base: None,
+        }
+    }
+
+    fn get(&self, index: usize) -> Option<&'a Paint> {
+        self.paints.get(index).and_then(|p| p.as_ref())
+    }
+
+    fn slice(&self, start: usize, len: usize) -> Self {
+        let start = start.min(self.paints.len());
+        let end = (start + len).min(self.paints.len());
+
+        Self {
+            paints: &self.paints[start..end],
+            ..*self
+        }
+    }
+}
+
 /// In PDF, correspondences between glyphs and Unicode codepoints are expressed
 /// via a CMAP. In a CMAP, you can assign a sequence of unicode codepoints to each
 /// glyph. There are two issues with this approach:
@@ -14,13 +14,26 @@
 //!
 //! [`Page`]: crate::page::Page
 
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
+
+use pdf_writer::{Chunk, Ref};
+use skrifa::GlyphId;
+#[cfg(feature = "pdf")]
+use tiny_skia_path::Transform;
 use tiny_skia_path::{Rect, Size};
 
-use crate::error::KrillaResult;
-use crate::metadata::Metadata;
+use crate::action::Action;
+use crate::error::{KrillaError, KrillaResult};
+use crate::font::Font;
+use crate::metadata::{Metadata, XmpWriter};
 use crate::object::outline::Outline;
+use crate::object::output_intent::OutputIntent;
 use crate::object::page::Page;
 use crate::object::page::PageLabel;
+#[cfg(feature = "pdf")]
+use crate::object::pdf_page::PdfPage;
+use crate::rewrite::{rewrite, RewriteSettings};
 use crate::serialize::{SerializeContext, SerializeSettings};
 use crate::tagging::TagTree;
 
@@ -66,29 +79,284 @@ impl Document {
         Page::new(&mut self.serializer_context, page_index, page_settings)
     }
 
+    /// Import a page from an existing PDF and append it to the document.
+    ///
+    /// The page is parsed from `data` and its content stream, together with all
+    /// resources it needs, is copied into a form XObject that is drawn onto a new page
+    /// with the same size. `page_index` is zero-based. Note that the imported content
+    /// is copied as is, so krilla cannot ensure that it conforms to the validator
+    /// of the document. Annotations and the structure of the page are not imported.
+    ///
+    /// Returns an error if the source PDF is malformed, encrypted or does not contain
+    /// a page with the given index.
+    #[cfg(feature = "pdf")]
+    pub fn import_page(&mut self, data: &[u8], page_index: usize) -> KrillaResult<()> {
+        let pdf_page = PdfPage::new(data, page_index)?;
+        let size = pdf_page.size();
+
+        let mut page = self.start_page_with(PageSettings::new(size.width(), size.height()));
+        let mut surface = page.surface();
+        surface.draw_imported_page(pdf_page, &Transform::identity());
+        surface.finish();
+        page.finish();
+
+        Ok(())
+    }
+
     /// Set the outline of the document.
     pub fn set_outline(&mut self, outline: Outline) {
         self.serializer_context.set_outline(outline);
     }
 
+    /// Set the action that should be executed when the document is opened.
+    ///
+    /// This can for example be used to navigate to a specific destination or to
+    /// run a document-level JavaScript.
+    pub fn set_open_action(&mut self, action: Action) {
+        self.serializer_context.set_open_action(action);
+    }
+
     /// Set the metadata of the document.
     pub fn set_metadata(&mut self, metadata: Metadata) {
         self.serializer_context.set_metadata(metadata);
     }
 
+    /// Set a hook that can write additional properties to the XMP metadata of the document.
+    ///
+    /// The hook is called with the XMP writer after the properties from the [`Metadata`]
+    /// have been written, but before krilla writes the properties that are required by the
+    /// PDF version and the validator (like the document ID or the PDF/A identification).
+    /// This makes it possible to add custom schemas, for example for rights management.
+    /// You must not write any properties that krilla writes itself, since the
+    /// resulting XMP metadata would contain them twice.
+    ///
+    /// The hook has no effect if XMP metadata is disabled in the serialize settings.
+    pub fn set_xmp_hook(&mut self, hook: impl Fn(&mut XmpWriter) + 'static) {
+        self.serializer_context.set_xmp_hook(Box::new(hook));
+    }
+
+    /// Set a hook that is called once the number of annotations on a page reaches
+    /// the given threshold.
+    ///
+    /// The hook is called with the index of the page and its current number of
+    /// annotations (see [`Page::annotation_count`]). Some standards limit the length
+    /// of arrays, which also limits the number of annotations on a single page (for
+    /// example to 8191 for PDF/A-1). Exceeding that limit only results in an error
+    /// when finishing the document, so this hook allows you to react beforehand,
+    /// for example by moving the remaining content to a new page.
+    pub fn set_annotation_warning(
+        &mut self,
+        threshold: usize,
+        hook: impl Fn(usize, usize) + 'static,
+    ) {
+        self.serializer_context
+            .set_annotation_warning(threshold, Box::new(hook));
+    }
+
+    /// Set the output intent of the document, replacing all output intents
+    /// that have been added so far.
+    ///
+    /// See the [`output_intent`] module for more information.
+    ///
+    /// [`output_intent`]: crate::output_intent
+    pub fn set_output_intent(&mut self, output_intent: OutputIntent) {
+        self.serializer_context.set_output_intent(output_intent);
+    }
+
+    /// Add an output intent to the document.
+    ///
+    /// A document can have multiple output intents, for example if it's supposed
+    /// to conform to both PDF/A and PDF/X. If the validator of the document requires
+    /// an output intent, the one with the corresponding subtype is used instead of
+    /// the default sRGB output intent.
+    ///
+    /// See the [`output_intent`] module for more information.
+    ///
+    /// [`output_intent`]: crate::output_intent
+    pub fn add_output_intent(&mut self, output_intent: OutputIntent) {
+        self.serializer_context.add_output_intent(output_intent);
+    }
+
+    /// Return the glyphs of each font that have been used in the document so far.
+    ///
+    /// This reflects the glyphs that will be embedded for each font (unless subsetting
+    /// was disabled via [`SerializeSettings::subset_fonts`], in which case all glyphs are
+    /// embedded), which can be useful for diagnosing issues with missing glyphs. Note that
+    /// glyphs that are only drawn as part of deferred text are only known once the
+    /// document is finished and thus not included.
+    pub fn used_glyphs(&self) -> HashMap<Font, BTreeSet<GlyphId>> {
+        self.serializer_context.used_glyphs()
+    }
+
+    /// Set the page labels of the document.
+    ///
+    /// Each entry consists of the index of the first page of a range and the
+    /// label that should be used for that range. A range extends until the start
+    /// of the next one, and numbering restarts at the beginning of each range.
+    /// The entries need to be sorted by their start index, which in turn needs to be
+    /// smaller than the number of pages, otherwise export will fail.
+    /// Pages before the first range will not have a label.
+    ///
+    /// If set, this takes precedence over page labels set via [`PageSettings::with_page_label`].
+    pub fn set_page_labels(&mut self, ranges: Vec<(usize, PageLabel)>) {
+        self.serializer_context.set_page_labels(ranges);
+    }
+
     /// Set the tag tree of the document.
     pub fn set_tag_tree(&mut self, tag_tree: TagTree) {
         self.serializer_context.set_tag_tree(tag_tree);
     }
 
+    /// Reserve an object number that krilla will not use for any of its own objects.
+    ///
+    /// This is an advanced hook for tools that post-process the PDF created by krilla,
+    /// for example to sign it or attach additional data. The reserved object numbers
+    /// will appear as free entries in the cross-reference table of the resulting file,
+    /// so that an external tool can fill them in via an incremental update, i.e. by
+    /// appending the new object, a new cross-reference section that contains the
+    /// reserved number and a trailer with a `/Prev` entry pointing to the original
+    /// cross-reference table.
+    ///
+    /// Reserved object numbers are guaranteed to be stable, i.e. the returned
+    /// reference will be the actual object number in the final PDF. Because of that,
+    /// they can't be used together with [`SerializeSettings::linearize`], which
+    /// renumbers all objects. Exporting such a document will return an error.
+    pub fn reserve_object(&mut self) -> Ref {
+        self.serializer_context.reserve_ref()
+    }
+
+    /// Write a raw indirect object to the document.
+    ///
+    /// This is an escape hatch for PDF features that krilla doesn't support (yet). The closure
+    /// receives a chunk as well as the reference of the new object and must write exactly one
+    /// indirect object with that reference into the chunk, for example by using
+    /// `chunk.indirect(ref_).dict()` or `chunk.stream(ref_, data)`. The returned reference
+    /// can then be used in other raw objects or in a custom entry of the document catalog
+    /// (see [`Document::set_catalog_entry`]). Note that the object will be renumbered
+    /// in the final PDF, so the reference is only meaningful within krilla.
+    ///
+    /// **Warning**: Raw objects bypass all validation that krilla performs, so it is
+    /// your responsibility to ensure that the resulting PDF is valid and still conforms
+    /// to the chosen validator. Raw objects may only reference other raw objects or objects
+    /// reserved via [`Document::reserve_object`]. Otherwise, exporting the document will fail.
+    ///
+    /// # Panics
+    /// Panics if the closure doesn't write exactly one object with the given reference.
+    pub fn write_raw_object(&mut self, write: impl FnOnce(&mut Chunk, Ref)) -> Ref {
+        self.serializer_context.register_raw_object(write)
+    }
+
+    /// Add a custom entry to the document catalog, referencing an object
+    /// written via [`Document::write_raw_object`].
+    ///
+    /// **Warning**: Just like raw objects, custom catalog entries bypass all validation.
+    /// You must not use keys that krilla might write itself, like `Pages` or `Outlines`.
+    pub fn set_catalog_entry(&mut self, key: String, value: Ref) {
+        self.serializer_context.set_catalog_entry(key, value);
+    }
+
+    /// Set whether viewers should regenerate the appearances of all form fields
+    /// when opening the document.
+    ///
+    /// This writes the `NeedAppearances` entry of the interactive form dictionary, which is
+    /// useful if you add form fields (for example text fields via [`Page::add_form_field`])
+    /// without building appearance streams for them. Note that relying on `NeedAppearances` is
+    /// forbidden by PDF/A, so enabling it will cause the export to fail in that case.
+    /// Disabled by default.
+    pub fn set_need_appearances(&mut self, need_appearances: bool) {
+        self.serializer_context
+            .set_need_appearances(need_appearances);
+    }
+
+    /// Add a new layer to the document.
+    ///
+    /// Layers (known as optional content groups in PDF) allow grouping content that
+    /// users can then show or hide in the viewer, for example annotations of a drawing.
+    /// The name is displayed in the layer panel of the viewer, and `visible` determines
+    /// whether the layer is shown by default when opening the document. Use
+    /// [`Surface::push_layer`] to add content to the layer. Note that layers are
+    /// forbidden by PDF/A1.
+    ///
+    /// [`Surface::push_layer`]: crate::surface::Surface::push_layer
+    pub fn add_layer(&mut self, name: &str, visible: bool) -> Layer {
+        Layer(self.serializer_context.register_layer(name, visible))
+    }
+
     /// Attempt to write the document to a PDF.
-    pub fn finish(mut self) -> KrillaResult<Vec<u8>> {
+    pub fn finish(self) -> KrillaResult<Vec<u8>> {
+        self.finish_impl(vec![], false)
+    }
+
+    /// Attempt to write the document to a PDF and stream it to the given writer.
+    ///
+    /// Instead of assembling the whole file in memory first, objects are written to
+    /// the writer one by one and dropped right after, which can considerably reduce the
+    /// peak memory usage for large documents. Consider wrapping the writer in a
+    /// [`BufWriter`] if each write to it is expensive.
+    ///
+    /// Object streams, linearization and encryption require rewriting the whole file,
+    /// so if any of them is enabled, the document is still assembled in memory.
+    ///
+    /// The document is validated before anything is written, so if it can't be exported,
+    /// the writer is left untouched. Only if writing to the writer itself fails, parts of
+    /// the document might already have been written to it.
+    ///
+    /// [`BufWriter`]: std::io::BufWriter
+    pub fn finish_to_writer<W: Write>(self, writer: W) -> KrillaResult<()> {
+        self.finish_impl(writer, true)?;
+
+        Ok(())
+    }
+
+    fn finish_impl<W: Write>(mut self, mut writer: W, streamed: bool) -> KrillaResult<W> {
         // Write empty page if none has been created yet.
         if self.serializer_context.page_infos().is_empty() {
             self.start_page();
         }
 
-        Ok(self.serializer_context.finish()?.finish())
+        let serialize_settings = self.serializer_context.serialize_settings();
+        #[cfg(feature = "encryption")]
+        let encrypt = serialize_settings.encryption.is_some();
+        #[cfg(not(feature = "encryption"))]
+        let encrypt = false;
+
+        if serialize_settings.use_object_streams || serialize_settings.linearize || encrypt {
+            let rewritten = {
+                let pdf = self.serializer_context.finish(vec![], false)?;
+
+                rewrite(
+                    &pdf,
+                    RewriteSettings {
+                        object_streams: serialize_settings.use_object_streams,
+                        linearize: serialize_settings.linearize,
+                        compression_level: serialize_settings.content_compression_level(),
+                        #[cfg(feature = "encryption")]
+                        encryption: serialize_settings.encryption.clone(),
+                    },
+                )?
+            };
+
+            writer
+                .write_all(&rewritten)
+                .map_err(|e| KrillaError::IoError(e.to_string()))?;
+
+            Ok(writer)
+        } else {
+            self.serializer_context.finish(writer, streamed)
+        }
+    }
+}
+
+/// A layer of a document.
+///
+/// Can be created via [`Document::add_layer`]. A layer may only be used with
+/// the document that it was created with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Layer(Ref);
+
+impl Layer {
+    pub(crate) fn ref_(&self) -> Ref {
+        self.0
     }
 }
 
@@ -101,6 +369,16 @@ pub struct PageSettings {
     page_label: PageLabel,
     /// The size of the surface.
     surface_size: Size,
+    /// The clockwise rotation of the page in degrees.
+    rotation: i32,
+    /// The crop box of the page.
+    crop_box: Option<Rect>,
+    /// The bleed box of the page.
+    bleed_box: Option<Rect>,
+    /// The trim box of the page.
+    trim_box: Option<Rect>,
+    /// The art box of the page.
+    art_box: Option<Rect>,
 }
 
 impl PageSettings {
@@ -133,6 +411,62 @@ impl PageSettings {
         self
     }
 
+    /// Change the crop box.
+    ///
+    /// The crop box defines the region the page should be clipped to when displaying
+    /// or printing it. It needs to be contained within the media box, otherwise
+    /// export will fail.
+    pub fn with_crop_box(mut self, crop_box: Rect) -> PageSettings {
+        self.crop_box = Some(crop_box);
+        self
+    }
+
+    /// Change the bleed box.
+    ///
+    /// The bleed box defines the region the page should be clipped to in a production
+    /// environment, which usually includes some extra area to account for inaccuracies
+    /// when cutting. It needs to be contained within the media box, otherwise export will fail.
+    pub fn with_bleed_box(mut self, bleed_box: Rect) -> PageSettings {
+        self.bleed_box = Some(bleed_box);
+        self
+    }
+
+    /// Change the trim box.
+    ///
+    /// The trim box defines the intended dimensions of the finished page after trimming.
+    /// It needs to be contained within the media box, otherwise export will fail.
+    pub fn with_trim_box(mut self, trim_box: Rect) -> PageSettings {
+        self.trim_box = Some(trim_box);
+        self
+    }
+
+    /// Change the art box.
+    ///
+    /// The art box defines the extent of the meaningful content of the page.
+    /// It needs to be contained within the media box, otherwise export will fail.
+    pub fn with_art_box(mut self, art_box: Rect) -> PageSettings {
+        self.art_box = Some(art_box);
+        self
+    }
+
+    /// Change the rotation of the page.
+    ///
+    /// The rotation is applied clockwise when displaying or printing the page and
+    /// does not affect the coordinate system of the page, i.e. the contents and annotations
+    /// of the page are still drawn in the unrotated page space.
+    ///
+    /// Returns an error if `degrees` is not a multiple of 90.
+    pub fn with_rotation(mut self, degrees: i32) -> KrillaResult<PageSettings> {
+        if degrees % 90 != 0 {
+            return Err(KrillaError::UserError(format!(
+                "the page rotation must be a multiple of 90, but is {degrees}"
+            )));
+        }
+
+        self.rotation = degrees.rem_euclid(360);
+        Ok(self)
+    }
+
     /// The current media box.
     pub(crate) fn media_box(&self) -> Option<Rect> {
         self.media_box
@@ -147,6 +481,31 @@ impl PageSettings {
     pub(crate) fn page_label(&self) -> &PageLabel {
         &self.page_label
     }
+
+    /// The current rotation.
+    pub(crate) fn rotation(&self) -> i32 {
+        self.rotation
+    }
+
+    /// The current crop box.
+    pub(crate) fn crop_box(&self) -> Option<Rect> {
+        self.crop_box
+    }
+
+    /// The current bleed box.
+    pub(crate) fn bleed_box(&self) -> Option<Rect> {
+        self.bleed_box
+    }
+
+    /// The current trim box.
+    pub(crate) fn trim_box(&self) -> Option<Rect> {
+        self.trim_box
+    }
+
+    /// The current art box.
+    pub(crate) fn art_box(&self) -> Option<Rect> {
+        self.art_box
+    }
 }
 
 impl Default for PageSettings {
@@ -159,15 +518,502 @@ impl Default for PageSettings {
             media_box: Some(Rect::from_xywh(0.0, 0.0, width, height).unwrap()),
             surface_size: Size::from_wh(width, height).unwrap(),
             page_label: PageLabel::default(),
+            rotation: 0,
+            crop_box: None,
+            bleed_box: None,
+            trim_box: None,
+            art_box: None,
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::metadata::{DateTime, Metadata};
-    use crate::Document;
+    use std::io::Write;
+
+    use crate::action::{JavaScriptAction, LaunchAction, NamedAction, NamedActionKind};
+    use crate::error::KrillaError;
+    use crate::metadata::xmp_writer::Namespace;
+    use crate::metadata::{DateTime, Metadata, Trapped};
+    use crate::page::Page;
+    use crate::tests::{rect_to_path, red_fill};
+    use crate::validation::{ValidationError, Validator};
+    use crate::{Document, PageSettings, SerializeSettings};
     use krilla_macros::snapshot;
+    use pdf_writer::{Name, Ref};
+
+    fn draw_page(page: &mut Page, index: usize) {
+        let mut surface = page.surface();
+        let offset = (index % 10) as f32 * 10.0;
+        surface.fill_path(
+            &rect_to_path(offset, offset, offset + 50.0, offset + 50.0),
+            red_fill(1.0),
+        );
+        surface.finish();
+    }
+
+    fn add_pages(document: &mut Document, num_pages: usize) {
+        for i in 0..num_pages {
+            let mut page = document.start_page_with(PageSettings::new(200.0, 200.0));
+            draw_page(&mut page, i);
+            page.finish();
+        }
+    }
+
+    fn streamed_document() -> Document {
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        add_pages(&mut document, 50);
+
+        document
+    }
+
+    /// A writer that keeps track of the largest buffer it was handed at once.
+    #[derive(Default)]
+    struct RecordingWriter {
+        buf: Vec<u8>,
+        max_write: usize,
+    }
+
+    impl Write for RecordingWriter {
+        fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+            self.max_write = self.max_write.max(data.len());
+            self.buf.extend_from_slice(data);
+            Ok(data.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn finish_to_writer_streams_objects() {
+        let expected = streamed_document().finish().unwrap();
+
+        let mut writer = RecordingWriter::default();
+        streamed_document().finish_to_writer(&mut writer).unwrap();
+
+        assert_eq!(writer.buf, expected);
+        assert!(writer.max_write < expected.len() / 10);
+    }
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn finish_to_writer_io_error() {
+        assert_eq!(
+            streamed_document().finish_to_writer(FailingWriter),
+            Err(KrillaError::IoError("disk full".to_string()))
+        );
+    }
+
+    #[test]
+    fn finish_to_writer_validation_error() {
+        let mut document = Document::new_with(SerializeSettings {
+            validator: Validator::UA1,
+            ..SerializeSettings::settings_1()
+        });
+        add_pages(&mut document, 5);
+
+        let mut writer = RecordingWriter::default();
+        let result = document.finish_to_writer(&mut writer);
+
+        let Err(KrillaError::ValidationError(errors)) = result else {
+            panic!("expected a validation error, got {result:?}");
+        };
+        assert!(errors.contains(&ValidationError::NoDocumentTitle));
+        assert!(writer.buf.is_empty());
+    }
+
+    fn page_tree_pdf(num_pages: usize, max_page_tree_kids: Option<usize>) -> String {
+        let mut document = Document::new_with(SerializeSettings {
+            max_page_tree_kids,
+            ..SerializeSettings::settings_1()
+        });
+        add_pages(&mut document, num_pages);
+
+        String::from_utf8_lossy(&document.finish().unwrap()).to_string()
+    }
+
+    // Return the number of kids of each page tree node.
+    fn page_tree_kids(pdf: &str) -> Vec<usize> {
+        pdf.split(" obj\n")
+            .filter(|o| o.contains("/Type /Pages"))
+            .map(|o| {
+                let kids = &o[o.find("/Kids [").unwrap()..];
+                kids[..kids.find(']').unwrap()].matches(" R").count()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn page_tree_flat_below_threshold() {
+        let pdf = page_tree_pdf(20, Some(32));
+
+        assert_eq!(page_tree_kids(&pdf), vec![20]);
+        assert_eq!(pdf, page_tree_pdf(20, None));
+    }
+
+    #[test]
+    fn page_tree_balanced_above_threshold() {
+        let pdf = page_tree_pdf(100, Some(4));
+        let kids = page_tree_kids(&pdf);
+
+        assert!(kids.len() > 1);
+        assert!(kids.iter().all(|k| *k <= 4));
+        assert_eq!(pdf.matches("/Count 100").count(), 1);
+        // Each page is the kid of exactly one node.
+        assert_eq!(pdf.matches("/Type /Page\n").count(), 100);
+        #[cfg(feature = "verify")]
+        assert_eq!(crate::verify::verify(pdf.as_bytes()), Ok(()));
+    }
+
+    #[test]
+    fn reserved_objects_in_xref() {
+        let mut document = Document::new();
+        let first = document.reserve_object();
+        let second = document.reserve_object();
+        assert_eq!((first.get(), second.get()), (1, 2));
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(!pdf.contains("\n1 0 obj") && !pdf.contains("\n2 0 obj"));
+        assert!(pdf.contains("\n3 0 obj"));
+
+        let xref = &pdf[pdf.rfind("xref\n").unwrap()..];
+        let entries = xref.lines().skip(2).take(4).collect::<Vec<_>>();
+        assert!(entries[0].ends_with("65535 f"));
+        assert!(entries[1].ends_with("00000 f"));
+        assert!(entries[2].ends_with("00000 f"));
+        assert!(entries[3].ends_with("00000 n"));
+    }
+
+    #[test]
+    fn reserved_objects_with_linearize() {
+        let mut document = Document::new_with(SerializeSettings {
+            linearize: true,
+            ..SerializeSettings::settings_1()
+        });
+        document.reserve_object();
+
+        assert!(matches!(document.finish(), Err(KrillaError::UserError(_))));
+    }
+
+    #[cfg(feature = "verify")]
+    #[test]
+    fn import_page() {
+        let mut source = Document::new();
+        let mut page = source.start_page_with(PageSettings::new(200.0, 100.0));
+        draw_page(&mut page, 1);
+        page.finish();
+        let source = source.finish().unwrap();
+
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        document.import_page(&source, 0).unwrap();
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.contains("/Subtype /Form"));
+        assert!(pdf.contains("/MediaBox [0 0 200 100]"));
+        assert_eq!(crate::verify::verify(pdf.as_bytes()), Ok(()));
+    }
+
+    #[test]
+    fn reproducible_output() {
+        let export = || {
+            let mut document = Document::new_with(SerializeSettings {
+                document_id: Some(std::array::from_fn(|i| i as u8)),
+                ..SerializeSettings::default()
+            });
+            metadata_impl(&mut document);
+
+            for i in 0..3 {
+                let mut page = document.start_page();
+                draw_page(&mut page, i);
+                page.finish();
+            }
+
+            document.finish().unwrap()
+        };
+
+        let first = export();
+        let second = export();
+
+        assert_eq!(first, second);
+        assert!(String::from_utf8_lossy(&first).contains("/ID [(AAECAwQFBgcICQoLDA0ODw==) ("));
+    }
+
+    #[cfg(feature = "simple-text")]
+    #[test]
+    fn compression_levels() {
+        use crate::font::Font;
+        use crate::surface::TextDirection;
+        use crate::tests::NOTO_SANS;
+        use crate::Compression;
+        use tiny_skia_path::Point;
+
+        let export = |compression| {
+            let mut document = Document::new_with(SerializeSettings {
+                ascii_compatible: false,
+                compression,
+                plain_content_streams: false,
+                ..SerializeSettings::settings_1()
+            });
+            let mut page = document.start_page();
+            let mut surface = page.surface();
+            let font = Font::new(NOTO_SANS.clone(), 0, true).unwrap();
+
+            for i in 0..50 {
+                surface.fill_text(
+                    Point::from_xy(0.0, i as f32 * 10.0),
+                    Default::default(),
+                    font.clone(),
+                    8.0,
+                    &[],
+                    "The quick brown fox jumps over the lazy dog.",
+                    false,
+                    TextDirection::Auto,
+                );
+            }
+
+            surface.finish();
+            page.finish();
+            document.finish().unwrap()
+        };
+
+        let none = export(Compression::None);
+        let fast = export(Compression::Fast);
+        let best = export(Compression::Best);
+
+        assert!(!String::from_utf8_lossy(&none).contains("/FlateDecode"));
+        assert!(fast.len() < none.len());
+        assert!(best.len() <= fast.len());
+    }
+
+    #[cfg(feature = "simple-text")]
+    #[test]
+    #[allow(deprecated)]
+    fn compress_content_streams_disables_all_compression() {
+        use crate::font::Font;
+        use crate::surface::TextDirection;
+        use crate::tests::NOTO_SANS;
+        use tiny_skia_path::Point;
+
+        let mut document = Document::new_with(SerializeSettings {
+            compress_content_streams: false,
+            ..SerializeSettings::default()
+        });
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        surface.fill_text(
+            Point::from_xy(0.0, 100.0),
+            Default::default(),
+            Font::new(NOTO_SANS.clone(), 0, true).unwrap(),
+            16.0,
+            &[],
+            "hello world",
+            false,
+            TextDirection::Auto,
+        );
+        surface.finish();
+        page.finish();
+
+        let pdf = document.finish().unwrap();
+        assert!(!String::from_utf8_lossy(&pdf).contains("/FlateDecode"));
+    }
+
+    #[cfg(feature = "pdf")]
+    #[test]
+    fn import_page_deduplicate_streams() {
+        // Two different files that contain the same page.
+        let sources = ["First", "Second"].map(|title| {
+            let mut source = Document::new();
+            source.set_metadata(Metadata::new().title(title.to_string()));
+            let mut page = source.start_page_with(PageSettings::new(200.0, 100.0));
+            draw_page(&mut page, 1);
+            page.finish();
+            source.finish().unwrap()
+        });
+
+        let import = |deduplicate_streams| {
+            let mut document = Document::new_with(SerializeSettings {
+                deduplicate_streams,
+                ..SerializeSettings::settings_1()
+            });
+
+            for source in &sources {
+                document.import_page(source, 0).unwrap();
+            }
+
+            document.finish().unwrap()
+        };
+
+        let pdf = import(false);
+        assert_eq!(
+            String::from_utf8_lossy(&pdf)
+                .matches("/Subtype /Form")
+                .count(),
+            2
+        );
+
+        let pdf = import(true);
+        assert_eq!(
+            String::from_utf8_lossy(&pdf)
+                .matches("/Subtype /Form")
+                .count(),
+            1
+        );
+        #[cfg(feature = "verify")]
+        assert_eq!(crate::verify::verify(&pdf), Ok(()));
+    }
+
+    #[cfg(feature = "pdf")]
+    #[test]
+    fn import_page_invalid() {
+        let mut source = Document::new();
+        source.start_page().finish();
+        let source = source.finish().unwrap();
+
+        let mut document = Document::new();
+        assert!(matches!(
+            document.import_page(b"not a pdf", 0),
+            Err(KrillaError::UserError(_))
+        ));
+        assert!(matches!(
+            document.import_page(&source, 1),
+            Err(KrillaError::UserError(_))
+        ));
+    }
+
+    #[snapshot(document)]
+    fn document_need_appearances(document: &mut Document) {
+        document.set_need_appearances(true);
+    }
+
+    #[test]
+    fn raw_object_in_catalog() {
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        let data = document.write_raw_object(|chunk, ref_| {
+            chunk.stream(ref_, b"custom data");
+        });
+        let custom = document.write_raw_object(|chunk, ref_| {
+            chunk
+                .indirect(ref_)
+                .dict()
+                .pair(Name(b"Type"), Name(b"Custom"))
+                .pair(Name(b"Data"), data);
+        });
+        document.set_catalog_entry("Custom".to_string(), custom);
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        let catalog = &pdf[pdf.find("/Type /Catalog").unwrap()..];
+        let custom_ref = catalog
+            .lines()
+            .find_map(|l| l.trim().strip_prefix("/Custom "))
+            .unwrap();
+        let custom_num = custom_ref.strip_suffix(" 0 R").unwrap();
+        let custom_obj = &pdf[pdf.find(&format!("\n{custom_num} 0 obj")).unwrap()..];
+
+        assert!(custom_obj.contains("/Type /Custom"));
+        let data_num = custom_obj
+            .lines()
+            .find_map(|l| l.trim().strip_prefix("/Data "))
+            .and_then(|l| l.strip_suffix(" 0 R"))
+            .unwrap();
+        let data_obj = &pdf[pdf.find(&format!("\n{data_num} 0 obj")).unwrap()..];
+        assert!(data_obj.contains("custom data"));
+    }
+
+    fn open_action_obj(pdf: &str) -> &str {
+        let catalog = &pdf[pdf.find("/Type /Catalog").unwrap()..];
+        let action_num = catalog
+            .lines()
+            .find_map(|l| l.trim().strip_prefix("/OpenAction "))
+            .and_then(|l| l.strip_suffix(" 0 R"))
+            .unwrap();
+
+        &pdf[pdf.find(&format!("\n{action_num} 0 obj")).unwrap()..]
+    }
+
+    #[test]
+    fn open_action_javascript() {
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        document.set_open_action(JavaScriptAction::new("app.alert('Hi');".to_string()).into());
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        let action = open_action_obj(&pdf);
+
+        assert!(action.contains("/S /JavaScript"));
+        assert!(action.contains("/JS (app.alert\\('Hi'\\);)"));
+    }
+
+    #[test]
+    fn open_action_named() {
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        document.start_page().finish();
+        document.set_open_action(NamedAction::new(NamedActionKind::LastPage).into());
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        let action = open_action_obj(&pdf);
+
+        assert!(action.contains("/S /Named"));
+        assert!(action.contains("/N /LastPage"));
+    }
+
+    #[test]
+    fn open_action_javascript_pdfa() {
+        let mut document = Document::new_with(SerializeSettings::settings_7());
+        document.set_open_action(JavaScriptAction::new("app.alert('Hi');".to_string()).into());
+
+        assert_eq!(
+            document.finish(),
+            Err(KrillaError::ValidationError(vec![
+                ValidationError::JavaScript
+            ]))
+        );
+    }
+
+    #[test]
+    fn open_action_launch_pdfa() {
+        let mut document = Document::new_with(SerializeSettings::settings_7());
+        document.set_open_action(LaunchAction::new("readme.txt".to_string()).into());
+
+        assert_eq!(
+            document.finish(),
+            Err(KrillaError::ValidationError(vec![
+                ValidationError::LaunchAction
+            ]))
+        );
+    }
+
+    #[test]
+    fn raw_object_with_dangling_reference() {
+        let mut document = Document::new();
+        let custom = document.write_raw_object(|chunk, ref_| {
+            chunk
+                .indirect(ref_)
+                .dict()
+                .pair(Name(b"Other"), Ref::new(100));
+        });
+        document.set_catalog_entry("Custom".to_string(), custom);
+
+        assert!(matches!(document.finish(), Err(KrillaError::UserError(_))));
+    }
 
     #[snapshot(document)]
     fn empty_document(_: &mut Document) {}
@@ -214,8 +1060,80 @@ mod tests {
         metadata_impl(document);
     }
 
+    #[test]
+    fn xmp_hook_custom_namespace() {
+        let mut document = Document::new_with(SerializeSettings::settings_5());
+        document.set_metadata(Metadata::new().title("A title".to_string()));
+        document.set_xmp_hook(|xmp| {
+            xmp.element(
+                "department",
+                Namespace::Custom(("Example", "ex", "http://example.com/ns/")),
+            )
+            .value("Accounting");
+        });
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.contains("http://example.com/ns/"));
+        assert!(pdf.contains("<ex:department>Accounting</ex:department>"));
+        // The properties written by krilla should still be present.
+        assert!(pdf.contains("<xmpMM:InstanceID>"));
+        assert!(pdf.contains("<dc:format>application/pdf</dc:format>"));
+    }
+
     #[snapshot(document, settings_16)]
     fn pdf_version_14(document: &mut Document) {
         metadata_impl(document);
     }
+
+    #[test]
+    fn metadata_trapped() {
+        let mut document = Document::new_with(SerializeSettings::settings_5());
+        document.set_metadata(Metadata::new().trapped(Trapped::False));
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.contains("/Trapped /False"));
+        assert!(pdf.contains("<pdf:Trapped>False</pdf:Trapped>"));
+    }
+
+    #[test]
+    fn metadata_custom_info() {
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        document.set_metadata(
+            Metadata::new()
+                .custom_info("Department".to_string(), "Sales".to_string())
+                .custom_info("DocId".to_string(), "1".to_string())
+                .custom_info("DocId".to_string(), "2".to_string())
+                // Ignored, since it's written by krilla itself.
+                .custom_info("Title".to_string(), "A title".to_string()),
+        );
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.contains("/Department (Sales)"));
+        assert!(pdf.contains("/DocId (2)"));
+        assert!(!pdf.contains("/DocId (1)"));
+        assert!(!pdf.contains("/Title"));
+    }
+
+    #[test]
+    fn metadata_custom_info_pdfa() {
+        let mut document = Document::new_with(SerializeSettings::settings_7());
+        document.set_metadata(
+            Metadata::new()
+                .title("A title".to_string())
+                .custom_info("Department".to_string(), "Sales".to_string()),
+        );
+
+        assert_eq!(
+            document.finish(),
+            Err(KrillaError::ValidationError(vec![
+                ValidationError::CustomDocumentInfo
+            ]))
+        );
+    }
 }
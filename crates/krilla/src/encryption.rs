@@ -0,0 +1,585 @@
+//! Encrypting PDF documents.
+//!
+//! krilla supports protecting documents with the standard security handler of PDF,
+//! which allows you to set a user password that is required for opening the document
+//! as well as an owner password that is required for lifting the restrictions
+//! defined by the [`Permissions`]. You can enable encryption by setting
+//! [`SerializeSettings::encryption`].
+//!
+//! The following algorithms are supported:
+//! - RC4 with a 128-bit key (revision 3 of the standard security handler).
+//! - AES with a 128-bit key (revision 4), which requires at least PDF 1.6.
+//! - AES with a 256-bit key (revision 6), which requires at least PDF 1.7.
+//!
+//! Note that an encrypted PDF is always binary, even if [`SerializeSettings::ascii_compatible`]
+//! is enabled. In addition to that, encryption is forbidden by PDF/A, so trying to export
+//! an encrypted document with a PDF/A validator will fail with a validation error.
+//!
+//! [`SerializeSettings::encryption`]: crate::SerializeSettings::encryption
+//! [`SerializeSettings::ascii_compatible`]: crate::SerializeSettings::ascii_compatible
+
+use std::fmt::Write;
+
+use aes::{Aes128, Aes256};
+use cbc::cipher::block_padding::{NoPadding, Pkcs7};
+use cbc::cipher::{BlockEncryptMut, KeyIvInit};
+use md5::{Digest, Md5};
+use sha2::{Sha256, Sha384, Sha512};
+
+use crate::error::{KrillaError, KrillaResult};
+use crate::util::hex;
+
+/// The settings for encrypting a document.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EncryptionSettings {
+    /// The password that is required for opening the document. Can be empty,
+    /// in which case the document can be opened without entering a password, but
+    /// the permissions still apply.
+    pub user_password: String,
+    /// The password that is required for lifting the restrictions defined by the
+    /// permissions.
+    ///
+    /// If `None`, a random owner password will be generated, meaning that
+    /// the restrictions can't be lifted at all.
+    pub owner_password: Option<String>,
+    /// The permissions that are granted to users who open the document with the
+    /// user password.
+    pub permissions: Permissions,
+    /// The encryption algorithm that should be used.
+    pub algorithm: EncryptionAlgorithm,
+}
+
+/// The permissions of a user who opened an encrypted document with the user password.
+///
+/// Note that it's up to the PDF viewer to respect these permissions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Permissions {
+    /// Whether the document may be printed.
+    pub print: bool,
+    /// Whether text and graphics may be copied from the document.
+    ///
+    /// Independently of this setting, extracting content for accessibility
+    /// purposes is always allowed, as it's required by PDF/UA.
+    pub copy: bool,
+    /// Whether the document may be modified, including adding annotations
+    /// and filling in form fields.
+    pub modify: bool,
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Self {
+            print: true,
+            copy: true,
+            modify: true,
+        }
+    }
+}
+
+impl Permissions {
+    /// The value of the `P` entry of the encryption dictionary.
+    fn to_p(self) -> i32 {
+        // Bits 7-8 and 13-32 are reserved and must be set.
+        let mut p = 0xFFFF_F0C0u32;
+        // Extracting text for accessibility purposes.
+        p |= 1 << 9;
+
+        if self.print {
+            // Printing and high-quality printing.
+            p |= (1 << 2) | (1 << 11);
+        }
+
+        if self.modify {
+            // Modifying, annotating, filling in forms and assembling the document.
+            p |= (1 << 3) | (1 << 5) | (1 << 8) | (1 << 10);
+        }
+
+        if self.copy {
+            p |= 1 << 4;
+        }
+
+        p as i32
+    }
+}
+
+/// An encryption algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EncryptionAlgorithm {
+    /// RC4 with a 128-bit key.
+    ///
+    /// RC4 is considered to be insecure, so you should only use it if you need to
+    /// support very old PDF viewers.
+    Rc4_128,
+    /// AES with a 128-bit key.
+    Aes128,
+    /// AES with a 256-bit key.
+    Aes256,
+}
+
+/// The padding string used for deriving keys from passwords, see Algorithm 2.
+const PADDING: [u8; 32] = [
+    0x28, 0xBF, 0x4E, 0x5E, 0x4E, 0x75, 0x8A, 0x41, 0x64, 0x00, 0x4E, 0x56, 0xFF, 0xFA, 0x01, 0x08,
+    0x2E, 0x2E, 0x00, 0xB6, 0xD0, 0x68, 0x3E, 0x80, 0x2F, 0x0C, 0xA9, 0xFE, 0x64, 0x53, 0x69, 0x7A,
+];
+
+/// Derives the keys for the standard security handler and encrypts strings and streams.
+pub(crate) struct SecurityHandler {
+    algorithm: EncryptionAlgorithm,
+    /// The file encryption key.
+    key: Vec<u8>,
+    o: Vec<u8>,
+    u: Vec<u8>,
+    /// The encrypted owner and user key, as well as the encrypted permissions.
+    /// Only used for AES-256.
+    oe_ue_perms: Option<(Vec<u8>, Vec<u8>, Vec<u8>)>,
+    p: i32,
+    rng: Rng,
+}
+
+impl SecurityHandler {
+    /// Create a new security handler. `id` is the first string of the file ID.
+    pub(crate) fn new(settings: &EncryptionSettings, id: &[u8]) -> KrillaResult<Self> {
+        let mut rng = Rng::new()?;
+
+        let owner_password = match &settings.owner_password {
+            Some(owner_password) => owner_password.clone(),
+            None => hex(&rng.next_block()),
+        };
+
+        Ok(Self::new_with(
+            settings.algorithm,
+            &settings.user_password,
+            &owner_password,
+            settings.permissions.to_p(),
+            id,
+            rng,
+        ))
+    }
+
+    fn new_with(
+        algorithm: EncryptionAlgorithm,
+        user_password: &str,
+        owner_password: &str,
+        p: i32,
+        id: &[u8],
+        mut rng: Rng,
+    ) -> Self {
+        match algorithm {
+            EncryptionAlgorithm::Rc4_128 | EncryptionAlgorithm::Aes128 => {
+                let user_password = pad_password(user_password);
+                let owner_password = pad_password(owner_password);
+
+                let o = compute_o(&owner_password, &user_password);
+                let key = compute_key(&user_password, &o, p, id);
+                let u = compute_u(&key, id);
+
+                Self {
+                    algorithm,
+                    key,
+                    o,
+                    u,
+                    oe_ue_perms: None,
+                    p,
+                    rng,
+                }
+            }
+            EncryptionAlgorithm::Aes256 => {
+                let user_password = utf8_password(user_password);
+                let owner_password = utf8_password(owner_password);
+
+                let key = [rng.next_block(), rng.next_block()].concat();
+                let salts = rng.next_block();
+                let (user_validation, user_key) = (&salts[..8], &salts[8..]);
+                let salts = rng.next_block();
+                let (owner_validation, owner_key) = (&salts[..8], &salts[8..]);
+
+                let u = [
+                    &hash_r6(user_password, user_validation, &[])[..],
+                    user_validation,
+                    user_key,
+                ]
+                .concat();
+                let ue = aes256_no_iv(&hash_r6(user_password, user_key, &[]), &key);
+
+                let o = [
+                    &hash_r6(owner_password, owner_validation, &u)[..],
+                    owner_validation,
+                    owner_key,
+                ]
+                .concat();
+                let oe = aes256_no_iv(&hash_r6(owner_password, owner_key, &u), &key);
+
+                let mut perms = [0xFF; 16];
+                perms[..4].copy_from_slice(&p.to_le_bytes());
+                // We always encrypt the metadata.
+                perms[8..12].copy_from_slice(b"Tadb");
+                perms[12..].copy_from_slice(&rng.next_block()[..4]);
+                let perms = aes256_no_iv(&key, &perms);
+
+                Self {
+                    algorithm,
+                    key,
+                    o,
+                    u,
+                    oe_ue_perms: Some((oe, ue, perms)),
+                    p,
+                    rng,
+                }
+            }
+        }
+    }
+
+    /// Encrypt the string or stream data of the object with the given number.
+    pub(crate) fn encrypt(&mut self, data: &[u8], num: u32, gen: u16) -> Vec<u8> {
+        match self.algorithm {
+            EncryptionAlgorithm::Rc4_128 => rc4(&self.object_key(num, gen), data),
+            EncryptionAlgorithm::Aes128 => {
+                let iv = self.rng.next_block();
+                let encrypted =
+                    cbc::Encryptor::<Aes128>::new_from_slices(&self.object_key(num, gen), &iv)
+                        .unwrap()
+                        .encrypt_padded_vec_mut::<Pkcs7>(data);
+
+                [&iv[..], &encrypted].concat()
+            }
+            EncryptionAlgorithm::Aes256 => {
+                let iv = self.rng.next_block();
+                let encrypted = cbc::Encryptor::<Aes256>::new_from_slices(&self.key, &iv)
+                    .unwrap()
+                    .encrypt_padded_vec_mut::<Pkcs7>(data);
+
+                [&iv[..], &encrypted].concat()
+            }
+        }
+    }
+
+    /// Compute the key of an individual object, see Algorithm 1.
+    fn object_key(&self, num: u32, gen: u16) -> Vec<u8> {
+        let mut md5 = Md5::new();
+        md5.update(&self.key);
+        md5.update(&num.to_le_bytes()[..3]);
+        md5.update(gen.to_le_bytes());
+
+        if self.algorithm == EncryptionAlgorithm::Aes128 {
+            md5.update(b"sAlT");
+        }
+
+        // The key length is n + 5 bytes, but at most 16 bytes.
+        md5.finalize()[..16].to_vec()
+    }
+
+    /// Write the encryption dictionary.
+    pub(crate) fn write_dict(&self, buf: &mut String) {
+        buf.push_str("<<\n  /Filter /Standard\n");
+
+        let (v, r, length, cfm) = match self.algorithm {
+            EncryptionAlgorithm::Rc4_128 => (2, 3, 128, None),
+            EncryptionAlgorithm::Aes128 => (4, 4, 128, Some("AESV2")),
+            EncryptionAlgorithm::Aes256 => (5, 6, 256, Some("AESV3")),
+        };
+
+        writeln!(buf, "  /V {v}\n  /R {r}\n  /Length {length}").unwrap();
+
+        if let Some(cfm) = cfm {
+            writeln!(
+                buf,
+                "  /CF <<\n    /StdCF <<\n      /CFM /{cfm}\n      /AuthEvent /DocOpen\n      \
+                 /Length {}\n    >>\n  >>\n  /StmF /StdCF\n  /StrF /StdCF",
+                length / 8
+            )
+            .unwrap();
+        }
+
+        writeln!(buf, "  /O <{}>\n  /U <{}>", hex(&self.o), hex(&self.u)).unwrap();
+
+        if let Some((oe, ue, perms)) = &self.oe_ue_perms {
+            writeln!(
+                buf,
+                "  /OE <{}>\n  /UE <{}>\n  /Perms <{}>",
+                hex(oe),
+                hex(ue),
+                hex(perms)
+            )
+            .unwrap();
+        }
+
+        writeln!(buf, "  /P {}\n>>", self.p).unwrap();
+    }
+}
+
+/// Encode a password for revision 3 and 4, which expect PDFDocEncoding.
+fn pad_password(password: &str) -> [u8; 32] {
+    let mut padded = PADDING;
+    let bytes = password
+        .chars()
+        .map(|c| u8::try_from(c).unwrap_or(b'?'))
+        .take(32)
+        .collect::<Vec<_>>();
+
+    padded[..bytes.len()].copy_from_slice(&bytes);
+    padded[bytes.len()..].copy_from_slice(&PADDING[..32 - bytes.len()]);
+    padded
+}
+
+/// Encode a password for revision 6, which expects UTF-8 with at most 127 bytes.
+fn utf8_password(password: &str) -> &[u8] {
+    let mut len = password.len().min(127);
+
+    while !password.is_char_boundary(len) {
+        len -= 1;
+    }
+
+    &password.as_bytes()[..len]
+}
+
+/// Compute the owner password entry, see Algorithm 3.
+fn compute_o(owner_password: &[u8; 32], user_password: &[u8; 32]) -> Vec<u8> {
+    let mut hash = Md5::digest(owner_password);
+    for _ in 0..50 {
+        hash = Md5::digest(hash);
+    }
+
+    xor_rc4(&hash, user_password)
+}
+
+/// Compute the file encryption key, see Algorithm 2.
+fn compute_key(user_password: &[u8; 32], o: &[u8], p: i32, id: &[u8]) -> Vec<u8> {
+    let mut md5 = Md5::new();
+    md5.update(user_password);
+    md5.update(o);
+    md5.update(p.to_le_bytes());
+    md5.update(id);
+
+    let mut hash = md5.finalize();
+    for _ in 0..50 {
+        hash = Md5::digest(hash);
+    }
+
+    hash.to_vec()
+}
+
+/// Compute the user password entry, see Algorithm 5.
+fn compute_u(key: &[u8], id: &[u8]) -> Vec<u8> {
+    let mut md5 = Md5::new();
+    md5.update(PADDING);
+    md5.update(id);
+
+    let mut u = xor_rc4(key, &md5.finalize());
+    // The remaining 16 bytes are arbitrary.
+    u.extend_from_slice(&PADDING[..16]);
+    u
+}
+
+/// Encrypt the data 20 times with RC4, each time XOR'ing the key with the iteration number.
+fn xor_rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut data = rc4(key, data);
+
+    for i in 1..=19 {
+        let key = key.iter().map(|b| b ^ i).collect::<Vec<_>>();
+        data = rc4(&key, &data);
+    }
+
+    data
+}
+
+/// Compute the hash of a password for revision 6, see Algorithm 2.B.
+fn hash_r6(password: &[u8], salt: &[u8], user_key: &[u8]) -> [u8; 32] {
+    let mut sha = Sha256::new();
+    sha.update(password);
+    sha.update(salt);
+    sha.update(user_key);
+    let mut k = sha.finalize().to_vec();
+
+    let mut round = 0;
+    loop {
+        let k1 = [password, &k[..], user_key].concat().repeat(64);
+        let e = cbc::Encryptor::<Aes128>::new_from_slices(&k[..16], &k[16..32])
+            .unwrap()
+            .encrypt_padded_vec_mut::<NoPadding>(&k1);
+
+        let sum = e[..16].iter().map(|b| *b as u32).sum::<u32>();
+        k = match sum % 3 {
+            0 => Sha256::digest(&e).to_vec(),
+            1 => Sha384::digest(&e).to_vec(),
+            _ => Sha512::digest(&e).to_vec(),
+        };
+
+        round += 1;
+
+        if round >= 64 && (*e.last().unwrap() as i32) <= round - 32 {
+            break;
+        }
+    }
+
+    k[..32].try_into().unwrap()
+}
+
+/// Encrypt data whose length is a multiple of 16 with AES-256 and a zero
+/// initialization vector.
+fn aes256_no_iv(key: &[u8], data: &[u8]) -> Vec<u8> {
+    cbc::Encryptor::<Aes256>::new_from_slices(key, &[0; 16])
+        .unwrap()
+        .encrypt_padded_vec_mut::<NoPadding>(data)
+}
+
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut s: [u8; 256] = std::array::from_fn(|i| i as u8);
+
+    let mut j = 0u8;
+    for (i, k) in key.iter().cycle().take(256).enumerate() {
+        j = j.wrapping_add(s[i]).wrapping_add(*k);
+        s.swap(i, j as usize);
+    }
+
+    let (mut i, mut j) = (0u8, 0u8);
+    data.iter()
+        .map(|b| {
+            i = i.wrapping_add(1);
+            j = j.wrapping_add(s[i as usize]);
+            s.swap(i as usize, j as usize);
+            b ^ s[s[i as usize].wrapping_add(s[j as usize]) as usize]
+        })
+        .collect()
+}
+
+/// A generator for initialization vectors, salts and keys.
+///
+/// It is seeded once by the operating system and then derives new values by
+/// hashing the seed with a counter, which is cheaper than querying the operating
+/// system for each string and stream.
+struct Rng {
+    seed: [u8; 32],
+    counter: u64,
+}
+
+impl Rng {
+    fn new() -> KrillaResult<Self> {
+        let mut seed = [0; 32];
+        getrandom::getrandom(&mut seed).map_err(|e| {
+            KrillaError::UserError(format!(
+                "failed to generate random data for encryption: {e}"
+            ))
+        })?;
+
+        Ok(Self { seed, counter: 0 })
+    }
+
+    fn next_block(&mut self) -> [u8; 16] {
+        let mut sha = Sha256::new();
+        sha.update(self.seed);
+        sha.update(self.counter.to_le_bytes());
+        self.counter += 1;
+
+        sha.finalize()[..16].try_into().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encryption::{rc4, EncryptionAlgorithm, EncryptionSettings, Permissions};
+    use crate::error::KrillaError;
+    use crate::metadata::Metadata;
+    use crate::validation::ValidationError;
+    use crate::version::PdfVersion;
+    use crate::{Document, SerializeSettings};
+
+    fn encrypted_pdf(algorithm: EncryptionAlgorithm) -> Vec<u8> {
+        let mut document = Document::new_with(SerializeSettings {
+            encryption: Some(EncryptionSettings {
+                user_password: "user".to_string(),
+                owner_password: Some("owner".to_string()),
+                permissions: Permissions {
+                    print: true,
+                    copy: false,
+                    modify: false,
+                },
+                algorithm,
+            }),
+            ..SerializeSettings::settings_1()
+        });
+        document.set_metadata(Metadata::new().title("A secret title".to_string()));
+        document.finish().unwrap()
+    }
+
+    #[test]
+    fn encryption_rc4_known_answer() {
+        assert_eq!(
+            rc4(b"Key", b"Plaintext"),
+            [0xBB, 0xF3, 0x16, 0xE8, 0xD9, 0x40, 0xAF, 0x0A, 0xD3]
+        );
+    }
+
+    #[test]
+    fn encryption_rc4() {
+        let pdf = encrypted_pdf(EncryptionAlgorithm::Rc4_128);
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(!pdf.contains("A secret title"));
+        assert!(pdf.contains("/Filter /Standard\n  /V 2\n  /R 3\n  /Length 128"));
+        // Printing and accessibility are allowed, everything else is not.
+        assert!(pdf.contains(&format!("/P {}", 0xFFFF_FAC4u32 as i32)));
+        assert!(pdf.contains("/Encrypt "));
+    }
+
+    #[test]
+    fn encryption_aes128() {
+        let pdf = encrypted_pdf(EncryptionAlgorithm::Aes128);
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(!pdf.contains("A secret title"));
+        assert!(pdf.contains("/CFM /AESV2"));
+    }
+
+    #[test]
+    fn encryption_aes256() {
+        let pdf = encrypted_pdf(EncryptionAlgorithm::Aes256);
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(!pdf.contains("A secret title"));
+        assert!(pdf.contains("/V 5\n  /R 6\n  /Length 256"));
+        assert!(pdf.contains("/CFM /AESV3"));
+        assert!(pdf.contains("/ExtensionLevel 3"));
+    }
+
+    #[test]
+    fn encryption_aes_requires_pdf16() {
+        let mut document = Document::new_with(SerializeSettings {
+            pdf_version: PdfVersion::Pdf15,
+            encryption: Some(EncryptionSettings {
+                user_password: String::new(),
+                owner_password: None,
+                permissions: Permissions::default(),
+                algorithm: EncryptionAlgorithm::Aes128,
+            }),
+            ..SerializeSettings::settings_1()
+        });
+        document.start_page();
+
+        assert_eq!(
+            document.finish(),
+            Err(KrillaError::UserError(
+                "AES-128 encryption requires at least PDF 1.6".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn encryption_pdfa() {
+        let document = Document::new_with(SerializeSettings {
+            encryption: Some(EncryptionSettings {
+                user_password: String::new(),
+                owner_password: None,
+                permissions: Permissions::default(),
+                algorithm: EncryptionAlgorithm::Aes128,
+            }),
+            ..SerializeSettings::settings_7()
+        });
+
+        assert_eq!(
+            document.finish(),
+            Err(KrillaError::ValidationError(vec![
+                ValidationError::Encryption
+            ]))
+        );
+    }
+}
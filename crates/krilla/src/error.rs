@@ -22,6 +22,13 @@ pub enum KrillaError {
     ///
     /// [`SerializeSettings`]: crate::SerializeSettings
     ValidationError(Vec<ValidationError>),
+    /// The content streams of the document exceeded the budget (in bytes) that
+    /// was set in [`SerializeSettings::max_content_bytes`].
+    ///
+    /// [`SerializeSettings::max_content_bytes`]: crate::SerializeSettings::max_content_bytes
+    ContentBudgetExceeded(usize),
+    /// Writing the PDF to the output failed.
+    IoError(String),
     /// An image couldn't be processed properly.
     #[cfg(feature = "raster-images")]
     ImageError(crate::image::Image),
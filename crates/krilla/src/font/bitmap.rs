@@ -18,7 +18,14 @@ pub(crate) fn draw_glyph(font: Font, glyph: GlyphId, surface: &mut Surface) -> O
 
     let bitmap_strikes = BitmapStrikes::new(font.font_ref());
 
-    let bitmap_glyph = bitmap_strikes.iter().filter_map(|s| s.get(glyph)).last()?;
+    let candidates = bitmap_strikes
+        .iter()
+        .filter_map(|s| s.get(glyph))
+        .collect::<Vec<_>>();
+    let ppems = candidates.iter().map(|g| g.ppem_y).collect::<Vec<_>>();
+    let bitmap_glyph = candidates
+        .into_iter()
+        .nth(select_strike(&ppems, font.bitmap_strike_size())?)?;
     let upem = metrics.units_per_em as f32;
 
     match bitmap_glyph.data {
@@ -67,6 +74,47 @@ pub(crate) fn draw_glyph(font: Font, glyph: GlyphId, surface: &mut Surface) -> O
     }
 }
 
+/// Return the size of the strike that should be used for glyphs that are drawn with
+/// the given number of pixels per em, or `None` if the font doesn't have multiple
+/// strikes to choose from.
+pub(crate) fn strike_size(font: &Font, ppem: f32) -> Option<f32> {
+    let ppems = BitmapStrikes::new(font.font_ref())
+        .iter()
+        .map(|s| s.ppem())
+        .collect::<Vec<_>>();
+
+    if ppems.len() < 2 {
+        return None;
+    }
+
+    select_strike(&ppems, Some(ppem)).map(|i| ppems[i])
+}
+
+/// Select the index of the strike that should be used for the given target size.
+///
+/// Without a target size, the largest strike is chosen. Otherwise, we choose the
+/// smallest strike that is at least as large as the target size, or the largest
+/// strike if all of them are smaller.
+fn select_strike(ppems: &[f32], target: Option<f32>) -> Option<usize> {
+    let largest = ppems
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, _)| i)?;
+
+    let Some(target) = target else {
+        return Some(largest);
+    };
+
+    ppems
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| **p >= target)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, _)| i)
+        .or(Some(largest))
+}
+
 mod utils {
     // Copyright 2024 the Vello Authors
     // SPDX-License-Identifier: Apache-2.0 OR MIT
@@ -446,8 +494,87 @@ mod utils {
 #[cfg(test)]
 mod tests {
     use crate::document::Document;
-    use crate::tests::{all_glyphs_to_pdf, NOTO_COLOR_EMOJI_CBDT};
+    use crate::font::bitmap::select_strike;
+    use crate::font::Font;
+    use crate::path::Fill;
+    use crate::surface::TextDirection;
+    use crate::tests::{all_glyphs_to_pdf, NOTO_COLOR_EMOJI_CBDT, NOTO_COLOR_EMOJI_CBDT_STRIKES};
+    use crate::SerializeSettings;
     use krilla_macros::visreg;
+    use tiny_skia_path::Point;
+
+    // The font contains a strike with 109 and one with 27 pixels per em.
+    const EMOJIS: &str = "☀☄☕☘☠☹⚡⚽";
+
+    fn draw_emojis(document: &mut Document, font: Font, sizes: &[f32]) {
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        let mut y = 0.0;
+
+        for size in sizes {
+            y += size * 1.5;
+            surface.fill_text(
+                Point::from_xy(10.0, y),
+                Fill::default(),
+                font.clone(),
+                *size,
+                &[],
+                EMOJIS,
+                false,
+                TextDirection::Auto,
+            );
+        }
+
+        surface.finish();
+        page.finish();
+    }
+
+    fn strikes_pdf(font: Font, font_size: f32) -> String {
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        draw_emojis(&mut document, font, &[font_size]);
+
+        String::from_utf8_lossy(&document.finish().unwrap()).into_owned()
+    }
+
+    fn strikes_font() -> Font {
+        Font::new(NOTO_COLOR_EMOJI_CBDT_STRIKES.clone(), 0, true).unwrap()
+    }
+
+    #[test]
+    fn bitmap_strike_small_font_size() {
+        let pdf = strikes_pdf(strikes_font(), 12.0);
+
+        assert!(pdf.contains("/Width 34\n"));
+        assert!(!pdf.contains("/Width 136\n"));
+    }
+
+    #[test]
+    fn bitmap_strike_large_font_size() {
+        let pdf = strikes_pdf(strikes_font(), 40.0);
+
+        assert!(pdf.contains("/Width 136\n"));
+        assert!(!pdf.contains("/Width 34\n"));
+    }
+
+    #[test]
+    fn bitmap_strike_explicit_size() {
+        let pdf = strikes_pdf(strikes_font().with_bitmap_strike_size(109.0), 12.0);
+
+        assert!(pdf.contains("/Width 136\n"));
+        assert!(!pdf.contains("/Width 34\n"));
+    }
+
+    #[test]
+    fn bitmap_strike_selection() {
+        let ppems = [20.0, 109.0, 40.0, 64.0];
+
+        assert_eq!(select_strike(&ppems, None), Some(1));
+        assert_eq!(select_strike(&ppems, Some(12.0)), Some(0));
+        assert_eq!(select_strike(&ppems, Some(30.0)), Some(2));
+        assert_eq!(select_strike(&ppems, Some(64.0)), Some(3));
+        assert_eq!(select_strike(&ppems, Some(200.0)), Some(1));
+        assert_eq!(select_strike(&[], Some(20.0)), None);
+    }
 
     // We don't run on pdf.js because it leads to a high pixel difference in CI
     // for some reason.
@@ -457,6 +584,11 @@ mod tests {
         all_glyphs_to_pdf(font_data, None, false, true, document);
     }
 
+    #[visreg(document, pdfium, mupdf, pdfbox, ghostscript, poppler, quartz)]
+    fn noto_color_emoji_cbdt_strikes(document: &mut Document) {
+        draw_emojis(document, strikes_font(), &[12.0, 24.0, 40.0]);
+    }
+
     #[cfg(target_os = "macos")]
     #[visreg(document, all)]
     fn apple_color_emoji(document: &mut Document) {
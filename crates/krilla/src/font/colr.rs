@@ -315,6 +315,7 @@ impl ColorPainter for ColrBuilder {
                     spread_method: extend.to_spread_method(),
                     transform,
                     anti_alias: false,
+                    interpolation: Default::default(),
                 };
 
                 Some(Fill {
@@ -355,6 +356,7 @@ impl ColorPainter for ColrBuilder {
                     spread_method: extend.to_spread_method(),
                     transform,
                     anti_alias: false,
+                    interpolation: Default::default(),
                 };
 
                 Some(Fill {
@@ -388,10 +390,12 @@ impl ColorPainter for ColrBuilder {
                     cy: c0.y,
                     start_angle,
                     end_angle,
+                    rotation: 0.0,
                     stops: stops.into(),
                     spread_method: extend.to_spread_method(),
                     transform,
                     anti_alias: false,
+                    interpolation: Default::default(),
                 };
 
                 Some(Fill {
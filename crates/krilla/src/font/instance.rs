@@ -0,0 +1,308 @@
+//! Creating static instances of variable fonts.
+//!
+//! Neither PDF nor the subsetter support font variations, so in order to use a variable
+//! font, we bake the variation coordinates into a new static font. Currently, this only
+//! works for fonts with TrueType outlines: Each glyph is drawn at the given location and
+//! written back into a new `glyf` table, and the advance widths are written into a new
+//! `hmtx` table. All tables that are related to variations are dropped.
+//!
+//! This has a couple of limitations: Composite glyphs are decomposed, hinting instructions
+//! of glyphs are dropped and font-wide metrics (like the ascender) always use the values
+//! of the default instance.
+
+use skrifa::instance::Size;
+use skrifa::outline::{DrawSettings, OutlinePen};
+use skrifa::raw::TableProvider;
+use skrifa::{FontRef, GlyphId, MetadataProvider, Tag};
+
+/// Tables that only make sense for variable fonts or that depend on the
+/// original outlines, and thus need to be dropped.
+const DROPPED_TABLES: &[&[u8; 4]] = &[
+    b"avar", b"cvar", b"fvar", b"gvar", b"HVAR", b"MVAR", b"VVAR", b"STAT", b"hdmx", b"LTSH",
+    b"VDMX", b"DSIG",
+];
+
+/// Tables that will be rewritten.
+const REWRITTEN_TABLES: &[&[u8; 4]] = &[b"glyf", b"loca", b"hmtx", b"head", b"hhea", b"maxp"];
+
+/// Bake the given variation coordinates into a new static font.
+///
+/// Returns `None` if the font couldn't be read or doesn't contain TrueType outlines.
+pub(crate) fn instance(data: &[u8], index: u32, coords: &[(Tag, f32)]) -> Option<Vec<u8>> {
+    let font_ref = FontRef::from_index(data, index).ok()?;
+    // Fonts with CFF2 outlines are not supported yet.
+    font_ref.glyf().ok()?;
+
+    let location = font_ref.axes().location(coords.iter().copied());
+    let num_glyphs = font_ref.maxp().ok()?.num_glyphs();
+    let outline_glyphs = font_ref.outline_glyphs();
+    let glyph_metrics = font_ref.glyph_metrics(Size::unscaled(), &location);
+
+    let mut glyf = vec![];
+    let mut loca = vec![];
+    let mut hmtx = vec![];
+    let mut max_points = 0;
+    let mut max_contours = 0;
+
+    for glyph_id in 0..num_glyphs {
+        let glyph_id = GlyphId::new(u32::from(glyph_id));
+        let mut pen = ContourPen::default();
+        let mut advance_width = None;
+
+        if let Some(outline_glyph) = outline_glyphs.get(glyph_id) {
+            let metrics = outline_glyph
+                .draw(
+                    DrawSettings::unhinted(Size::unscaled(), &location),
+                    &mut pen,
+                )
+                .ok()?;
+            // For TrueType outlines, the advance width is derived from the
+            // phantom points, which also have variations applied to them.
+            advance_width = metrics.advance_width;
+        }
+
+        // TrueType outlines can't contain cubic curves.
+        if pen.has_cubics {
+            return None;
+        }
+
+        let advance_width = advance_width
+            .or_else(|| glyph_metrics.advance_width(glyph_id))
+            .unwrap_or(0.0);
+
+        loca.extend((glyf.len() as u32).to_be_bytes());
+        let x_min = write_glyph(&pen.contours, &mut glyf);
+        // Keep all glyphs 4-byte aligned.
+        glyf.resize(glyf.len().next_multiple_of(4), 0);
+
+        hmtx.extend((advance_width.round().clamp(0.0, u16::MAX as f32) as u16).to_be_bytes());
+        hmtx.extend(x_min.to_be_bytes());
+
+        max_points = max_points.max(pen.contours.iter().map(Vec::len).sum::<usize>());
+        max_contours = max_contours.max(pen.contours.len());
+    }
+
+    loca.extend((glyf.len() as u32).to_be_bytes());
+
+    let mut tables = vec![];
+
+    for record in font_ref.table_directory.table_records() {
+        let tag = record.tag();
+
+        if DROPPED_TABLES.iter().any(|t| Tag::new(t) == tag) {
+            continue;
+        }
+
+        let mut table = font_ref.data_for_tag(tag)?.as_bytes().to_vec();
+
+        if tag == Tag::new(b"head") {
+            if table.len() < 54 {
+                return None;
+            }
+
+            // Reset the checksum adjustment, we recompute it once the whole font is written.
+            table[8..12].copy_from_slice(&[0; 4]);
+            // We always write a long `loca` table.
+            table[50..52].copy_from_slice(&1_i16.to_be_bytes());
+        } else if tag == Tag::new(b"hhea") {
+            if table.len() < 36 {
+                return None;
+            }
+
+            // Our `hmtx` table contains a full entry for each glyph.
+            table[34..36].copy_from_slice(&num_glyphs.to_be_bytes());
+        } else if tag == Tag::new(b"maxp") && table.len() >= 32 {
+            let max_points = u16::try_from(max_points).unwrap_or(u16::MAX);
+            let max_contours = u16::try_from(max_contours).unwrap_or(u16::MAX);
+
+            table[6..8].copy_from_slice(&max_points.to_be_bytes());
+            table[8..10].copy_from_slice(&max_contours.to_be_bytes());
+            // Composite glyphs have been decomposed, so reset all composite-related values.
+            table[10..14].copy_from_slice(&[0; 4]);
+            table[28..32].copy_from_slice(&[0; 4]);
+        } else if REWRITTEN_TABLES.iter().any(|t| Tag::new(t) == tag) {
+            continue;
+        }
+
+        tables.push((tag, table));
+    }
+
+    tables.push((Tag::new(b"glyf"), glyf));
+    tables.push((Tag::new(b"loca"), loca));
+    tables.push((Tag::new(b"hmtx"), hmtx));
+
+    Some(write_font(tables))
+}
+
+/// Write a simple glyph to the `glyf` table and return its minimum x coordinate.
+fn write_glyph(contours: &[Vec<Point>], glyf: &mut Vec<u8>) -> i16 {
+    let points = contours.iter().flatten();
+
+    // Empty glyphs are represented by an empty entry in the `loca` table.
+    let Some(x_min) = points.clone().map(|p| p.x).min() else {
+        return 0;
+    };
+    let y_min = points.clone().map(|p| p.y).min().unwrap_or(0);
+    let x_max = points.clone().map(|p| p.x).max().unwrap_or(0);
+    let y_max = points.clone().map(|p| p.y).max().unwrap_or(0);
+
+    glyf.extend((contours.len() as i16).to_be_bytes());
+    for value in [x_min, y_min, x_max, y_max] {
+        glyf.extend(value.to_be_bytes());
+    }
+
+    let mut end_point = 0;
+    for contour in contours {
+        end_point += contour.len();
+        glyf.extend((end_point as u16 - 1).to_be_bytes());
+    }
+
+    // We don't keep any instructions.
+    glyf.extend(0_u16.to_be_bytes());
+
+    const ON_CURVE_POINT: u8 = 0x01;
+    const OVERLAP_SIMPLE: u8 = 0x40;
+
+    for (i, point) in points.clone().enumerate() {
+        let mut flags = if point.on_curve { ON_CURVE_POINT } else { 0 };
+
+        // Variable fonts usually contain overlapping contours, which
+        // some rasterizers need to be told about.
+        if i == 0 {
+            flags |= OVERLAP_SIMPLE;
+        }
+
+        glyf.push(flags);
+    }
+
+    // Coordinates are stored as deltas to the previous point. For simplicity,
+    // we always use the long format.
+    let mut last = 0;
+    for point in points.clone() {
+        glyf.extend(point.x.wrapping_sub(last).to_be_bytes());
+        last = point.x;
+    }
+
+    let mut last = 0;
+    for point in points {
+        glyf.extend(point.y.wrapping_sub(last).to_be_bytes());
+        last = point.y;
+    }
+
+    x_min
+}
+
+/// Assemble a font file from its tables.
+fn write_font(mut tables: Vec<(Tag, Vec<u8>)>) -> Vec<u8> {
+    tables.sort_by_key(|(tag, _)| *tag);
+
+    let num_tables = tables.len() as u16;
+    let entry_selector = num_tables.checked_ilog2().unwrap_or(0) as u16;
+    let search_range = (1 << entry_selector) * 16;
+    let range_shift = num_tables * 16 - search_range;
+
+    let mut font = vec![];
+    font.extend(0x00010000_u32.to_be_bytes());
+    font.extend(num_tables.to_be_bytes());
+    font.extend(search_range.to_be_bytes());
+    font.extend(entry_selector.to_be_bytes());
+    font.extend(range_shift.to_be_bytes());
+
+    let mut offset = 12 + 16 * tables.len();
+    let mut head_offset = None;
+
+    for (tag, table) in &tables {
+        if *tag == Tag::new(b"head") {
+            head_offset = Some(offset);
+        }
+
+        font.extend(tag.to_be_bytes());
+        font.extend(checksum(table).to_be_bytes());
+        font.extend((offset as u32).to_be_bytes());
+        font.extend((table.len() as u32).to_be_bytes());
+
+        offset += table.len().next_multiple_of(4);
+    }
+
+    for (_, table) in &tables {
+        font.extend(table);
+        font.resize(font.len().next_multiple_of(4), 0);
+    }
+
+    if let Some(head_offset) = head_offset {
+        let adjustment = 0xB1B0AFBA_u32.wrapping_sub(checksum(&font));
+        font[head_offset + 8..head_offset + 12].copy_from_slice(&adjustment.to_be_bytes());
+    }
+
+    font
+}
+
+/// Calculate the checksum of a table or a whole font.
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0_u32, |sum, chunk| {
+        let mut bytes = [0; 4];
+        bytes[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(bytes))
+    })
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Point {
+    x: i16,
+    y: i16,
+    on_curve: bool,
+}
+
+impl Point {
+    fn new(x: f32, y: f32, on_curve: bool) -> Self {
+        Self {
+            x: x.round() as i16,
+            y: y.round() as i16,
+            on_curve,
+        }
+    }
+}
+
+/// A pen that collects the contours of a TrueType glyph.
+#[derive(Default)]
+struct ContourPen {
+    contours: Vec<Vec<Point>>,
+    has_cubics: bool,
+}
+
+impl ContourPen {
+    fn push(&mut self, point: Point) {
+        if let Some(contour) = self.contours.last_mut() {
+            contour.push(point);
+        }
+    }
+}
+
+impl OutlinePen for ContourPen {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.contours.push(vec![Point::new(x, y, true)]);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.push(Point::new(x, y, true));
+    }
+
+    fn quad_to(&mut self, cx0: f32, cy0: f32, x: f32, y: f32) {
+        self.push(Point::new(cx0, cy0, false));
+        self.push(Point::new(x, y, true));
+    }
+
+    fn curve_to(&mut self, _: f32, _: f32, _: f32, _: f32, _: f32, _: f32) {
+        self.has_cubics = true;
+    }
+
+    fn close(&mut self) {
+        // Contours are implicitly closed in TrueType, so we don't need
+        // the closing point if it's the same as the start point.
+        if let Some(contour) = self.contours.last_mut() {
+            if contour.len() > 1 && contour.first() == contour.last() {
+                contour.pop();
+            }
+        }
+    }
+}
@@ -13,11 +13,12 @@
 use std::fmt::{Debug, Formatter};
 use std::hash::{Hash, Hasher};
 use std::ops::Range;
+use std::path::Path;
 use std::sync::Arc;
 
 use skrifa::prelude::{LocationRef, Size};
 use skrifa::raw::types::NameId;
-use skrifa::raw::TableProvider;
+use skrifa::raw::{FileRef, TableProvider};
 use skrifa::{FontRef, MetadataProvider};
 use tiny_skia_path::{FiniteF32, Rect, Transform};
 use yoke::{Yoke, Yokeable};
@@ -29,11 +30,13 @@ use crate::util::{Prehashed, RectWrapper};
 use skrifa::instance::Location;
 use skrifa::metrics::GlyphMetrics;
 
-pub use skrifa::GlyphId;
+pub use pdf_writer::types::FontFlags;
+pub use skrifa::{GlyphId, Tag};
 
 #[cfg(feature = "raster-images")]
 pub(crate) mod bitmap;
 pub(crate) mod colr;
+pub(crate) mod instance;
 pub(crate) mod outline;
 #[cfg(feature = "svg")]
 pub(crate) mod svg;
@@ -75,15 +78,77 @@ impl Font {
         Font::new_with_info(data, Arc::new(font_info))
     }
 
+    /// Create a new font by reading the file at the given path.
+    ///
+    /// This is a shorthand for reading the file and passing its data to [`Font::new`].
+    /// Returns `None` if the file couldn't be read, the index is invalid or the font
+    /// couldn't be parsed.
+    pub fn from_path(path: impl AsRef<Path>, index: u32, allow_color: bool) -> Option<Self> {
+        let data = std::fs::read(path).ok()?;
+
+        Font::new(Arc::new(data), index, allow_color)
+    }
+
+    /// Create a static instance of a variable font.
+    ///
+    /// krilla can't embed variable fonts directly, so this creates a new font from the
+    /// font at the given index in `data`, with the variation coordinates baked in. Each
+    /// coordinate consists of the tag of an axis and its value in user space (for example
+    /// `(Tag::new(b"wght"), 700.0)`). Values are clamped to the range of the axis, and axes
+    /// that aren't given keep their default value. The resulting font can be embedded as a
+    /// normal CID font.
+    ///
+    /// Currently, only fonts with TrueType outlines (`glyf`) are supported. Note that
+    /// hinting instructions are dropped and that font-wide metrics (like the ascender)
+    /// always use the values of the default instance.
+    ///
+    /// Returns `None` if the index is invalid, the font couldn't be read or it doesn't
+    /// contain TrueType outlines.
+    pub fn instance(data: &[u8], index: u32, coords: &[(Tag, f32)]) -> Option<Self> {
+        let data = instance::instance(data, index, coords)?;
+
+        Font::new(Arc::new(data), 0, true)
+    }
+
+    /// Return the number of faces in some font data.
+    ///
+    /// This is 1 for a single font and the number of fonts for a TrueType collection.
+    /// Each value in `0..num_faces` is a valid index for [`Font::new`]. Returns 0 if the
+    /// data is neither a font nor a collection.
+    pub fn num_faces(data: &[u8]) -> u32 {
+        match FileRef::new(data) {
+            Ok(FileRef::Font(_)) => 1,
+            Ok(FileRef::Collection(collection)) => collection.len(),
+            Err(_) => 0,
+        }
+    }
+
+    /// Return the PostScript names of all faces in some font data.
+    ///
+    /// The position of a name in the vector corresponds to the index of the face, so this
+    /// can be used to find the index of a specific font in a TrueType collection. Faces
+    /// without a PostScript name are represented by an empty string.
+    pub fn postscript_names(data: &[u8]) -> Vec<String> {
+        (0..Font::num_faces(data))
+            .map(|index| {
+                FontRef::from_index(data, index)
+                    .ok()
+                    .and_then(|font_ref| postscript_name(&font_ref))
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
     pub(crate) fn new_with_info(
         data: Arc<dyn AsRef<[u8]> + Send + Sync>,
         font_info: Arc<FontInfo>,
     ) -> Option<Self> {
+        let index = font_info.index;
         let font_ref_yoke =
             Yoke::<FontRefYoke<'static>, Arc<dyn AsRef<[u8]> + Send + Sync>>::attach_to_cart(
                 data.clone(),
                 |data| {
-                    let font_ref = FontRef::from_index(data.as_ref(), 0).unwrap();
+                    let font_ref = FontRef::from_index(data.as_ref(), index).unwrap();
                     FontRefYoke {
                         font_ref: font_ref.clone(),
                         glyph_metrics: font_ref
@@ -99,6 +164,168 @@ impl Font {
         }))))
     }
 
+    /// Override the flags that are written to the font descriptor of the font.
+    ///
+    /// By default, krilla derives the flags (for example whether the font is serif
+    /// or italic) from the metadata of the font. For fonts with incorrect metadata
+    /// (or symbol fonts), this can lead to viewers picking the wrong encoding,
+    /// in which case you can set them manually with this method.
+    ///
+    /// Note that this creates a new instance of the font, which will be embedded
+    /// separately from fonts with different flags.
+    pub fn with_descriptor_flags(self, flags: FontFlags) -> Font {
+        let mut font_info = (*self.font_info()).clone();
+        font_info.descriptor_flags = Some(flags.bits());
+
+        // Can't fail, since the font has already been parsed successfully before.
+        Font::new_with_info(self.font_data(), Arc::new(font_info)).unwrap()
+    }
+
+    /// Set the size (in pixels per em) for which bitmap glyphs of the font should
+    /// be embedded.
+    ///
+    /// Bitmap color fonts (using the `sbix` or `CBDT` table) can contain multiple strikes
+    /// of the same glyph at different sizes. krilla picks the smallest strike that is at
+    /// least as large as the given size, falling back to the largest strike if there is
+    /// no such strike. By default, the size that the glyphs end up with on the page
+    /// (i.e. the font size scaled by the current transform) is used, which means that
+    /// text drawn at different sizes can end up using different strikes. Use this method to always use the same strike instead, for
+    /// example a larger one if the document will be printed at a high resolution.
+    ///
+    /// Note that this creates a new instance of the font, which will be embedded
+    /// separately from fonts with a different size.
+    pub fn with_bitmap_strike_size(self, ppem: f32) -> Font {
+        let mut font_info = (*self.font_info()).clone();
+        font_info.bitmap_strike_size = FiniteF32::new(ppem);
+
+        // Can't fail, since the font has already been parsed successfully before.
+        Font::new_with_info(self.font_data(), Arc::new(font_info)).unwrap()
+    }
+
+    /// Write an explicit `/CIDToGIDMap` stream for the font instead of using the
+    /// `/Identity` mapping.
+    ///
+    /// This only has an effect for TrueType-based fonts, since the mapping doesn't exist for
+    /// CFF-based fonts. Since krilla subsets all fonts, the explicit mapping will contain
+    /// the same information as the identity mapping, but some consumers of PDF files
+    /// (for example pre-press tools that process fonts that have been subset externally)
+    /// require it to be present. If you don't need this, you should not use this method,
+    /// since it slightly increases the file size.
+    ///
+    /// Note that this creates a new instance of the font, which will be embedded
+    /// separately from the font without an explicit mapping.
+    pub fn with_explicit_cid_to_gid_map(self) -> Font {
+        let mut font_info = (*self.font_info()).clone();
+        font_info.explicit_cid_to_gid_map = true;
+
+        // Can't fail, since the font has already been parsed successfully before.
+        Font::new_with_info(self.font_data(), Arc::new(font_info)).unwrap()
+    }
+
+    /// Override the `/StemV` value that is written to the font descriptor of the font.
+    ///
+    /// The value is given in glyph space units, i.e. thousandths of an em. By default,
+    /// krilla estimates the thickness of the vertical stems from the weight of the font.
+    /// Some viewers use this value for hinting, so if the estimation is off for your
+    /// font, you can use this method to provide the correct value.
+    ///
+    /// Note that this creates a new instance of the font, which will be embedded
+    /// separately from fonts with a different value.
+    pub fn with_stem_v(self, stem_v: f32) -> Font {
+        let mut font_info = (*self.font_info()).clone();
+        font_info.stem_v = FiniteF32::new(stem_v);
+
+        // Can't fail, since the font has already been parsed successfully before.
+        Font::new_with_info(self.font_data(), Arc::new(font_info)).unwrap()
+    }
+
+    /// Override the width that PDF readers use for glyphs that don't have an explicit width.
+    ///
+    /// The value is given in thousandths of an em. For CID fonts, it is written as the `/DW`
+    /// entry of the CIDFont and defaults to the advance width of the `.notdef` glyph. For
+    /// Type 3 fonts, it is written as the `/MissingWidth` entry of the font descriptor,
+    /// but only if it was set explicitly.
+    ///
+    /// Note that this creates a new instance of the font, which will be embedded
+    /// separately from fonts with a different value.
+    pub fn with_missing_width(self, missing_width: f32) -> Font {
+        let mut font_info = (*self.font_info()).clone();
+        font_info.missing_width = FiniteF32::new(missing_width);
+
+        // Can't fail, since the font has already been parsed successfully before.
+        Font::new_with_info(self.font_data(), Arc::new(font_info)).unwrap()
+    }
+
+    /// Return the `/StemV` value that should be written to the font descriptor.
+    pub(crate) fn stem_v(&self) -> f32 {
+        if let Some(stem_v) = self.0.font_info.stem_v {
+            return stem_v.get();
+        }
+
+        // A common heuristic that maps the weight of the font to the thickness of
+        // its vertical stems, yielding 88 for regular and 166 for bold fonts.
+        (50.0 + (self.weight() / 65.0).powi(2)).round()
+    }
+
+    /// Return the overridden missing width, if there is one.
+    pub(crate) fn missing_width(&self) -> Option<f32> {
+        self.0.font_info.missing_width.map(|w| w.get())
+    }
+
+    pub(crate) fn explicit_cid_to_gid_map(&self) -> bool {
+        self.0.font_info.explicit_cid_to_gid_map
+    }
+
+    pub(crate) fn bitmap_strike_size(&self) -> Option<f32> {
+        self.0.font_info.bitmap_strike_size.map(|s| s.get())
+    }
+
+    /// Return the flags that should be written to the font descriptor.
+    pub(crate) fn descriptor_flags(&self) -> FontFlags {
+        if let Some(bits) = self.0.font_info.descriptor_flags {
+            return FontFlags::from_bits_truncate(bits);
+        }
+
+        let mut flags = FontFlags::empty();
+        flags.set(
+            FontFlags::SERIF,
+            self.postscript_name().is_some_and(|n| n.contains("Serif")),
+        );
+        flags.set(FontFlags::FIXED_PITCH, self.is_monospaced());
+        flags.set(FontFlags::ITALIC, self.italic_angle() != 0.0);
+        flags.insert(FontFlags::SYMBOLIC);
+        flags.insert(FontFlags::SMALL_CAP);
+
+        flags
+    }
+
+    /// Return whether all characters of the string are covered by the font.
+    ///
+    /// A character counts as covered if the `cmap` table of the font maps it to a glyph.
+    /// Note that this only checks for simple character-to-glyph mappings, so whether text
+    /// can actually be rendered correctly might still depend on the shaping process.
+    pub fn covers_str(&self, text: &str) -> bool {
+        let charmap = self.font_ref().charmap();
+        text.chars().all(|c| charmap.map(c).is_some())
+    }
+
+    /// Return all characters of the string that are not covered by the font,
+    /// in the order in which they first appear.
+    ///
+    /// See [`Font::covers_str`] for more information.
+    pub fn missing_chars(&self, text: &str) -> Vec<char> {
+        let charmap = self.font_ref().charmap();
+        let mut missing = vec![];
+
+        for c in text.chars() {
+            if charmap.map(c).is_none() && !missing.contains(&c) {
+                missing.push(c);
+            }
+        }
+
+        missing
+    }
+
     pub(crate) fn postscript_name(&self) -> Option<&str> {
         self.0.font_info.postscript_name.as_deref()
     }
@@ -124,6 +351,15 @@ impl Font {
         self.0.font_info.allow_color
     }
 
+    /// Return the same font with a different `allow_color` flag.
+    pub(crate) fn with_allow_color(self, allow_color: bool) -> Font {
+        let mut font_info = (*self.font_info()).clone();
+        font_info.allow_color = allow_color;
+
+        // Can't fail, since the font has already been parsed successfully before.
+        Font::new_with_info(self.font_data(), Arc::new(font_info)).unwrap()
+    }
+
     pub(crate) fn weight(&self) -> f32 {
         self.0.font_info.weight.get()
     }
@@ -189,7 +425,7 @@ impl Debug for Font {
 /// information, such as the font name and the checksum, and has this instead.
 /// This is much faster, and since we also include the checksum, the odds of two
 /// different fonts ending up with the same hash is pretty much zero.
-#[derive(Debug, Hash, Eq, PartialEq)]
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub(crate) struct FontInfo {
     index: u32,
     checksum: u32,
@@ -205,6 +441,11 @@ pub(crate) struct FontInfo {
     italic_angle: FiniteF32,
     weight: FiniteF32,
     stretch: FiniteF32,
+    descriptor_flags: Option<u32>,
+    bitmap_strike_size: Option<FiniteF32>,
+    explicit_cid_to_gid_map: bool,
+    stem_v: Option<FiniteF32>,
+    missing_width: Option<FiniteF32>,
 }
 
 struct Repr {
@@ -249,21 +490,7 @@ impl FontInfo {
                 units_per_em as f32,
             )?);
 
-        let postscript_name = {
-            if let Ok(name) = font_ref.name() {
-                name.name_record().iter().find_map(|n| {
-                    if n.name_id.get() == NameId::POSTSCRIPT_NAME {
-                        if let Ok(string) = n.string(name.string_data()) {
-                            return Some(string.to_string());
-                        }
-                    }
-
-                    None
-                })
-            } else {
-                None
-            }
-        };
+        let postscript_name = postscript_name(&font_ref);
 
         Some(FontInfo {
             index,
@@ -280,6 +507,11 @@ impl FontInfo {
             stretch,
             italic_angle,
             global_bbox: RectWrapper(global_bbox),
+            descriptor_flags: None,
+            bitmap_strike_size: None,
+            explicit_cid_to_gid_map: false,
+            stem_v: None,
+            missing_width: None,
         })
     }
 }
@@ -446,3 +678,153 @@ impl KrillaGlyph {
         }
     }
 }
+
+/// A glyph with an explicit position.
+///
+/// In contrast to [`Glyph`], which is placed relative to the previous glyph by means of
+/// advances, a positioned glyph stores its position relative to the origin of the run it
+/// belongs to. This is useful if you do your own text layout and already know where
+/// each glyph should end up. See [`Surface::draw_text_run`] for more information.
+///
+/// [`Surface::draw_text_run`]: crate::surface::Surface::draw_text_run
+#[derive(Debug, Clone)]
+pub struct PositionedGlyph {
+    /// The glyph ID of the glyph.
+    pub glyph_id: GlyphId,
+    /// The range in the original text that corresponds to the
+    /// cluster of the glyph.
+    pub text_range: Range<usize>,
+    /// The x position of the glyph in user space units, relative to the origin of the run.
+    pub x: f32,
+    /// The y position of the glyph in user space units, relative to the origin of the run.
+    pub y: f32,
+}
+
+impl PositionedGlyph {
+    /// Create a new positioned glyph.
+    pub fn new(glyph_id: GlyphId, x: f32, y: f32, range: Range<usize>) -> Self {
+        Self {
+            glyph_id,
+            text_range: range,
+            x,
+            y,
+        }
+    }
+}
+
+fn postscript_name(font_ref: &FontRef) -> Option<String> {
+    let name = font_ref.name().ok()?;
+
+    name.name_record().iter().find_map(|n| {
+        if n.name_id.get() == NameId::POSTSCRIPT_NAME {
+            if let Ok(string) = n.string(name.string_data()) {
+                return Some(string.to_string());
+            }
+        }
+
+        None
+    })
+}
+
+/// Return the number of pixels per em of glyphs that are drawn with the given font
+/// size under the given transform, assuming one pixel per point.
+pub(crate) fn ppem(font_size: f32, transform: Transform) -> f32 {
+    let (sx, sy) = transform.get_scale();
+    font_size * sx.max(sy)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::font::Font;
+    use crate::tests::{FONT_PATH, NOTO_SANS, NOTO_SANS_CJK, NOTO_SANS_VARIABLE};
+    use skrifa::raw::TableProvider;
+    use skrifa::{GlyphId, MetadataProvider, Tag};
+
+    #[test]
+    fn font_from_path() {
+        let font = Font::from_path(FONT_PATH.join("NotoSans-Regular.ttf"), 0, true).unwrap();
+
+        assert_eq!(font.postscript_name(), Some("NotoSans-Regular"));
+        assert!(Font::from_path(FONT_PATH.join("NotoSans-Regular.ttf"), 1, true).is_none());
+        assert!(Font::from_path(FONT_PATH.join("does-not-exist.ttf"), 0, true).is_none());
+    }
+
+    #[test]
+    fn font_collection() {
+        let data = std::fs::read(FONT_PATH.join("collection.ttc")).unwrap();
+
+        assert_eq!(Font::num_faces(&data), 2);
+        assert_eq!(
+            Font::postscript_names(&data),
+            ["TwitterColorEmojiSVGinOT", "COLRv1StaticTestGlyphs-Regular"]
+        );
+
+        let font = Font::new(std::sync::Arc::new(data), 1, true).unwrap();
+        assert_eq!(font.index(), 1);
+        assert_eq!(
+            font.postscript_name(),
+            Some("COLRv1StaticTestGlyphs-Regular")
+        );
+        assert_eq!(font.font_ref().maxp().unwrap().num_glyphs(), 221);
+
+        assert!(Font::from_path(FONT_PATH.join("collection.ttc"), 2, true).is_none());
+    }
+
+    #[test]
+    fn font_instance() {
+        let wght = Tag::new(b"wght");
+        let thin = Font::instance(&NOTO_SANS_VARIABLE, 0, &[(wght, 100.0)]).unwrap();
+        let black = Font::instance(&NOTO_SANS_VARIABLE, 0, &[(wght, 900.0)]).unwrap();
+        let glyph = thin.font_ref().charmap().map('H').unwrap();
+
+        assert!(thin.font_ref().fvar().is_err());
+        assert!(thin.font_ref().gvar().is_err());
+        assert!(thin.advance_width(glyph).unwrap() < black.advance_width(glyph).unwrap());
+        assert_eq!(
+            thin.font_ref().maxp().unwrap().num_glyphs(),
+            black.font_ref().maxp().unwrap().num_glyphs()
+        );
+        assert!(thin
+            .font_ref()
+            .outline_glyphs()
+            .get(GlyphId::new(0))
+            .is_some());
+    }
+
+    #[test]
+    fn font_instance_cff() {
+        let wght = Tag::new(b"wght");
+
+        assert!(Font::instance(&NOTO_SANS_CJK, 0, &[(wght, 700.0)]).is_none());
+    }
+
+    #[test]
+    fn font_faces() {
+        assert_eq!(Font::num_faces(&NOTO_SANS), 1);
+        assert_eq!(Font::num_faces(b"not a font"), 0);
+        assert_eq!(
+            Font::postscript_names(&NOTO_SANS),
+            vec!["NotoSans-Regular".to_string()]
+        );
+        assert!(Font::postscript_names(b"not a font").is_empty());
+    }
+
+    #[test]
+    fn font_covers_latin() {
+        let font = Font::new(NOTO_SANS.clone(), 0, true).unwrap();
+
+        assert!(font.covers_str("Hello World!"));
+        assert!(font.missing_chars("Hello World!").is_empty());
+    }
+
+    #[test]
+    fn font_missing_cjk_chars() {
+        let latin = Font::new(NOTO_SANS.clone(), 0, true).unwrap();
+        let cjk = Font::new(NOTO_SANS_CJK.clone(), 0, true).unwrap();
+        let text = "Hi 你好你";
+
+        assert!(!latin.covers_str(text));
+        assert_eq!(latin.missing_chars(text), vec!['你', '好']);
+        assert!(cjk.covers_str(text));
+    }
+}
@@ -110,13 +110,17 @@ std::fs::write("../../target/example.pdf", &pdf).unwrap();
 mod chunk_container;
 mod graphics_state;
 mod object;
+mod output;
 mod resource;
+mod rewrite;
 mod serialize;
 #[cfg(feature = "svg")]
 mod svg;
 mod util;
 
 pub mod document;
+#[cfg(feature = "encryption")]
+pub mod encryption;
 pub mod error;
 pub mod font;
 pub mod geom;
@@ -128,6 +132,8 @@ pub mod stream;
 pub mod surface;
 pub mod tagging;
 pub mod validation;
+#[cfg(feature = "verify")]
+pub mod verify;
 pub mod version;
 
 pub(crate) mod content;
@@ -135,4 +141,6 @@ pub(crate) mod content;
 pub(crate) mod tests;
 
 pub use document::*;
-pub use serialize::{SerializeSettings, SvgSettings};
+pub use serialize::{Compression, NotdefCallback, NotdefFallback, SerializeSettings, SvgSettings};
+#[cfg(feature = "verify")]
+pub use verify::verify;
@@ -4,10 +4,37 @@
 //! you can simply create a [`Metadata`] object, set the data, and then include it
 //! in the document via [`Document::set_metadata`].
 //!
+//! If you need to write XMP properties that krilla doesn't support, you can in addition
+//! register a hook via [`Document::set_xmp_hook`], which gives you direct access to
+//! the [`XmpWriter`] that is used to write the XMP metadata.
+//!
 //! [`Document::set_metadata`]: crate::document::Document::set_metadata
+//! [`Document::set_xmp_hook`]: crate::document::Document::set_xmp_hook
+
+use pdf_writer::types::TrappingStatus;
+use pdf_writer::writers::DocumentInfo;
+use pdf_writer::{Finish, Name, TextStr};
+use xmp_writer::{LangId, Namespace, Timezone};
+
+use crate::validation::Validator;
+
+pub use xmp_writer;
+pub use xmp_writer::XmpWriter;
+
+pub(crate) type XmpHook = Box<dyn Fn(&mut XmpWriter)>;
 
-use pdf_writer::{Pdf, Ref, TextStr};
-use xmp_writer::{LangId, Timezone, XmpWriter};
+/// The keys of the document information dictionary that are written by krilla itself.
+const RESERVED_INFO_KEYS: &[&str] = &[
+    "Title",
+    "Author",
+    "Subject",
+    "Keywords",
+    "Creator",
+    "Producer",
+    "CreationDate",
+    "ModDate",
+    "Trapped",
+];
 
 /// Metadata for a PDF document.
 #[derive(Default, Clone)]
@@ -22,6 +49,8 @@ pub struct Metadata {
     pub(crate) language: Option<String>,
     pub(crate) modification_date: Option<DateTime>,
     pub(crate) creation_date: Option<DateTime>,
+    pub(crate) trapped: Option<Trapped>,
+    pub(crate) custom_info: Vec<(String, String)>,
 }
 
 impl Metadata {
@@ -49,6 +78,10 @@ impl Metadata {
     }
 
     /// The keywords that describe the document.
+    ///
+    /// They are written as a comma-separated list to the `/Keywords` entry of the
+    /// document information dictionary and the `pdf:Keywords` property of the XMP
+    /// metadata. In addition, each keyword is added to the `dc:subject` property.
     pub fn keywords(mut self, keywords: Vec<String>) -> Self {
         if !keywords.is_empty() {
             self.keywords = Some(keywords);
@@ -65,6 +98,10 @@ impl Metadata {
     }
 
     /// The creator tool of the document.
+    ///
+    /// It is written to the `/Creator` entry of the document information dictionary
+    /// as well as to the `xmp:CreatorTool` property of the XMP metadata, so that
+    /// both always agree, as required by PDF/A.
     pub fn creator(mut self, creator: String) -> Self {
         if !creator.is_empty() {
             self.creator = Some(creator);
@@ -73,6 +110,10 @@ impl Metadata {
     }
 
     /// The producer tool of the document.
+    ///
+    /// It is written to the `/Producer` entry of the document information dictionary
+    /// as well as to the `pdf:Producer` property of the XMP metadata, so that
+    /// both always agree, as required by PDF/A.
     pub fn producer(mut self, producer: String) -> Self {
         if !producer.is_empty() {
             self.producer = Some(producer);
@@ -89,6 +130,9 @@ impl Metadata {
     }
 
     /// The creation date of the document.
+    ///
+    /// krilla never writes the current time by itself, so if no date is set,
+    /// none will be written to the document.
     pub fn creation_date(mut self, creation_date: DateTime) -> Self {
         self.creation_date = Some(creation_date);
         self
@@ -109,6 +153,40 @@ impl Metadata {
         self
     }
 
+    /// Whether the document has been trapped.
+    ///
+    /// It is written to the `/Trapped` entry of the document information dictionary
+    /// as well as to the `pdf:Trapped` property of the XMP metadata.
+    pub fn trapped(mut self, trapped: Trapped) -> Self {
+        self.trapped = Some(trapped);
+        self
+    }
+
+    /// Add a custom entry to the document information dictionary.
+    ///
+    /// Setting the same key twice overrides the previous value. Keys of entries that
+    /// are already exposed by other methods of `Metadata` (like `Title` or `Keywords`)
+    /// are ignored.
+    ///
+    /// Note that custom entries are not mirrored in the XMP metadata, so they
+    /// are rejected when exporting to PDF/A. If you need them there, write them
+    /// via [`Document::set_xmp_hook`] together with a corresponding extension schema.
+    ///
+    /// [`Document::set_xmp_hook`]: crate::document::Document::set_xmp_hook
+    pub fn custom_info(mut self, key: String, value: String) -> Self {
+        if key.is_empty() || RESERVED_INFO_KEYS.contains(&key.as_str()) {
+            return self;
+        }
+
+        if let Some(entry) = self.custom_info.iter_mut().find(|(k, _)| *k == key) {
+            entry.1 = value;
+        } else {
+            self.custom_info.push((key, value));
+        }
+
+        self
+    }
+
     pub(crate) fn has_document_info(&self) -> bool {
         self.title.is_some()
             || self.producer.is_some()
@@ -118,6 +196,8 @@ impl Metadata {
             || self.modification_date.is_some()
             || self.creation_date.is_some()
             || self.subject.is_some()
+            || self.trapped.is_some()
+            || !self.custom_info.is_empty()
     }
 
     pub(crate) fn serialize_xmp_metadata(&self, xmp: &mut XmpWriter) {
@@ -125,8 +205,17 @@ impl Metadata {
             xmp.title([(None, title.as_str())]);
         }
 
-        if let Some(subject) = &self.subject {
-            xmp.subject([subject.as_str()]);
+        // `dc:subject` is a bag of descriptive phrases, so both the subject and the
+        // keywords end up there.
+        let subjects = self
+            .subject
+            .iter()
+            .chain(self.keywords.iter().flatten())
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+
+        if !subjects.is_empty() {
+            xmp.subject(subjects);
         }
 
         if let Some(keywords) = &self.keywords {
@@ -163,6 +252,11 @@ impl Metadata {
             xmp.producer(producer);
         }
 
+        if let Some(trapped) = self.trapped {
+            xmp.element("Trapped", Namespace::AdobePdf)
+                .value(trapped.to_xmp());
+        }
+
         if let Some(lang) = &self.language {
             xmp.language([LangId(lang)]);
         }
@@ -176,44 +270,87 @@ impl Metadata {
         }
     }
 
-    pub(crate) fn serialize_document_info(&self, ref_: &mut Ref, pdf: &mut Pdf) {
-        if self.has_document_info() {
-            let ref_ = ref_.bump();
-            let mut document_info = pdf.document_info(ref_);
+    pub(crate) fn serialize_document_info(
+        &self,
+        mut document_info: DocumentInfo,
+        validator: Validator,
+    ) {
+        if let Some(title) = &self.title {
+            document_info.title(TextStr(title));
+        }
 
-            if let Some(title) = &self.title {
-                document_info.title(TextStr(title));
-            }
+        if let Some(subject) = &self.subject {
+            document_info.subject(TextStr(subject));
+        }
 
-            if let Some(subject) = &self.subject {
-                document_info.subject(TextStr(subject));
-            }
+        if let Some(keywords) = &self.keywords {
+            let joined = keywords.join(", ");
+            document_info.keywords(TextStr(&joined));
+        }
 
-            if let Some(keywords) = &self.keywords {
-                let joined = keywords.join(", ");
-                document_info.keywords(TextStr(&joined));
-            }
+        if let Some(authors) = &self.authors {
+            let joined = authors.join(", ");
+            document_info.author(TextStr(&joined));
+        }
 
-            if let Some(authors) = &self.authors {
-                let joined = authors.join(", ");
-                document_info.author(TextStr(&joined));
-            }
+        if let Some(creator) = &self.creator {
+            document_info.creator(TextStr(creator));
+        }
 
-            if let Some(creator) = &self.creator {
-                document_info.creator(TextStr(creator));
-            }
+        if let Some(producer) = &self.producer {
+            document_info.producer(TextStr(producer));
+        }
 
-            if let Some(producer) = &self.producer {
-                document_info.producer(TextStr(producer));
-            }
+        if let Some(date_time) = self.modification_date {
+            document_info.modified_date(pdf_date(date_time));
+        }
 
-            if let Some(date_time) = self.modification_date {
-                document_info.modified_date(pdf_date(date_time));
-            }
+        if let Some(date_time) = self.creation_date {
+            document_info.creation_date(pdf_date(date_time));
+        }
 
-            if let Some(date_time) = self.creation_date {
-                document_info.creation_date(pdf_date(date_time));
-            }
+        if let Some(trapped) = self.trapped {
+            document_info.trapped(trapped.to_pdf());
+        }
+
+        for (key, value) in &self.custom_info {
+            document_info.pair(Name(key.as_bytes()), TextStr(value));
+        }
+
+        if let Some(version) = validator.pdfx_version() {
+            document_info.pair(Name(b"GTS_PDFXVersion"), TextStr(version));
+        }
+
+        document_info.finish();
+    }
+}
+
+/// The trapping status of a document.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Trapped {
+    /// The document has been fully trapped.
+    True,
+    /// The document has not been trapped.
+    False,
+    /// It is unknown whether the document has been trapped, or it has only been
+    /// partially trapped.
+    Unknown,
+}
+
+impl Trapped {
+    fn to_pdf(self) -> TrappingStatus {
+        match self {
+            Trapped::True => TrappingStatus::Trapped,
+            Trapped::False => TrappingStatus::NotTrapped,
+            Trapped::Unknown => TrappingStatus::Unknown,
+        }
+    }
+
+    fn to_xmp(self) -> &'static str {
+        match self {
+            Trapped::True => "True",
+            Trapped::False => "False",
+            Trapped::Unknown => "Unknown",
         }
     }
 }
@@ -3,15 +3,29 @@
 //! PDF has the concept of "actions", which encompass things like navigating to a URL,
 //! opening some file on the system, and so on. The PDF reference defines a whole bunch
 //! of actions, but krilla does not expose nearly all of them, and never will. As of right now,
-//! the only available action is the link action, which allows you to specify a link that
-//! should be opened, when activating the action.
+//! the following actions are available:
+//! - [`LinkAction`]: Opens a URI.
+//! - [`Action::Goto`]: Navigates to a destination in the document.
+//! - [`RemoteGoToAction`]: Navigates to a destination in another PDF file.
+//! - [`LaunchAction`]: Launches an external file. Note that launch actions are forbidden
+//!   in PDF/A.
+//! - [`NamedAction`]: Executes one of the predefined navigation actions of the viewer.
+//! - [`JavaScriptAction`]: Executes a JavaScript script. Note that JavaScript is forbidden
+//!   in PDF/A.
+//!
+//! Actions can be used as the target of annotations or as the action that is executed
+//! when opening the document, see [`Document::set_open_action`].
+//!
+//! [`Document::set_open_action`]: crate::Document::set_open_action
 
 use pdf_writer::types::ActionType;
-use pdf_writer::{Name, Str};
+use pdf_writer::{Name, Str, TextStr};
 
 use crate::destination::Destination;
 use crate::error::KrillaResult;
 use crate::serialize::SerializeContext;
+use crate::validation::ValidationError;
+use crate::version::PdfVersion;
 
 /// A type of action.
 pub enum Action {
@@ -19,6 +33,14 @@ pub enum Action {
     Link(LinkAction),
     /// A go-to action.
     Goto(Destination),
+    /// A remote go-to action.
+    RemoteGoto(RemoteGoToAction),
+    /// A launch action.
+    Launch(LaunchAction),
+    /// A named action.
+    Named(NamedAction),
+    /// A JavaScript action.
+    JavaScript(JavaScriptAction),
 }
 
 impl Action {
@@ -33,6 +55,10 @@ impl Action {
                 let dest_entry = action.action_type(ActionType::GoTo).insert(Name(b"D"));
                 dest.serialize(sc, dest_entry)?
             }
+            Action::RemoteGoto(remote) => remote.serialize(sc, action),
+            Action::Launch(launch) => launch.serialize(sc, action),
+            Action::Named(named) => named.serialize(action),
+            Action::JavaScript(js) => js.serialize(sc, action),
         };
 
         Ok(())
@@ -64,3 +90,193 @@ impl LinkAction {
             .uri(Str(self.uri.as_bytes()));
     }
 }
+
+/// A destination in another PDF file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RemoteDestination {
+    /// The page with the given (zero-based) index. The page will be fit into the window.
+    Page(usize),
+    /// A named destination of the other PDF file.
+    Named(String),
+}
+
+/// A remote go-to action. Will navigate to a destination in another PDF file
+/// when clicked.
+pub struct RemoteGoToAction {
+    file: String,
+    destination: RemoteDestination,
+    new_window: Option<bool>,
+}
+
+impl From<RemoteGoToAction> for Action {
+    fn from(value: RemoteGoToAction) -> Self {
+        Action::RemoteGoto(value)
+    }
+}
+
+impl RemoteGoToAction {
+    /// Create a new remote go-to action. `file` is the path to the other PDF file,
+    /// which will be written verbatim, so relative paths are resolved relative to
+    /// the location of the current document by the viewer.
+    pub fn new(file: String, destination: RemoteDestination) -> Self {
+        Self {
+            file,
+            destination,
+            new_window: None,
+        }
+    }
+
+    /// Set whether the other file should be opened in a new window. If unset,
+    /// the behavior is left to the viewer.
+    pub fn with_new_window(mut self, new_window: bool) -> Self {
+        self.new_window = Some(new_window);
+        self
+    }
+
+    fn serialize(&self, sc: &mut SerializeContext, mut action: pdf_writer::writers::Action) {
+        action.action_type(ActionType::RemoteGoTo);
+        write_file_spec(sc, &mut action, &self.file);
+
+        match &self.destination {
+            RemoteDestination::Page(index) => {
+                // For remote go-to actions, the page is specified as a page index
+                // instead of a reference.
+                action
+                    .insert(Name(b"D"))
+                    .array()
+                    .item(*index as i32)
+                    .item(Name(b"Fit"));
+            }
+            RemoteDestination::Named(name) => {
+                action.pair(Name(b"D"), Str(name.as_bytes()));
+            }
+        }
+
+        if let Some(new_window) = self.new_window {
+            action.new_window(new_window);
+        }
+    }
+}
+
+/// A launch action. Will launch an external file, for example by opening it
+/// with the default application of the system, when clicked.
+pub struct LaunchAction {
+    file: String,
+    new_window: Option<bool>,
+}
+
+impl From<LaunchAction> for Action {
+    fn from(value: LaunchAction) -> Self {
+        Action::Launch(value)
+    }
+}
+
+impl LaunchAction {
+    /// Create a new launch action. `file` is the path to the file that should be
+    /// launched and will be written verbatim.
+    pub fn new(file: String) -> Self {
+        Self {
+            file,
+            new_window: None,
+        }
+    }
+
+    /// Set whether the file should be opened in a new window, in case it is
+    /// a PDF file. If unset, the behavior is left to the viewer.
+    pub fn with_new_window(mut self, new_window: bool) -> Self {
+        self.new_window = Some(new_window);
+        self
+    }
+
+    fn serialize(&self, sc: &mut SerializeContext, mut action: pdf_writer::writers::Action) {
+        sc.register_validation_error(ValidationError::LaunchAction);
+
+        action.action_type(ActionType::Launch);
+        write_file_spec(sc, &mut action, &self.file);
+
+        if let Some(new_window) = self.new_window {
+            action.new_window(new_window);
+        }
+    }
+}
+
+fn write_file_spec(sc: &SerializeContext, action: &mut pdf_writer::writers::Action, file: &str) {
+    let mut file_spec = action.file_spec();
+    file_spec.path(Str(file.as_bytes()));
+
+    if sc.serialize_settings().pdf_version >= PdfVersion::Pdf17 {
+        file_spec.unic_file(TextStr(file));
+    }
+}
+
+/// A predefined navigation action of the PDF viewer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum NamedActionKind {
+    /// Go to the next page of the document.
+    NextPage,
+    /// Go to the previous page of the document.
+    PrevPage,
+    /// Go to the first page of the document.
+    FirstPage,
+    /// Go to the last page of the document.
+    LastPage,
+}
+
+impl NamedActionKind {
+    fn to_pdf(self) -> Name<'static> {
+        match self {
+            NamedActionKind::NextPage => Name(b"NextPage"),
+            NamedActionKind::PrevPage => Name(b"PrevPage"),
+            NamedActionKind::FirstPage => Name(b"FirstPage"),
+            NamedActionKind::LastPage => Name(b"LastPage"),
+        }
+    }
+}
+
+/// A named action. Will execute a predefined navigation action of the viewer
+/// when clicked.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct NamedAction(pub NamedActionKind);
+
+impl From<NamedAction> for Action {
+    fn from(value: NamedAction) -> Self {
+        Action::Named(value)
+    }
+}
+
+impl NamedAction {
+    /// Create a new named action.
+    pub fn new(kind: NamedActionKind) -> Self {
+        Self(kind)
+    }
+
+    fn serialize(&self, mut action: pdf_writer::writers::Action) {
+        action.pair(Name(b"S"), Name(b"Named"));
+        action.pair(Name(b"N"), self.0.to_pdf());
+    }
+}
+
+/// A JavaScript action. Will execute a script when clicked.
+pub struct JavaScriptAction {
+    script: String,
+}
+
+impl From<JavaScriptAction> for Action {
+    fn from(value: JavaScriptAction) -> Self {
+        Action::JavaScript(value)
+    }
+}
+
+impl JavaScriptAction {
+    /// Create a new JavaScript action that will execute the given script.
+    pub fn new(script: String) -> Self {
+        Self { script }
+    }
+
+    fn serialize(&self, sc: &mut SerializeContext, mut action: pdf_writer::writers::Action) {
+        sc.register_validation_error(ValidationError::JavaScript);
+
+        action.pair(Name(b"S"), Name(b"JavaScript"));
+        action.pair(Name(b"JS"), TextStr(&self.script));
+    }
+}
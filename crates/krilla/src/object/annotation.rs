@@ -2,19 +2,61 @@
 //!
 //! PDF has the concept of annotations, which allow you to associate certain regions of
 //! a page with an "annotation". The PDF reference defines many different actions, however,
-//! krilla does not and never will expose all of them. As of right now, the only annotations
-//! that are supported are "link annotations", which allow you associate a certain region of
-//! the page with a link.
+//! krilla does not and never will expose all of them. As of right now, the following annotations
+//! are supported:
+//!
+//! - Link annotations, which allow you associate a certain region of the page with a link.
+//! - Text annotations, which display a note icon that opens a comment when clicked.
+//! - Free text annotations, which display a box of text directly on the page.
+//! - Text markup annotations (strike-out, underline and squiggly), which mark up a region of
+//!   text on the page.
+//! - Caret annotations, which indicate the insertion point of text.
+//! - Square and circle annotations, which draw a rectangle or an ellipse on the page,
+//!   optionally with a cloudy border.
+//! - Line annotations, which draw a straight line with optional [`LineEnding`]s (like
+//!   arrows) on the page.
+//! - Ink, polygon and polyline annotations, which draw freehand lines or shapes made
+//!   of straight lines on the page.
+//! - Stamp annotations, whose appearance is defined by a custom [`Stream`].
+//! - Widget annotations of form fields, see the [`form`] module.
+//!
+//! All annotations except for link annotations are written with an appearance stream,
+//! so that they show up consistently across viewers (which is also required by PDF/A).
+//!
+//! Markup annotations (i.e. all annotations except for link and widget annotations) can in addition
+//! have a [`Popup`] note attached to them and can form reply threads by replying to
+//! another annotation that was added before.
+//!
+//! [`form`]: crate::form
 
-use pdf_writer::types::AnnotationFlags;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use pdf_writer::types::{AnnotationFlags, BorderType};
+#[cfg(feature = "simple-text")]
+use pdf_writer::Str;
 use pdf_writer::{Chunk, Finish, Name, Ref, TextStr};
-use tiny_skia_path::Rect;
+#[cfg(feature = "simple-text")]
+use skrifa::MetadataProvider;
+use tiny_skia_path::{Path, PathBuilder, Point, Rect, Transform};
 
+use crate::color::rgb;
 use crate::error::KrillaResult;
+#[cfg(feature = "simple-text")]
+use crate::font::Font;
 use crate::object::action::Action;
 use crate::object::destination::Destination;
+#[cfg(feature = "simple-text")]
+use crate::object::form::FieldFont;
+use crate::object::form::FormField;
+use crate::object::xobject::XObject;
 use crate::page::page_root_transform;
+use crate::path::{Fill, Stroke, StrokeDash};
 use crate::serialize::SerializeContext;
+use crate::stream::{Stream, StreamBuilder};
+use crate::surface::Surface;
+#[cfg(feature = "simple-text")]
+use crate::surface::TextDirection;
+use crate::tagging::Tag;
 use crate::util::RectExt;
 use crate::validation::ValidationError;
 
@@ -22,7 +64,53 @@ use crate::validation::ValidationError;
 pub struct Annotation {
     pub(crate) annotation_type: AnnotationType,
     pub(crate) alt: Option<String>,
+    pub(crate) language: Option<String>,
     pub(crate) struct_parent: Option<i32>,
+    pub(crate) popup: Option<Popup>,
+    pub(crate) in_reply_to: Option<AnnotationHandle>,
+}
+
+/// A handle to an annotation that has been added to a page.
+///
+/// Can be used to create replies to the annotation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AnnotationHandle {
+    pub(crate) page_index: usize,
+    pub(crate) annotation_index: usize,
+}
+
+impl AnnotationHandle {
+    pub(crate) fn new(page_index: usize, annotation_index: usize) -> Self {
+        Self {
+            page_index,
+            annotation_index,
+        }
+    }
+}
+
+/// A popup note that is attached to a markup annotation.
+pub struct Popup {
+    rect: Rect,
+    author: Option<String>,
+    contents: String,
+    open: bool,
+}
+
+impl Popup {
+    /// Create a new popup.
+    ///
+    /// The `rect` indicates where the popup window should be displayed on the page
+    /// when opened, and `open` whether it should initially be displayed open.
+    /// The `contents` will be used as the text of the annotation the popup
+    /// belongs to, replacing its alt text.
+    pub fn new(rect: Rect, author: Option<String>, contents: String, open: bool) -> Self {
+        Self {
+            rect,
+            author,
+            contents,
+            open,
+        }
+    }
 }
 
 impl Annotation {
@@ -34,52 +122,246 @@ impl Annotation {
         Self {
             annotation_type: AnnotationType::Link(annotation),
             alt: alt_text,
+            language: None,
             struct_parent: None,
+            popup: None,
+            in_reply_to: None,
         }
     }
 }
 
-impl From<LinkAnnotation> for Annotation {
-    fn from(value: LinkAnnotation) -> Self {
+impl Annotation {
+    /// Create a new annotation of any type with some alt text.
+    ///
+    /// Note that the alt text might be required in some cases, for example
+    /// when exporting to PDF/UA.
+    pub fn new(annotation_type: impl Into<AnnotationType>, alt_text: Option<String>) -> Self {
         Self {
-            annotation_type: AnnotationType::Link(value),
-            alt: None,
+            annotation_type: annotation_type.into(),
+            alt: alt_text,
+            language: None,
             struct_parent: None,
+            popup: None,
+            in_reply_to: None,
+        }
+    }
+
+    /// Set the alt text of the annotation, replacing the one that was provided
+    /// when creating it.
+    ///
+    /// The alt text is written as the `/Contents` of the annotation and is required
+    /// for all annotations when exporting to PDF/UA. Note that if the annotation
+    /// has a [`Popup`], its contents will be used instead.
+    pub fn set_alt_text(&mut self, alt_text: String) {
+        self.alt = Some(alt_text);
+    }
+
+    /// Set the natural language of the alt text of the annotation, in the
+    /// form of a language identifier such as `en-US`.
+    ///
+    /// If not set, the language is inherited from the document.
+    pub fn set_language(&mut self, language: String) {
+        self.language = Some(language);
+    }
+
+    /// Attach a popup note to the annotation.
+    ///
+    /// Popups are only supported for markup annotations, i.e. all annotations
+    /// except for link annotations.
+    pub fn set_popup(&mut self, popup: Popup) {
+        self.popup = Some(popup);
+    }
+
+    /// Mark the annotation as a reply to another annotation.
+    ///
+    /// The parent annotation must either be on the same page or on a previous page.
+    /// Otherwise, an error will be returned when finishing the document.
+    pub fn reply_to(&mut self, parent: &AnnotationHandle) {
+        self.in_reply_to = Some(*parent);
+    }
+
+    /// The tag that should be used when automatically including the
+    /// annotation in the tag tree.
+    pub(crate) fn default_tag(&self) -> Tag {
+        match self.annotation_type {
+            AnnotationType::Link(_) => Tag::Link,
+            AnnotationType::Widget(_) => Tag::Form,
+            _ => Tag::Annot,
         }
     }
 }
 
+impl From<LinkAnnotation> for Annotation {
+    fn from(value: LinkAnnotation) -> Self {
+        Self::new(value, None)
+    }
+}
+
+impl From<TextAnnotation> for Annotation {
+    fn from(value: TextAnnotation) -> Self {
+        Self::new(value, None)
+    }
+}
+
+#[cfg(feature = "simple-text")]
+impl From<FreeTextAnnotation> for Annotation {
+    fn from(value: FreeTextAnnotation) -> Self {
+        Self::new(value, None)
+    }
+}
+
+impl From<StrikeOutAnnotation> for Annotation {
+    fn from(value: StrikeOutAnnotation) -> Self {
+        Self::new(value, None)
+    }
+}
+
+impl From<UnderlineAnnotation> for Annotation {
+    fn from(value: UnderlineAnnotation) -> Self {
+        Self::new(value, None)
+    }
+}
+
+impl From<SquigglyAnnotation> for Annotation {
+    fn from(value: SquigglyAnnotation) -> Self {
+        Self::new(value, None)
+    }
+}
+
+impl From<CaretAnnotation> for Annotation {
+    fn from(value: CaretAnnotation) -> Self {
+        Self::new(value, None)
+    }
+}
+
+impl From<StampAnnotation> for Annotation {
+    fn from(value: StampAnnotation) -> Self {
+        Self::new(value, None)
+    }
+}
+
+impl From<SquareAnnotation> for Annotation {
+    fn from(value: SquareAnnotation) -> Self {
+        Self::new(value, None)
+    }
+}
+
+impl From<CircleAnnotation> for Annotation {
+    fn from(value: CircleAnnotation) -> Self {
+        Self::new(value, None)
+    }
+}
+
+impl From<LineAnnotation> for Annotation {
+    fn from(value: LineAnnotation) -> Self {
+        Self::new(value, None)
+    }
+}
+
+impl From<InkAnnotation> for Annotation {
+    fn from(value: InkAnnotation) -> Self {
+        Self::new(value, None)
+    }
+}
+
+impl From<PolygonAnnotation> for Annotation {
+    fn from(value: PolygonAnnotation) -> Self {
+        Self::new(value, None)
+    }
+}
+
+impl From<PolyLineAnnotation> for Annotation {
+    fn from(value: PolyLineAnnotation) -> Self {
+        Self::new(value, None)
+    }
+}
+
 impl Annotation {
+    /// Serialize the annotation. Returns the chunk, as well as the reference of
+    /// the popup annotation, if one exists.
     pub(crate) fn serialize(
         &self,
         sc: &mut SerializeContext,
         root_ref: Ref,
         page_height: f32,
-    ) -> KrillaResult<Chunk> {
+        in_reply_to: Option<Ref>,
+    ) -> KrillaResult<(Chunk, Option<Ref>)> {
         let mut chunk = Chunk::new();
+        let popup_ref = self.popup.as_ref().map(|_| sc.new_ref());
         let mut annotation = chunk
             .indirect(root_ref)
             .start::<pdf_writer::writers::Annotation>();
 
         self.annotation_type
-            .serialize_type(sc, &mut annotation, page_height)?;
+            .serialize_type(sc, &mut annotation, page_height, root_ref)?;
 
         // Only required by PDF/A, but we always write this regardless.
-        annotation.flags(AnnotationFlags::PRINT);
+        let mut flags = AnnotationFlags::PRINT;
+
+        // PDF/A additionally requires the icons of text annotations to
+        // keep their size and orientation.
+        if matches!(self.annotation_type, AnnotationType::Text(_)) {
+            flags |= AnnotationFlags::NO_ZOOM | AnnotationFlags::NO_ROTATE;
+        }
+
+        annotation.flags(flags);
 
         if let Some(struct_parent) = self.struct_parent {
             annotation.struct_parent(struct_parent);
         }
 
-        if let Some(alt_text) = &self.alt {
-            annotation.contents(TextStr(alt_text));
+        let contents = self
+            .annotation_type
+            .contents()
+            .or(self.popup.as_ref().map(|p| p.contents.as_str()))
+            .or(self.alt.as_deref());
+
+        if let Some(contents) = contents {
+            // The alt text of form fields is stored as their tooltip instead.
+            if matches!(self.annotation_type, AnnotationType::Widget(_)) {
+                annotation.pair(Name(b"TU"), TextStr(contents));
+            } else {
+                annotation.contents(TextStr(contents));
+            }
         } else {
             sc.register_validation_error(ValidationError::MissingAnnotationAltText);
         }
 
+        if let Some(language) = &self.language {
+            annotation.pair(Name(b"Lang"), TextStr(language));
+        }
+
+        if let Some(author) = self.popup.as_ref().and_then(|p| p.author.as_ref()) {
+            annotation.pair(Name(b"T"), TextStr(author));
+        }
+
+        if let Some(popup_ref) = popup_ref {
+            annotation.pair(Name(b"Popup"), popup_ref);
+        }
+
+        if let Some(in_reply_to) = in_reply_to {
+            annotation.pair(Name(b"IRT"), in_reply_to);
+        }
+
         annotation.finish();
 
-        Ok(chunk)
+        if let (Some(popup), Some(popup_ref)) = (&self.popup, popup_ref) {
+            let mut popup_annotation = chunk
+                .indirect(popup_ref)
+                .start::<pdf_writer::writers::Annotation>();
+            popup_annotation.pair(Name(b"Subtype"), Name(b"Popup"));
+
+            let actual_rect = popup
+                .rect
+                .transform(page_root_transform(page_height))
+                .unwrap();
+            popup_annotation.rect(actual_rect.to_pdf_rect());
+            popup_annotation.pair(Name(b"Parent"), root_ref);
+            popup_annotation.pair(Name(b"Open"), popup.open);
+            popup_annotation.finish();
+        }
+
+        Ok((chunk, popup_ref))
     }
 }
 
@@ -87,21 +369,278 @@ impl Annotation {
 pub enum AnnotationType {
     /// A link annotation.
     Link(LinkAnnotation),
+    /// A text annotation.
+    Text(TextAnnotation),
+    /// A free text annotation.
+    #[cfg(feature = "simple-text")]
+    FreeText(FreeTextAnnotation),
+    /// A strike-out annotation.
+    StrikeOut(StrikeOutAnnotation),
+    /// An underline annotation.
+    Underline(UnderlineAnnotation),
+    /// A squiggly-underline annotation.
+    Squiggly(SquigglyAnnotation),
+    /// A caret annotation.
+    Caret(CaretAnnotation),
+    /// A stamp annotation.
+    Stamp(StampAnnotation),
+    /// A square annotation.
+    Square(SquareAnnotation),
+    /// A circle annotation.
+    Circle(CircleAnnotation),
+    /// A line annotation.
+    Line(LineAnnotation),
+    /// An ink annotation.
+    Ink(InkAnnotation),
+    /// A polygon annotation.
+    Polygon(PolygonAnnotation),
+    /// A polyline annotation.
+    PolyLine(PolyLineAnnotation),
+    /// A widget annotation of a form field. Usually, you should add form fields
+    /// via [`Page::add_form_field`] instead.
+    ///
+    /// [`Page::add_form_field`]: crate::page::Page::add_form_field
+    Widget(FormField),
 }
 
 impl AnnotationType {
+    /// The contents that are defined by the annotation itself.
+    fn contents(&self) -> Option<&str> {
+        match self {
+            AnnotationType::Text(t) => Some(&t.contents),
+            #[cfg(feature = "simple-text")]
+            AnnotationType::FreeText(f) => Some(&f.contents),
+            _ => None,
+        }
+    }
+
     fn serialize_type(
         &self,
         sc: &mut SerializeContext,
         annotation: &mut pdf_writer::writers::Annotation,
         page_height: f32,
+        root_ref: Ref,
     ) -> KrillaResult<()> {
         match self {
             AnnotationType::Link(l) => l.serialize_type(sc, annotation, page_height),
+            AnnotationType::Widget(w) => w.serialize_type(sc, annotation, page_height, root_ref),
+            AnnotationType::Text(t) => {
+                t.serialize_type(sc, annotation, page_height);
+
+                Ok(())
+            }
+            #[cfg(feature = "simple-text")]
+            AnnotationType::FreeText(f) => {
+                f.serialize_type(sc, annotation, page_height);
+
+                Ok(())
+            }
+            AnnotationType::StrikeOut(s) => {
+                s.0.serialize_type(
+                    sc,
+                    annotation,
+                    page_height,
+                    pdf_writer::types::AnnotationType::StrikeOut,
+                    |surface, quad, stroke| {
+                        let y = quad.top() + quad.height() / 2.0;
+                        draw_line(surface, quad.left(), quad.right(), y, stroke);
+                    },
+                );
+
+                Ok(())
+            }
+            AnnotationType::Underline(u) => {
+                u.0.serialize_type(
+                    sc,
+                    annotation,
+                    page_height,
+                    pdf_writer::types::AnnotationType::Underline,
+                    |surface, quad, stroke| {
+                        let y = quad.bottom() - stroke.width / 2.0;
+                        draw_line(surface, quad.left(), quad.right(), y, stroke);
+                    },
+                );
+
+                Ok(())
+            }
+            AnnotationType::Squiggly(s) => {
+                s.0.serialize_type(
+                    sc,
+                    annotation,
+                    page_height,
+                    pdf_writer::types::AnnotationType::Squiggly,
+                    |surface, quad, stroke| {
+                        let amplitude = stroke.width * 1.5;
+                        let y = quad.bottom() - amplitude;
+
+                        let mut builder = PathBuilder::new();
+                        builder.move_to(quad.left(), y);
+
+                        let mut x = quad.left();
+                        let mut up = true;
+
+                        while x < quad.right() {
+                            x = (x + amplitude * 2.0).min(quad.right());
+                            let offset = if up { -amplitude } else { amplitude };
+                            builder.line_to(x, y + offset);
+                            up = !up;
+                        }
+
+                        if let Some(path) = builder.finish() {
+                            surface.stroke_path(&path, stroke);
+                        }
+                    },
+                );
+
+                Ok(())
+            }
+            AnnotationType::Caret(c) => {
+                c.serialize_type(sc, annotation, page_height);
+
+                Ok(())
+            }
+            AnnotationType::Stamp(s) => {
+                s.serialize_type(sc, annotation, page_height);
+
+                Ok(())
+            }
+            AnnotationType::Square(s) => {
+                s.0.serialize_type(
+                    sc,
+                    annotation,
+                    page_height,
+                    pdf_writer::types::AnnotationType::Square,
+                    false,
+                );
+
+                Ok(())
+            }
+            AnnotationType::Circle(c) => {
+                c.0.serialize_type(
+                    sc,
+                    annotation,
+                    page_height,
+                    pdf_writer::types::AnnotationType::Circle,
+                    true,
+                );
+
+                Ok(())
+            }
+            AnnotationType::Line(l) => {
+                l.serialize_type(sc, annotation, page_height);
+
+                Ok(())
+            }
+            AnnotationType::Ink(i) => {
+                i.serialize_type(sc, annotation, page_height);
+
+                Ok(())
+            }
+            AnnotationType::Polygon(p) => {
+                p.0.serialize_type(sc, annotation, page_height, Name(b"Polygon"), true);
+
+                Ok(())
+            }
+            AnnotationType::PolyLine(p) => {
+                p.0.serialize_type(sc, annotation, page_height, Name(b"PolyLine"), false);
+
+                Ok(())
+            }
         }
     }
 }
 
+impl From<LinkAnnotation> for AnnotationType {
+    fn from(value: LinkAnnotation) -> Self {
+        Self::Link(value)
+    }
+}
+
+impl From<TextAnnotation> for AnnotationType {
+    fn from(value: TextAnnotation) -> Self {
+        Self::Text(value)
+    }
+}
+
+#[cfg(feature = "simple-text")]
+impl From<FreeTextAnnotation> for AnnotationType {
+    fn from(value: FreeTextAnnotation) -> Self {
+        Self::FreeText(value)
+    }
+}
+
+impl From<StrikeOutAnnotation> for AnnotationType {
+    fn from(value: StrikeOutAnnotation) -> Self {
+        Self::StrikeOut(value)
+    }
+}
+
+impl From<UnderlineAnnotation> for AnnotationType {
+    fn from(value: UnderlineAnnotation) -> Self {
+        Self::Underline(value)
+    }
+}
+
+impl From<SquigglyAnnotation> for AnnotationType {
+    fn from(value: SquigglyAnnotation) -> Self {
+        Self::Squiggly(value)
+    }
+}
+
+impl From<CaretAnnotation> for AnnotationType {
+    fn from(value: CaretAnnotation) -> Self {
+        Self::Caret(value)
+    }
+}
+
+impl From<FormField> for AnnotationType {
+    fn from(value: FormField) -> Self {
+        AnnotationType::Widget(value)
+    }
+}
+
+impl From<StampAnnotation> for AnnotationType {
+    fn from(value: StampAnnotation) -> Self {
+        Self::Stamp(value)
+    }
+}
+
+impl From<SquareAnnotation> for AnnotationType {
+    fn from(value: SquareAnnotation) -> Self {
+        Self::Square(value)
+    }
+}
+
+impl From<CircleAnnotation> for AnnotationType {
+    fn from(value: CircleAnnotation) -> Self {
+        Self::Circle(value)
+    }
+}
+
+impl From<LineAnnotation> for AnnotationType {
+    fn from(value: LineAnnotation) -> Self {
+        Self::Line(value)
+    }
+}
+
+impl From<InkAnnotation> for AnnotationType {
+    fn from(value: InkAnnotation) -> Self {
+        Self::Ink(value)
+    }
+}
+
+impl From<PolygonAnnotation> for AnnotationType {
+    fn from(value: PolygonAnnotation) -> Self {
+        Self::Polygon(value)
+    }
+}
+
+impl From<PolyLineAnnotation> for AnnotationType {
+    fn from(value: PolyLineAnnotation) -> Self {
+        Self::PolyLine(value)
+    }
+}
+
 /// An annotation target.
 pub enum Target {
     /// A destination within the document.
@@ -150,70 +689,1708 @@ impl LinkAnnotation {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::document::{Document, PageSettings};
-    use crate::object::action::LinkAction;
-    use crate::object::annotation::{LinkAnnotation, Target};
-    use crate::object::destination::XyzDestination;
-
-    use crate::object::page::Page;
+/// The icon of a text annotation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TextIcon {
+    /// A comment icon.
+    Comment,
+    /// A key icon.
+    Key,
+    /// A note icon.
+    #[default]
+    Note,
+    /// A help icon.
+    Help,
+    /// A new paragraph icon.
+    NewParagraph,
+    /// A paragraph icon.
+    Paragraph,
+    /// An insert icon.
+    Insert,
+}
 
-    use crate::tests::{green_fill, rect_to_path, red_fill};
+impl TextIcon {
+    fn to_pdf_name(self) -> Name<'static> {
+        match self {
+            TextIcon::Comment => Name(b"Comment"),
+            TextIcon::Key => Name(b"Key"),
+            TextIcon::Note => Name(b"Note"),
+            TextIcon::Help => Name(b"Help"),
+            TextIcon::NewParagraph => Name(b"NewParagraph"),
+            TextIcon::Paragraph => Name(b"Paragraph"),
+            TextIcon::Insert => Name(b"Insert"),
+        }
+    }
+}
 
-    use crate::SerializeSettings;
-    use krilla_macros::snapshot;
-    use tiny_skia_path::{Point, Rect};
+/// A text annotation, which displays an icon on the page that opens
+/// a note (like a "sticky note") when clicked.
+pub struct TextAnnotation {
+    rect: Rect,
+    contents: String,
+    icon: TextIcon,
+    open: bool,
+}
 
-    #[snapshot(single_page)]
-    fn annotation_to_link(page: &mut Page) {
-        page.add_annotation(
-            LinkAnnotation::new(
-                Rect::from_xywh(50.0, 50.0, 100.0, 100.0).unwrap(),
-                Target::Action(LinkAction::new("https://www.youtube.com".to_string()).into()),
-            )
-            .into(),
-        );
+impl TextAnnotation {
+    /// Create a new text annotation.
+    ///
+    /// The `rect` indicates where the icon should be displayed on the page, and `open`
+    /// whether the note should initially be displayed open. The `contents` are the text
+    /// of the note, which take precedence over the contents of a [`Popup`] and the
+    /// alt text of the annotation.
+    ///
+    /// Note that krilla always draws the same icon in the appearance stream of the
+    /// annotation, the `icon` only serves as a hint for viewers.
+    pub fn new(rect: Rect, contents: String, icon: TextIcon, open: bool) -> Self {
+        Self {
+            rect,
+            contents,
+            icon,
+            open,
+        }
     }
 
-    #[test]
-    fn annotation_to_invalid_destination() {
-        let mut d = Document::new_with(SerializeSettings::settings_1());
-        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
-        page.add_annotation(
-            LinkAnnotation::new(
-                Rect::from_xywh(50.0, 50.0, 100.0, 100.0).unwrap(),
-                Target::Destination(XyzDestination::new(1, Point::from_xy(100.0, 100.0)).into()),
-            )
-            .into(),
-        );
-        page.finish();
-        assert!(d.finish().is_err())
-    }
+    fn serialize_type(
+        &self,
+        sc: &mut SerializeContext,
+        annotation: &mut pdf_writer::writers::Annotation,
+        page_height: f32,
+    ) {
+        annotation.subtype(pdf_writer::types::AnnotationType::Text);
 
-    #[snapshot(document)]
-    fn annotation_to_destination(d: &mut Document) {
-        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
-        page.add_annotation(
-            LinkAnnotation::new(
-                Rect::from_xywh(50.0, 0.0, 100.0, 100.0).unwrap(),
-                Target::Destination(XyzDestination::new(1, Point::from_xy(100.0, 100.0)).into()),
-            )
-            .into(),
-        );
+        let actual_rect = self
+            .rect
+            .transform(page_root_transform(page_height))
+            .unwrap();
+        annotation.rect(actual_rect.to_pdf_rect());
 
-        let mut surface = page.surface();
-        surface.fill_path(&rect_to_path(50.0, 0.0, 150.0, 100.0), red_fill(1.0));
-        surface.finish();
-        page.finish();
+        let color = rgb::Color::new(255, 220, 80);
+        let [r, g, b] = color.to_pdf_color();
+        annotation.color_rgb(r, g, b);
+        annotation.pair(Name(b"Name"), self.icon.to_pdf_name());
+        annotation.pair(Name(b"Open"), self.open);
 
-        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
-        page.add_annotation(
-            LinkAnnotation::new(
-                Rect::from_xywh(50.0, 100.0, 100.0, 100.0).unwrap(),
-                Target::Destination(XyzDestination::new(0, Point::from_xy(0.0, 0.0)).into()),
-            )
-            .into(),
+        write_appearance(sc, annotation, actual_rect, page_height, |surface| {
+            let rect = self.rect;
+            let line_width = (rect.height() / 20.0).max(0.5);
+            let half_width = line_width / 2.0;
+
+            let mut builder = PathBuilder::new();
+            builder.push_rect(
+                Rect::from_ltrb(
+                    rect.left() + half_width,
+                    rect.top() + half_width,
+                    rect.right() - half_width,
+                    rect.bottom() - half_width,
+                )
+                .unwrap_or(rect),
+            );
+
+            if let Some(path) = builder.finish() {
+                surface.fill_path(
+                    &path,
+                    Fill {
+                        paint: color.into(),
+                        ..Fill::default()
+                    },
+                );
+                surface.stroke_path(
+                    &path,
+                    Stroke {
+                        paint: rgb::Color::black().into(),
+                        width: line_width,
+                        ..Stroke::default()
+                    },
+                );
+            }
+
+            let stroke = Stroke {
+                paint: rgb::Color::black().into(),
+                width: line_width,
+                ..Stroke::default()
+            };
+            let left = rect.left() + rect.width() * 0.2;
+            let right = rect.right() - rect.width() * 0.2;
+
+            for factor in [0.3, 0.5, 0.7] {
+                let y = rect.top() + rect.height() * factor;
+                draw_line(surface, left, right, y, stroke.clone());
+            }
+        });
+    }
+}
+
+/// The horizontal alignment of the text of a free text annotation.
+#[cfg(feature = "simple-text")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Justification {
+    /// Align the text to the left.
+    #[default]
+    Left,
+    /// Center the text.
+    Center,
+    /// Align the text to the right.
+    Right,
+}
+
+#[cfg(feature = "simple-text")]
+impl Justification {
+    fn to_pdf(self) -> i32 {
+        match self {
+            Justification::Left => 0,
+            Justification::Center => 1,
+            Justification::Right => 2,
+        }
+    }
+}
+
+/// A free text annotation, which displays text directly on the page.
+#[cfg(feature = "simple-text")]
+pub struct FreeTextAnnotation {
+    rect: Rect,
+    contents: String,
+    font: Font,
+    font_size: f32,
+    text_color: rgb::Color,
+    justification: Justification,
+    field_font: FieldFont,
+}
+
+#[cfg(feature = "simple-text")]
+impl FreeTextAnnotation {
+    /// Create a new free text annotation, which displays `contents` inside of `rect`.
+    ///
+    /// The appearance of the annotation is generated by breaking the text into
+    /// lines that fit the width of the `rect` and drawing them with the given
+    /// font and font size. Text that doesn't fit into the `rect` is cut off.
+    /// By default, the text is drawn in black and aligned to the left.
+    pub fn new(rect: Rect, contents: String, font: Font, font_size: f32) -> Self {
+        Self {
+            rect,
+            contents,
+            font,
+            font_size,
+            text_color: rgb::Color::black(),
+            justification: Justification::Left,
+            field_font: FieldFont::Helvetica,
+        }
+    }
+
+    /// Set the color of the text.
+    pub fn with_text_color(mut self, text_color: rgb::Color) -> Self {
+        self.text_color = text_color;
+        self
+    }
+
+    /// Set the horizontal alignment of the text.
+    pub fn with_justification(mut self, justification: Justification) -> Self {
+        self.justification = justification;
+        self
+    }
+
+    /// Set the standard font that viewers should use when they regenerate the
+    /// appearance of the annotation, for example after it has been edited.
+    ///
+    /// Defaults to Helvetica.
+    pub fn with_field_font(mut self, field_font: FieldFont) -> Self {
+        self.field_font = field_font;
+        self
+    }
+
+    fn serialize_type(
+        &self,
+        sc: &mut SerializeContext,
+        annotation: &mut pdf_writer::writers::Annotation,
+        page_height: f32,
+    ) {
+        annotation.pair(Name(b"Subtype"), Name(b"FreeText"));
+
+        let actual_rect = self
+            .rect
+            .transform(page_root_transform(page_height))
+            .unwrap();
+        annotation.rect(actual_rect.to_pdf_rect());
+
+        sc.register_form_font(self.field_font);
+        let [r, g, b] = self.text_color.to_pdf_color();
+        let default_appearance = format!(
+            "/{} {} Tf {r} {g} {b} rg",
+            self.field_font.resource_name(),
+            self.font_size
+        );
+        annotation.pair(Name(b"DA"), Str(default_appearance.as_bytes()));
+        annotation.pair(Name(b"Q"), self.justification.to_pdf());
+
+        write_appearance(sc, annotation, actual_rect, page_height, |surface| {
+            const PADDING: f32 = 2.0;
+
+            let scale = self.font_size / self.font.units_per_em();
+            let line_height = (self.font.ascent() - self.font.descent()) * scale;
+            let max_width = self.rect.width() - 2.0 * PADDING;
+            let mut baseline = self.rect.top() + PADDING + self.font.ascent() * scale;
+
+            for (line, width) in break_lines(&self.font, self.font_size, &self.contents, max_width)
+            {
+                let x = match self.justification {
+                    Justification::Left => self.rect.left() + PADDING,
+                    Justification::Center => self.rect.left() + (self.rect.width() - width) / 2.0,
+                    Justification::Right => self.rect.right() - PADDING - width,
+                };
+
+                surface.fill_text(
+                    Point::from_xy(x, baseline),
+                    Fill {
+                        paint: self.text_color.into(),
+                        ..Fill::default()
+                    },
+                    self.font.clone(),
+                    self.font_size,
+                    &[],
+                    line,
+                    false,
+                    TextDirection::Auto,
+                );
+
+                baseline += line_height;
+            }
+        });
+    }
+}
+
+/// Break the text into lines that are at most `max_width` wide, only breaking at
+/// spaces and explicit line breaks. Returns each line together with its width.
+#[cfg(feature = "simple-text")]
+fn break_lines<'a>(font: &Font, size: f32, text: &'a str, max_width: f32) -> Vec<(&'a str, f32)> {
+    let charmap = font.font_ref().charmap();
+    let scale = size / font.units_per_em();
+    let width = |s: &str| {
+        s.chars()
+            .filter_map(|c| charmap.map(c))
+            .filter_map(|g| font.advance_width(g))
+            .sum::<f32>()
+            * scale
+    };
+
+    let mut lines = vec![];
+
+    for paragraph in text.lines() {
+        let mut start = 0;
+        let mut end = 0;
+
+        for (index, _) in paragraph
+            .match_indices(' ')
+            .chain(std::iter::once((paragraph.len(), "")))
+        {
+            if end > start && width(&paragraph[start..index]) > max_width {
+                lines.push(&paragraph[start..end]);
+                start = end + 1;
+            }
+
+            end = index;
+        }
+
+        lines.push(&paragraph[start..]);
+    }
+
+    lines.into_iter().map(|l| (l, width(l))).collect()
+}
+
+/// Shared state of all text markup annotations.
+struct TextMarkup {
+    quads: Vec<Rect>,
+    color: rgb::Color,
+}
+
+impl TextMarkup {
+    fn new(quads: Vec<Rect>, color: rgb::Color) -> Self {
+        Self { quads, color }
+    }
+
+    fn serialize_type(
+        &self,
+        sc: &mut SerializeContext,
+        annotation: &mut pdf_writer::writers::Annotation,
+        page_height: f32,
+        subtype: pdf_writer::types::AnnotationType,
+        draw_quad: impl Fn(&mut Surface, &Rect, Stroke),
+    ) {
+        annotation.subtype(subtype);
+
+        let rect = self
+            .quads
+            .iter()
+            .copied()
+            .reduce(|mut r1, r2| {
+                r1.expand(&r2);
+                r1
+            })
+            .unwrap_or(Rect::from_xywh(0.0, 0.0, 0.0, 0.0).unwrap());
+        let actual_rect = rect.transform(page_root_transform(page_height)).unwrap();
+        annotation.rect(actual_rect.to_pdf_rect());
+
+        let [r, g, b] = self.color.to_pdf_color();
+        annotation.color_rgb(r, g, b);
+
+        // The points of each quadrilateral are written in the order top-left, top-right,
+        // bottom-left, bottom-right. The PDF specification describes a counter-clockwise
+        // order, but this is the order that Acrobat writes and that viewers expect.
+        let root_transform = page_root_transform(page_height);
+        annotation.quad_points(self.quads.iter().flat_map(|q| {
+            let mut points = [
+                Point::from_xy(q.left(), q.top()),
+                Point::from_xy(q.right(), q.top()),
+                Point::from_xy(q.left(), q.bottom()),
+                Point::from_xy(q.right(), q.bottom()),
+            ];
+            root_transform.map_points(&mut points);
+            points.into_iter().flat_map(|p| [p.x, p.y])
+        }));
+
+        let color = self.color;
+        write_appearance(sc, annotation, actual_rect, page_height, |surface| {
+            for quad in &self.quads {
+                let stroke = Stroke {
+                    paint: color.into(),
+                    width: (quad.height() / 14.0).max(0.5),
+                    ..Stroke::default()
+                };
+                draw_quad(surface, quad, stroke);
+            }
+        });
+    }
+}
+
+/// A strike-out annotation, which strikes out one or more regions of text.
+pub struct StrikeOutAnnotation(TextMarkup);
+
+impl StrikeOutAnnotation {
+    /// Create a new strike-out annotation.
+    ///
+    /// The `quads` describe the regions of the text that should be struck out,
+    /// typically one per line of text.
+    pub fn new(quads: Vec<Rect>, color: rgb::Color) -> Self {
+        Self(TextMarkup::new(quads, color))
+    }
+}
+
+/// An underline annotation, which underlines one or more regions of text.
+pub struct UnderlineAnnotation(TextMarkup);
+
+impl UnderlineAnnotation {
+    /// Create a new underline annotation.
+    ///
+    /// The `quads` describe the regions of the text that should be underlined,
+    /// typically one per line of text.
+    pub fn new(quads: Vec<Rect>, color: rgb::Color) -> Self {
+        Self(TextMarkup::new(quads, color))
+    }
+}
+
+/// A squiggly annotation, which underlines one or more regions of text
+/// with a jagged line.
+pub struct SquigglyAnnotation(TextMarkup);
+
+impl SquigglyAnnotation {
+    /// Create a new squiggly annotation.
+    ///
+    /// The `quads` describe the regions of the text that should be underlined,
+    /// typically one per line of text.
+    pub fn new(quads: Vec<Rect>, color: rgb::Color) -> Self {
+        Self(TextMarkup::new(quads, color))
+    }
+}
+
+/// A caret annotation, which indicates where text should be inserted.
+pub struct CaretAnnotation {
+    point: Point,
+    size: f32,
+    color: rgb::Color,
+}
+
+impl CaretAnnotation {
+    /// Create a new caret annotation.
+    ///
+    /// The `point` indicates the insertion point on the baseline of the text,
+    /// the `size` the height of the caret.
+    pub fn new(point: Point, size: f32, color: rgb::Color) -> Self {
+        Self { point, size, color }
+    }
+
+    fn serialize_type(
+        &self,
+        sc: &mut SerializeContext,
+        annotation: &mut pdf_writer::writers::Annotation,
+        page_height: f32,
+    ) {
+        annotation.subtype(pdf_writer::types::AnnotationType::Caret);
+
+        let half_width = self.size / 2.0;
+        let rect = Rect::from_xywh(
+            self.point.x - half_width,
+            self.point.y - self.size,
+            self.size,
+            self.size,
+        )
+        .unwrap_or(Rect::from_xywh(self.point.x, self.point.y, 0.0, 0.0).unwrap());
+        let actual_rect = rect.transform(page_root_transform(page_height)).unwrap();
+        annotation.rect(actual_rect.to_pdf_rect());
+
+        let [r, g, b] = self.color.to_pdf_color();
+        annotation.color_rgb(r, g, b);
+
+        write_appearance(sc, annotation, actual_rect, page_height, |surface| {
+            let mut builder = PathBuilder::new();
+            builder.move_to(rect.left(), rect.bottom());
+            builder.quad_to(self.point.x, rect.bottom(), self.point.x, rect.top());
+            builder.quad_to(self.point.x, rect.bottom(), rect.right(), rect.bottom());
+            builder.close();
+
+            if let Some(path) = builder.finish() {
+                surface.fill_path(
+                    &path,
+                    Fill {
+                        paint: self.color.into(),
+                        ..Fill::default()
+                    },
+                );
+            }
+        });
+    }
+}
+
+/// A stamp annotation, which displays custom content (for example an approval
+/// stamp or a signature) on the page.
+pub struct StampAnnotation {
+    rect: Rect,
+    appearance: Stream,
+}
+
+impl StampAnnotation {
+    /// Create a new stamp annotation with a custom appearance.
+    ///
+    /// The `appearance` can be built using a [`StreamBuilder`]. Its bounding box
+    /// will be scaled so that it fills the `rect` of the annotation.
+    pub fn with_appearance(rect: Rect, appearance: Stream) -> Self {
+        Self { rect, appearance }
+    }
+
+    fn serialize_type(
+        &self,
+        sc: &mut SerializeContext,
+        annotation: &mut pdf_writer::writers::Annotation,
+        page_height: f32,
+    ) {
+        annotation.subtype(pdf_writer::types::AnnotationType::Stamp);
+
+        let actual_rect = self
+            .rect
+            .transform(page_root_transform(page_height))
+            .unwrap();
+        annotation.rect(actual_rect.to_pdf_rect());
+
+        let appearance_ref =
+            scaled_appearance(sc, &self.appearance, self.rect, actual_rect, page_height);
+        annotation.appearance().normal().stream(appearance_ref);
+    }
+}
+
+/// Shared state of square and circle annotations.
+struct Shape {
+    rect: Rect,
+    interior_color: Option<rgb::Color>,
+    border_color: rgb::Color,
+    border_width: f32,
+    dash: Option<StrokeDash>,
+    cloud_intensity: Option<f32>,
+}
+
+impl Shape {
+    fn new(
+        rect: Rect,
+        interior_color: Option<rgb::Color>,
+        border_color: rgb::Color,
+        border_width: f32,
+    ) -> Self {
+        Self {
+            rect,
+            interior_color,
+            border_color,
+            border_width,
+            dash: None,
+            cloud_intensity: None,
+        }
+    }
+
+    fn serialize_type(
+        &self,
+        sc: &mut SerializeContext,
+        annotation: &mut pdf_writer::writers::Annotation,
+        page_height: f32,
+        subtype: pdf_writer::types::AnnotationType,
+        ellipse: bool,
+    ) {
+        annotation.subtype(subtype);
+
+        let actual_rect = self
+            .rect
+            .transform(page_root_transform(page_height))
+            .unwrap();
+        annotation.rect(actual_rect.to_pdf_rect());
+
+        let cloud_radius = self
+            .cloud_intensity
+            .map(|intensity| cloud_radius(intensity, self.border_width));
+        // The border is drawn inside of the rect of the annotation, as required by the
+        // PDF specification. This includes the bumps of a cloudy border.
+        let inset = self.border_width / 2.0 + cloud_radius.unwrap_or(0.0);
+
+        let [r, g, b] = self.border_color.to_pdf_color();
+        annotation.color_rgb(r, g, b);
+        write_border_style(annotation, self.border_width, self.dash.as_ref());
+        write_interior_color(annotation, self.interior_color);
+
+        if let Some(intensity) = self.cloud_intensity {
+            write_cloudy_border_effect(annotation, intensity);
+            annotation.insert(Name(b"RD")).array().items([inset; 4]);
+        }
+
+        write_appearance(sc, annotation, actual_rect, page_height, |surface| {
+            let inner_rect = Rect::from_ltrb(
+                self.rect.left() + inset,
+                self.rect.top() + inset,
+                self.rect.right() - inset,
+                self.rect.bottom() - inset,
+            )
+            .unwrap_or(self.rect);
+
+            let path = if let Some(radius) = cloud_radius {
+                let outline = if ellipse {
+                    ellipse_points(inner_rect)
+                } else {
+                    vec![
+                        Point::from_xy(inner_rect.left(), inner_rect.top()),
+                        Point::from_xy(inner_rect.right(), inner_rect.top()),
+                        Point::from_xy(inner_rect.right(), inner_rect.bottom()),
+                        Point::from_xy(inner_rect.left(), inner_rect.bottom()),
+                    ]
+                };
+
+                cloud_path(&outline, radius)
+            } else {
+                let mut builder = PathBuilder::new();
+
+                if ellipse {
+                    builder.push_oval(inner_rect);
+                } else {
+                    builder.push_rect(inner_rect);
+                }
+
+                builder.finish()
+            };
+
+            let Some(path) = path else {
+                return;
+            };
+
+            if let Some(interior_color) = self.interior_color {
+                surface.fill_path(
+                    &path,
+                    Fill {
+                        paint: interior_color.into(),
+                        ..Fill::default()
+                    },
+                );
+            }
+
+            if self.border_width > 0.0 {
+                surface.stroke_path(
+                    &path,
+                    border_stroke(self.border_color, self.border_width, self.dash.as_ref()),
+                );
+            }
+        });
+    }
+}
+
+/// A square annotation, which displays a rectangle on the page.
+pub struct SquareAnnotation(Shape);
+
+impl SquareAnnotation {
+    /// Create a new square annotation.
+    ///
+    /// The border is drawn inside of the `rect`. If no `interior_color` is
+    /// provided, the rectangle will not be filled.
+    pub fn new(
+        rect: Rect,
+        interior_color: Option<rgb::Color>,
+        border_color: rgb::Color,
+        border_width: f32,
+    ) -> Self {
+        Self(Shape::new(rect, interior_color, border_color, border_width))
+    }
+
+    /// Draw the border with a dash pattern.
+    pub fn with_dash(mut self, dash: StrokeDash) -> Self {
+        self.0.dash = Some(dash);
+        self
+    }
+
+    /// Draw the border as a cloud made of circular bumps.
+    ///
+    /// The intensity ranges from 0 to 2 and controls the size of the bumps. To keep
+    /// the cloud inside of the `rect`, the rectangle is shrunk accordingly.
+    pub fn with_cloudy_border(mut self, intensity: f32) -> Self {
+        self.0.cloud_intensity = cloud_intensity(intensity);
+        self
+    }
+}
+
+/// A circle annotation, which displays an ellipse on the page.
+pub struct CircleAnnotation(Shape);
+
+impl CircleAnnotation {
+    /// Create a new circle annotation.
+    ///
+    /// The ellipse is inscribed in the `rect`, with the border being drawn inside of it.
+    /// If no `interior_color` is provided, the ellipse will not be filled.
+    pub fn new(
+        rect: Rect,
+        interior_color: Option<rgb::Color>,
+        border_color: rgb::Color,
+        border_width: f32,
+    ) -> Self {
+        Self(Shape::new(rect, interior_color, border_color, border_width))
+    }
+
+    /// Draw the border with a dash pattern.
+    pub fn with_dash(mut self, dash: StrokeDash) -> Self {
+        self.0.dash = Some(dash);
+        self
+    }
+
+    /// Draw the border as a cloud made of circular bumps.
+    ///
+    /// The intensity ranges from 0 to 2 and controls the size of the bumps. To keep
+    /// the cloud inside of the `rect`, the ellipse is shrunk accordingly.
+    pub fn with_cloudy_border(mut self, intensity: f32) -> Self {
+        self.0.cloud_intensity = cloud_intensity(intensity);
+        self
+    }
+}
+
+/// The style of the ending of a line annotation or a polyline annotation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LineEnding {
+    /// No line ending.
+    #[default]
+    None,
+    /// A square centered on the endpoint.
+    Square,
+    /// A circle centered on the endpoint.
+    Circle,
+    /// A diamond centered on the endpoint.
+    Diamond,
+    /// Two short lines meeting in an acute angle at the endpoint, forming an open arrowhead.
+    OpenArrow,
+    /// Like [`LineEnding::OpenArrow`], but closed to form a triangular arrowhead.
+    ClosedArrow,
+    /// A short line at the endpoint that is perpendicular to the line.
+    Butt,
+    /// Like [`LineEnding::OpenArrow`], but pointing in the reverse direction.
+    ROpenArrow,
+    /// Like [`LineEnding::ClosedArrow`], but pointing in the reverse direction.
+    RClosedArrow,
+    /// A short line at the endpoint, rotated 30 degrees clockwise from
+    /// the perpendicular of the line.
+    Slash,
+}
+
+impl LineEnding {
+    fn to_pdf_name(self) -> Name<'static> {
+        match self {
+            LineEnding::None => Name(b"None"),
+            LineEnding::Square => Name(b"Square"),
+            LineEnding::Circle => Name(b"Circle"),
+            LineEnding::Diamond => Name(b"Diamond"),
+            LineEnding::OpenArrow => Name(b"OpenArrow"),
+            LineEnding::ClosedArrow => Name(b"ClosedArrow"),
+            LineEnding::Butt => Name(b"Butt"),
+            LineEnding::ROpenArrow => Name(b"ROpenArrow"),
+            LineEnding::RClosedArrow => Name(b"RClosedArrow"),
+            LineEnding::Slash => Name(b"Slash"),
+        }
+    }
+
+    /// The size of the line ending for a given border width.
+    fn size(width: f32) -> f32 {
+        6.0 * width.max(1.0)
+    }
+
+    /// Draw the line ending at `tip`, where `from` is the previous point on the line.
+    fn draw(
+        self,
+        surface: &mut Surface,
+        tip: Point,
+        from: Point,
+        color: rgb::Color,
+        interior_color: Option<rgb::Color>,
+        width: f32,
+    ) {
+        let mut dir = tip - from;
+
+        if self == LineEnding::None || !dir.normalize() {
+            return;
+        }
+
+        let size = Self::size(width);
+        let normal = Point::from_xy(-dir.y, dir.x);
+        let at = |along: f32, across: f32| {
+            Point::from_xy(
+                tip.x + dir.x * along + normal.x * across,
+                tip.y + dir.y * along + normal.y * across,
+            )
+        };
+
+        // The arrowheads have an opening angle of 60 degrees.
+        let (arrow_length, arrow_width) = (size * 30f32.to_radians().cos(), size / 2.0);
+        let half = size / 2.0;
+
+        let mut builder = PathBuilder::new();
+        let (closed, filled) = match self {
+            LineEnding::None => return,
+            LineEnding::Square => {
+                push_polygon(
+                    &mut builder,
+                    &[
+                        at(half, half),
+                        at(half, -half),
+                        at(-half, -half),
+                        at(-half, half),
+                    ],
+                );
+                (true, true)
+            }
+            LineEnding::Circle => {
+                builder.push_circle(tip.x, tip.y, half);
+                (true, true)
+            }
+            LineEnding::Diamond => {
+                push_polygon(
+                    &mut builder,
+                    &[at(half, 0.0), at(0.0, -half), at(-half, 0.0), at(0.0, half)],
+                );
+                (true, true)
+            }
+            LineEnding::OpenArrow | LineEnding::ClosedArrow => {
+                push_polygon(
+                    &mut builder,
+                    &[
+                        at(-arrow_length, arrow_width),
+                        tip,
+                        at(-arrow_length, -arrow_width),
+                    ],
+                );
+                let closed = self == LineEnding::ClosedArrow;
+                (closed, closed)
+            }
+            LineEnding::ROpenArrow | LineEnding::RClosedArrow => {
+                push_polygon(
+                    &mut builder,
+                    &[
+                        at(arrow_length, arrow_width),
+                        tip,
+                        at(arrow_length, -arrow_width),
+                    ],
+                );
+                let closed = self == LineEnding::RClosedArrow;
+                (closed, closed)
+            }
+            LineEnding::Butt => {
+                push_polygon(&mut builder, &[at(0.0, half), at(0.0, -half)]);
+                (false, false)
+            }
+            LineEnding::Slash => {
+                let (sin, cos) = 30f32.to_radians().sin_cos();
+                push_polygon(
+                    &mut builder,
+                    &[at(half * sin, half * cos), at(-half * sin, -half * cos)],
+                );
+                (false, false)
+            }
+        };
+
+        if closed {
+            builder.close();
+        }
+
+        let Some(path) = builder.finish() else {
+            return;
+        };
+
+        if let (true, Some(interior_color)) = (filled, interior_color) {
+            surface.fill_path(
+                &path,
+                Fill {
+                    paint: interior_color.into(),
+                    ..Fill::default()
+                },
+            );
+        }
+
+        // Line endings are always drawn solid.
+        surface.stroke_path(&path, border_stroke(color, width, None));
+    }
+}
+
+/// A line annotation, which displays a single straight line on the page.
+pub struct LineAnnotation {
+    start: Point,
+    end: Point,
+    color: rgb::Color,
+    width: f32,
+    interior_color: Option<rgb::Color>,
+    line_endings: (LineEnding, LineEnding),
+    dash: Option<StrokeDash>,
+}
+
+impl LineAnnotation {
+    /// Create a new line annotation from `start` to `end`.
+    pub fn new(start: Point, end: Point, color: rgb::Color, width: f32) -> Self {
+        Self {
+            start,
+            end,
+            color,
+            width,
+            interior_color: None,
+            line_endings: (LineEnding::None, LineEnding::None),
+            dash: None,
+        }
+    }
+
+    /// Set the color that is used to fill closed line endings.
+    pub fn with_interior_color(mut self, interior_color: rgb::Color) -> Self {
+        self.interior_color = Some(interior_color);
+        self
+    }
+
+    /// Set the line endings at the start and at the end of the line.
+    ///
+    /// The size of the line endings is proportional to the width of the line.
+    pub fn with_line_endings(mut self, start: LineEnding, end: LineEnding) -> Self {
+        self.line_endings = (start, end);
+        self
+    }
+
+    /// Draw the line with a dash pattern.
+    ///
+    /// Line endings are always drawn solid.
+    pub fn with_dash(mut self, dash: StrokeDash) -> Self {
+        self.dash = Some(dash);
+        self
+    }
+
+    fn serialize_type(
+        &self,
+        sc: &mut SerializeContext,
+        annotation: &mut pdf_writer::writers::Annotation,
+        page_height: f32,
+    ) {
+        annotation.subtype(pdf_writer::types::AnnotationType::Line);
+
+        let points = [self.start, self.end];
+        let actual_rect = line_rect(&points, self.width, self.line_endings)
+            .transform(page_root_transform(page_height))
+            .unwrap();
+        annotation.rect(actual_rect.to_pdf_rect());
+        annotation
+            .insert(Name(b"L"))
+            .array()
+            .items(transformed_coords(&points, page_height));
+
+        let [r, g, b] = self.color.to_pdf_color();
+        annotation.color_rgb(r, g, b);
+        write_border_style(annotation, self.width, self.dash.as_ref());
+        write_interior_color(annotation, self.interior_color);
+        write_line_endings(annotation, self.line_endings);
+
+        write_appearance(sc, annotation, actual_rect, page_height, |surface| {
+            draw_polyline(
+                surface,
+                &points,
+                self.color,
+                self.width,
+                self.dash.as_ref(),
+                self.interior_color,
+                self.line_endings,
+            );
+        });
+    }
+}
+
+/// An ink annotation, which represents a freehand drawing made of one or
+/// more disjoint paths.
+pub struct InkAnnotation {
+    paths: Vec<Vec<Point>>,
+    color: rgb::Color,
+    width: f32,
+}
+
+impl InkAnnotation {
+    /// Create a new ink annotation.
+    ///
+    /// Each entry of `paths` describes one continuous stroke as a list of points that
+    /// will be connected with straight lines. The `color` and `width` are used both for
+    /// the border style of the annotation and for drawing its appearance stream.
+    /// Returns `None` if there are no paths or one of the paths has less than two points.
+    pub fn new(paths: Vec<Vec<Point>>, color: rgb::Color, width: f32) -> Option<Self> {
+        if paths.is_empty() || paths.iter().any(|p| p.len() < 2) {
+            return None;
+        }
+
+        Some(Self {
+            paths,
+            color,
+            width,
+        })
+    }
+
+    fn serialize_type(
+        &self,
+        sc: &mut SerializeContext,
+        annotation: &mut pdf_writer::writers::Annotation,
+        page_height: f32,
+    ) {
+        annotation.pair(Name(b"Subtype"), Name(b"Ink"));
+
+        let points = self.paths.iter().flatten().copied().collect::<Vec<_>>();
+        let actual_rect = points_rect(&points, self.width)
+            .transform(page_root_transform(page_height))
+            .unwrap();
+        annotation.rect(actual_rect.to_pdf_rect());
+
+        let [r, g, b] = self.color.to_pdf_color();
+        annotation.color_rgb(r, g, b);
+        annotation.border_style().width(self.width);
+
+        let mut ink_list = annotation.insert(Name(b"InkList")).array();
+        for path in &self.paths {
+            ink_list
+                .push()
+                .array()
+                .items(transformed_coords(path, page_height));
+        }
+        ink_list.finish();
+
+        write_appearance(sc, annotation, actual_rect, page_height, |surface| {
+            for path in &self.paths {
+                if let Some(path) = points_path(path, false) {
+                    surface.stroke_path(&path, border_stroke(self.color, self.width, None));
+                }
+            }
+        });
+    }
+}
+
+/// Shared state of polygon and polyline annotations.
+struct Vertices {
+    vertices: Vec<Point>,
+    color: rgb::Color,
+    width: f32,
+    interior_color: Option<rgb::Color>,
+    line_endings: (LineEnding, LineEnding),
+    dash: Option<StrokeDash>,
+    cloud_intensity: Option<f32>,
+}
+
+impl Vertices {
+    fn new(vertices: Vec<Point>, color: rgb::Color, width: f32) -> Option<Self> {
+        if vertices.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            vertices,
+            color,
+            width,
+            interior_color: None,
+            line_endings: (LineEnding::None, LineEnding::None),
+            dash: None,
+            cloud_intensity: None,
+        })
+    }
+
+    fn serialize_type(
+        &self,
+        sc: &mut SerializeContext,
+        annotation: &mut pdf_writer::writers::Annotation,
+        page_height: f32,
+        subtype: Name,
+        closed: bool,
+    ) {
+        annotation.pair(Name(b"Subtype"), subtype);
+
+        let cloud_radius = self
+            .cloud_intensity
+            .map(|intensity| cloud_radius(intensity, self.width));
+        // The bumps of a cloudy border are centered on the outline.
+        let extent = self.width + 2.0 * cloud_radius.unwrap_or(0.0);
+        let actual_rect = line_rect(&self.vertices, extent, self.line_endings)
+            .transform(page_root_transform(page_height))
+            .unwrap();
+        annotation.rect(actual_rect.to_pdf_rect());
+
+        let [r, g, b] = self.color.to_pdf_color();
+        annotation.color_rgb(r, g, b);
+        write_border_style(annotation, self.width, self.dash.as_ref());
+        write_interior_color(annotation, self.interior_color);
+
+        if let Some(intensity) = self.cloud_intensity {
+            write_cloudy_border_effect(annotation, intensity);
+        }
+        annotation
+            .insert(Name(b"Vertices"))
+            .array()
+            .items(transformed_coords(&self.vertices, page_height));
+
+        if !closed {
+            write_line_endings(annotation, self.line_endings);
+        }
+
+        write_appearance(sc, annotation, actual_rect, page_height, |surface| {
+            if closed {
+                let path = match cloud_radius {
+                    Some(radius) => cloud_path(&self.vertices, radius),
+                    None => points_path(&self.vertices, true),
+                };
+
+                let Some(path) = path else {
+                    return;
+                };
+
+                if let Some(interior_color) = self.interior_color {
+                    surface.fill_path(
+                        &path,
+                        Fill {
+                            paint: interior_color.into(),
+                            ..Fill::default()
+                        },
+                    );
+                }
+
+                surface.stroke_path(
+                    &path,
+                    border_stroke(self.color, self.width, self.dash.as_ref()),
+                );
+            } else {
+                draw_polyline(
+                    surface,
+                    &self.vertices,
+                    self.color,
+                    self.width,
+                    self.dash.as_ref(),
+                    self.interior_color,
+                    self.line_endings,
+                );
+            }
+        });
+    }
+}
+
+/// A polygon annotation, which displays a closed shape made of straight lines.
+pub struct PolygonAnnotation(Vertices);
+
+impl PolygonAnnotation {
+    /// Create a new polygon annotation.
+    ///
+    /// The last vertex will automatically be connected with the first one.
+    /// Returns `None` if `vertices` is empty.
+    pub fn new(vertices: Vec<Point>, color: rgb::Color, width: f32) -> Option<Self> {
+        Some(Self(Vertices::new(vertices, color, width)?))
+    }
+
+    /// Set the color that is used to fill the polygon.
+    pub fn with_interior_color(mut self, interior_color: rgb::Color) -> Self {
+        self.0.interior_color = Some(interior_color);
+        self
+    }
+
+    /// Draw the border with a dash pattern.
+    pub fn with_dash(mut self, dash: StrokeDash) -> Self {
+        self.0.dash = Some(dash);
+        self
+    }
+
+    /// Draw the border as a cloud made of circular bumps.
+    ///
+    /// The intensity ranges from 0 to 2 and controls the size of the bumps. The
+    /// bumps are centered on the outline of the polygon.
+    pub fn with_cloudy_border(mut self, intensity: f32) -> Self {
+        self.0.cloud_intensity = cloud_intensity(intensity);
+        self
+    }
+}
+
+/// A polyline annotation, which displays an open shape made of straight lines.
+pub struct PolyLineAnnotation(Vertices);
+
+impl PolyLineAnnotation {
+    /// Create a new polyline annotation.
+    ///
+    /// Returns `None` if `vertices` is empty.
+    pub fn new(vertices: Vec<Point>, color: rgb::Color, width: f32) -> Option<Self> {
+        Some(Self(Vertices::new(vertices, color, width)?))
+    }
+
+    /// Set the color that is used to fill closed line endings.
+    pub fn with_interior_color(mut self, interior_color: rgb::Color) -> Self {
+        self.0.interior_color = Some(interior_color);
+        self
+    }
+
+    /// Set the line endings at the first and at the last vertex.
+    ///
+    /// The size of the line endings is proportional to the width of the line.
+    pub fn with_line_endings(mut self, start: LineEnding, end: LineEnding) -> Self {
+        self.0.line_endings = (start, end);
+        self
+    }
+
+    /// Draw the line with a dash pattern.
+    ///
+    /// Line endings are always drawn solid.
+    pub fn with_dash(mut self, dash: StrokeDash) -> Self {
+        self.0.dash = Some(dash);
+        self
+    }
+}
+
+/// Return the bounding box of the points, expanded so that the stroke and
+/// the line endings fit into it.
+fn line_rect(points: &[Point], width: f32, line_endings: (LineEnding, LineEnding)) -> Rect {
+    if line_endings == (LineEnding::None, LineEnding::None) {
+        points_rect(points, width)
+    } else {
+        points_rect(points, width + 2.0 * LineEnding::size(width))
+    }
+}
+
+/// Return the bounding box of the points, expanded by half of the stroke width.
+fn points_rect(points: &[Point], width: f32) -> Rect {
+    let half_width = (width / 2.0).max(0.5);
+    let bounds = Rect::from_points(points)
+        .unwrap_or(Rect::from_xywh(points[0].x, points[0].y, 0.0, 0.0).unwrap());
+
+    Rect::from_ltrb(
+        bounds.left() - half_width,
+        bounds.top() - half_width,
+        bounds.right() + half_width,
+        bounds.bottom() + half_width,
+    )
+    .unwrap()
+}
+
+/// Return the coordinates of the points in the coordinate system of the PDF page.
+fn transformed_coords(points: &[Point], page_height: f32) -> Vec<f32> {
+    let mut points = points.to_vec();
+    page_root_transform(page_height).map_points(&mut points);
+    points.into_iter().flat_map(|p| [p.x, p.y]).collect()
+}
+
+fn points_path(points: &[Point], closed: bool) -> Option<Path> {
+    let mut builder = PathBuilder::new();
+    push_polygon(&mut builder, points);
+
+    if closed {
+        builder.close();
+    }
+
+    builder.finish()
+}
+
+fn push_polygon(builder: &mut PathBuilder, points: &[Point]) {
+    builder.move_to(points[0].x, points[0].y);
+
+    for point in &points[1..] {
+        builder.line_to(point.x, point.y);
+    }
+}
+
+/// Draw an open line through the points, with line endings at both ends.
+fn draw_polyline(
+    surface: &mut Surface,
+    points: &[Point],
+    color: rgb::Color,
+    width: f32,
+    dash: Option<&StrokeDash>,
+    interior_color: Option<rgb::Color>,
+    line_endings: (LineEnding, LineEnding),
+) {
+    if let Some(path) = points_path(points, false) {
+        surface.stroke_path(&path, border_stroke(color, width, dash));
+    }
+
+    if let [first, second, ..] = points {
+        line_endings
+            .0
+            .draw(surface, *first, *second, color, interior_color, width);
+    }
+
+    if let [.., second_last, last] = points {
+        line_endings
+            .1
+            .draw(surface, *last, *second_last, color, interior_color, width);
+    }
+}
+
+fn border_stroke(color: rgb::Color, width: f32, dash: Option<&StrokeDash>) -> Stroke {
+    Stroke {
+        paint: color.into(),
+        width,
+        dash: dash.cloned(),
+        ..Stroke::default()
+    }
+}
+
+fn write_border_style(
+    annotation: &mut pdf_writer::writers::Annotation,
+    width: f32,
+    dash: Option<&StrokeDash>,
+) {
+    let mut border_style = annotation.border_style();
+    border_style.width(width);
+
+    if let Some(dash) = dash {
+        border_style
+            .style(BorderType::Dashed)
+            .dashes(dash.array.iter().copied());
+    }
+}
+
+/// Clamp the intensity of a cloudy border to the valid range, where an
+/// intensity of 0 means that there is no effect.
+fn cloud_intensity(intensity: f32) -> Option<f32> {
+    let intensity = intensity.clamp(0.0, 2.0);
+    (intensity > 0.0).then_some(intensity)
+}
+
+/// The radius of the bumps of a cloudy border.
+fn cloud_radius(intensity: f32, width: f32) -> f32 {
+    4.0 * intensity + width
+}
+
+fn write_cloudy_border_effect(annotation: &mut pdf_writer::writers::Annotation, intensity: f32) {
+    annotation
+        .insert(Name(b"BE"))
+        .dict()
+        .pair(Name(b"S"), Name(b"C"))
+        .pair(Name(b"I"), intensity);
+}
+
+/// Return points on the ellipse inscribed in the rect.
+fn ellipse_points(rect: Rect) -> Vec<Point> {
+    const NUM_POINTS: usize = 64;
+
+    let (rx, ry) = (rect.width() / 2.0, rect.height() / 2.0);
+    let (cx, cy) = (rect.left() + rx, rect.top() + ry);
+
+    (0..NUM_POINTS)
+        .map(|i| {
+            let (sin, cos) = (i as f32 / NUM_POINTS as f32 * TAU).sin_cos();
+            Point::from_xy(cx + rx * cos, cy + ry * sin)
+        })
+        .collect()
+}
+
+/// Create the path of a cloudy border around a closed outline. The cloud consists
+/// of overlapping circular bumps, whose centers are evenly distributed on the outline.
+fn cloud_path(outline: &[Point], radius: f32) -> Option<Path> {
+    let mut points = outline.to_vec();
+    points.push(outline[0]);
+
+    let lengths = points
+        .windows(2)
+        .map(|w| w[0].distance(w[1]))
+        .collect::<Vec<_>>();
+    let perimeter = lengths.iter().sum::<f32>();
+
+    // The outline is too small to put bumps on it.
+    if perimeter < radius {
+        return points_path(outline, true);
+    }
+
+    // Neighboring bumps need to overlap.
+    let count = ((perimeter / (1.5 * radius)).ceil() as usize).max(3);
+    let spacing = perimeter / count as f32;
+
+    let mut centers = Vec::with_capacity(count);
+    let mut segment = 0;
+    let mut segment_start = 0.0;
+
+    for i in 0..count {
+        let distance = i as f32 * spacing;
+
+        while segment + 1 < lengths.len() && segment_start + lengths[segment] < distance {
+            segment_start += lengths[segment];
+            segment += 1;
+        }
+
+        let (a, b) = (points[segment], points[segment + 1]);
+        let t = if lengths[segment] > 0.0 {
+            ((distance - segment_start) / lengths[segment]).min(1.0)
+        } else {
+            0.0
+        };
+        centers.push(Point::from_xy(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t));
+    }
+
+    // The direction that points away from the outline, based on its orientation.
+    let area = points
+        .windows(2)
+        .map(|w| w[0].x * w[1].y - w[1].x * w[0].y)
+        .sum::<f32>();
+    let outward = |from: Point, to: Point| {
+        let (dx, dy) = (to.x - from.x, to.y - from.y);
+        if area > 0.0 {
+            (dy, -dx)
+        } else {
+            (-dy, dx)
+        }
+    };
+
+    // The outer intersection point of each bump with the next one.
+    let intersections = (0..count)
+        .map(|i| {
+            let (a, b) = (centers[i], centers[(i + 1) % count]);
+            let distance = a.distance(b).max(f32::EPSILON);
+            let height = (radius * radius - distance * distance / 4.0)
+                .max(0.0)
+                .sqrt();
+            let (nx, ny) = outward(a, b);
+
+            Point::from_xy(
+                (a.x + b.x) / 2.0 + nx / distance * height,
+                (a.y + b.y) / 2.0 + ny / distance * height,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut builder = PathBuilder::new();
+    let start = intersections[count - 1];
+    builder.move_to(start.x, start.y);
+
+    for i in 0..count {
+        let previous = centers[(i + count - 1) % count];
+        let next = centers[(i + 1) % count];
+
+        push_arc(
+            &mut builder,
+            centers[i],
+            radius,
+            intersections[(i + count - 1) % count],
+            intersections[i],
+            outward(previous, next),
+        );
+    }
+
+    builder.close();
+    builder.finish()
+}
+
+/// Add a circular arc from `from` to `to` to the path, going around the side
+/// of the circle that `direction` points to.
+fn push_arc(
+    builder: &mut PathBuilder,
+    center: Point,
+    radius: f32,
+    from: Point,
+    to: Point,
+    direction: (f32, f32),
+) {
+    let start = (from.y - center.y).atan2(from.x - center.x);
+    let end = (to.y - center.y).atan2(to.x - center.x);
+    let mut sweep = (end - start).rem_euclid(TAU);
+
+    // If the arc in positive direction doesn't pass the given direction, we
+    // need to go the other way around.
+    let middle = start + sweep / 2.0;
+    let deviation = (middle - direction.1.atan2(direction.0) + PI).rem_euclid(TAU) - PI;
+    if deviation.abs() > FRAC_PI_2 {
+        sweep -= TAU;
+    }
+
+    // Approximate the arc with cubic curves that span at most a quarter circle each.
+    let count = (sweep.abs() / FRAC_PI_2).ceil().max(1.0);
+    let step = sweep / count;
+    let handle = 4.0 / 3.0 * (step / 4.0).tan() * radius;
+
+    for i in 0..count as usize {
+        let (sin0, cos0) = (start + step * i as f32).sin_cos();
+        let (sin1, cos1) = (start + step * (i + 1) as f32).sin_cos();
+
+        builder.cubic_to(
+            center.x + radius * cos0 - handle * sin0,
+            center.y + radius * sin0 + handle * cos0,
+            center.x + radius * cos1 + handle * sin1,
+            center.y + radius * sin1 - handle * cos1,
+            center.x + radius * cos1,
+            center.y + radius * sin1,
+        );
+    }
+}
+
+fn write_interior_color(
+    annotation: &mut pdf_writer::writers::Annotation,
+    interior_color: Option<rgb::Color>,
+) {
+    if let Some(interior_color) = interior_color {
+        annotation
+            .insert(Name(b"IC"))
+            .array()
+            .items(interior_color.to_pdf_color());
+    }
+}
+
+fn write_line_endings(
+    annotation: &mut pdf_writer::writers::Annotation,
+    line_endings: (LineEnding, LineEnding),
+) {
+    if line_endings != (LineEnding::None, LineEnding::None) {
+        annotation
+            .insert(Name(b"LE"))
+            .array()
+            .items([line_endings.0.to_pdf_name(), line_endings.1.to_pdf_name()]);
+    }
+}
+
+fn draw_line(surface: &mut Surface, x1: f32, x2: f32, y: f32, stroke: Stroke) {
+    let mut builder = PathBuilder::new();
+    builder.move_to(x1, y);
+    builder.line_to(x2, y);
+
+    if let Some(path) = builder.finish() {
+        surface.stroke_path(&path, stroke);
+    }
+}
+
+/// Write the normal appearance stream of an annotation. The drawing function
+/// operates in the coordinate system of the page.
+fn write_appearance(
+    sc: &mut SerializeContext,
+    annotation: &mut pdf_writer::writers::Annotation,
+    actual_rect: Rect,
+    page_height: f32,
+    draw: impl FnOnce(&mut Surface),
+) {
+    let appearance_ref = appearance(sc, actual_rect, page_height, draw);
+    annotation.appearance().normal().stream(appearance_ref);
+}
+
+/// Register an appearance stream and return its reference. The drawing function
+/// operates in the coordinate system of the page.
+pub(crate) fn appearance(
+    sc: &mut SerializeContext,
+    actual_rect: Rect,
+    page_height: f32,
+    draw: impl FnOnce(&mut Surface),
+) -> Ref {
+    let mut stream_builder = StreamBuilder::new(sc);
+    let mut surface = stream_builder.surface();
+    surface.push_transform(&page_root_transform(page_height));
+    draw(&mut surface);
+    surface.pop();
+    surface.finish();
+    let stream = stream_builder.finish();
+
+    let x_object = XObject::new(stream, false, false, Some(actual_rect));
+    sc.register_cacheable(x_object)
+}
+
+/// Register an appearance stream that shows a custom stream, scaled so that
+/// its bounding box fills `rect`, and return its reference.
+pub(crate) fn scaled_appearance(
+    sc: &mut SerializeContext,
+    stream: &Stream,
+    rect: Rect,
+    actual_rect: Rect,
+    page_height: f32,
+) -> Ref {
+    let bbox = *stream.bbox;
+    let scale = |target: f32, actual: f32| {
+        if actual > 0.0 {
+            target / actual
+        } else {
+            1.0
+        }
+    };
+    let transform = Transform::from_translate(rect.left(), rect.top())
+        .pre_scale(
+            scale(rect.width(), bbox.width()),
+            scale(rect.height(), bbox.height()),
+        )
+        .pre_translate(-bbox.left(), -bbox.top());
+
+    appearance(sc, actual_rect, page_height, |surface| {
+        surface.push_transform(&transform);
+        surface.draw_stream(stream.clone());
+        surface.pop();
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::rgb;
+    use crate::document::{Document, PageSettings};
+    use crate::object::action::{
+        LaunchAction, LinkAction, NamedAction, NamedActionKind, RemoteDestination, RemoteGoToAction,
+    };
+    use crate::object::annotation::{
+        Annotation, AnnotationHandle, CaretAnnotation, CircleAnnotation, InkAnnotation,
+        LineAnnotation, LineEnding, LinkAnnotation, PolyLineAnnotation, PolygonAnnotation, Popup,
+        SquareAnnotation, SquigglyAnnotation, StampAnnotation, StrikeOutAnnotation, Target,
+        TextAnnotation, TextIcon, UnderlineAnnotation,
+    };
+    #[cfg(feature = "simple-text")]
+    use crate::object::annotation::{FreeTextAnnotation, Justification};
+    use crate::object::destination::XyzDestination;
+    use crate::path::StrokeDash;
+
+    use crate::object::page::Page;
+
+    #[cfg(feature = "simple-text")]
+    use crate::tests::NOTO_SANS;
+    use crate::tests::{blue_fill, green_fill, rect_to_path, red_fill, red_stroke};
+
+    use crate::SerializeSettings;
+    use krilla_macros::{snapshot, visreg};
+    use tiny_skia_path::{Point, Rect};
+
+    #[snapshot(single_page)]
+    fn annotation_to_link(page: &mut Page) {
+        page.add_annotation(
+            LinkAnnotation::new(
+                Rect::from_xywh(50.0, 50.0, 100.0, 100.0).unwrap(),
+                Target::Action(LinkAction::new("https://www.youtube.com".to_string()).into()),
+            )
+            .into(),
+        );
+    }
+
+    #[snapshot(single_page)]
+    fn annotation_to_named_action(page: &mut Page) {
+        page.add_annotation(
+            LinkAnnotation::new(
+                Rect::from_xywh(50.0, 50.0, 100.0, 100.0).unwrap(),
+                Target::Action(NamedAction::new(NamedActionKind::NextPage).into()),
+            )
+            .into(),
+        );
+    }
+
+    #[snapshot(single_page)]
+    fn annotation_to_remote_destination(page: &mut Page) {
+        page.add_annotation(
+            LinkAnnotation::new(
+                Rect::from_xywh(50.0, 50.0, 100.0, 100.0).unwrap(),
+                Target::Action(
+                    RemoteGoToAction::new(
+                        "../other/chapter 2.pdf".to_string(),
+                        RemoteDestination::Named("intro".to_string()),
+                    )
+                    .with_new_window(true)
+                    .into(),
+                ),
+            )
+            .into(),
+        );
+    }
+
+    #[test]
+    fn annotation_remote_destination_dictionary() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+        page.add_annotation(
+            LinkAnnotation::new(
+                Rect::from_xywh(50.0, 50.0, 100.0, 100.0).unwrap(),
+                Target::Action(
+                    RemoteGoToAction::new("./b.pdf".to_string(), RemoteDestination::Page(2)).into(),
+                ),
+            )
+            .into(),
+        );
+        page.finish();
+
+        let pdf = d.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/S /GoToR"));
+        assert!(pdf.contains("/F (./b.pdf)"));
+        assert!(pdf.contains("/UF (./b.pdf)"));
+        assert!(pdf.contains("/D [2 /Fit]"));
+        assert!(!pdf.contains("/NewWindow"));
+    }
+
+    #[test]
+    fn annotation_launch_dictionary() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+        page.add_annotation(
+            LinkAnnotation::new(
+                Rect::from_xywh(50.0, 50.0, 100.0, 100.0).unwrap(),
+                Target::Action(
+                    LaunchAction::new("docs/readme.txt".to_string())
+                        .with_new_window(false)
+                        .into(),
+                ),
+            )
+            .into(),
+        );
+        page.finish();
+
+        let pdf = d.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/S /Launch"));
+        assert!(pdf.contains("/F (docs/readme.txt)"));
+        assert!(pdf.contains("/NewWindow false"));
+    }
+
+    #[test]
+    fn annotation_to_invalid_destination() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+        page.add_annotation(
+            LinkAnnotation::new(
+                Rect::from_xywh(50.0, 50.0, 100.0, 100.0).unwrap(),
+                Target::Destination(XyzDestination::new(1, Point::from_xy(100.0, 100.0)).into()),
+            )
+            .into(),
+        );
+        page.finish();
+        assert!(d.finish().is_err())
+    }
+
+    #[snapshot(document)]
+    fn annotation_to_destination(d: &mut Document) {
+        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+        page.add_annotation(
+            LinkAnnotation::new(
+                Rect::from_xywh(50.0, 0.0, 100.0, 100.0).unwrap(),
+                Target::Destination(XyzDestination::new(1, Point::from_xy(100.0, 100.0)).into()),
+            )
+            .into(),
+        );
+
+        let mut surface = page.surface();
+        surface.fill_path(&rect_to_path(50.0, 0.0, 150.0, 100.0), red_fill(1.0));
+        surface.finish();
+        page.finish();
+
+        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+        page.add_annotation(
+            LinkAnnotation::new(
+                Rect::from_xywh(50.0, 100.0, 100.0, 100.0).unwrap(),
+                Target::Destination(XyzDestination::new(0, Point::from_xy(0.0, 0.0)).into()),
+            )
+            .into(),
         );
         let mut my_surface = page.surface();
         my_surface.fill_path(&rect_to_path(50.0, 100.0, 150.0, 200.0), green_fill(1.0));
@@ -221,4 +2398,565 @@ mod tests {
         my_surface.finish();
         page.finish();
     }
+
+    fn markup_quads() -> Vec<Rect> {
+        vec![
+            Rect::from_xywh(20.0, 20.0, 160.0, 20.0).unwrap(),
+            Rect::from_xywh(20.0, 50.0, 100.0, 20.0).unwrap(),
+        ]
+    }
+
+    #[snapshot(single_page)]
+    fn annotation_strike_out(page: &mut Page) {
+        page.add_annotation(
+            StrikeOutAnnotation::new(markup_quads(), rgb::Color::new(255, 0, 0)).into(),
+        );
+    }
+
+    #[snapshot(single_page)]
+    fn annotation_underline(page: &mut Page) {
+        page.add_annotation(
+            UnderlineAnnotation::new(markup_quads(), rgb::Color::new(0, 0, 255)).into(),
+        );
+    }
+
+    #[snapshot(single_page)]
+    fn annotation_squiggly(page: &mut Page) {
+        page.add_annotation(
+            SquigglyAnnotation::new(markup_quads(), rgb::Color::new(0, 128, 0)).into(),
+        );
+    }
+
+    #[snapshot(single_page)]
+    fn annotation_caret(page: &mut Page) {
+        page.add_annotation(
+            CaretAnnotation::new(Point::from_xy(100.0, 100.0), 12.0, rgb::Color::black()).into(),
+        );
+    }
+
+    #[snapshot(single_page)]
+    fn annotation_square(page: &mut Page) {
+        page.add_annotation(
+            SquareAnnotation::new(
+                Rect::from_xywh(20.0, 20.0, 160.0, 100.0).unwrap(),
+                Some(rgb::Color::new(255, 255, 0)),
+                rgb::Color::new(255, 0, 0),
+                2.0,
+            )
+            .into(),
+        );
+    }
+
+    #[snapshot(single_page)]
+    fn annotation_circle(page: &mut Page) {
+        page.add_annotation(
+            CircleAnnotation::new(
+                Rect::from_xywh(20.0, 20.0, 160.0, 100.0).unwrap(),
+                None,
+                rgb::Color::new(0, 0, 255),
+                3.0,
+            )
+            .into(),
+        );
+    }
+
+    #[visreg(document)]
+    fn annotation_square_and_circle(d: &mut Document) {
+        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+        page.add_annotation(
+            SquareAnnotation::new(
+                Rect::from_xywh(20.0, 20.0, 160.0, 70.0).unwrap(),
+                Some(rgb::Color::new(255, 255, 0)),
+                rgb::Color::new(255, 0, 0),
+                4.0,
+            )
+            .into(),
+        );
+        page.add_annotation(
+            CircleAnnotation::new(
+                Rect::from_xywh(20.0, 110.0, 160.0, 70.0).unwrap(),
+                Some(rgb::Color::new(0, 255, 255)),
+                rgb::Color::new(0, 0, 255),
+                4.0,
+            )
+            .into(),
+        );
+        page.finish();
+    }
+
+    fn scribble() -> Vec<Vec<Point>> {
+        vec![
+            (0..20)
+                .map(|i| {
+                    let x = 20.0 + i as f32 * 8.0;
+                    let y = 60.0 + if i % 2 == 0 { -20.0 } else { 20.0 };
+                    Point::from_xy(x, y)
+                })
+                .collect(),
+            vec![Point::from_xy(30.0, 150.0), Point::from_xy(170.0, 130.0)],
+        ]
+    }
+
+    fn triangle() -> Vec<Point> {
+        vec![
+            Point::from_xy(100.0, 20.0),
+            Point::from_xy(180.0, 180.0),
+            Point::from_xy(20.0, 180.0),
+        ]
+    }
+
+    #[snapshot(single_page)]
+    fn annotation_ink(page: &mut Page) {
+        page.add_annotation(
+            InkAnnotation::new(scribble(), rgb::Color::new(255, 0, 0), 2.0)
+                .unwrap()
+                .into(),
+        );
+    }
+
+    #[snapshot(single_page)]
+    fn annotation_line(page: &mut Page) {
+        page.add_annotation(
+            LineAnnotation::new(
+                Point::from_xy(20.0, 20.0),
+                Point::from_xy(180.0, 100.0),
+                rgb::Color::new(0, 0, 255),
+                2.0,
+            )
+            .with_line_endings(LineEnding::OpenArrow, LineEnding::ClosedArrow)
+            .with_interior_color(rgb::Color::new(255, 255, 0))
+            .into(),
+        );
+    }
+
+    #[visreg(document)]
+    fn annotation_line_endings(d: &mut Document) {
+        let mut page = d.start_page_with(PageSettings::new(200.0, 300.0));
+
+        let endings = [
+            LineEnding::Square,
+            LineEnding::Circle,
+            LineEnding::Diamond,
+            LineEnding::OpenArrow,
+            LineEnding::ClosedArrow,
+            LineEnding::Butt,
+            LineEnding::ROpenArrow,
+            LineEnding::RClosedArrow,
+            LineEnding::Slash,
+        ];
+
+        for (i, ending) in endings.into_iter().enumerate() {
+            let y = 25.0 + i as f32 * 30.0;
+            page.add_annotation(
+                LineAnnotation::new(
+                    Point::from_xy(30.0, y),
+                    Point::from_xy(170.0, y + 10.0),
+                    rgb::Color::new(255, 0, 0),
+                    2.0,
+                )
+                .with_line_endings(ending, ending)
+                .with_interior_color(rgb::Color::new(0, 0, 255))
+                .into(),
+            );
+        }
+
+        page.finish();
+    }
+
+    #[visreg(document)]
+    fn annotation_dashed_shapes(d: &mut Document) {
+        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+        let dash = StrokeDash {
+            array: vec![6.0, 3.0],
+            offset: 0.0,
+        };
+
+        page.add_annotation(
+            SquareAnnotation::new(
+                Rect::from_xywh(20.0, 20.0, 70.0, 70.0).unwrap(),
+                Some(rgb::Color::new(255, 255, 0)),
+                rgb::Color::new(255, 0, 0),
+                2.0,
+            )
+            .with_dash(dash.clone())
+            .into(),
+        );
+        page.add_annotation(
+            PolygonAnnotation::new(triangle(), rgb::Color::new(0, 128, 0), 2.0)
+                .unwrap()
+                .with_interior_color(rgb::Color::new(0, 255, 255))
+                .with_dash(dash.clone())
+                .into(),
+        );
+        page.add_annotation(
+            PolyLineAnnotation::new(
+                vec![
+                    Point::from_xy(110.0, 180.0),
+                    Point::from_xy(140.0, 120.0),
+                    Point::from_xy(180.0, 170.0),
+                ],
+                rgb::Color::new(0, 0, 255),
+                2.0,
+            )
+            .unwrap()
+            .with_line_endings(LineEnding::Circle, LineEnding::OpenArrow)
+            .with_dash(dash)
+            .into(),
+        );
+
+        page.finish();
+    }
+
+    #[visreg(document)]
+    fn annotation_cloudy_border(d: &mut Document) {
+        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+        let square = |rect| {
+            SquareAnnotation::new(
+                rect,
+                Some(rgb::Color::new(255, 255, 0)),
+                rgb::Color::new(255, 0, 0),
+                2.0,
+            )
+        };
+
+        page.add_annotation(square(Rect::from_xywh(10.0, 10.0, 80.0, 80.0).unwrap()).into());
+        page.add_annotation(
+            square(Rect::from_xywh(110.0, 10.0, 80.0, 80.0).unwrap())
+                .with_cloudy_border(1.0)
+                .into(),
+        );
+        page.add_annotation(
+            CircleAnnotation::new(
+                Rect::from_xywh(10.0, 110.0, 80.0, 80.0).unwrap(),
+                None,
+                rgb::Color::new(0, 0, 255),
+                1.0,
+            )
+            .with_cloudy_border(2.0)
+            .into(),
+        );
+        page.add_annotation(
+            PolygonAnnotation::new(
+                vec![
+                    Point::from_xy(120.0, 180.0),
+                    Point::from_xy(150.0, 120.0),
+                    Point::from_xy(180.0, 180.0),
+                ],
+                rgb::Color::new(0, 128, 0),
+                1.0,
+            )
+            .unwrap()
+            .with_interior_color(rgb::Color::new(0, 255, 255))
+            .with_dash(StrokeDash {
+                array: vec![4.0, 2.0],
+                offset: 0.0,
+            })
+            .with_cloudy_border(1.0)
+            .into(),
+        );
+
+        page.finish();
+    }
+
+    #[test]
+    fn annotation_cloudy_border_dictionary() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+        page.add_annotation(
+            SquareAnnotation::new(
+                Rect::from_xywh(20.0, 20.0, 100.0, 100.0).unwrap(),
+                None,
+                rgb::Color::black(),
+                2.0,
+            )
+            .with_cloudy_border(1.0)
+            .into(),
+        );
+        page.add_annotation(
+            SquareAnnotation::new(
+                Rect::from_xywh(20.0, 20.0, 100.0, 100.0).unwrap(),
+                None,
+                rgb::Color::black(),
+                2.0,
+            )
+            .with_cloudy_border(0.0)
+            .into(),
+        );
+        page.finish();
+
+        let pdf = d.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert_eq!(pdf.matches("/BE <<").count(), 1);
+        assert!(pdf.contains("/S /C"));
+        assert!(pdf.contains("/I 1"));
+        assert!(pdf.contains("/RD [7 7 7 7]"));
+    }
+
+    #[test]
+    fn annotation_line_dictionary() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+        page.add_annotation(
+            LineAnnotation::new(
+                Point::from_xy(20.0, 20.0),
+                Point::from_xy(180.0, 20.0),
+                rgb::Color::black(),
+                1.0,
+            )
+            .with_line_endings(LineEnding::None, LineEnding::ClosedArrow)
+            .with_dash(StrokeDash {
+                array: vec![3.0, 2.0],
+                offset: 0.0,
+            })
+            .into(),
+        );
+        page.finish();
+
+        let pdf = d.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/Subtype /Line"));
+        assert!(pdf.contains("/L [20 180 180 180]"));
+        assert!(pdf.contains("/LE [/None /ClosedArrow]"));
+        assert!(pdf.contains("/S /D"));
+        assert!(pdf.contains("/D [3 2]"));
+    }
+
+    #[snapshot(single_page)]
+    fn annotation_polygon(page: &mut Page) {
+        page.add_annotation(
+            PolygonAnnotation::new(triangle(), rgb::Color::new(0, 128, 0), 2.0)
+                .unwrap()
+                .into(),
+        );
+    }
+
+    #[snapshot(single_page)]
+    fn annotation_polyline(page: &mut Page) {
+        page.add_annotation(
+            PolyLineAnnotation::new(triangle(), rgb::Color::new(0, 0, 255), 2.0)
+                .unwrap()
+                .into(),
+        );
+    }
+
+    #[visreg(document)]
+    fn annotation_ink_scribble(d: &mut Document) {
+        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+        page.add_annotation(
+            InkAnnotation::new(scribble(), rgb::Color::new(255, 0, 0), 3.0)
+                .unwrap()
+                .into(),
+        );
+        page.finish();
+    }
+
+    #[test]
+    fn annotation_ink_border_matches_appearance() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+        page.add_annotation(
+            InkAnnotation::new(scribble(), rgb::Color::new(255, 0, 0), 3.0)
+                .unwrap()
+                .into(),
+        );
+        page.finish();
+
+        let pdf = d.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/C [1 0 0]"));
+        assert!(pdf.contains("/W 3"));
+        assert!(pdf.contains("\n3 w\n"));
+    }
+
+    #[test]
+    fn annotation_empty_points() {
+        let color = rgb::Color::black();
+
+        assert!(InkAnnotation::new(vec![], color, 1.0).is_none());
+        assert!(InkAnnotation::new(vec![vec![]], color, 1.0).is_none());
+        assert!(InkAnnotation::new(
+            vec![
+                vec![Point::from_xy(0.0, 0.0), Point::from_xy(1.0, 1.0)],
+                vec![Point::from_xy(0.0, 0.0)]
+            ],
+            color,
+            1.0
+        )
+        .is_none());
+        assert!(PolygonAnnotation::new(vec![], color, 1.0).is_none());
+        assert!(PolyLineAnnotation::new(vec![], color, 1.0).is_none());
+    }
+
+    #[snapshot(document)]
+    fn annotation_popup_and_reply(d: &mut Document) {
+        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+
+        let mut square = Annotation::new(
+            SquareAnnotation::new(
+                Rect::from_xywh(20.0, 20.0, 80.0, 40.0).unwrap(),
+                None,
+                rgb::Color::new(255, 0, 0),
+                2.0,
+            ),
+            None,
+        );
+        square.set_popup(Popup::new(
+            Rect::from_xywh(100.0, 20.0, 80.0, 60.0).unwrap(),
+            Some("Alice".to_string()),
+            "Please check this.".to_string(),
+            true,
+        ));
+        let parent = page.add_annotation(square);
+
+        let mut reply = Annotation::new(
+            CaretAnnotation::new(Point::from_xy(60.0, 60.0), 10.0, rgb::Color::black()),
+            None,
+        );
+        reply.set_popup(Popup::new(
+            Rect::from_xywh(100.0, 100.0, 80.0, 60.0).unwrap(),
+            Some("Bob".to_string()),
+            "Looks good to me.".to_string(),
+            false,
+        ));
+        reply.reply_to(&parent);
+        page.add_annotation(reply);
+
+        page.finish();
+    }
+
+    #[test]
+    fn annotation_reply_to_later_page() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+        let mut reply = Annotation::new(
+            CaretAnnotation::new(Point::from_xy(60.0, 60.0), 10.0, rgb::Color::black()),
+            None,
+        );
+        reply.reply_to(&AnnotationHandle::new(1, 0));
+        page.add_annotation(reply);
+        page.finish();
+
+        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+        page.add_annotation(
+            CaretAnnotation::new(Point::from_xy(60.0, 60.0), 10.0, rgb::Color::black()).into(),
+        );
+        page.finish();
+
+        assert!(d.finish().is_err());
+    }
+
+    #[visreg(document)]
+    fn annotation_stamp_with_appearance(d: &mut Document) {
+        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+        let mut surface = page.surface();
+        surface.fill_path(&rect_to_path(0.0, 0.0, 200.0, 200.0), green_fill(0.5));
+
+        // Draw the stamp at a different size than the annotation rect, so that
+        // it needs to be scaled.
+        let mut stream_builder = surface.stream_builder();
+        let mut stream_surface = stream_builder.surface();
+        stream_surface.fill_path(&rect_to_path(0.0, 0.0, 50.0, 25.0), blue_fill(1.0));
+        stream_surface.stroke_path(&rect_to_path(5.0, 5.0, 45.0, 20.0), red_stroke(1.0, 2.0));
+        stream_surface.finish();
+        let stream = stream_builder.finish();
+        surface.finish();
+
+        page.add_annotation(
+            StampAnnotation::with_appearance(
+                Rect::from_xywh(40.0, 60.0, 120.0, 60.0).unwrap(),
+                stream,
+            )
+            .into(),
+        );
+        page.finish();
+    }
+
+    #[snapshot(single_page)]
+    fn annotation_text(page: &mut Page) {
+        page.add_annotation(
+            TextAnnotation::new(
+                Rect::from_xywh(20.0, 20.0, 20.0, 20.0).unwrap(),
+                "This needs another look.".to_string(),
+                TextIcon::Comment,
+                true,
+            )
+            .into(),
+        );
+    }
+
+    #[test]
+    fn annotation_text_contents_take_precedence() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+        page.add_annotation(Annotation::new(
+            TextAnnotation::new(
+                Rect::from_xywh(20.0, 20.0, 20.0, 20.0).unwrap(),
+                "Note".to_string(),
+                TextIcon::default(),
+                false,
+            ),
+            Some("Alt".to_string()),
+        ));
+        page.finish();
+
+        let pdf = d.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/Contents (Note)"));
+        assert!(!pdf.contains("(Alt)"));
+    }
+
+    #[cfg(feature = "simple-text")]
+    #[visreg(document)]
+    fn annotation_free_text(d: &mut Document) {
+        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+
+        for (i, justification) in [
+            Justification::Left,
+            Justification::Center,
+            Justification::Right,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            page.add_annotation(
+                FreeTextAnnotation::new(
+                    Rect::from_xywh(20.0, 20.0 + i as f32 * 60.0, 160.0, 50.0).unwrap(),
+                    "A free text annotation that spans multiple lines.".to_string(),
+                    NOTO_SANS.clone(),
+                    12.0,
+                )
+                .with_text_color(rgb::Color::new(0, 0, 255))
+                .with_justification(justification)
+                .into(),
+            );
+        }
+
+        page.finish();
+    }
+
+    #[cfg(feature = "simple-text")]
+    #[test]
+    fn annotation_free_text_default_appearance() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+        page.add_annotation(
+            FreeTextAnnotation::new(
+                Rect::from_xywh(20.0, 20.0, 160.0, 50.0).unwrap(),
+                "Text".to_string(),
+                NOTO_SANS.clone(),
+                14.0,
+            )
+            .with_justification(Justification::Right)
+            .into(),
+        );
+        page.finish();
+
+        let pdf = d.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/Subtype /FreeText"));
+        assert!(pdf.contains("/DA (/Helv 14 Tf 0 0 0 rg)"));
+        assert!(pdf.contains("/Q 2"));
+        assert!(pdf.contains("/AcroForm"));
+        assert!(pdf.contains("/BaseFont /Helvetica"));
+    }
 }
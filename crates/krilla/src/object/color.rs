@@ -2,10 +2,11 @@
 //!
 //! # Color spaces
 //!
-//! krilla currently supports three color spaces:
+//! krilla currently supports four color spaces:
 //! - Rgb (including linear RGB)
 //! - Luma
 //! - CMYK
+//! - CIE L*a*b*
 //!
 //! Each color space is associated with its specific color type, which you can use to create new
 //! instances of a specific color in that color space.
@@ -34,9 +35,22 @@
 //! In 90% of the cases, it is totally fine to just use a device-dependent colorspace, and it's
 //! what krilla does by default. However, if you do care about that, then you can set the
 //! `no_device_cs` property to true, in which case krilla will embed an ICC profile for the
-//! sgrey and srgb color space (for luma and rgb colors, respectively). If a CMYK profile
+//! sgrey and srgb color space (for luma and rgb colors, respectively). You can provide
+//! a custom gray profile that replaces the built-in sgrey profile. If a CMYK profile
 //! was provided to the serialize settings, this will be used for CMYK colors. Otherwise,
 //! it will fall back to device CMYK.
+//!
+//! If you only want CMYK colors to be color-managed, you can instead provide a CMYK profile
+//! and set the `always_use_cmyk_profile` property to true, in which case only CMYK colors
+//! will use the ICC profile, while all other colors will still be device-dependent.
+//!
+//! Lab colors are always device-independent, regardless of the above settings.
+//!
+//! # Spot colors
+//!
+//! In addition to the above, krilla supports spot colors (for example Pantone inks) via
+//! the [`separation`] module. A spot color has a name identifying the colorant, a tint,
+//! and an alternate color that is used by devices that don't have the colorant.
 
 use std::fmt::Debug;
 use std::hash::Hash;
@@ -47,9 +61,10 @@ use pdf_writer::{Chunk, Finish, Name, Ref};
 
 use crate::object::{Cacheable, ChunkContainerFn, Resourceable};
 use crate::resource;
-use crate::serialize::SerializeContext;
+use crate::resource::Resource;
+use crate::serialize::{MaybeDeviceColorSpace, SerializeContext};
 use crate::stream::{deflate_encode, FilterStreamBuilder};
-use crate::util::Prehashed;
+use crate::util::{NameExt, Prehashed};
 use crate::validation::ValidationError;
 
 /// The PDF name for the device RGB color space.
@@ -68,6 +83,8 @@ pub(crate) enum Color {
     Luma(luma::Color),
     /// A device CMYK color.
     Cmyk(cmyk::Color),
+    /// A CIE L*a*b* color.
+    Lab(lab::Color),
 }
 
 impl Color {
@@ -76,6 +93,15 @@ impl Color {
             Color::Rgb(rgb) => rgb.to_pdf_color().to_vec(),
             Color::Luma(l) => vec![l.to_pdf_color()],
             Color::Cmyk(cmyk) => cmyk.to_pdf_color().to_vec(),
+            Color::Lab(lab) => lab.to_pdf_color().to_vec(),
+        }
+    }
+
+    /// The range of each component of the color, as a flat list of minimum and maximum values.
+    pub(crate) fn ranges(&self) -> Vec<f32> {
+        match self {
+            Color::Lab(_) => lab::RANGES.to_vec(),
+            _ => [0.0, 1.0].repeat(self.to_pdf_color().len()),
         }
     }
 
@@ -90,6 +116,7 @@ impl Color {
                 }
                 color_space
             }
+            Color::Lab(l) => l.color_space(),
         }
     }
 }
@@ -170,7 +197,7 @@ pub mod cmyk {
         }
 
         pub(crate) fn color_space(ss: &SerializeSettings) -> ColorSpace {
-            if ss.no_device_cs {
+            if ss.no_device_cs || ss.always_use_cmyk_profile {
                 ss.clone()
                     .cmyk_profile
                     .map(|p| ColorSpace::Cmyk(ICCBasedColorSpace::<4>(p.clone())))
@@ -196,7 +223,7 @@ pub mod cmyk {
 
 /// RGB colors.
 pub mod rgb {
-    use crate::object::color::ColorSpace;
+    use crate::object::color::{lab, ColorSpace};
 
     /// An RGB color.
     #[derive(Debug, Hash, Eq, PartialEq, Clone, Copy)]
@@ -237,6 +264,41 @@ pub mod rgb {
             ]
         }
 
+        /// Convert the color into a Lab color with the D65 white point, assuming
+        /// that it's an sRGB color.
+        pub(crate) fn to_lab(self) -> lab::Color {
+            fn linearize(c: f32) -> f32 {
+                if c <= 0.04045 {
+                    c / 12.92
+                } else {
+                    ((c + 0.055) / 1.055).powf(2.4)
+                }
+            }
+
+            fn f(t: f32) -> f32 {
+                const DELTA: f32 = 6.0 / 29.0;
+
+                if t > DELTA * DELTA * DELTA {
+                    t.cbrt()
+                } else {
+                    t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+                }
+            }
+
+            let [r, g, b] = self.to_pdf_color().map(linearize);
+
+            let x = 0.4124564 * r + 0.3575761 * g + 0.1804375 * b;
+            let y = 0.2126729 * r + 0.7151522 * g + 0.0721750 * b;
+            let z = 0.0193339 * r + 0.1191920 * g + 0.9503041 * b;
+
+            let white_point = lab::WhitePoint::d65();
+            let [xn, yn, zn] = white_point.to_pdf_white_point();
+            let (fx, fy, fz) = (f(x / xn), f(y / yn), f(z / zn));
+
+            lab::Color::new(116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+                .with_white_point(white_point)
+        }
+
         pub(crate) fn color_space(&self, no_device_cs: bool) -> ColorSpace {
             Color::rgb_color_space(no_device_cs)
         }
@@ -257,6 +319,189 @@ pub mod rgb {
     }
 }
 
+/// CIE L*a*b* colors.
+pub mod lab {
+    use tiny_skia_path::FiniteF32;
+
+    use crate::object::color::{ColorSpace, LabColorSpace};
+
+    /// The range of each component of a Lab color, in the order L, a, b.
+    pub(crate) const RANGES: [f32; 6] = [0.0, 100.0, -128.0, 127.0, -128.0, 127.0];
+
+    fn finite(val: f32, min: f32, max: f32) -> FiniteF32 {
+        FiniteF32::new(val.clamp(min, max)).unwrap_or_default()
+    }
+
+    /// The white point of a Lab color space, in CIE 1931 XYZ coordinates.
+    ///
+    /// The Y component of a white point is always 1.0.
+    #[derive(Debug, Hash, Eq, PartialEq, Clone, Copy)]
+    pub struct WhitePoint {
+        x: FiniteF32,
+        z: FiniteF32,
+    }
+
+    impl WhitePoint {
+        /// Create a new white point from its X and Z components.
+        ///
+        /// Returns `None` if one of the components is not positive or not finite.
+        pub fn new(x: f32, z: f32) -> Option<Self> {
+            if x > 0.0 && z > 0.0 {
+                Some(Self {
+                    x: FiniteF32::new(x)?,
+                    z: FiniteF32::new(z)?,
+                })
+            } else {
+                None
+            }
+        }
+
+        /// The D50 standard illuminant, which is used in most print workflows.
+        pub fn d50() -> Self {
+            Self::new(0.9642, 0.8249).unwrap()
+        }
+
+        /// The D65 standard illuminant, which is used by sRGB.
+        pub fn d65() -> Self {
+            Self::new(0.9505, 1.089).unwrap()
+        }
+
+        pub(crate) fn to_pdf_white_point(self) -> [f32; 3] {
+            [self.x.get(), 1.0, self.z.get()]
+        }
+    }
+
+    impl Default for WhitePoint {
+        fn default() -> Self {
+            Self::d50()
+        }
+    }
+
+    /// A Lab color.
+    #[derive(Debug, Hash, Eq, PartialEq, Clone, Copy)]
+    pub struct Color {
+        l: FiniteF32,
+        a: FiniteF32,
+        b: FiniteF32,
+        white_point: WhitePoint,
+    }
+
+    impl Color {
+        /// Create a new Lab color with the D50 white point.
+        ///
+        /// The lightness `l` is clamped to the range 0 to 100, and the `a` and `b`
+        /// components are clamped to the range -128 to 127.
+        pub fn new(l: f32, a: f32, b: f32) -> Self {
+            Self {
+                l: finite(l, RANGES[0], RANGES[1]),
+                a: finite(a, RANGES[2], RANGES[3]),
+                b: finite(b, RANGES[4], RANGES[5]),
+                white_point: WhitePoint::default(),
+            }
+        }
+
+        /// Use a different white point for the color.
+        pub fn with_white_point(mut self, white_point: WhitePoint) -> Self {
+            self.white_point = white_point;
+            self
+        }
+
+        pub(crate) fn to_pdf_color(self) -> [f32; 3] {
+            [self.l.get(), self.a.get(), self.b.get()]
+        }
+
+        pub(crate) fn color_space(&self) -> ColorSpace {
+            ColorSpace::Lab(LabColorSpace(self.white_point))
+        }
+    }
+
+    impl From<Color> for super::Color {
+        fn from(val: Color) -> Self {
+            super::Color::Lab(val)
+        }
+    }
+
+    impl Default for Color {
+        fn default() -> Self {
+            Color::new(0.0, 0.0, 0.0)
+        }
+    }
+}
+
+/// Spot colors.
+pub mod separation {
+    use std::sync::Arc;
+
+    use tiny_skia_path::NormalizedF32;
+
+    use crate::object::color::{cmyk, lab, SeparationColorSpace};
+
+    /// The color that should be used to approximate a spot color on devices
+    /// that don't have the corresponding colorant.
+    #[derive(Debug, Hash, Eq, PartialEq, Clone, Copy)]
+    pub enum AlternateColor {
+        /// A CMYK color.
+        Cmyk(cmyk::Color),
+        /// A Lab color.
+        Lab(lab::Color),
+    }
+
+    impl AlternateColor {
+        pub(crate) fn to_color(self) -> super::Color {
+            match self {
+                AlternateColor::Cmyk(c) => c.into(),
+                AlternateColor::Lab(l) => l.into(),
+            }
+        }
+
+        /// The color corresponding to a tint of zero, i.e. no colorant at all.
+        pub(crate) fn blank(self) -> super::Color {
+            match self {
+                AlternateColor::Cmyk(_) => cmyk::Color::new(0, 0, 0, 0).into(),
+                AlternateColor::Lab(_) => lab::Color::new(100.0, 0.0, 0.0).into(),
+            }
+        }
+    }
+
+    /// A spot color.
+    ///
+    /// Spot colors with the same name and alternate color share the same color space
+    /// in the PDF. Note that some export modes, like PDF/A, require all spot colors
+    /// with the same name to also use the same alternate color.
+    #[derive(Debug, Hash, Eq, PartialEq, Clone)]
+    pub struct Color {
+        name: Arc<str>,
+        tint: NormalizedF32,
+        alternate: AlternateColor,
+    }
+
+    impl Color {
+        /// Create a new spot color.
+        ///
+        /// `name` is the name of the colorant, `tint` indicates how much of the colorant
+        /// should be applied and `alternate` is the color that approximates the colorant
+        /// at full tint.
+        pub fn new(name: &str, tint: NormalizedF32, alternate: AlternateColor) -> Self {
+            Self {
+                name: Arc::from(name),
+                tint,
+                alternate,
+            }
+        }
+
+        pub(crate) fn to_pdf_color(&self) -> [f32; 1] {
+            [self.tint.get()]
+        }
+
+        pub(crate) fn color_space(&self) -> SeparationColorSpace {
+            SeparationColorSpace {
+                name: self.name.clone(),
+                alternate: self.alternate,
+            }
+        }
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub(crate) enum ColorSpace {
     DeviceRgb,
@@ -265,6 +510,91 @@ pub(crate) enum ColorSpace {
     Srgb,
     Luma,
     Cmyk(ICCBasedColorSpace<4>),
+    Lab(LabColorSpace),
+}
+
+/// A CIE-based Lab color space with a specific white point.
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub(crate) struct LabColorSpace(pub(crate) lab::WhitePoint);
+
+impl Cacheable for LabColorSpace {
+    fn chunk_container(&self) -> ChunkContainerFn {
+        Box::new(|cc| &mut cc.color_spaces)
+    }
+
+    fn serialize(self, _: &mut SerializeContext, root_ref: Ref) -> Chunk {
+        let mut chunk = Chunk::new();
+
+        let mut array = chunk.indirect(root_ref).array();
+        array.item(Name(b"Lab"));
+        let mut dict = array.push().dict();
+        dict.insert(Name(b"WhitePoint"))
+            .array()
+            .items(self.0.to_pdf_white_point());
+        dict.insert(Name(b"Range"))
+            .array()
+            .items(lab::RANGES[2..].iter().copied());
+        dict.finish();
+        array.finish();
+
+        chunk
+    }
+}
+
+impl Resourceable for LabColorSpace {
+    type Resource = resource::ColorSpace;
+}
+
+/// A Separation color space for a spot color. The tint transform linearly
+/// interpolates between a blank color and the alternate color.
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub(crate) struct SeparationColorSpace {
+    name: Arc<str>,
+    alternate: separation::AlternateColor,
+}
+
+impl Cacheable for SeparationColorSpace {
+    fn chunk_container(&self) -> ChunkContainerFn {
+        Box::new(|cc| &mut cc.color_spaces)
+    }
+
+    fn serialize(self, sc: &mut SerializeContext, root_ref: Ref) -> Chunk {
+        let mut chunk = Chunk::new();
+
+        let alternate = self.alternate.to_color();
+        let alternate_cs = alternate.color_space(sc);
+        let function_ref = sc.new_ref();
+
+        let mut array = chunk.indirect(root_ref).array();
+        array.item(Name(b"Separation"));
+        array.item(Name(self.name.as_bytes()));
+
+        match sc.register_colorspace(alternate_cs) {
+            MaybeDeviceColorSpace::DeviceGray => array.item(DEVICE_GRAY.to_pdf_name()),
+            MaybeDeviceColorSpace::DeviceRgb => array.item(DEVICE_RGB.to_pdf_name()),
+            MaybeDeviceColorSpace::DeviceCMYK => array.item(DEVICE_CMYK.to_pdf_name()),
+            MaybeDeviceColorSpace::ColorSpace(cs) => array.item(cs.get_ref()),
+        };
+
+        array.item(function_ref);
+        array.finish();
+
+        // Use an exponential instead of a PostScript function, so that the
+        // color space can also be used in export modes like PDF/A.
+        let mut tint_transform = chunk.exponential_function(function_ref);
+        tint_transform.domain([0.0, 1.0]);
+        tint_transform.range(alternate.ranges());
+        tint_transform.c0(self.alternate.blank().to_pdf_color());
+        tint_transform.c1(alternate.to_pdf_color());
+        tint_transform.n(1.0);
+        tint_transform.finish();
+
+        chunk
+    }
+}
+
+impl Resourceable for SeparationColorSpace {
+    type Resource = resource::ColorSpace;
 }
 
 #[derive(Clone, Hash, Debug)]
@@ -330,6 +660,16 @@ impl<const C: u8> ICCProfile<C> {
     pub(crate) fn metadata(&self) -> &ICCMetadata {
         &self.0.metadata
     }
+
+    pub(crate) fn to_generic(&self) -> GenericICCProfile {
+        // The number of channels is validated when creating the profile.
+        match C {
+            1 => GenericICCProfile::Luma(ICCProfile(self.0.clone())),
+            3 => GenericICCProfile::Rgb(ICCProfile(self.0.clone())),
+            4 => GenericICCProfile::Cmyk(ICCProfile(self.0.clone())),
+            _ => unreachable!(),
+        }
+    }
 }
 
 impl<const C: u8> Cacheable for ICCProfile<C> {
@@ -339,8 +679,10 @@ impl<const C: u8> Cacheable for ICCProfile<C> {
 
     fn serialize(self, sc: &mut SerializeContext, root_ref: Ref) -> Chunk {
         let mut chunk = Chunk::new();
-        let icc_stream = FilterStreamBuilder::new_from_deflated(&self.0.deref().data)
-            .finish(&sc.serialize_settings());
+        let serialize_settings = sc.serialize_settings();
+        let icc_stream =
+            FilterStreamBuilder::new_from_deflated(&self.0.deref().data, &serialize_settings)
+                .finish(&serialize_settings);
 
         let mut icc_profile = chunk.icc_profile(root_ref, icc_stream.encoded_data());
         icc_profile.n(C as i32).range([0.0, 1.0].repeat(C as usize));
@@ -473,12 +815,18 @@ mod tests {
 
     use crate::serialize::SerializeContext;
 
-    use crate::color::ColorSpace;
+    use crate::color::{cmyk, lab, luma, separation, ColorSpace};
+    use crate::error::KrillaError;
     use crate::page::Page;
+    use crate::paint::{LinearGradient, Paint, SpreadMethod, Stop};
     use crate::path::Fill;
     use crate::surface::Surface;
     use crate::tests::{cmyk_fill, rect_to_path, red_fill};
+    use crate::validation::ValidationError;
+    use crate::version::GREY_V2_ICC;
+    use crate::{Document, SerializeSettings};
     use krilla_macros::{snapshot, visreg};
+    use tiny_skia_path::NormalizedF32;
 
     #[snapshot]
     fn color_space_sgray(sc: &mut SerializeContext) {
@@ -502,6 +850,210 @@ mod tests {
         surface.fill_path(&rect_to_path(50.0, 50.0, 100.0, 100.0), Fill::default());
     }
 
+    fn fill_impl(settings: SerializeSettings, paint: Paint) -> String {
+        let mut document = Document::new_with(settings);
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        surface.fill_path(
+            &rect_to_path(50.0, 50.0, 100.0, 100.0),
+            Fill {
+                paint,
+                ..Fill::default()
+            },
+        );
+        surface.finish();
+        page.finish();
+
+        String::from_utf8_lossy(&document.finish().unwrap()).to_string()
+    }
+
+    fn luma_fill_impl(settings: SerializeSettings) -> String {
+        fill_impl(settings, luma::Color::new(128).into())
+    }
+
+    #[test]
+    fn luma_pdfa2_uses_icc_gray() {
+        let pdf = luma_fill_impl(SerializeSettings::settings_7());
+
+        assert!(!pdf.contains("/DeviceGray cs"));
+        assert!(pdf.contains("/ICCBased"));
+        assert!(pdf.contains("/N 1"));
+    }
+
+    #[test]
+    fn luma_custom_gray_profile() {
+        let default = luma_fill_impl(SerializeSettings::settings_2());
+        // PDF 1.7 uses the v4 profile by default, so we pick the v2 one to see a difference.
+        let custom = luma_fill_impl(SerializeSettings {
+            gray_profile: Some(GREY_V2_ICC.clone()),
+            ..SerializeSettings::settings_2()
+        });
+
+        assert!(custom.contains("/N 1"));
+        assert_ne!(default, custom);
+    }
+
+    #[test]
+    fn lab_color_space() {
+        let pdf = fill_impl(
+            SerializeSettings::settings_1(),
+            lab::Color::new(50.0, 20.0, -30.0).into(),
+        );
+
+        assert!(pdf.contains("/Lab"));
+        assert!(pdf.contains("/WhitePoint [0.9642 1 0.8249]"));
+        assert!(pdf.contains("/Range [-128 127 -128 127]"));
+        assert!(pdf.contains("50 20 -30 scn"));
+    }
+
+    #[test]
+    fn lab_custom_white_point() {
+        let white_point = lab::WhitePoint::d65();
+        let pdf = fill_impl(
+            SerializeSettings::settings_1(),
+            lab::Color::new(50.0, 20.0, -30.0)
+                .with_white_point(white_point)
+                .into(),
+        );
+
+        assert!(pdf.contains("/WhitePoint [0.9505 1 1.089]"));
+        assert!(lab::WhitePoint::new(-1.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn lab_clamped() {
+        let color = lab::Color::new(150.0, -200.0, f32::NAN);
+        assert_eq!(color, lab::Color::new(100.0, -128.0, 0.0));
+    }
+
+    #[test]
+    fn lab_pdfa2_is_device_independent() {
+        // Lab colors don't require an ICC profile, so this must not lead to a validation error.
+        let pdf = fill_impl(
+            SerializeSettings::settings_7(),
+            lab::Color::new(50.0, 20.0, -30.0).into(),
+        );
+
+        assert!(pdf.contains("/Lab"));
+    }
+
+    #[test]
+    fn lab_gradient() {
+        let stops = vec![
+            Stop {
+                offset: NormalizedF32::ZERO,
+                color: lab::Color::new(20.0, 60.0, -40.0),
+                opacity: NormalizedF32::ONE,
+            },
+            Stop {
+                offset: NormalizedF32::ONE,
+                color: lab::Color::new(90.0, -60.0, 40.0),
+                opacity: NormalizedF32::ONE,
+            },
+        ];
+
+        let gradient = LinearGradient {
+            x1: 50.0,
+            y1: 0.0,
+            x2: 100.0,
+            y2: 0.0,
+            transform: Default::default(),
+            spread_method: SpreadMethod::Pad,
+            stops: stops.into(),
+            anti_alias: false,
+            interpolation: Default::default(),
+        };
+
+        let pdf = fill_impl(SerializeSettings::settings_1(), gradient.into());
+
+        assert!(pdf.contains("/Lab"));
+        assert!(pdf.contains("/Range [0 100 -128 127 -128 127]"));
+        assert!(pdf.contains("/C0 [20 60 -40]"));
+    }
+
+    fn spot_color(tint: f32, alternate: separation::AlternateColor) -> Paint {
+        separation::Color::new("Spot Orange", NormalizedF32::new(tint).unwrap(), alternate).into()
+    }
+
+    #[test]
+    fn separation_color_space_shared() {
+        let alternate = separation::AlternateColor::Cmyk(cmyk::Color::new(0, 128, 255, 0));
+
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        surface.fill_path(
+            &rect_to_path(20.0, 20.0, 100.0, 100.0),
+            Fill {
+                paint: spot_color(1.0, alternate),
+                ..Fill::default()
+            },
+        );
+        surface.fill_path(
+            &rect_to_path(100.0, 100.0, 180.0, 180.0),
+            Fill {
+                paint: spot_color(0.5, alternate),
+                ..Fill::default()
+            },
+        );
+        surface.finish();
+        page.finish();
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert_eq!(
+            pdf.matches("/Separation /Spot#20Orange /DeviceCMYK")
+                .count(),
+            1
+        );
+        assert!(pdf.contains("/C0 [0 0 0 0]"));
+        assert!(pdf.contains("1 scn"));
+        assert!(pdf.contains("0.5 scn"));
+    }
+
+    #[test]
+    fn separation_pdfa2_lab_alternate() {
+        let alternate = separation::AlternateColor::Lab(lab::Color::new(60.0, 50.0, 70.0));
+        let pdf = fill_impl(SerializeSettings::settings_7(), spot_color(0.8, alternate));
+
+        assert!(pdf.contains("/Separation /Spot#20Orange"));
+        assert!(pdf.contains("/FunctionType 2"));
+        assert!(!pdf.contains("/FunctionType 4"));
+    }
+
+    #[test]
+    fn separation_pdfa2_cmyk_alternate_without_profile() {
+        let alternate = separation::AlternateColor::Cmyk(cmyk::Color::new(0, 128, 255, 0));
+
+        let mut document = Document::new_with(SerializeSettings::settings_7());
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        surface.fill_path(
+            &rect_to_path(20.0, 20.0, 100.0, 100.0),
+            Fill {
+                paint: spot_color(1.0, alternate),
+                ..Fill::default()
+            },
+        );
+        surface.finish();
+        page.finish();
+
+        assert_eq!(
+            document.finish(),
+            Err(KrillaError::ValidationError(vec![
+                ValidationError::MissingCMYKProfile
+            ]))
+        );
+    }
+
+    #[snapshot(single_page, settings_23)]
+    fn cmyk_icc_with_device_rgb(page: &mut Page) {
+        let mut surface = page.surface();
+        surface.fill_path(&rect_to_path(20.0, 20.0, 100.0, 100.0), cmyk_fill(1.0));
+        surface.fill_path(&rect_to_path(100.0, 100.0, 180.0, 180.0), red_fill(1.0));
+    }
+
     #[visreg(all)]
     fn cmyk_color(surface: &mut Surface) {
         let path = rect_to_path(20.0, 20.0, 180.0, 180.0);
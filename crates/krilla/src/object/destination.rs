@@ -4,21 +4,29 @@
 //! example when defining the outline, or when link to a different section in the document
 //! from a link. To achieve this, you can use destinations, which are associated with a page
 //! and a specific location on that page.
+//!
+//! krilla supports all the page destination types of the PDF specification: [`XyzDestination`]
+//! jumps to a specific point, while a [`FitDestination`] can be used to fit either the whole page,
+//! its width, its height or a specific rectangle of it into the viewer window.
 
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
 use pdf_writer::{Chunk, Obj, Ref, Str};
-use tiny_skia_path::{Point, Transform};
+use tiny_skia_path::{Point, Rect, Transform};
 
 use crate::error::{KrillaError, KrillaResult};
-use crate::serialize::SerializeContext;
+use crate::object::page::page_root_transform;
+use crate::serialize::{PageInfo, SerializeContext};
+use crate::util::RectExt;
 
 /// The type of destination.
-#[derive(Hash)]
+#[derive(Clone, Debug, Hash)]
 pub enum Destination {
     /// An XYZ destination.
     Xyz(XyzDestination),
+    /// A fit destination.
+    Fit(FitDestination),
     /// A named destination.
     Named(NamedDestination),
 }
@@ -27,7 +35,12 @@ impl Destination {
     pub(crate) fn serialize(&self, sc: &mut SerializeContext, buffer: Obj) -> KrillaResult<()> {
         match self {
             Destination::Xyz(xyz) => {
-                let ref_ = sc.register_xyz_destination(xyz.clone());
+                let ref_ = sc.register_page_destination(PageDestination::Xyz(xyz.clone()));
+                buffer.primitive(ref_);
+                Ok(())
+            }
+            Destination::Fit(fit) => {
+                let ref_ = sc.register_page_destination(PageDestination::Fit(fit.clone()));
                 buffer.primitive(ref_);
                 Ok(())
             }
@@ -36,6 +49,38 @@ impl Destination {
     }
 }
 
+/// A destination that directly points to a page, as opposed to a named destination.
+///
+/// Those can only be serialized in the very end, once the refs of all pages are known.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub(crate) enum PageDestination {
+    Xyz(XyzDestination),
+    Fit(FitDestination),
+}
+
+impl PageDestination {
+    pub(crate) fn serialize(
+        &self,
+        sc: &mut SerializeContext,
+        root_ref: Ref,
+    ) -> KrillaResult<Chunk> {
+        match self {
+            PageDestination::Xyz(xyz) => xyz.serialize(sc, root_ref),
+            PageDestination::Fit(fit) => fit.serialize(sc, root_ref),
+        }
+    }
+}
+
+fn get_page_info(sc: &SerializeContext, page_index: usize) -> KrillaResult<&PageInfo> {
+    sc.page_infos()
+        .get(page_index)
+        .ok_or(KrillaError::UserError(format!(
+            "attempted to link to page {}, but document only has {} pages",
+            page_index + 1,
+            sc.page_infos().len()
+        )))
+}
+
 /// A destination associated with a name.
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
 pub struct NamedDestination {
@@ -124,14 +169,7 @@ impl XyzDestination {
         let mut chunk = Chunk::new();
         let destination = chunk.destination(root_ref);
 
-        let page_info = sc
-            .page_infos()
-            .get(self.0.page_index)
-            .ok_or(KrillaError::UserError(format!(
-                "attempted to link to page {}, but document only has {} pages",
-                self.0.page_index + 1,
-                sc.page_infos().len()
-            )))?;
+        let page_info = get_page_info(sc, self.0.page_index)?;
         let page_ref = page_info.ref_;
         let page_size = page_info.surface_size.height();
 
@@ -148,12 +186,159 @@ impl XyzDestination {
     }
 }
 
+/// A destination that fits a page (or a part of it) into the window of the viewer.
+///
+/// All coordinates are specified in the coordinate system of the page, i.e. with the
+/// origin in the top-left corner. If the page index is out of range, export will
+/// fail gracefully.
+#[derive(Clone, Debug)]
+pub enum FitDestination {
+    /// Fit the whole page into the window (`/Fit`).
+    Page {
+        /// The index of the target page.
+        page_index: usize,
+    },
+    /// Fit the width of the page into the window, with the given
+    /// y coordinate positioned at the top of the window (`/FitH`).
+    Horizontal {
+        /// The index of the target page.
+        page_index: usize,
+        /// The y coordinate that should be at the top of the window.
+        top: f32,
+    },
+    /// Fit the height of the page into the window, with the given
+    /// x coordinate positioned at the left edge of the window (`/FitV`).
+    Vertical {
+        /// The index of the target page.
+        page_index: usize,
+        /// The x coordinate that should be at the left edge of the window.
+        left: f32,
+    },
+    /// Fit the given rectangle of the page into the window (`/FitR`).
+    Rect {
+        /// The index of the target page.
+        page_index: usize,
+        /// The rectangle that should be fit into the window.
+        rect: Rect,
+    },
+}
+
+impl FitDestination {
+    /// Return the index of the page the destination points to.
+    pub fn page_index(&self) -> usize {
+        match self {
+            FitDestination::Page { page_index }
+            | FitDestination::Horizontal { page_index, .. }
+            | FitDestination::Vertical { page_index, .. }
+            | FitDestination::Rect { page_index, .. } => *page_index,
+        }
+    }
+
+    pub(crate) fn serialize(
+        &self,
+        sc: &mut SerializeContext,
+        root_ref: Ref,
+    ) -> KrillaResult<Chunk> {
+        let page_info = get_page_info(sc, self.page_index())?;
+        let page_ref = page_info.ref_;
+        let page_size = page_info.surface_size.height();
+
+        let mut chunk = Chunk::new();
+        let destination = chunk.destination(root_ref).page(page_ref);
+
+        // Convert to PDF coordinates
+        match self {
+            FitDestination::Page { .. } => destination.fit(),
+            FitDestination::Horizontal { top, .. } => destination.fit_horizontal(page_size - *top),
+            FitDestination::Vertical { left, .. } => destination.fit_vertical(*left),
+            FitDestination::Rect { rect, .. } => {
+                // Since we only flip the y-axis, the transformed rect is always valid.
+                let actual_rect = rect.transform(page_root_transform(page_size)).unwrap();
+                destination.fit_rect(actual_rect.to_pdf_rect())
+            }
+        }
+
+        Ok(chunk)
+    }
+}
+
+impl From<FitDestination> for Destination {
+    fn from(val: FitDestination) -> Self {
+        Destination::Fit(val)
+    }
+}
+
+impl Hash for FitDestination {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        self.page_index().hash(state);
+
+        match self {
+            FitDestination::Page { .. } => {}
+            FitDestination::Horizontal { top, .. } => top.to_bits().hash(state),
+            FitDestination::Vertical { left, .. } => left.to_bits().hash(state),
+            FitDestination::Rect { rect, .. } => {
+                rect.left().to_bits().hash(state);
+                rect.top().to_bits().hash(state);
+                rect.right().to_bits().hash(state);
+                rect.bottom().to_bits().hash(state);
+            }
+        }
+    }
+}
+
+impl PartialEq for FitDestination {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (FitDestination::Page { page_index: p1 }, FitDestination::Page { page_index: p2 }) => {
+                p1 == p2
+            }
+            (
+                FitDestination::Horizontal {
+                    page_index: p1,
+                    top: t1,
+                },
+                FitDestination::Horizontal {
+                    page_index: p2,
+                    top: t2,
+                },
+            ) => p1 == p2 && t1 == t2,
+            (
+                FitDestination::Vertical {
+                    page_index: p1,
+                    left: l1,
+                },
+                FitDestination::Vertical {
+                    page_index: p2,
+                    left: l2,
+                },
+            ) => p1 == p2 && l1 == l2,
+            (
+                FitDestination::Rect {
+                    page_index: p1,
+                    rect: r1,
+                },
+                FitDestination::Rect {
+                    page_index: p2,
+                    rect: r2,
+                },
+            ) => p1 == p2 && r1 == r2,
+            _ => false,
+        }
+    }
+}
+
+// We don't care about Nan's
+impl Eq for FitDestination {}
+
 #[cfg(test)]
 mod tests {
     use crate::annotation::{LinkAnnotation, Target};
-    use crate::destination::{NamedDestination, XyzDestination};
+    use crate::destination::{FitDestination, NamedDestination, XyzDestination};
+    use crate::error::KrillaError;
+    use crate::outline::{Outline, OutlineNode};
     use crate::tests::{blue_fill, green_fill, rect_to_path, red_fill};
-    use crate::Document;
+    use crate::{Document, PageSettings, SerializeSettings};
     use krilla_macros::snapshot;
     use tiny_skia_path::{Point, Rect};
 
@@ -203,4 +388,72 @@ mod tests {
         surface.finish();
         page.finish();
     }
+
+    #[test]
+    fn fit_destinations() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        for _ in 0..2 {
+            let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+            page.add_annotation(
+                LinkAnnotation::new(
+                    Rect::from_xywh(0.0, 0.0, 100.0, 100.0).unwrap(),
+                    Target::Destination(FitDestination::Page { page_index: 1 }.into()),
+                )
+                .into(),
+            );
+            page.finish();
+        }
+
+        let mut outline = Outline::new();
+        outline.push_child(OutlineNode::new(
+            "Horizontal".to_string(),
+            FitDestination::Horizontal {
+                page_index: 0,
+                top: 50.0,
+            },
+        ));
+        outline.push_child(OutlineNode::new(
+            "Vertical".to_string(),
+            FitDestination::Vertical {
+                page_index: 1,
+                left: 30.0,
+            },
+        ));
+        outline.push_child(OutlineNode::new(
+            "Rect".to_string(),
+            FitDestination::Rect {
+                page_index: 1,
+                rect: Rect::from_xywh(10.0, 20.0, 50.0, 60.0).unwrap(),
+            },
+        ));
+        d.set_outline(outline);
+
+        let pdf = d.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.contains("/Fit]"));
+        assert!(pdf.contains("/FitH 150]"));
+        assert!(pdf.contains("/FitV 30]"));
+        assert!(pdf.contains("/FitR 10 120 60 180]"));
+    }
+
+    #[test]
+    fn fit_destination_invalid_page() {
+        let mut d = Document::new();
+        d.start_page().finish();
+
+        let mut outline = Outline::new();
+        outline.push_child(OutlineNode::new(
+            "Heading".to_string(),
+            FitDestination::Page { page_index: 3 },
+        ));
+        d.set_outline(outline);
+
+        assert_eq!(
+            d.finish(),
+            Err(KrillaError::UserError(
+                "attempted to link to page 4, but document only has 1 pages".to_string()
+            ))
+        );
+    }
 }
@@ -1,10 +1,10 @@
 //! CID fonts.
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::hash::Hash;
 use std::ops::DerefMut;
 
-use pdf_writer::types::{CidFontType, FontFlags, SystemInfo, UnicodeCmap};
+use pdf_writer::types::{CidFontType, SystemInfo, UnicodeCmap};
 use pdf_writer::writers::WMode;
 use pdf_writer::{Chunk, Finish, Name, Ref, Str};
 use skrifa::raw::tables::cff::Cff;
@@ -47,21 +47,47 @@ pub(crate) struct CIDFont {
     cmap_entries: BTreeMap<u16, String>,
     /// The widths of the glyphs, _index by their CID_.
     widths: Vec<f32>,
+    /// The glyphs that have actually been used in the document.
+    used_glyphs: BTreeSet<GlyphId>,
+    /// Whether the font will be subsetted.
+    subset: bool,
 }
 
 impl CIDFont {
     /// Create a new CID-keyed font.
-    pub(crate) fn new(font: Font) -> CIDFont {
+    ///
+    /// If `subset` is false, all glyphs of the font will be embedded, and the CID of
+    /// each glyph will be the same as its glyph ID.
+    pub(crate) fn new(font: Font, subset: bool) -> CIDFont {
         // Always include the .notdef glyph. Will also always be included by the subsetter in
         // the glyph remapper.
         let widths = vec![font.advance_width(GlyphId::new(0)).unwrap_or(0.0)];
 
-        Self {
+        let mut cid_font = Self {
             glyph_remapper: GlyphRemapper::new(),
             cmap_entries: BTreeMap::new(),
             widths,
+            used_glyphs: BTreeSet::new(),
+            subset,
             font,
+        };
+
+        if !subset {
+            // We still run the font through the subsetter so that we get an identity
+            // CID-to-GID mapping for CID-keyed CFF fonts, but we simply include all glyphs.
+            let num_glyphs = cid_font
+                .font
+                .font_ref()
+                .maxp()
+                .map(|m| m.num_glyphs())
+                .unwrap_or(1);
+
+            for gid in 1..num_glyphs {
+                cid_font.remap(GlyphId::new(gid as u32));
+            }
         }
+
+        cid_font
     }
 
     pub(crate) fn font(&self) -> Font {
@@ -83,6 +109,17 @@ impl CIDFont {
     /// Add a new glyph (if it has not already been added) and return its CID.
     #[inline]
     pub(crate) fn add_glyph(&mut self, glyph_id: GlyphId) -> Cid {
+        self.used_glyphs.insert(glyph_id);
+        self.remap(glyph_id)
+    }
+
+    /// Return the glyphs that have been used in the document so far.
+    pub(crate) fn used_glyphs(&self) -> &BTreeSet<GlyphId> {
+        &self.used_glyphs
+    }
+
+    #[inline]
+    fn remap(&mut self, glyph_id: GlyphId) -> Cid {
         let new_id = self
             .glyph_remapper
             .remap(u16::try_from(glyph_id.to_u32()).unwrap());
@@ -113,21 +150,10 @@ impl CIDFont {
         FontIdentifier::Cid(CIDIdentifer(self.font.clone()))
     }
 
-    pub(crate) fn serialize(
-        &self,
-        sc: &mut SerializeContext,
-        root_ref: Ref,
-    ) -> KrillaResult<Chunk> {
-        let mut chunk = Chunk::new();
-
-        let cid_ref = sc.new_ref();
-        let descriptor_ref = sc.new_ref();
-        let cmap_ref = sc.new_ref();
-        let cid_set_ref = sc.new_ref();
-        let data_ref = sc.new_ref();
-
-        let glyph_remapper = &self.glyph_remapper;
-
+    /// Create the subset of the font that contains all glyphs that have been added so far.
+    ///
+    /// This doesn't depend on any other state, so it can be run on a different thread.
+    pub(crate) fn subset(&self) -> KrillaResult<Vec<u8>> {
         let is_glyf = self.font.font_ref().glyf().is_ok();
         let is_cff = self.font.font_ref().cff().is_ok();
 
@@ -147,7 +173,25 @@ impl CIDFont {
             };
         }
 
-        let subsetted = subset_font(self.font.clone(), glyph_remapper)?;
+        subset_font(self.font.clone(), &self.glyph_remapper)
+    }
+
+    /// Serialize the font, given the subset that was created with [`CIDFont::subset`].
+    pub(crate) fn serialize(
+        &self,
+        sc: &mut SerializeContext,
+        root_ref: Ref,
+        subsetted: Vec<u8>,
+    ) -> KrillaResult<Chunk> {
+        let mut chunk = Chunk::new();
+
+        let cid_ref = sc.new_ref();
+        let descriptor_ref = sc.new_ref();
+        let cmap_ref = sc.new_ref();
+        let cid_set_ref = sc.new_ref();
+        let data_ref = sc.new_ref();
+
+        let is_cff = self.font.font_ref().cff().is_ok();
 
         let font_stream = {
             let mut data = subsetted.as_slice();
@@ -161,10 +205,15 @@ impl CIDFont {
                 data = cff.as_bytes();
             }
 
-            FilterStreamBuilder::new_from_binary_data(data).finish(&sc.serialize_settings())
+            FilterStreamBuilder::new_from_binary_data(data, &sc.serialize_settings())
+                .finish(&sc.serialize_settings())
         };
 
-        let base_font = base_font_name(&self.font, &self.glyph_remapper);
+        let base_font = if self.subset {
+            base_font_name(&self.font, &self.glyph_remapper)
+        } else {
+            full_font_name(&self.font)
+        };
         let base_font_type0 = if is_cff {
             format!("{base_font}-{}", IDENTITY_H)
         } else {
@@ -187,20 +236,35 @@ impl CIDFont {
         cid.base_font(Name(base_font.as_bytes()));
         cid.system_info(SYSTEM_INFO);
         cid.font_descriptor(descriptor_ref);
-        cid.default_width(0.0);
-
-        if !is_cff {
-            cid.cid_to_gid_map_predefined(Name(b"Identity"));
-        }
 
         // IN CID fonts, a upem value of 1000 is assumed for all fonts, so we need to convert.
         let to_pdf_units = |v: f32| v / self.font.units_per_em() * self.units_per_em();
 
+        // CID fonts store the width of glyphs that are missing from `/W` as `/DW` on the
+        // CIDFont itself, the `/MissingWidth` of the font descriptor only applies to
+        // simple fonts.
+        let default_width = self
+            .font
+            .missing_width()
+            .unwrap_or_else(|| to_pdf_units(self.widths[0]));
+        cid.default_width(default_width);
+
+        let cid_to_gid_map_ref = if is_cff {
+            None
+        } else if self.font.explicit_cid_to_gid_map() {
+            let map_ref = sc.new_ref();
+            cid.cid_to_gid_map_stream(map_ref);
+            Some(map_ref)
+        } else {
+            cid.cid_to_gid_map_predefined(Name(b"Identity"));
+            None
+        };
+
         let mut first = 0;
         let mut width_writer = cid.widths();
         for (w, group) in self.widths.group_by_key(|&w| w) {
             let end = first + group.len();
-            if w != 0.0 {
+            if to_pdf_units(w) != default_width {
                 let last = end - 1;
                 width_writer.same(first as u16, last as u16, to_pdf_units(w));
             }
@@ -210,17 +274,7 @@ impl CIDFont {
         width_writer.finish();
         cid.finish();
 
-        let mut flags = FontFlags::empty();
-        flags.set(
-            FontFlags::SERIF,
-            self.font
-                .postscript_name()
-                .is_some_and(|n| n.contains("Serif")),
-        );
-        flags.set(FontFlags::FIXED_PITCH, self.font.is_monospaced());
-        flags.set(FontFlags::ITALIC, self.font.italic_angle() != 0.0);
-        flags.insert(FontFlags::SYMBOLIC);
-        flags.insert(FontFlags::SMALL_CAP);
+        let flags = self.font.descriptor_flags();
 
         let bbox = self.font.bbox().to_pdf_rect();
 
@@ -228,7 +282,7 @@ impl CIDFont {
         let ascender = to_pdf_units(self.font.ascent());
         let descender = to_pdf_units(self.font.descent());
         let cap_height = self.font.cap_height().map(to_pdf_units).unwrap_or(ascender);
-        let stem_v = 10.0 + 0.244 * (self.font.weight() - 50.0);
+        let stem_v = self.font.stem_v();
 
         let mut font_descriptor = chunk.font_descriptor(descriptor_ref);
         font_descriptor
@@ -295,6 +349,20 @@ impl CIDFont {
             cmap
         };
 
+        if let Some(cid_to_gid_map_ref) = cid_to_gid_map_ref {
+            // The subsetter assigns consecutive GIDs in the same order as our CIDs, so
+            // each CID simply maps to the GID with the same value.
+            let map_data = (0..self.glyph_remapper.num_gids())
+                .flat_map(|cid| cid.to_be_bytes())
+                .collect::<Vec<_>>();
+            let map_stream =
+                FilterStreamBuilder::new_from_binary_data(&map_data, &sc.serialize_settings())
+                    .finish(&sc.serialize_settings());
+            let mut map = chunk.stream(cid_to_gid_map_ref, map_stream.encoded_data());
+            map_stream.write_filters(map.deref_mut());
+            map.finish();
+        }
+
         let cmap_stream = cmap.finish();
         let mut cmap = chunk.cmap(cmap_ref, &cmap_stream);
         cmap.writing_mode(WMode::Horizontal);
@@ -313,8 +381,9 @@ impl CIDFont {
             bytes
         };
 
-        let cid_stream = FilterStreamBuilder::new_from_binary_data(&cid_stream_data)
-            .finish(&sc.serialize_settings());
+        let cid_stream =
+            FilterStreamBuilder::new_from_binary_data(&cid_stream_data, &sc.serialize_settings())
+                .finish(&sc.serialize_settings());
         let mut cid_set = chunk.stream(cid_set_ref, cid_stream.encoded_data());
         cid_stream.write_filters(cid_set.deref_mut());
         cid_set.finish();
@@ -359,6 +428,17 @@ pub(crate) fn base_font_name<T: Hash>(font: &Font, data: &T) -> String {
     format!("{subset_tag}+{trimmed}")
 }
 
+/// The base font name of a font that is embedded in full, which must not
+/// contain a subset tag.
+fn full_font_name(font: &Font) -> String {
+    const REST_LEN: usize = 1 + IDENTITY_H.len();
+    let postscript_name = font.postscript_name().unwrap_or("unknown");
+
+    let max_len = 127 - REST_LEN;
+
+    postscript_name[..postscript_name.len().min(max_len)].to_string()
+}
+
 #[cfg_attr(feature = "comemo", comemo::memoize)]
 fn subset_font(font: Font, glyph_remapper: &GlyphRemapper) -> KrillaResult<Vec<u8>> {
     let font_data = font.font_data();
@@ -368,17 +448,232 @@ fn subset_font(font: Font, glyph_remapper: &GlyphRemapper) -> KrillaResult<Vec<u
 
 #[cfg(test)]
 mod tests {
-    use crate::font::Font;
+    use crate::font::{Font, FontFlags};
 
     use crate::object::font::FontContainer;
     use crate::path::Fill;
     use crate::serialize::SerializeContext;
     use crate::surface::{Surface, TextDirection};
     use crate::tests::{LATIN_MODERN_ROMAN, NOTO_SANS, NOTO_SANS_ARABIC};
+    use crate::{Document, SerializeSettings};
     use krilla_macros::{snapshot, visreg};
     use skrifa::GlyphId;
     use tiny_skia_path::Point;
 
+    #[test]
+    fn cid_font_overridden_descriptor_flags() {
+        let font = Font::new(NOTO_SANS.clone(), 0, true)
+            .unwrap()
+            .with_descriptor_flags(FontFlags::NON_SYMBOLIC | FontFlags::SERIF);
+
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        surface.fill_text(
+            Point::from_xy(0.0, 100.0),
+            Fill::default(),
+            font,
+            32.0,
+            &[],
+            "hello world",
+            false,
+            TextDirection::Auto,
+        );
+        surface.finish();
+        page.finish();
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/Flags 34"));
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn cid_font_parallel_subset() {
+        use crate::tests::NOTO_SANS_CJK;
+
+        let pdf = |parallel| {
+            let mut document = Document::new_with(SerializeSettings {
+                parallel,
+                ..SerializeSettings::settings_1()
+            });
+            let mut page = document.start_page();
+            let mut surface = page.surface();
+
+            // Use several fonts, so that the subsets are actually created on
+            // different threads and need to be put back into the right order.
+            let texts = [
+                (NOTO_SANS.clone(), "hello world"),
+                (LATIN_MODERN_ROMAN.clone(), "hello world"),
+                (NOTO_SANS_ARABIC.clone(), "مرحبا بالعالم"),
+                (NOTO_SANS_CJK.clone(), "你好世界"),
+            ];
+
+            for (i, (data, text)) in texts.into_iter().enumerate() {
+                surface.fill_text(
+                    Point::from_xy(0.0, 50.0 + i as f32 * 50.0),
+                    Fill::default(),
+                    Font::new(data, 0, true).unwrap(),
+                    32.0,
+                    &[],
+                    text,
+                    false,
+                    TextDirection::Auto,
+                );
+            }
+
+            surface.finish();
+            page.finish();
+
+            document.finish().unwrap()
+        };
+
+        assert_eq!(pdf(true), pdf(false));
+    }
+
+    fn subset_document(subset_fonts: bool) -> Document {
+        let font = Font::new(NOTO_SANS.clone(), 0, true).unwrap();
+
+        let mut document = Document::new_with(SerializeSettings {
+            subset_fonts,
+            ..SerializeSettings::settings_1()
+        });
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        surface.fill_text(
+            Point::from_xy(0.0, 100.0),
+            Fill::default(),
+            font.clone(),
+            32.0,
+            &[],
+            "abba",
+            false,
+            TextDirection::Auto,
+        );
+        surface.finish();
+        page.finish();
+
+        let used_glyphs = document.used_glyphs();
+        assert_eq!(used_glyphs.len(), 1);
+        assert_eq!(
+            used_glyphs[&font].iter().copied().collect::<Vec<_>>(),
+            vec![GlyphId::new(68), GlyphId::new(69)]
+        );
+
+        document
+    }
+
+    #[test]
+    fn cid_font_subset() {
+        let pdf = subset_document(true).finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("+NotoSans-Regular"));
+    }
+
+    #[test]
+    fn cid_font_no_subset() {
+        let subsetted = subset_document(true).finish().unwrap();
+        let pdf = subset_document(false).finish().unwrap();
+        assert!(pdf.len() > subsetted.len() * 10);
+
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/BaseFont /NotoSans-Regular"));
+        assert!(!pdf.contains("+NotoSans-Regular"));
+    }
+
+    #[test]
+    fn cid_font_overridden_stem_v() {
+        let font = Font::new(NOTO_SANS.clone(), 0, true)
+            .unwrap()
+            .with_stem_v(120.0);
+
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        surface.fill_text(
+            Point::from_xy(0.0, 100.0),
+            Fill::default(),
+            font,
+            32.0,
+            &[],
+            "hello world",
+            false,
+            TextDirection::Auto,
+        );
+        surface.finish();
+        page.finish();
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/StemV 120"));
+        // The advance width of the `.notdef` glyph of Noto Sans, which doesn't need to
+        // be repeated in the widths array.
+        assert!(pdf.contains("/DW 600"));
+        assert!(!pdf.contains("/W [0 0 600"));
+        assert!(!pdf.contains("/MissingWidth"));
+    }
+
+    #[test]
+    fn cid_font_shared_between_color_and_monochrome() {
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+
+        for (allow_color, y) in [(true, 100.0), (false, 150.0)] {
+            surface.fill_text(
+                Point::from_xy(0.0, y),
+                Fill::default(),
+                Font::new(NOTO_SANS.clone(), 0, allow_color).unwrap(),
+                32.0,
+                &[],
+                "hello world",
+                false,
+                TextDirection::Auto,
+            );
+        }
+
+        surface.finish();
+        page.finish();
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert_eq!(pdf.matches("/Subtype /CIDFontType2").count(), 1);
+        assert_eq!(pdf.matches("/FontFile2").count(), 1);
+    }
+
+    #[test]
+    fn cid_font_explicit_cid_to_gid_map() {
+        let font = Font::new(NOTO_SANS.clone(), 0, true)
+            .unwrap()
+            .with_explicit_cid_to_gid_map();
+
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        surface.fill_text(
+            Point::from_xy(0.0, 100.0),
+            Fill::default(),
+            font,
+            32.0,
+            &[],
+            "hello world",
+            false,
+            TextDirection::Auto,
+        );
+        surface.finish();
+        page.finish();
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(!pdf.contains("/CIDToGIDMap /Identity"));
+        assert!(pdf
+            .lines()
+            .any(|l| l.trim_start().starts_with("/CIDToGIDMap ") && l.ends_with(" 0 R")));
+        // The text should still be extractable.
+        assert!(pdf.contains("/ToUnicode"));
+        assert!(pdf.contains("beginbfchar") || pdf.contains("beginbfrange"));
+    }
+
     #[snapshot]
     fn cid_font_noto_sans_two_glyphs(sc: &mut SerializeContext) {
         let font = Font::new(NOTO_SANS.clone(), 0, true).unwrap();
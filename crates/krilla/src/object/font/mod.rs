@@ -1,5 +1,9 @@
 //! PDF fonts.
 
+use std::collections::BTreeSet;
+
+use skrifa::GlyphId;
+
 use crate::content::PdfFont;
 use crate::font::Font;
 use crate::object::font::cid_font::CIDFont;
@@ -100,6 +104,14 @@ pub(crate) enum FontContainer {
 }
 
 impl FontContainer {
+    /// Return the glyphs of the font that have been used in the document so far.
+    pub(crate) fn used_glyphs(&self) -> BTreeSet<GlyphId> {
+        match self {
+            FontContainer::Type3(t3) => t3.used_glyphs(),
+            FontContainer::CIDFont(cid) => cid.used_glyphs().clone(),
+        }
+    }
+
     #[inline]
     pub(crate) fn font_identifier(&self, glyph: CoveredGlyph) -> Option<FontIdentifier> {
         match self {
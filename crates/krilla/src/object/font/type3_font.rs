@@ -1,10 +1,10 @@
 //! Type 3 fonts.
 
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::hash::{Hash, Hasher};
 use std::ops::DerefMut;
 
-use pdf_writer::types::{FontFlags, UnicodeCmap};
+use pdf_writer::types::UnicodeCmap;
 use pdf_writer::writers::WMode;
 use pdf_writer::{Chunk, Content, Finish, Name, Ref, Str};
 use skrifa::GlyphId;
@@ -251,17 +251,7 @@ impl Type3Font {
         };
         let cmap_ref = sc.new_ref();
 
-        let mut flags = FontFlags::empty();
-        flags.set(
-            FontFlags::SERIF,
-            self.font
-                .postscript_name()
-                .is_some_and(|n| n.contains("Serif")),
-        );
-        flags.set(FontFlags::FIXED_PITCH, self.font.is_monospaced());
-        flags.set(FontFlags::ITALIC, self.font.italic_angle() != 0.0);
-        flags.insert(FontFlags::SYMBOLIC);
-        flags.insert(FontFlags::SMALL_CAP);
+        let flags = self.font.descriptor_flags();
 
         let italic_angle = self.font.italic_angle();
         let ascender = font_bbox.bottom();
@@ -341,6 +331,12 @@ impl Type3Font {
                     }
                 });
 
+            // Glyph space is given in font units, while the override is given in
+            // thousandths of an em.
+            if let Some(missing_width) = self.font.missing_width() {
+                font_descriptor.missing_width(missing_width / 1000.0 * self.font.units_per_em());
+            }
+
             font_descriptor.finish();
         }
 
@@ -489,6 +485,14 @@ impl Type3FontMapper {
         &self.fonts
     }
 
+    /// Return the glyphs that have been used in the document so far.
+    pub(crate) fn used_glyphs(&self) -> BTreeSet<GlyphId> {
+        self.fonts
+            .iter()
+            .flat_map(|f| f.glyphs.iter().map(|g| g.glyph_id))
+            .collect()
+    }
+
     pub(crate) fn add_glyph(&mut self, glyph: OwnedCoveredGlyph) -> (FontIdentifier, Gid) {
         // If the glyph has already been added, return the font identifier of
         // the type 3 font as well as the Type3 gid in that font.
@@ -0,0 +1,673 @@
+//! Interactive forms, allowing users to fill in a document.
+//!
+//! PDF allows adding interactive form fields to a document, which are displayed via
+//! so-called widget annotations. krilla only supports a small subset of the form fields
+//! defined by the PDF reference, and each field is always represented by exactly one widget
+//! on one page. As of right now, the following fields are supported:
+//!
+//! - Text fields, which allow entering text.
+//! - Check boxes, which can be toggled on and off.
+//! - Push buttons, which can trigger an [`Action`] when clicked.
+//!
+//! Form fields can be added to a page via [`Page::add_form_field`]. Each field needs a name
+//! that is unique within the document. The appearance of a field is defined by a custom
+//! [`Stream`], which is scaled so that it fills the rectangle of the field, similarly to
+//! stamp annotations. Check boxes get a generated appearance by default, whose mark is
+//! drawn as a vector path at the size of the field. Since viewers usually regenerate the
+//! appearance of a text field once it is edited, text fields additionally define the font
+//! and size that should be used for their text.
+//!
+//! [`Page::add_form_field`]: crate::page::Page::add_form_field
+
+use pdf_writer::{Finish, Name, Ref, Str, TextStr};
+use tiny_skia_path::{PathBuilder, Rect};
+
+use crate::color::rgb;
+use crate::error::{KrillaError, KrillaResult};
+use crate::object::action::Action;
+use crate::object::annotation::{appearance, scaled_appearance};
+use crate::page::page_root_transform;
+use crate::path::{Fill, LineCap, LineJoin, Stroke};
+use crate::serialize::SerializeContext;
+use crate::stream::Stream;
+use crate::util::RectExt;
+
+/// A font that can be used for the text of a text field.
+///
+/// Only the standard fonts are supported, since viewers need to be able to
+/// render arbitrary text with them when a user edits the field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FieldFont {
+    /// The Helvetica font.
+    Helvetica,
+    /// The Times Roman font.
+    TimesRoman,
+    /// The Courier font.
+    Courier,
+}
+
+impl FieldFont {
+    /// The name of the font in the resources of the interactive form.
+    pub(crate) fn resource_name(&self) -> &'static str {
+        match self {
+            FieldFont::Helvetica => "Helv",
+            FieldFont::TimesRoman => "TiRo",
+            FieldFont::Courier => "Cour",
+        }
+    }
+
+    pub(crate) fn base_font(&self) -> Name<'static> {
+        match self {
+            FieldFont::Helvetica => Name(b"Helvetica"),
+            FieldFont::TimesRoman => Name(b"Times-Roman"),
+            FieldFont::Courier => Name(b"Courier"),
+        }
+    }
+}
+
+/// A form field.
+pub enum FormField {
+    /// A text field.
+    Text(TextField),
+    /// A check box.
+    CheckBox(CheckBox),
+    /// A push button.
+    PushButton(PushButton),
+}
+
+impl From<TextField> for FormField {
+    fn from(value: TextField) -> Self {
+        FormField::Text(value)
+    }
+}
+
+impl From<CheckBox> for FormField {
+    fn from(value: CheckBox) -> Self {
+        FormField::CheckBox(value)
+    }
+}
+
+impl From<PushButton> for FormField {
+    fn from(value: PushButton) -> Self {
+        FormField::PushButton(value)
+    }
+}
+
+impl FormField {
+    fn name(&self) -> &str {
+        match self {
+            FormField::Text(t) => &t.name,
+            FormField::CheckBox(c) => &c.name,
+            FormField::PushButton(p) => &p.name,
+        }
+    }
+
+    fn rect(&self) -> Rect {
+        match self {
+            FormField::Text(t) => t.rect,
+            FormField::CheckBox(c) => c.rect,
+            FormField::PushButton(p) => p.rect,
+        }
+    }
+
+    pub(crate) fn serialize_type(
+        &self,
+        sc: &mut SerializeContext,
+        annotation: &mut pdf_writer::writers::Annotation,
+        page_height: f32,
+        root_ref: Ref,
+    ) -> KrillaResult<()> {
+        let name = self.name();
+
+        // Periods are used to separate the names of nested fields.
+        if name.is_empty() || name.contains('.') {
+            return Err(KrillaError::UserError(format!(
+                "the form field name `{name}` must be non-empty and must not contain periods"
+            )));
+        }
+
+        sc.register_form_field(name, root_ref)?;
+
+        annotation.pair(Name(b"Subtype"), Name(b"Widget"));
+        annotation.pair(Name(b"T"), TextStr(name));
+
+        let rect = self.rect();
+        let actual_rect = rect.transform(page_root_transform(page_height)).unwrap();
+        annotation.rect(actual_rect.to_pdf_rect());
+
+        match self {
+            FormField::Text(t) => t.serialize_type(sc, annotation, actual_rect, page_height),
+            FormField::CheckBox(c) => c.serialize_type(sc, annotation, actual_rect, page_height),
+            FormField::PushButton(p) => {
+                p.serialize_type(sc, annotation, actual_rect, page_height)?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A text field.
+pub struct TextField {
+    name: String,
+    rect: Rect,
+    value: Option<String>,
+    font: FieldFont,
+    font_size: f32,
+    text_color: rgb::Color,
+    multiline: bool,
+    appearance: Option<Stream>,
+}
+
+impl TextField {
+    /// Create a new, empty text field with the given name, which covers `rect` on the page.
+    ///
+    /// By default, the text of the field is drawn in black using 12pt Helvetica.
+    pub fn new(name: String, rect: Rect) -> Self {
+        Self {
+            name,
+            rect,
+            value: None,
+            font: FieldFont::Helvetica,
+            font_size: 12.0,
+            text_color: rgb::Color::black(),
+            multiline: false,
+            appearance: None,
+        }
+    }
+
+    /// Set the initial value of the text field.
+    ///
+    /// Note that the value is not drawn automatically, so it should also be
+    /// part of the appearance of the field.
+    pub fn with_value(mut self, value: String) -> Self {
+        self.value = Some(value);
+        self
+    }
+
+    /// Set the font and font size that viewers should use for the text of the field.
+    pub fn with_font(mut self, font: FieldFont, font_size: f32) -> Self {
+        self.font = font;
+        self.font_size = font_size;
+        self
+    }
+
+    /// Set the color that viewers should use for the text of the field.
+    pub fn with_text_color(mut self, text_color: rgb::Color) -> Self {
+        self.text_color = text_color;
+        self
+    }
+
+    /// Set whether the text field may contain multiple lines of text.
+    pub fn with_multiline(mut self, multiline: bool) -> Self {
+        self.multiline = multiline;
+        self
+    }
+
+    /// Set the appearance of the text field.
+    ///
+    /// If no appearance is set, the field will initially be displayed empty, unless
+    /// the document asks viewers to regenerate the appearances of all fields via
+    /// [`Document::set_need_appearances`].
+    ///
+    /// [`Document::set_need_appearances`]: crate::Document::set_need_appearances
+    pub fn with_appearance(mut self, appearance: Stream) -> Self {
+        self.appearance = Some(appearance);
+        self
+    }
+
+    fn serialize_type(
+        &self,
+        sc: &mut SerializeContext,
+        annotation: &mut pdf_writer::writers::Annotation,
+        actual_rect: Rect,
+        page_height: f32,
+    ) {
+        annotation.pair(Name(b"FT"), Name(b"Tx"));
+
+        if let Some(value) = &self.value {
+            annotation.pair(Name(b"V"), TextStr(value));
+        }
+
+        if self.multiline {
+            annotation.pair(Name(b"Ff"), 1 << 12);
+        }
+
+        sc.register_form_font(self.font);
+        let [r, g, b] = self.text_color.to_pdf_color();
+        let default_appearance = format!(
+            "/{} {} Tf {r} {g} {b} rg",
+            self.font.resource_name(),
+            self.font_size
+        );
+        annotation.pair(Name(b"DA"), Str(default_appearance.as_bytes()));
+
+        let appearance_ref = match &self.appearance {
+            Some(stream) => scaled_appearance(sc, stream, self.rect, actual_rect, page_height),
+            None => appearance(sc, actual_rect, page_height, |_| {}),
+        };
+        annotation.appearance().normal().stream(appearance_ref);
+    }
+}
+
+/// The look of the generated appearance of a check box.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CheckBoxStyle {
+    /// A square box with a checkmark.
+    #[default]
+    Check,
+    /// A circle with a dot, like a radio button.
+    Dot,
+}
+
+/// A check box.
+pub struct CheckBox {
+    name: String,
+    rect: Rect,
+    checked: bool,
+    style: CheckBoxStyle,
+    color: rgb::Color,
+    appearances: Option<(Stream, Stream)>,
+}
+
+impl CheckBox {
+    /// Create a new, unchecked check box with the given name, which covers `rect` on the page.
+    ///
+    /// By default, the check box is drawn as a black box with a checkmark, which
+    /// is scaled to the size of `rect`.
+    pub fn new(name: String, rect: Rect) -> Self {
+        Self {
+            name,
+            rect,
+            checked: false,
+            style: CheckBoxStyle::default(),
+            color: rgb::Color::black(),
+            appearances: None,
+        }
+    }
+
+    /// Set whether the check box is initially checked.
+    pub fn with_checked(mut self, checked: bool) -> Self {
+        self.checked = checked;
+        self
+    }
+
+    /// Set the look of the generated appearance.
+    pub fn with_style(mut self, style: CheckBoxStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Set the color of the border and the mark of the generated appearance.
+    pub fn with_color(mut self, color: rgb::Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Use custom appearances instead of the generated one.
+    ///
+    /// `on` and `off` define the appearance of the check box when it is checked
+    /// and unchecked, respectively.
+    pub fn with_appearances(mut self, on: Stream, off: Stream) -> Self {
+        self.appearances = Some((on, off));
+        self
+    }
+
+    /// Register the generated appearance of the check box. All sizes are relative
+    /// to the size of the field, so that the mark looks the same at any size.
+    fn generated_appearance(
+        &self,
+        sc: &mut SerializeContext,
+        actual_rect: Rect,
+        page_height: f32,
+        checked: bool,
+    ) -> Ref {
+        let rect = self.rect;
+        let size = rect.width().min(rect.height());
+        let border_width = (size * 0.06).max(0.5);
+        let half = border_width / 2.0;
+
+        let stroke = |width: f32| Stroke {
+            paint: self.color.into(),
+            width,
+            line_cap: LineCap::Round,
+            line_join: LineJoin::Round,
+            ..Stroke::default()
+        };
+
+        appearance(sc, actual_rect, page_height, |surface| {
+            let Some(inner) = Rect::from_ltrb(
+                rect.left() + half,
+                rect.top() + half,
+                rect.right() - half,
+                rect.bottom() - half,
+            ) else {
+                return;
+            };
+
+            let mut builder = PathBuilder::new();
+            match self.style {
+                CheckBoxStyle::Check => builder.push_rect(inner),
+                CheckBoxStyle::Dot => builder.push_oval(inner),
+            }
+
+            if let Some(path) = builder.finish() {
+                surface.stroke_path(&path, stroke(border_width));
+            }
+
+            if !checked {
+                return;
+            }
+
+            let (cx, cy) = (
+                rect.left() + rect.width() / 2.0,
+                rect.top() + rect.height() / 2.0,
+            );
+            let mut builder = PathBuilder::new();
+
+            match self.style {
+                CheckBoxStyle::Check => {
+                    // The checkmark spans most of the box, relative to its center.
+                    builder.move_to(cx - size * 0.28, cy + size * 0.02);
+                    builder.line_to(cx - size * 0.08, cy + size * 0.22);
+                    builder.line_to(cx + size * 0.28, cy - size * 0.22);
+
+                    if let Some(path) = builder.finish() {
+                        surface.stroke_path(&path, stroke(size * 0.12));
+                    }
+                }
+                CheckBoxStyle::Dot => {
+                    let radius = size * 0.22;
+                    if let Some(dot) =
+                        Rect::from_xywh(cx - radius, cy - radius, 2.0 * radius, 2.0 * radius)
+                    {
+                        builder.push_oval(dot);
+                    }
+
+                    if let Some(path) = builder.finish() {
+                        surface.fill_path(
+                            &path,
+                            Fill {
+                                paint: self.color.into(),
+                                ..Fill::default()
+                            },
+                        );
+                    }
+                }
+            }
+        })
+    }
+
+    fn serialize_type(
+        &self,
+        sc: &mut SerializeContext,
+        annotation: &mut pdf_writer::writers::Annotation,
+        actual_rect: Rect,
+        page_height: f32,
+    ) {
+        annotation.pair(Name(b"FT"), Name(b"Btn"));
+
+        let state = if self.checked {
+            Name(b"Yes")
+        } else {
+            Name(b"Off")
+        };
+        annotation.pair(Name(b"V"), state);
+        annotation.pair(Name(b"AS"), state);
+
+        let (on_ref, off_ref) = match &self.appearances {
+            Some((on, off)) => (
+                scaled_appearance(sc, on, self.rect, actual_rect, page_height),
+                scaled_appearance(sc, off, self.rect, actual_rect, page_height),
+            ),
+            None => (
+                self.generated_appearance(sc, actual_rect, page_height, true),
+                self.generated_appearance(sc, actual_rect, page_height, false),
+            ),
+        };
+
+        let mut appearance = annotation.insert(Name(b"AP")).dict();
+        let mut normal = appearance.insert(Name(b"N")).dict();
+        normal.pair(Name(b"Yes"), on_ref);
+        normal.pair(Name(b"Off"), off_ref);
+        normal.finish();
+        appearance.finish();
+    }
+}
+
+/// A push button.
+pub struct PushButton {
+    name: String,
+    rect: Rect,
+    appearance: Stream,
+    action: Option<Action>,
+}
+
+impl PushButton {
+    /// Create a new push button with the given name and appearance, which
+    /// covers `rect` on the page.
+    pub fn new(name: String, rect: Rect, appearance: Stream) -> Self {
+        Self {
+            name,
+            rect,
+            appearance,
+            action: None,
+        }
+    }
+
+    /// Set the action that should be performed when the button is clicked.
+    pub fn with_action(mut self, action: impl Into<Action>) -> Self {
+        self.action = Some(action.into());
+        self
+    }
+
+    fn serialize_type(
+        &self,
+        sc: &mut SerializeContext,
+        annotation: &mut pdf_writer::writers::Annotation,
+        actual_rect: Rect,
+        page_height: f32,
+    ) -> KrillaResult<()> {
+        annotation.pair(Name(b"FT"), Name(b"Btn"));
+        annotation.pair(Name(b"Ff"), 1 << 16);
+
+        let appearance_ref =
+            scaled_appearance(sc, &self.appearance, self.rect, actual_rect, page_height);
+        annotation.appearance().normal().stream(appearance_ref);
+
+        if let Some(action) = &self.action {
+            action.serialize(sc, annotation.action())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::action::LinkAction;
+    use crate::color::rgb;
+    use crate::document::Document;
+    use crate::error::{KrillaError, KrillaResult};
+    use crate::form::{CheckBox, CheckBoxStyle, FieldFont, PushButton, TextField};
+    use crate::page::Page;
+    use crate::stream::Stream;
+    use crate::surface::Surface;
+    use crate::tests::{green_fill, rect_to_path, red_fill};
+    use crate::SerializeSettings;
+    use krilla_macros::visreg;
+    use tiny_skia_path::Rect;
+
+    fn box_stream(surface: &mut Surface, checked: bool) -> Stream {
+        let mut stream_builder = surface.stream_builder();
+        let mut box_surface = stream_builder.surface();
+        let fill = if checked {
+            green_fill(1.0)
+        } else {
+            red_fill(1.0)
+        };
+        box_surface.fill_path(&rect_to_path(0.0, 0.0, 10.0, 10.0), fill);
+        box_surface.finish();
+
+        stream_builder.finish()
+    }
+
+    fn form_pdf(add_fields: impl FnOnce(&mut Page, Stream, Stream)) -> KrillaResult<String> {
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        let on = box_stream(&mut surface, true);
+        let off = box_stream(&mut surface, false);
+        surface.finish();
+        add_fields(&mut page, on, off);
+        page.finish();
+
+        document
+            .finish()
+            .map(|pdf| String::from_utf8_lossy(&pdf).to_string())
+    }
+
+    #[test]
+    fn form_text_field() {
+        let pdf = form_pdf(|page, _, _| {
+            page.add_form_field(
+                TextField::new(
+                    "name".to_string(),
+                    Rect::from_xywh(20.0, 20.0, 100.0, 20.0).unwrap(),
+                )
+                .with_value("John".to_string())
+                .with_font(FieldFont::Courier, 10.0),
+                Some("Your name".to_string()),
+            );
+        })
+        .unwrap();
+
+        assert!(pdf.contains("/Subtype /Widget"));
+        assert!(pdf.contains("/FT /Tx"));
+        assert!(pdf.contains("/T (name)"));
+        assert!(pdf.contains("/V (John)"));
+        assert!(pdf.contains("/TU (Your name)"));
+        assert!(pdf.contains("/DA (/Cour 10 Tf 0 0 0 rg)"));
+        assert!(pdf.contains("/BaseFont /Courier"));
+        assert!(pdf.contains("/AcroForm"));
+        assert!(!pdf.contains("/NeedAppearances"));
+    }
+
+    #[test]
+    fn form_check_box() {
+        let pdf = form_pdf(|page, on, off| {
+            page.add_form_field(
+                CheckBox::new(
+                    "agree".to_string(),
+                    Rect::from_xywh(20.0, 20.0, 10.0, 10.0).unwrap(),
+                )
+                .with_appearances(on, off)
+                .with_checked(true),
+                None,
+            );
+        })
+        .unwrap();
+
+        assert!(pdf.contains("/FT /Btn"));
+        assert!(pdf.contains("/V /Yes"));
+        assert!(pdf.contains("/AS /Yes"));
+        assert!(pdf.contains("/Yes "));
+        assert!(pdf.contains("/Off "));
+        assert!(!pdf.contains("/DR"));
+    }
+
+    #[test]
+    fn form_check_box_generated_appearance() {
+        let pdf = form_pdf(|page, _, _| {
+            page.add_form_field(
+                CheckBox::new(
+                    "agree".to_string(),
+                    Rect::from_xywh(20.0, 20.0, 10.0, 10.0).unwrap(),
+                )
+                .with_checked(true),
+                None,
+            );
+        })
+        .unwrap();
+
+        assert!(pdf.contains("/AS /Yes"));
+        // The border and the checkmark are stroked in the generated appearance.
+        assert_eq!(pdf.lines().filter(|l| *l == "S").count(), 3);
+    }
+
+    #[visreg(document)]
+    fn form_check_box_sizes(document: &mut Document) {
+        let mut page = document.start_page_with(crate::PageSettings::new(200.0, 100.0));
+
+        for (i, size) in [8.0, 32.0].into_iter().enumerate() {
+            let y = 10.0 + i as f32 * 40.0;
+
+            for (j, style) in [CheckBoxStyle::Check, CheckBoxStyle::Dot]
+                .into_iter()
+                .enumerate()
+            {
+                for (k, checked) in [true, false].into_iter().enumerate() {
+                    let x = 10.0 + (j * 2 + k) as f32 * 45.0;
+
+                    page.add_form_field(
+                        CheckBox::new(
+                            format!("box-{size}-{j}-{k}"),
+                            Rect::from_xywh(x, y, size, size).unwrap(),
+                        )
+                        .with_style(style)
+                        .with_color(rgb::Color::new(0, 0, 255))
+                        .with_checked(checked),
+                        None,
+                    );
+                }
+            }
+        }
+
+        page.finish();
+    }
+
+    #[test]
+    fn form_push_button() {
+        let pdf = form_pdf(|page, on, _| {
+            page.add_form_field(
+                PushButton::new(
+                    "website".to_string(),
+                    Rect::from_xywh(20.0, 20.0, 50.0, 20.0).unwrap(),
+                    on,
+                )
+                .with_action(LinkAction::new("https://example.com".to_string())),
+                None,
+            );
+        })
+        .unwrap();
+
+        assert!(pdf.contains("/Ff 65536"));
+        assert!(pdf.contains("/URI (https://example.com)"));
+    }
+
+    #[test]
+    fn form_duplicate_field_name() {
+        let rect = Rect::from_xywh(20.0, 20.0, 100.0, 20.0).unwrap();
+        let result = form_pdf(|page, _, _| {
+            page.add_form_field(TextField::new("name".to_string(), rect), None);
+            page.add_form_field(TextField::new("name".to_string(), rect), None);
+        });
+
+        assert_eq!(
+            result,
+            Err(KrillaError::UserError(
+                "the form field name `name` is used more than once".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn form_invalid_field_name() {
+        let rect = Rect::from_xywh(20.0, 20.0, 100.0, 20.0).unwrap();
+        let result = form_pdf(|page, _, _| {
+            page.add_form_field(TextField::new("a.b".to_string(), rect), None);
+        });
+
+        assert!(matches!(result, Err(KrillaError::UserError(_))));
+    }
+}
@@ -6,14 +6,19 @@
 //! - JPG
 //! - GIF
 //! - WEBP
+//! - TIFF
+//!
+//! If the `svg` feature is enabled, you can also rasterize SVGs into an image
+//! by using [`Image::from_svg`].
 
 // TODO: CLean up and update docs
+use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
 use std::hash::{Hash, Hasher};
 use std::ops::DerefMut;
 use std::sync::Arc;
 
-use pdf_writer::{Chunk, Finish, Name, Ref};
+use pdf_writer::{Chunk, Finish, Name, Ref, Str};
 use zune_jpeg::zune_core::result::DecodingResult;
 use zune_jpeg::JpegDecoder;
 use zune_png::zune_core::colorspace::ColorSpace;
@@ -82,34 +87,48 @@ impl TryFrom<ColorSpace> for ImageColorspace {
     }
 }
 
+#[derive(Clone)]
 struct SampledRepr {
     color_channel: Vec<u8>,
     alpha_channel: Option<Vec<u8>>,
     bits_per_component: BitsPerComponent,
+    /// The color table of an indexed image. If present, the color channel
+    /// contains one index per pixel instead of the actual color components.
+    palette: Option<Vec<u8>>,
 }
 
+#[derive(Clone)]
 struct JpegRepr {
     data: Arc<dyn AsRef<[u8]> + Send + Sync>,
     bits_per_component: BitsPerComponent,
     invert_cmyk: bool,
 }
 
+#[derive(Clone)]
+struct StencilRepr {
+    data: Vec<u8>,
+    decode_inverted: bool,
+}
+
+#[derive(Clone)]
 enum Repr {
     Sampled(SampledRepr),
     Jpeg(JpegRepr),
+    Stencil(StencilRepr),
 }
 
 impl Repr {
-    fn bits_per_component(&self) -> BitsPerComponent {
+    fn bits_per_component(&self) -> u8 {
         match self {
-            Repr::Sampled(s) => s.bits_per_component,
-            Repr::Jpeg(j) => j.bits_per_component,
+            Repr::Sampled(s) => s.bits_per_component.as_u8(),
+            Repr::Jpeg(j) => j.bits_per_component.as_u8(),
+            Repr::Stencil(_) => 1,
         }
     }
 }
 
 /// A trait for custom images, which you can use if the
-/// current methods provided by krilla (JPEG/PNG/WEBP/GIF) images
+/// current methods provided by krilla (JPEG/PNG/WEBP/GIF/TIFF) images
 /// are not suitable for your own purpose.
 ///
 /// Note that a struct implementing this trait should be cheap to
@@ -138,6 +157,8 @@ struct ImageMetadata {
 struct ImageRepr {
     inner: Deferred<Option<Repr>>,
     metadata: ImageMetadata,
+    /// An explicit soft mask, which overrides the alpha channel of the image.
+    soft_mask: Option<Image>,
     sip: u128,
 }
 
@@ -202,12 +223,17 @@ impl Image {
         Some(Self(Arc::new(ImageRepr {
             inner: Deferred::new(move || decode_png(data.as_ref().as_ref())),
             metadata,
+            soft_mask: None,
             sip: hash,
         })))
     }
 
     /// Create a new bitmap image from a `.jpg` file.
     ///
+    /// The image will not be decoded, instead the original DCT-encoded data
+    /// is written as is into the PDF, which means that there is no loss in
+    /// quality and the size of the image stays the same.
+    ///
     /// Returns `None` if krilla was unable to parse the file.
     pub fn from_jpeg(data: Arc<dyn AsRef<[u8]> + Send + Sync>) -> Option<Image> {
         let hash = data.as_ref().as_ref().sip_hash();
@@ -216,6 +242,7 @@ impl Image {
         Some(Self(Arc::new(ImageRepr {
             inner: Deferred::new(move || decode_jpeg(data)),
             metadata,
+            soft_mask: None,
             sip: hash,
         })))
     }
@@ -230,6 +257,7 @@ impl Image {
         Some(Self(Arc::new(ImageRepr {
             inner: Deferred::new(move || decode_gif(data)),
             metadata,
+            soft_mask: None,
             sip: hash,
         })))
     }
@@ -244,6 +272,26 @@ impl Image {
         Some(Self(Arc::new(ImageRepr {
             inner: Deferred::new(move || decode_webp(data)),
             metadata,
+            soft_mask: None,
+            sip: hash,
+        })))
+    }
+
+    /// Create a new bitmap image from a `.tiff` file.
+    ///
+    /// Only baseline TIFF images with 8 or 16 bits per sample in the gray, RGB
+    /// or CMYK color space are supported. CMYK images are embedded as is, without
+    /// converting them into RGB.
+    ///
+    /// Returns `None` if krilla was unable to parse the file.
+    pub fn from_tiff(data: Arc<dyn AsRef<[u8]> + Send + Sync>) -> Option<Image> {
+        let hash = data.as_ref().as_ref().sip_hash();
+        let metadata = tiff_metadata(data.as_ref().as_ref())?;
+
+        Some(Self(Arc::new(ImageRepr {
+            inner: Deferred::new(move || decode_tiff(data.as_ref().as_ref())),
+            metadata,
+            soft_mask: None,
             sip: hash,
         })))
     }
@@ -282,15 +330,56 @@ impl Image {
                     color_channel: deflate_encode(color_channel),
                     alpha_channel: image.alpha_channel().map(deflate_encode),
                     bits_per_component: image.bits_per_component(),
+                    palette: None,
+                }))
+            }),
+            metadata,
+            soft_mask: None,
+            sip: hash,
+        })))
+    }
+
+    /// Create a new stencil mask from 1-bit data.
+    ///
+    /// A stencil mask doesn't have any colors on its own, instead it is painted
+    /// with the fill you pass to [`Surface::draw_stencil`]. Each row of `data` must
+    /// start at a new byte, with the most significant bit of a byte being the leftmost
+    /// pixel. By default, pixels with a value of 0 are painted and pixels with a
+    /// value of 1 are left untouched. If `decode_inverted` is true, it's the other
+    /// way around.
+    ///
+    /// Returns `None` if the length of `data` doesn't match the dimensions.
+    ///
+    /// [`Surface::draw_stencil`]: crate::surface::Surface::draw_stencil
+    pub fn stencil(data: Vec<u8>, width: u32, height: u32, decode_inverted: bool) -> Option<Image> {
+        let row_len = width.div_ceil(8) as usize;
+
+        if width == 0 || height == 0 || data.len() != row_len * height as usize {
+            return None;
+        }
+
+        let hash = (&data, width, height, decode_inverted).sip_hash();
+        let metadata = ImageMetadata {
+            size: (width, height),
+            color_space: ImageColorspace::Luma,
+            icc: None,
+        };
+
+        Some(Self(Arc::new(ImageRepr {
+            inner: Deferred::new(move || {
+                Some(Repr::Stencil(StencilRepr {
+                    data: deflate_encode(&data),
+                    decode_inverted,
                 }))
             }),
             metadata,
+            soft_mask: None,
             sip: hash,
         })))
     }
 
-    // Used for SVG filters
-    pub(crate) fn from_rgba8(data: Vec<u8>, width: u32, height: u32) -> Self {
+    #[cfg(feature = "svg")]
+    fn from_rgba8(data: Vec<u8>, width: u32, height: u32) -> Self {
         let hash = data.sip_hash();
         let metadata = ImageMetadata {
             size: (width, height),
@@ -307,13 +396,118 @@ impl Image {
                     color_channel,
                     alpha_channel,
                     bits_per_component,
+                    palette: None,
                 }))
             }),
             metadata,
+            soft_mask: None,
             sip: hash,
         }))
     }
 
+    /// Rasterize an SVG into an image with the given resolution in dots per inch.
+    ///
+    /// One unit in the coordinate system of the SVG corresponds to one point (1/72 inch),
+    /// so with a resolution of 144 dpi, an SVG with a size of 100x100 will result in an
+    /// image with 200x200 pixels. This is useful if you want to flatten vector content,
+    /// for example to ensure that it renders the same way in all viewers or to get rid
+    /// of transparency, which is not allowed in some export modes like PDF/A-1.
+    ///
+    /// Returns `None` if the resulting image would be empty or too large.
+    #[cfg(feature = "svg")]
+    pub fn from_svg(tree: &usvg::Tree, dpi: f32) -> Option<Image> {
+        let scale = dpi / 72.0;
+        let width = (tree.size().width() * scale).round() as u32;
+        let height = (tree.size().height() * scale).round() as u32;
+
+        let mut pixmap = tiny_skia::Pixmap::new(width, height)?;
+        let transform = tiny_skia_path::Transform::from_scale(
+            width as f32 / tree.size().width(),
+            height as f32 / tree.size().height(),
+        );
+        resvg::render(tree, transform, &mut pixmap.as_mut());
+
+        Some(Self::from_pixmap(&pixmap))
+    }
+
+    // Used for SVG filters and rasterized SVGs.
+    #[cfg(feature = "svg")]
+    pub(crate) fn from_pixmap(pixmap: &tiny_skia::Pixmap) -> Self {
+        let demultiplied = pixmap
+            .pixels()
+            .iter()
+            .flat_map(|p| {
+                let c = p.demultiply();
+                [c.red(), c.green(), c.blue(), c.alpha()]
+            })
+            .collect::<Vec<_>>();
+
+        Self::from_rgba8(demultiplied, pixmap.width(), pixmap.height())
+    }
+
+    /// Return a version of this image that is embedded with an indexed color space.
+    ///
+    /// If the image has at most 256 distinct colors, each pixel will be stored
+    /// as a single byte pointing into a color table, which can drastically reduce
+    /// the file size for images with few colors, such as screenshots or diagrams.
+    /// Otherwise, as well as for 16-bit and JPEG images, the image will be embedded
+    /// as is.
+    ///
+    /// Note that the returned image is treated as a different image than the
+    /// original one, so using both of them will result in two separate image objects.
+    pub fn as_indexed(&self) -> Image {
+        let original = self.clone();
+        let color_space = self.color_space();
+        let metadata = ImageMetadata {
+            size: self.size(),
+            color_space,
+            icc: self.icc(),
+        };
+
+        Self(Arc::new(ImageRepr {
+            inner: Deferred::new(move || {
+                original.0.inner.wait().as_ref().map(|repr| match repr {
+                    Repr::Sampled(s) => {
+                        Repr::Sampled(index_sampled(s, color_space).unwrap_or_else(|| s.clone()))
+                    }
+                    Repr::Jpeg(j) => Repr::Jpeg(j.clone()),
+                    Repr::Stencil(s) => Repr::Stencil(s.clone()),
+                })
+            }),
+            metadata,
+            soft_mask: self.0.soft_mask.clone(),
+            sip: (self.0.sip, "indexed").sip_hash(),
+        }))
+    }
+
+    /// Return a version of the `base` image that uses `mask` as its soft mask.
+    ///
+    /// The mask must be a grayscale image, where black means fully transparent
+    /// and white means fully opaque. Any alpha channel of the base image will be
+    /// replaced by the mask. The mask doesn't need to have the same dimensions
+    /// as the base image, in which case it will be resampled by the PDF viewer.
+    ///
+    /// Returns `None` if the mask is not a grayscale image.
+    pub fn with_soft_mask(base: Image, mask: Image) -> Option<Image> {
+        if mask.color_space() != ImageColorspace::Luma {
+            return None;
+        }
+
+        let metadata = ImageMetadata {
+            size: base.size(),
+            color_space: base.color_space(),
+            icc: base.icc(),
+        };
+        let sip = (base.0.sip, mask.0.sip, "soft_mask").sip_hash();
+
+        Some(Self(Arc::new(ImageRepr {
+            inner: Deferred::new(move || base.0.inner.wait().clone()),
+            metadata,
+            soft_mask: Some(mask),
+            sip,
+        })))
+    }
+
     /// Return the size of the image.
     pub fn size(&self) -> (u32, u32) {
         self.0.size()
@@ -365,30 +559,72 @@ impl Image {
                 .as_ref()
                 .ok_or(KrillaError::ImageError(self.clone()))?;
 
-            let alpha_mask = match repr {
-                Repr::Sampled(sampled) => sampled.alpha_channel.as_ref().map(|mask_data| {
-                    let mask_stream = FilterStreamBuilder::new_from_deflated(mask_data)
-                        .finish(&serialize_settings);
-                    let mut s_mask = chunk.image_xobject(soft_mask_id, mask_stream.encoded_data());
-                    mask_stream.write_filters(s_mask.deref_mut().deref_mut());
-                    s_mask.width(self.size().0 as i32);
-                    s_mask.height(self.size().1 as i32);
-                    s_mask.pair(
-                        Name(b"ColorSpace"),
-                        // Mask color space must be device gray -- see Table 145.
-                        DEVICE_GRAY.to_pdf_name(),
-                    );
-                    s_mask.bits_per_component(repr.bits_per_component().as_u8() as i32);
-                    soft_mask_id
-                }),
-                Repr::Jpeg(_) => None,
+            let alpha_mask = if let Some(soft_mask) = &self.0.soft_mask {
+                let mask_repr = soft_mask
+                    .0
+                    .inner
+                    .wait()
+                    .as_ref()
+                    .ok_or(KrillaError::ImageError(soft_mask.clone()))?;
+
+                let mask_stream = match mask_repr {
+                    Repr::Sampled(s) => FilterStreamBuilder::new_from_deflated(
+                        &s.color_channel,
+                        &serialize_settings,
+                    )
+                    .finish(&serialize_settings),
+                    Repr::Stencil(s) => {
+                        FilterStreamBuilder::new_from_deflated(&s.data, &serialize_settings)
+                            .finish(&serialize_settings)
+                    }
+                    Repr::Jpeg(j) => {
+                        FilterStreamBuilder::new_from_jpeg_data(j.data.as_ref().as_ref())
+                            .finish(&serialize_settings)
+                    }
+                };
+                let mut s_mask = chunk.image_xobject(soft_mask_id, mask_stream.encoded_data());
+                mask_stream.write_filters(s_mask.deref_mut().deref_mut());
+                s_mask.width(soft_mask.size().0 as i32);
+                s_mask.height(soft_mask.size().1 as i32);
+                s_mask.pair(Name(b"ColorSpace"), DEVICE_GRAY.to_pdf_name());
+                s_mask.bits_per_component(mask_repr.bits_per_component() as i32);
+                s_mask.finish();
+
+                Some(soft_mask_id)
+            } else {
+                match repr {
+                    Repr::Sampled(sampled) => sampled.alpha_channel.as_ref().map(|mask_data| {
+                        let mask_stream =
+                            FilterStreamBuilder::new_from_deflated(mask_data, &serialize_settings)
+                                .finish(&serialize_settings);
+                        let mut s_mask =
+                            chunk.image_xobject(soft_mask_id, mask_stream.encoded_data());
+                        mask_stream.write_filters(s_mask.deref_mut().deref_mut());
+                        s_mask.width(self.size().0 as i32);
+                        s_mask.height(self.size().1 as i32);
+                        s_mask.pair(
+                            Name(b"ColorSpace"),
+                            // Mask color space must be device gray -- see Table 145.
+                            DEVICE_GRAY.to_pdf_name(),
+                        );
+                        s_mask.bits_per_component(repr.bits_per_component() as i32);
+                        soft_mask_id
+                    }),
+                    Repr::Jpeg(_) | Repr::Stencil(_) => None,
+                }
             };
 
             let filter_stream = match repr {
-                Repr::Sampled(s) => FilterStreamBuilder::new_from_deflated(&s.color_channel)
-                    .finish(&serialize_settings),
+                Repr::Sampled(s) => {
+                    FilterStreamBuilder::new_from_deflated(&s.color_channel, &serialize_settings)
+                        .finish(&serialize_settings)
+                }
                 Repr::Jpeg(j) => FilterStreamBuilder::new_from_jpeg_data(j.data.as_ref().as_ref())
                     .finish(&serialize_settings),
+                Repr::Stencil(s) => {
+                    FilterStreamBuilder::new_from_deflated(&s.data, &serialize_settings)
+                        .finish(&serialize_settings)
+                }
             };
 
             let mut image_x_object = chunk.image_xobject(root_ref, filter_stream.encoded_data());
@@ -396,16 +632,42 @@ impl Image {
             image_x_object.width(self.size().0 as i32);
             image_x_object.height(self.size().1 as i32);
 
-            if let Some(icc_ref) = icc_ref {
+            let base_name = match self.color_space() {
+                ImageColorspace::Rgb => DEVICE_RGB.to_pdf_name(),
+                ImageColorspace::Luma => DEVICE_GRAY.to_pdf_name(),
+                ImageColorspace::Cmyk => DEVICE_CMYK.to_pdf_name(),
+            };
+
+            let palette = match repr {
+                Repr::Sampled(s) => s.palette.as_ref(),
+                Repr::Jpeg(_) | Repr::Stencil(_) => None,
+            };
+
+            if let Repr::Stencil(s) = repr {
+                // Stencil masks don't have a color space, they are painted with the
+                // current fill color instead.
+                image_x_object.image_mask(true);
+                if s.decode_inverted {
+                    image_x_object.decode([1.0, 0.0]);
+                }
+            } else if let Some(palette) = palette {
+                let num_components = self.color_space().num_components() as usize;
+                let hival = palette.len() / num_components - 1;
+
+                let mut array = image_x_object.insert(Name(b"ColorSpace")).array();
+                array.item(Name(b"Indexed"));
+                if let Some(icc_ref) = icc_ref {
+                    array.item(icc_ref);
+                } else {
+                    array.item(base_name);
+                }
+                array.item(hival as i32);
+                array.item(Str(palette));
+                array.finish();
+            } else if let Some(icc_ref) = icc_ref {
                 image_x_object.pair(Name(b"ColorSpace"), icc_ref);
             } else {
-                let name = match self.color_space() {
-                    ImageColorspace::Rgb => DEVICE_RGB.to_pdf_name(),
-                    ImageColorspace::Luma => DEVICE_GRAY.to_pdf_name(),
-                    ImageColorspace::Cmyk => DEVICE_CMYK.to_pdf_name(),
-                };
-
-                image_x_object.pair(Name(b"ColorSpace"), name);
+                image_x_object.pair(Name(b"ColorSpace"), base_name);
             }
 
             // Photoshop CMYK images need to be inverted, see
@@ -420,7 +682,7 @@ impl Image {
                 }
             }
 
-            image_x_object.bits_per_component(repr.bits_per_component().as_u8() as i32);
+            image_x_object.bits_per_component(repr.bits_per_component() as i32);
             if let Some(soft_mask_id) = alpha_mask {
                 image_x_object.s_mask(soft_mask_id);
             }
@@ -472,6 +734,7 @@ fn decode_png(data: &[u8]) -> Option<Repr> {
         color_channel,
         alpha_channel,
         bits_per_component,
+        palette: None,
     }))
 }
 
@@ -536,6 +799,7 @@ fn decode_gif(data: Arc<dyn AsRef<[u8]> + Send + Sync>) -> Option<Repr> {
         color_channel,
         alpha_channel,
         bits_per_component,
+        palette: None,
     }))
 }
 
@@ -584,9 +848,108 @@ fn decode_webp(data: Arc<dyn AsRef<[u8]> + Send + Sync>) -> Option<Repr> {
         color_channel,
         alpha_channel,
         bits_per_component,
+        palette: None,
     }))
 }
 
+/// The tag under which TIFF files store their embedded ICC profile.
+const TIFF_ICC_PROFILE_TAG: u16 = 34675;
+
+fn tiff_color_space(color_type: tiff::ColorType) -> Option<ColorSpace> {
+    match color_type {
+        tiff::ColorType::Gray(8 | 16) => Some(ColorSpace::Luma),
+        tiff::ColorType::GrayA(8 | 16) => Some(ColorSpace::LumaA),
+        tiff::ColorType::RGB(8 | 16) => Some(ColorSpace::RGB),
+        tiff::ColorType::RGBA(8 | 16) => Some(ColorSpace::RGBA),
+        tiff::ColorType::CMYK(8 | 16) => Some(ColorSpace::CMYK),
+        _ => None,
+    }
+}
+
+fn tiff_metadata(data: &[u8]) -> Option<ImageMetadata> {
+    let mut decoder = tiff::decoder::Decoder::new(std::io::Cursor::new(data)).ok()?;
+
+    let size = decoder.dimensions().ok()?;
+    let color_space = tiff_color_space(decoder.colortype().ok()?)?;
+    let image_color_space = color_space.try_into().ok()?;
+    let icc = decoder
+        .find_tag(tiff::tags::Tag::Unknown(TIFF_ICC_PROFILE_TAG))
+        .ok()?
+        .and_then(|v| v.into_u8_vec().ok())
+        .and_then(|d| get_icc_profile_type(&d, image_color_space));
+
+    Some(ImageMetadata {
+        size,
+        color_space: image_color_space,
+        icc,
+    })
+}
+
+fn decode_tiff(data: &[u8]) -> Option<Repr> {
+    let mut decoder = tiff::decoder::Decoder::new(std::io::Cursor::new(data)).ok()?;
+
+    let color_space = tiff_color_space(decoder.colortype().ok()?)?;
+
+    let (color_channel, alpha_channel, bits_per_component) = match decoder.read_image().ok()? {
+        tiff::decoder::DecodingResult::U8(u8) => handle_u8_image(&u8, color_space),
+        tiff::decoder::DecodingResult::U16(u16) => handle_u16_image(&u16, color_space),
+        _ => return None,
+    };
+
+    Some(Repr::Sampled(SampledRepr {
+        color_channel,
+        alpha_channel,
+        bits_per_component,
+        palette: None,
+    }))
+}
+
+/// The maximum number of colors that can be stored in the color table of an indexed image.
+const MAX_PALETTE_SIZE: usize = 256;
+
+/// Try to convert an 8-bit image into an indexed image. Returns `None` if the
+/// image has too many distinct colors.
+fn index_sampled(repr: &SampledRepr, color_space: ImageColorspace) -> Option<SampledRepr> {
+    let num_components = color_space.num_components() as usize;
+
+    // For grayscale images, an index takes up just as much space as the color itself.
+    if repr.bits_per_component != BitsPerComponent::Eight || num_components == 1 {
+        return None;
+    }
+
+    let data = miniz_oxide::inflate::decompress_to_vec_zlib(&repr.color_channel).ok()?;
+
+    let mut lookup = HashMap::new();
+    let mut palette = vec![];
+    let mut indices = Vec::with_capacity(data.len() / num_components);
+
+    for color in data.chunks_exact(num_components) {
+        let index = match lookup.get(color) {
+            Some(index) => *index,
+            None => {
+                let index = lookup.len();
+
+                if index >= MAX_PALETTE_SIZE {
+                    return None;
+                }
+
+                lookup.insert(color, index as u8);
+                palette.extend_from_slice(color);
+                index as u8
+            }
+        };
+
+        indices.push(index);
+    }
+
+    Some(SampledRepr {
+        color_channel: deflate_encode(&indices),
+        alpha_channel: repr.alpha_channel.clone(),
+        bits_per_component: BitsPerComponent::Eight,
+        palette: Some(palette),
+    })
+}
+
 fn handle_u8_image(data: &[u8], cs: ColorSpace) -> (Vec<u8>, Option<Vec<u8>>, BitsPerComponent) {
     let mut alphas = if cs.has_alpha() {
         Vec::with_capacity(data.len() / cs.num_components())
@@ -612,6 +975,7 @@ fn handle_u8_image(data: &[u8], cs: ColorSpace) -> (Vec<u8>, Option<Vec<u8>>, Bi
             deflate_encode(&data)
         }
         ColorSpace::Luma => deflate_encode(data),
+        ColorSpace::CMYK => deflate_encode(data),
         ColorSpace::LumaA => {
             let data = data
                 .iter()
@@ -627,7 +991,7 @@ fn handle_u8_image(data: &[u8], cs: ColorSpace) -> (Vec<u8>, Option<Vec<u8>>, Bi
                 .collect::<Vec<_>>();
             deflate_encode(&data)
         }
-        // PNG/WEBP/GIF only support those three, so should be enough?
+        // PNG/WEBP/GIF/TIFF only support those, so should be enough?
         _ => unimplemented!(),
     };
 
@@ -672,7 +1036,7 @@ fn handle_u16_image(data: &[u16], cs: ColorSpace) -> (Vec<u8>, Option<Vec<u8>>,
                 .collect::<Vec<_>>();
             deflate_encode(&data)
         }
-        ColorSpace::Luma => {
+        ColorSpace::Luma | ColorSpace::CMYK => {
             let data = data
                 .iter()
                 .flat_map(|b| b.to_be_bytes())
@@ -695,7 +1059,7 @@ fn handle_u16_image(data: &[u16], cs: ColorSpace) -> (Vec<u8>, Option<Vec<u8>>,
                 .collect::<Vec<_>>();
             deflate_encode(&data)
         }
-        // PNG/WEBP/GIF only support those three, so should be enough?
+        // PNG/WEBP/GIF/TIFF only support those, so should be enough?
         _ => unimplemented!(),
     };
 
@@ -711,16 +1075,183 @@ fn handle_u16_image(data: &[u16], cs: ColorSpace) -> (Vec<u8>, Option<Vec<u8>>,
 #[cfg(test)]
 mod tests {
     use crate::image::Image;
+    use crate::path::FillRule;
     use crate::serialize::SerializeContext;
     use crate::surface::Surface;
+    use crate::tests::{blue_fill, rect_to_path, red_fill};
     use crate::tests::{
         load_custom_image, load_custom_image_with_icc, load_gif_image, load_jpg_image,
-        load_png_image, load_webp_image,
+        load_png_image, load_tiff_image, load_webp_image,
     };
-    use crate::Document;
+    use crate::{Document, SerializeSettings};
     use krilla_macros::{snapshot, visreg};
+    use std::sync::Arc;
     use tiny_skia_path::Size;
 
+    #[test]
+    fn image_icc_profile_shared_with_output_intent() {
+        // PDF/A requires an output intent with the sRGB profile, which is the same one
+        // as the one embedded in the image, so it should only be written once.
+        let mut document = Document::new_with(SerializeSettings::settings_7());
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+
+        for _ in 0..2 {
+            let image = load_custom_image_with_icc(
+                "rgb8.png",
+                include_bytes!("../../icc/sRGB-v4.icc").to_vec(),
+            );
+            surface.draw_image(image, Size::from_wh(100.0, 100.0).unwrap());
+        }
+
+        surface.finish();
+        page.finish();
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert_eq!(pdf.matches("/Range [0 1 0 1 0 1]").count(), 1);
+        assert_eq!(pdf.matches("/ICCBased").count(), 1);
+    }
+
+    fn png_from_fn(f: impl Fn(u32, u32) -> [u8; 3]) -> Image {
+        let image = image::RgbImage::from_fn(200, 200, |x, y| image::Rgb(f(x, y)));
+
+        let mut data = std::io::Cursor::new(vec![]);
+        image.write_to(&mut data, image::ImageFormat::Png).unwrap();
+
+        Image::from_png(Arc::new(data.into_inner())).unwrap()
+    }
+
+    fn low_color_png() -> Image {
+        let colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 0]];
+        png_from_fn(|x, y| colors[((x * 7 + y * 13) ^ (x * y)) as usize % colors.len()])
+    }
+
+    fn image_pdf(image: Image) -> Vec<u8> {
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        surface.draw_image(image, Size::from_wh(200.0, 200.0).unwrap());
+        surface.finish();
+        page.finish();
+
+        document.finish().unwrap()
+    }
+
+    #[test]
+    fn image_indexed_is_smaller() {
+        let rgb = image_pdf(low_color_png());
+        let indexed = image_pdf(low_color_png().as_indexed());
+
+        assert!(!String::from_utf8_lossy(&rgb).contains("/Indexed"));
+        assert!(String::from_utf8_lossy(&indexed).contains("/Indexed /DeviceRGB 3 "));
+        assert!(indexed.len() < rgb.len());
+    }
+
+    #[test]
+    fn image_indexed_too_many_colors() {
+        let image = png_from_fn(|x, y| [x as u8, y as u8, 0]);
+        let pdf = image_pdf(image.as_indexed());
+        assert!(!String::from_utf8_lossy(&pdf).contains("/Indexed"));
+    }
+
+    fn gradient_mask() -> Image {
+        let image = image::GrayImage::from_fn(100, 100, |x, _| image::Luma([(x * 255 / 99) as u8]));
+
+        let mut data = std::io::Cursor::new(vec![]);
+        image.write_to(&mut data, image::ImageFormat::Png).unwrap();
+
+        Image::from_png(Arc::new(data.into_inner())).unwrap()
+    }
+
+    #[test]
+    fn image_with_soft_mask() {
+        let image = Image::with_soft_mask(load_png_image("rgb8.png"), gradient_mask()).unwrap();
+        let pdf = image_pdf(image);
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert_eq!(pdf.matches("/SMask").count(), 1);
+        assert_eq!(pdf.matches("/Subtype /Image").count(), 2);
+    }
+
+    #[test]
+    fn image_with_soft_mask_not_gray() {
+        assert!(
+            Image::with_soft_mask(load_png_image("luma8.png"), load_png_image("rgb8.png"))
+                .is_none()
+        );
+    }
+
+    #[visreg(all)]
+    fn image_rgb8_png_with_gradient_soft_mask(surface: &mut Surface) {
+        let image = Image::with_soft_mask(load_png_image("rgb8.png"), gradient_mask()).unwrap();
+        let size = image.size();
+        surface.draw_image(image, Size::from_wh(size.0 as f32, size.1 as f32).unwrap());
+    }
+
+    fn checkerboard_stencil(decode_inverted: bool) -> Image {
+        // 20x20 pixels, so each row takes up 3 bytes.
+        let data = (0..20)
+            .flat_map(|y| {
+                let row: u32 = (0..20).fold(0, |row, x| {
+                    let bit = ((x / 5) + (y / 5)) % 2;
+                    row | (bit << (23 - x))
+                });
+                [(row >> 16) as u8, (row >> 8) as u8, row as u8]
+            })
+            .collect::<Vec<_>>();
+
+        Image::stencil(data, 20, 20, decode_inverted).unwrap()
+    }
+
+    #[test]
+    fn image_stencil() {
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        surface.draw_stencil(
+            checkerboard_stencil(true),
+            Size::from_wh(100.0, 100.0).unwrap(),
+            red_fill(1.0),
+        );
+        surface.finish();
+        page.finish();
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.contains("/ImageMask true"));
+        assert!(pdf.contains("/Decode [1 0]"));
+        assert!(pdf.contains("/BitsPerComponent 1"));
+        assert!(!pdf.contains("/ColorSpace"));
+    }
+
+    #[test]
+    fn image_stencil_invalid_length() {
+        assert!(Image::stencil(vec![0; 2], 20, 1, false).is_none());
+        assert!(Image::stencil(vec![], 0, 0, false).is_none());
+    }
+
+    #[visreg(all)]
+    fn image_stencil(surface: &mut Surface) {
+        surface.draw_stencil(
+            checkerboard_stencil(false),
+            Size::from_wh(200.0, 200.0).unwrap(),
+            red_fill(1.0),
+        );
+    }
+
+    #[visreg(all)]
+    fn image_stencil_inverted_with_clip_path(surface: &mut Surface) {
+        surface.push_clip_path(&rect_to_path(20.0, 20.0, 180.0, 180.0), &FillRule::NonZero);
+        surface.draw_stencil(
+            checkerboard_stencil(true),
+            Size::from_wh(200.0, 200.0).unwrap(),
+            blue_fill(0.5),
+        );
+        surface.pop();
+    }
+
     #[snapshot]
     fn image_luma8_png(sc: &mut SerializeContext) {
         sc.register_image(load_png_image("luma8.png"));
@@ -790,6 +1321,31 @@ mod tests {
         sc.register_image(load_jpg_image("cmyk.jpg"));
     }
 
+    #[test]
+    fn image_jpg_passthrough() {
+        let data = std::fs::read(crate::tests::ASSETS_PATH.join("images/rgb8.jpg")).unwrap();
+        let image = Image::from_jpeg(Arc::new(data.clone())).unwrap();
+
+        let settings = SerializeSettings {
+            ascii_compatible: false,
+            ..SerializeSettings::settings_1()
+        };
+        let mut document = Document::new_with(settings);
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        surface.draw_image(image, Size::from_wh(100.0, 100.0).unwrap());
+        surface.finish();
+        page.finish();
+
+        let pdf = document.finish().unwrap();
+        assert!(pdf.windows(data.len()).any(|w| w == data.as_slice()));
+
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/Filter /DCTDecode"));
+        assert!(pdf.contains("/BitsPerComponent 8"));
+        assert!(pdf.contains("/ColorSpace /DeviceRGB"));
+    }
+
     // Currently gets converted into RGBA.
     #[snapshot]
     fn image_rgb8_gif(sc: &mut SerializeContext) {
@@ -805,12 +1361,68 @@ mod tests {
         sc.register_image(load_webp_image("rgba8.webp"));
     }
 
+    #[snapshot]
+    fn image_luma8_tiff(sc: &mut SerializeContext) {
+        sc.register_image(load_tiff_image("luma8.tiff"));
+    }
+
+    #[snapshot]
+    fn image_rgb8_tiff(sc: &mut SerializeContext) {
+        sc.register_image(load_tiff_image("rgb8.tiff"));
+    }
+
+    #[snapshot]
+    fn image_rgb16_tiff(sc: &mut SerializeContext) {
+        sc.register_image(load_tiff_image("rgb16.tiff"));
+    }
+
+    #[snapshot]
+    fn image_cmyk_tiff(sc: &mut SerializeContext) {
+        sc.register_image(load_tiff_image("cmyk.tiff"));
+    }
+
     fn image_visreg_impl(surface: &mut Surface, name: &str, load_fn: fn(&str) -> Image) {
         let image = load_fn(name);
         let size = image.size();
         surface.draw_image(image, Size::from_wh(size.0 as f32, size.1 as f32).unwrap());
     }
 
+    fn gradient_circle_svg() -> usvg::Tree {
+        let data = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+            <linearGradient id="gradient">
+                <stop offset="0" stop-color="red"/>
+                <stop offset="1" stop-color="blue" stop-opacity="0.5"/>
+            </linearGradient>
+            <circle cx="50" cy="50" r="40" fill="url(#gradient)"/>
+        </svg>"#;
+
+        usvg::Tree::from_str(data, &usvg::Options::default()).unwrap()
+    }
+
+    #[visreg]
+    fn image_rasterized_svg_gradient(surface: &mut Surface) {
+        let image = Image::from_svg(&gradient_circle_svg(), 144.0).unwrap();
+        assert_eq!(image.size(), (200, 200));
+        surface.draw_image(image, Size::from_wh(200.0, 200.0).unwrap());
+    }
+
+    #[test]
+    fn image_rasterized_svg_has_no_shading() {
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        let image = Image::from_svg(&gradient_circle_svg(), 72.0).unwrap();
+        surface.draw_image(image, Size::from_wh(100.0, 100.0).unwrap());
+        surface.finish();
+        page.finish();
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.contains("/Subtype /Image"));
+        assert!(!pdf.contains("/ShadingType"));
+    }
+
     #[visreg(all)]
     fn image_luma8_png(surface: &mut Surface) {
         image_visreg_impl(surface, "luma8.png", load_png_image);
@@ -886,6 +1498,26 @@ mod tests {
         image_visreg_impl(surface, "rgba8.webp", load_webp_image);
     }
 
+    #[visreg(all)]
+    fn image_luma8_tiff(surface: &mut Surface) {
+        image_visreg_impl(surface, "luma8.tiff", load_tiff_image);
+    }
+
+    #[visreg(all)]
+    fn image_rgb8_tiff(surface: &mut Surface) {
+        image_visreg_impl(surface, "rgb8.tiff", load_tiff_image);
+    }
+
+    #[visreg(all)]
+    fn image_rgb16_tiff(surface: &mut Surface) {
+        image_visreg_impl(surface, "rgb16.tiff", load_tiff_image);
+    }
+
+    #[visreg(pdfium, mupdf, pdfbox, pdfjs, poppler, quartz)]
+    fn image_cmyk_tiff(surface: &mut Surface) {
+        image_visreg_impl(surface, "cmyk.tiff", load_tiff_image);
+    }
+
     #[visreg]
     fn image_cmyk_icc_jpg(surface: &mut Surface) {
         image_visreg_impl(surface, "cmyk_icc.jpg", load_jpg_image);
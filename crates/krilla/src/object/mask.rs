@@ -1,28 +1,43 @@
 //! Alpha and luminosity masks.
 
 use pdf_writer::{Chunk, Finish, Name, Ref};
-use tiny_skia_path::{Rect, Transform};
+use tiny_skia_path::{PathBuilder, Rect, Transform};
 
+use crate::color::rgb;
 use crate::object::shading_function::{GradientProperties, ShadingFunction};
 use crate::object::xobject::XObject;
 use crate::object::{Cacheable, ChunkContainerFn, Resourceable};
+use crate::paint::Paint;
+use crate::path::Fill;
 use crate::resource;
 use crate::serialize::SerializeContext;
 use crate::stream::Stream;
 use crate::stream::StreamBuilder;
 use crate::util::RectWrapper;
+
+/// The content of a mask.
+#[derive(PartialEq, Eq, Debug, Hash)]
+enum MaskContent {
+    Stream(Stream),
+    /// A paint that fills the bbox of the mask. Since creating a stream requires
+    /// access to the serializer, the stream is only created once the mask is serialized.
+    Paint(Paint),
+}
+
 /// A mask. Can be a luminance mask or an alpha mask.
 #[derive(PartialEq, Eq, Debug, Hash)]
 pub struct Mask {
-    /// The stream of the mask.
-    stream: Stream,
+    /// The content of the mask.
+    content: MaskContent,
     /// The type of the mask.
     mask_type: MaskType,
-    /// A custom bbox of the mask. The only reason we need this is that for gradients with
-    /// transparencies, we create a custom mask where we call the shading operator. In this case,
-    /// we want to manually set the bbox of the underlying XObject to match the shape that the
-    /// gradient is being applied to.
+    /// A custom bbox of the mask. We need this for gradients with transparencies, where we
+    /// create a custom mask that calls the shading operator, and for masks that are created
+    /// from a paint. In these cases, we want to manually set the bbox of the underlying XObject
+    /// to match the shape that the gradient is being applied to.
     custom_bbox: Option<RectWrapper>,
+    /// The backdrop color of a luminosity mask.
+    backdrop: Option<rgb::Color>,
 }
 
 impl Mask {
@@ -30,12 +45,42 @@ impl Mask {
     /// of the mask, and `mask_type` indicates the type of mask.
     pub fn new(stream: Stream, mask_type: MaskType) -> Self {
         Self {
-            stream,
+            content: MaskContent::Stream(stream),
             mask_type,
             custom_bbox: None,
+            backdrop: None,
+        }
+    }
+
+    /// Create a new mask whose content is the given rectangle filled with a
+    /// gradient (or any other paint).
+    ///
+    /// This makes it possible to create smooth masks without having to draw
+    /// the gradient into a stream yourself. For example, a radial gradient from
+    /// white in the center to black at the edges, used as a luminosity mask,
+    /// results in a vignette. The rectangle is given in the coordinate system
+    /// of the surface the mask is pushed onto, and everything outside of it is
+    /// masked out.
+    pub fn from_shading(gradient: impl Into<Paint>, mask_type: MaskType, bbox: Rect) -> Self {
+        Self {
+            content: MaskContent::Paint(gradient.into()),
+            mask_type,
+            custom_bbox: Some(RectWrapper(bbox)),
+            backdrop: None,
         }
     }
 
+    /// Set the backdrop color of a luminosity mask.
+    ///
+    /// The luminosity of the backdrop color determines the mask value wherever the
+    /// content of the mask doesn't paint anything. By default, the backdrop is black,
+    /// meaning that everything outside of the content is masked out. Has no effect
+    /// on alpha masks.
+    pub fn with_backdrop(mut self, color: rgb::Color) -> Self {
+        self.backdrop = Some(color);
+        self
+    }
+
     /// Create a new mask for a shading to encode the opacity channels.
     pub(crate) fn new_from_shading(
         gradient_properties: GradientProperties,
@@ -54,6 +99,16 @@ impl Mask {
                     return None;
                 }
             }
+            GradientProperties::MeshGradient(mg) => {
+                if mg
+                    .triangles
+                    .iter()
+                    .flatten()
+                    .all(|v| v.opacity.get() == 1.0)
+                {
+                    return None;
+                }
+            }
         }
 
         let shading_function = ShadingFunction::new(gradient_properties, true);
@@ -69,9 +124,10 @@ impl Mask {
         };
 
         Some(Self {
-            stream: shading_stream,
+            content: MaskContent::Stream(shading_stream),
             mask_type: MaskType::Luminosity,
             custom_bbox: Some(RectWrapper(bbox)),
+            backdrop: None,
         })
     }
 }
@@ -103,8 +159,28 @@ impl Cacheable for Mask {
     fn serialize(self, sc: &mut SerializeContext, root_ref: Ref) -> Chunk {
         let mut chunk = Chunk::new();
 
+        let stream = match self.content {
+            MaskContent::Stream(stream) => stream,
+            MaskContent::Paint(paint) => {
+                // Always set for masks created from a paint.
+                let bbox = self.custom_bbox.unwrap().0;
+
+                let mut builder = StreamBuilder::new(sc);
+                let mut surface = builder.surface();
+                surface.fill_path(
+                    &PathBuilder::from_rect(bbox),
+                    Fill {
+                        paint,
+                        ..Fill::default()
+                    },
+                );
+                surface.finish();
+                builder.finish()
+            }
+        };
+
         let x_object = sc.register_cacheable(XObject::new(
-            self.stream,
+            stream,
             false,
             true,
             self.custom_bbox.map(|c| c.0),
@@ -115,6 +191,13 @@ impl Cacheable for Mask {
         dict.pair(Name(b"S"), self.mask_type.to_name());
         dict.pair(Name(b"G"), x_object);
 
+        if let (MaskType::Luminosity, Some(backdrop)) = (self.mask_type, self.backdrop) {
+            // The transparency group of the mask always uses an RGB color space.
+            dict.insert(Name(b"BC"))
+                .array()
+                .items(backdrop.to_pdf_color());
+        }
+
         dict.finish();
 
         chunk
@@ -135,9 +218,11 @@ mod tests {
     use crate::color::rgb;
     use crate::mask::MaskType;
 
+    use crate::paint::{RadialGradient, SpreadMethod, Stop};
     use crate::path::Fill;
     use crate::stream::StreamBuilder;
-    use crate::tests::{basic_mask, rect_to_path, red_fill};
+    use crate::tests::{basic_mask, green_fill, rect_to_path, red_fill};
+    use crate::{Document, SerializeSettings};
     use krilla_macros::{snapshot, visreg};
     use tiny_skia_path::{PathBuilder, Rect};
     use usvg::NormalizedF32;
@@ -185,4 +270,80 @@ mod tests {
     pub fn mask_alpha(surface: &mut Surface) {
         mask_visreg_impl(MaskType::Luminosity, surface, rgb::Color::new(0, 0, 128));
     }
+
+    fn vignette(backdrop: Option<rgb::Color>) -> Mask {
+        let gradient = RadialGradient {
+            fx: 100.0,
+            fy: 100.0,
+            fr: 0.0,
+            cx: 100.0,
+            cy: 100.0,
+            cr: 80.0,
+            transform: Default::default(),
+            spread_method: SpreadMethod::Pad,
+            stops: vec![
+                Stop {
+                    offset: NormalizedF32::new(0.5).unwrap(),
+                    color: rgb::Color::white(),
+                    opacity: NormalizedF32::ONE,
+                },
+                Stop {
+                    offset: NormalizedF32::ONE,
+                    color: rgb::Color::black(),
+                    opacity: NormalizedF32::ONE,
+                },
+            ]
+            .into(),
+            anti_alias: false,
+            interpolation: Default::default(),
+        };
+
+        let mask = Mask::from_shading(
+            gradient,
+            MaskType::Luminosity,
+            Rect::from_xywh(20.0, 20.0, 160.0, 160.0).unwrap(),
+        );
+
+        match backdrop {
+            Some(backdrop) => mask.with_backdrop(backdrop),
+            None => mask,
+        }
+    }
+
+    #[visreg]
+    fn mask_from_shading_vignette(surface: &mut Surface) {
+        surface.push_mask(vignette(None));
+        surface.fill_path(&rect_to_path(0.0, 0.0, 200.0, 200.0), green_fill(1.0));
+        surface.pop();
+    }
+
+    #[visreg]
+    fn mask_from_shading_white_backdrop(surface: &mut Surface) {
+        surface.push_mask(vignette(Some(rgb::Color::white())));
+        surface.fill_path(&rect_to_path(0.0, 0.0, 200.0, 200.0), green_fill(1.0));
+        surface.pop();
+    }
+
+    #[test]
+    fn mask_from_shading_backdrop() {
+        let pdf = |backdrop| {
+            let mut d = Document::new_with(SerializeSettings::settings_1());
+            let mut page = d.start_page();
+            let mut surface = page.surface();
+            surface.push_mask(vignette(backdrop));
+            surface.fill_path(&rect_to_path(0.0, 0.0, 200.0, 200.0), green_fill(1.0));
+            surface.pop();
+            surface.finish();
+            page.finish();
+
+            String::from_utf8_lossy(&d.finish().unwrap()).into_owned()
+        };
+
+        let without_backdrop = pdf(None);
+        assert!(without_backdrop.contains("/S /Luminosity"));
+        assert!(without_backdrop.contains("/ShadingType 3"));
+        assert!(!without_backdrop.contains("/BC"));
+
+        assert!(pdf(Some(rgb::Color::white())).contains("/BC [1 1 1]"));
+    }
 }
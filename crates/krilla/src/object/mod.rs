@@ -13,11 +13,15 @@ pub mod color;
 pub mod destination;
 pub(crate) mod ext_g_state;
 pub(crate) mod font;
+pub mod form;
 #[cfg(feature = "raster-images")]
 pub mod image;
 pub mod mask;
 pub mod outline;
+pub mod output_intent;
 pub mod page;
+#[cfg(feature = "pdf")]
+pub(crate) mod pdf_page;
 pub(crate) mod shading_function;
 pub(crate) mod shading_pattern;
 pub(crate) mod tiling_pattern;
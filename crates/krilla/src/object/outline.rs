@@ -22,7 +22,7 @@ use pdf_writer::writers::OutlineItem;
 use pdf_writer::{Chunk, Finish, Name, Ref, TextStr};
 
 use crate::error::KrillaResult;
-use crate::object::destination::XyzDestination;
+use crate::object::destination::Destination;
 use crate::serialize::SerializeContext;
 
 /// An outline.
@@ -125,7 +125,7 @@ pub struct OutlineNode {
     /// The text of the outline entry.
     text: String,
     /// The destination of the outline entry.
-    destination: XyzDestination,
+    destination: Destination,
 }
 
 impl OutlineNode {
@@ -133,12 +133,12 @@ impl OutlineNode {
     ///
     /// `text` is the string that should be displayed in the outline tree, and
     /// `destination` is the destination that should be jumped to when clicking on
-    /// the outline entry.
-    pub fn new(text: String, destination: XyzDestination) -> Self {
+    /// the outline entry. Any kind of [`Destination`] can be used.
+    pub fn new(text: String, destination: impl Into<Destination>) -> Self {
         Self {
             children: vec![],
             text,
-            destination,
+            destination: destination.into(),
         }
     }
 
@@ -183,8 +183,8 @@ impl OutlineNode {
             outline_entry.title(TextStr(&self.text));
         }
 
-        let dest_ref = sc.register_xyz_destination(self.destination.clone());
-        outline_entry.pair(Name(b"Dest"), dest_ref);
+        self.destination
+            .serialize(sc, outline_entry.insert(Name(b"Dest")))?;
 
         outline_entry.finish();
 
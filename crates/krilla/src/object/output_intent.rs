@@ -0,0 +1,121 @@
+//! Output intents.
+//!
+//! An output intent describes the color characteristics of the output device the
+//! document is intended for. You can add output intents to a document via
+//! [`Document::add_output_intent`], which is for example necessary if the print
+//! house you are working with relies on a specific output condition identifier and
+//! profile.
+//!
+//! When exporting to PDF/A or PDF/X, an output intent with the corresponding subtype
+//! is required. If you haven't added one, krilla writes a default output intent that
+//! uses the sRGB profile as the destination profile and is described as a custom
+//! "sRGB" output condition. For PDF/X, providing a destination profile is mandatory.
+//!
+//! [`Document::add_output_intent`]: crate::document::Document::add_output_intent
+
+use pdf_writer::{Name, TextStr};
+
+use crate::color::{GenericICCProfile, ICCProfile};
+
+/// The subtype of an output intent, which determines the standard it is intended for.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub enum OutputIntentSubtype {
+    /// An output intent for PDF/A (`GTS_PDFA1`).
+    PdfA,
+    /// An output intent for PDF/X (`GTS_PDFX`).
+    PdfX,
+    /// An output intent for PDF/E (`ISO_PDFE1`).
+    PdfE,
+    /// A custom subtype, as defined by an ISO 32000 extension.
+    Custom(String),
+}
+
+impl OutputIntentSubtype {
+    pub(crate) fn to_pdf(&self) -> pdf_writer::types::OutputIntentSubtype<'_> {
+        match self {
+            OutputIntentSubtype::PdfA => pdf_writer::types::OutputIntentSubtype::PDFA,
+            OutputIntentSubtype::PdfX => pdf_writer::types::OutputIntentSubtype::PDFX,
+            OutputIntentSubtype::PdfE => pdf_writer::types::OutputIntentSubtype::PDFE,
+            OutputIntentSubtype::Custom(name) => {
+                pdf_writer::types::OutputIntentSubtype::Custom(Name(name.as_bytes()))
+            }
+        }
+    }
+}
+
+/// The description of an output intent.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct OutputIntent {
+    pub(crate) subtype: Option<OutputIntentSubtype>,
+    pub(crate) output_condition_identifier: String,
+    pub(crate) output_condition: Option<String>,
+    pub(crate) registry_name: Option<String>,
+    pub(crate) info: Option<String>,
+    pub(crate) dest_profile: Option<GenericICCProfile>,
+}
+
+impl OutputIntent {
+    /// Create a new output intent with the given output condition identifier,
+    /// for example `FOGRA39` or `CGATS TR 001`.
+    pub fn new(output_condition_identifier: String) -> Self {
+        Self {
+            subtype: None,
+            output_condition_identifier,
+            output_condition: None,
+            registry_name: None,
+            info: None,
+            dest_profile: None,
+        }
+    }
+
+    /// Set the subtype of the output intent.
+    ///
+    /// If unset, the subtype required by the validator of the document is used,
+    /// or [`OutputIntentSubtype::PdfX`] if the validator doesn't require one.
+    pub fn with_subtype(mut self, subtype: OutputIntentSubtype) -> Self {
+        self.subtype = Some(subtype);
+        self
+    }
+
+    /// Set a human-readable description of the output condition.
+    pub fn with_output_condition(mut self, output_condition: String) -> Self {
+        self.output_condition = Some(output_condition);
+        self
+    }
+
+    /// Set the registry in which the output condition identifier is defined,
+    /// for example `http://www.color.org`.
+    pub fn with_registry_name(mut self, registry_name: String) -> Self {
+        self.registry_name = Some(registry_name);
+        self
+    }
+
+    /// Set additional information about the output condition.
+    pub fn with_info(mut self, info: String) -> Self {
+        self.info = Some(info);
+        self
+    }
+
+    /// Set the ICC profile that describes the output condition. Can be a
+    /// gray-scale, RGB or CMYK profile.
+    pub fn with_dest_profile<const C: u8>(mut self, profile: ICCProfile<C>) -> Self {
+        self.dest_profile = Some(profile.to_generic());
+        self
+    }
+
+    pub(crate) fn write(&self, oi: &mut pdf_writer::writers::OutputIntent) {
+        oi.output_condition_identifier(TextStr(&self.output_condition_identifier));
+
+        if let Some(output_condition) = &self.output_condition {
+            oi.output_condition(TextStr(output_condition));
+        }
+
+        if let Some(registry_name) = &self.registry_name {
+            oi.registry_name(TextStr(registry_name));
+        }
+
+        if let Some(info) = &self.info {
+            oi.info(TextStr(info));
+        }
+    }
+}
@@ -5,19 +5,28 @@ use std::ops::DerefMut;
 
 use pdf_writer::types::TabOrder;
 use pdf_writer::writers::NumberTree;
+#[cfg(feature = "simple-text")]
+use pdf_writer::Content;
 use pdf_writer::{Chunk, Finish, Ref, TextStr};
+#[cfg(feature = "simple-text")]
+use tiny_skia_path::Point;
 use tiny_skia_path::{Rect, Transform};
 
 use crate::content::ContentBuilder;
 use crate::document::PageSettings;
-use crate::error::KrillaResult;
-use crate::object::annotation::Annotation;
-use crate::resource::ResourceDictionary;
+use crate::error::{KrillaError, KrillaResult};
+#[cfg(feature = "simple-text")]
+use crate::font::Font;
+use crate::object::annotation::{Annotation, AnnotationHandle};
+use crate::object::form::FormField;
+use crate::resource::{Resource, ResourceDictionary, ResourceList};
 use crate::serialize::SerializeContext;
 use crate::stream::{FilterStreamBuilder, Stream};
 use crate::surface::Surface;
-use crate::tagging::{Identifier, PageTagIdentifier};
+use crate::tagging::{Identifier, PageTagIdentifier, TagGroup};
 use crate::util::{Deferred, RectExt};
+#[cfg(feature = "simple-text")]
+use crate::util::{NameExt, TransformExt};
 use crate::version::PdfVersion;
 
 pub use pdf_writer::types::NumberingStyle;
@@ -38,6 +47,9 @@ pub struct Page<'a> {
     page_stream: Stream,
     num_mcids: i32,
     annotations: Vec<Annotation>,
+    annotation_count: usize,
+    #[cfg(feature = "simple-text")]
+    deferred_texts: Vec<DeferredText>,
 }
 
 impl<'a> Page<'a> {
@@ -53,6 +65,9 @@ impl<'a> Page<'a> {
             num_mcids: 0,
             page_stream: Stream::empty(),
             annotations: vec![],
+            annotation_count: 0,
+            #[cfg(feature = "simple-text")]
+            deferred_texts: vec![],
         }
     }
 
@@ -61,8 +76,27 @@ impl<'a> Page<'a> {
     }
 
     /// Add an annotation to the page.
-    pub fn add_annotation(&mut self, annotation: Annotation) {
-        self.annotations.push(annotation);
+    ///
+    /// Returns a handle to the annotation, which can be used to create replies to it.
+    ///
+    /// If [`SerializeSettings::auto_tag_annotations`] is enabled, the annotation will
+    /// automatically be added to the tag tree.
+    ///
+    /// [`SerializeSettings::auto_tag_annotations`]: crate::SerializeSettings::auto_tag_annotations
+    pub fn add_annotation(&mut self, annotation: Annotation) -> AnnotationHandle {
+        let handle = AnnotationHandle::new(self.page_index, self.annotations.len());
+
+        if self.sc.serialize_settings().auto_tag_annotations
+            && self.sc.serialize_settings().enable_tagging
+        {
+            let mut group = TagGroup::new(annotation.default_tag());
+            group.push(self.add_tagged_annotation(annotation));
+            self.sc.register_annotation_tag_group(group);
+        } else {
+            self.push_annotation(annotation);
+        }
+
+        handle
     }
 
     /// Add a tagged annotation to the page.
@@ -72,7 +106,7 @@ impl<'a> Page<'a> {
             .sc
             .register_annotation_parent(self.page_index, annot_index);
         annotation.struct_parent = struct_parent;
-        self.add_annotation(annotation);
+        self.push_annotation(annotation);
 
         match struct_parent {
             None => Identifier::dummy(),
@@ -80,6 +114,58 @@ impl<'a> Page<'a> {
         }
     }
 
+    /// Add a form field to the page.
+    ///
+    /// This is a shorthand for adding an annotation with the widget of the form field,
+    /// see [`Page::add_annotation`] for more information. The alt text is displayed as the
+    /// tooltip of the field and is required when exporting to PDF/UA.
+    pub fn add_form_field(
+        &mut self,
+        field: impl Into<FormField>,
+        alt_text: Option<String>,
+    ) -> AnnotationHandle {
+        self.add_annotation(Annotation::new(field.into(), alt_text))
+    }
+
+    /// Return the number of annotations of the page.
+    ///
+    /// This includes the popups attached to annotations, since they are
+    /// annotations of their own in the final PDF.
+    pub fn annotation_count(&self) -> usize {
+        self.annotation_count
+    }
+
+    fn push_annotation(&mut self, annotation: Annotation) {
+        let before = self.annotation_count;
+        self.annotation_count += if annotation.popup.is_some() { 2 } else { 1 };
+        self.annotations.push(annotation);
+        self.sc
+            .check_annotation_count(self.page_index, before, self.annotation_count);
+    }
+
+    /// Add text to the page whose content is only determined once the document is finished.
+    ///
+    /// The closure is called with the index of this page and the total number of pages
+    /// of the document, which makes it possible to draw footers like "Page 2 of 3"
+    /// without knowing the number of pages upfront. The text is drawn with the default fill
+    /// on top of all other contents of the page. If tagging is enabled, it is automatically
+    /// marked as a pagination artifact.
+    #[cfg(feature = "simple-text")]
+    pub fn add_deferred_text(
+        &mut self,
+        start: Point,
+        font: Font,
+        font_size: f32,
+        text: impl Fn(usize, usize) -> String + 'static,
+    ) {
+        self.deferred_texts.push(DeferredText {
+            start,
+            font,
+            font_size,
+            text: Box::new(text),
+        });
+    }
+
     /// Get the surface of the page to draw on. Calling this multiple times
     /// on the same page will reset any previous drawings.
     pub fn surface(&mut self) -> Surface {
@@ -99,10 +185,76 @@ impl<'a> Page<'a> {
         Surface::new(self.sc, root_builder, page_identifier, finish_fn)
     }
 
+    /// Return a summary of the resources that are used by the content of the page.
+    ///
+    /// This is mainly meant for debugging purposes, for example to find out whether
+    /// a page accidentally references multiple copies of the same image. Note that the
+    /// summary only reflects the contents that have been drawn on a [`Page::surface`]
+    /// that has already been finished.
+    pub fn resource_summary(&self) -> ResourceSummary {
+        ResourceSummary::new(&self.page_stream.resource_dictionary)
+    }
+
     /// A shorthand for `std::mem::drop`.
     pub fn finish(self) {}
 }
 
+/// A summary of the resources that are used by the content stream of a page.
+///
+/// Each resource is identified by the name under which it is referenced in
+/// the content stream of the page.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceSummary {
+    /// The fonts of the page. Note that a single [`Font`] can be split into
+    /// multiple PDF fonts, for example if it contains color glyphs.
+    ///
+    /// [`Font`]: crate::font::Font
+    pub fonts: Vec<ResourceUsage>,
+    /// The XObjects of the page. This includes images, but also
+    /// nested content streams, for example for groups with an opacity.
+    pub x_objects: Vec<ResourceUsage>,
+    /// The patterns of the page, used for gradients and tiling patterns.
+    pub patterns: Vec<ResourceUsage>,
+    /// The shadings of the page.
+    pub shadings: Vec<ResourceUsage>,
+    /// The external graphics states of the page, used for opacities,
+    /// blend modes and masks.
+    pub ext_g_states: Vec<ResourceUsage>,
+    /// The color spaces of the page.
+    pub color_spaces: Vec<ResourceUsage>,
+    /// The property lists of the page, used for layers.
+    pub properties: Vec<ResourceUsage>,
+}
+
+impl ResourceSummary {
+    fn new(resources: &ResourceDictionary) -> Self {
+        fn usages<T: Resource>(list: &ResourceList<T>) -> Vec<ResourceUsage> {
+            list.get_usages()
+                .map(|(name, count)| ResourceUsage { name, count })
+                .collect()
+        }
+
+        Self {
+            fonts: usages(&resources.fonts),
+            x_objects: usages(&resources.x_objects),
+            patterns: usages(&resources.patterns),
+            shadings: usages(&resources.shadings),
+            ext_g_states: usages(&resources.ext_g_states),
+            color_spaces: usages(&resources.color_spaces),
+            properties: usages(&resources.properties),
+        }
+    }
+}
+
+/// The usage of a single resource in a content stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// The name of the resource in the resource dictionary.
+    pub name: String,
+    /// How many times the resource is referenced.
+    pub count: usize,
+}
+
 pub(crate) fn page_root_transform(height: f32) -> Transform {
     Transform::from_row(1.0, 0.0, 0.0, -1.0, 0.0, height)
 }
@@ -118,7 +270,31 @@ impl Drop for Page<'_> {
             .sc
             .register_page_struct_parent(self.page_index, self.num_mcids);
 
-        let stream = std::mem::replace(&mut self.page_stream, Stream::empty());
+        #[allow(unused_mut)]
+        let mut stream = std::mem::replace(&mut self.page_stream, Stream::empty());
+
+        // The deferred texts are drawn into a separate XObject that will only be
+        // written once the number of pages is known, so we just reference it here.
+        #[cfg(feature = "simple-text")]
+        {
+            let deferred_texts = std::mem::take(&mut self.deferred_texts);
+
+            if !deferred_texts.is_empty() {
+                let x_object_ref = self.sc.new_ref();
+                let x_object_name = stream.resource_dictionary.x_objects.push(x_object_ref);
+
+                let mut content = Content::new();
+                content.save_state();
+                content.transform(self.root_transform().to_pdf_transform());
+                content.x_object(x_object_name.to_pdf_name());
+                content.restore_state();
+                stream.content.extend(content.finish().to_bytes());
+
+                self.sc
+                    .register_deferred_texts(x_object_ref, self.page_index, deferred_texts);
+            }
+        }
+
         let page = InternalPage::new(
             stream,
             self.sc,
@@ -131,6 +307,17 @@ impl Drop for Page<'_> {
     }
 }
 
+pub(crate) type AnnotationWarningHook = Box<dyn Fn(usize, usize)>;
+
+/// Text whose content depends on the total number of pages in the document.
+#[cfg(feature = "simple-text")]
+pub(crate) struct DeferredText {
+    pub(crate) start: Point,
+    pub(crate) font: Font,
+    pub(crate) font_size: f32,
+    pub(crate) text: Box<dyn Fn(usize, usize) -> String>,
+}
+
 pub(crate) struct InternalPage {
     pub stream_ref: Ref,
     pub stream_resources: ResourceDictionary,
@@ -191,35 +378,101 @@ impl InternalPage {
     ) -> KrillaResult<Deferred<Chunk>> {
         let mut chunk = Chunk::new();
 
-        let mut annotation_refs = vec![];
+        // Allocate the refs upfront, so that annotations can reply to other
+        // annotations on the same page.
+        let annotation_refs = self
+            .annotations
+            .iter()
+            .map(|_| sc.new_ref())
+            .collect::<Vec<_>>();
+        let mut popup_refs = vec![];
+
+        for (annotation, annot_ref) in self.annotations.iter().zip(annotation_refs.iter()) {
+            let in_reply_to = match annotation.in_reply_to {
+                Some(handle) => {
+                    let parent_ref = if handle.page_index == self.page_index {
+                        annotation_refs.get(handle.annotation_index)
+                    } else {
+                        sc.page_infos()
+                            .get(handle.page_index)
+                            .and_then(|p| p.annotations.get(handle.annotation_index))
+                    };
+
+                    Some(*parent_ref.ok_or(KrillaError::UserError(
+                        "annotations can only reply to annotations on the same or a previous page"
+                            .to_string(),
+                    ))?)
+                }
+                None => None,
+            };
 
-        if !self.annotations.is_empty() {
-            for annotation in &self.annotations {
-                let annot_ref = sc.new_ref();
+            let (a, popup_ref) = annotation.serialize(
+                sc,
+                *annot_ref,
+                self.page_settings.surface_size().height(),
+                in_reply_to,
+            )?;
+            chunk.extend(&a);
+            popup_refs.extend(popup_ref);
+        }
 
-                let a = annotation.serialize(
-                    sc,
-                    annot_ref,
-                    self.page_settings.surface_size().height(),
-                )?;
-                chunk.extend(&a);
-                annotation_refs.push(annot_ref);
+        let media_box = self.page_settings.media_box().unwrap_or(self.bbox);
+        let page_transform = page_root_transform(self.page_settings.surface_size().height());
+
+        let boxes = [
+            ("crop", self.page_settings.crop_box()),
+            ("bleed", self.page_settings.bleed_box()),
+            ("trim", self.page_settings.trim_box()),
+            ("art", self.page_settings.art_box()),
+        ];
+
+        for (name, rect) in boxes {
+            if let Some(rect) = rect {
+                if rect.left() < media_box.left()
+                    || rect.top() < media_box.top()
+                    || rect.right() > media_box.right()
+                    || rect.bottom() > media_box.bottom()
+                {
+                    return Err(KrillaError::UserError(format!(
+                        "the {name} box of page {} is not contained within its media box",
+                        self.page_index + 1
+                    )));
+                }
             }
         }
 
+        let to_pdf_rect = |rect: Rect| rect.transform(page_transform).unwrap().to_pdf_rect();
+
         let mut page = chunk.page(root_ref);
         self.stream_resources.to_pdf_resources(&mut page);
 
-        let media_box = self
-            .page_settings
-            .media_box()
-            .unwrap_or(self.bbox)
-            .transform(page_root_transform(
-                self.page_settings.surface_size().height(),
-            ))
-            .unwrap();
+        page.media_box(to_pdf_rect(media_box));
+
+        if let Some(crop_box) = self.page_settings.crop_box() {
+            page.crop_box(to_pdf_rect(crop_box));
+        }
 
-        page.media_box(media_box.to_pdf_rect());
+        if let Some(bleed_box) = self.page_settings.bleed_box() {
+            page.bleed_box(to_pdf_rect(bleed_box));
+        }
+
+        if let Some(trim_box) = self.page_settings.trim_box() {
+            page.trim_box(to_pdf_rect(trim_box));
+        } else if self.page_settings.art_box().is_none()
+            && sc.serialize_settings().validator.requires_trim_box()
+        {
+            // Some standards require the finished page size to be given explicitly.
+            let trim_box = self.page_settings.crop_box().unwrap_or(media_box);
+            page.trim_box(to_pdf_rect(trim_box));
+        }
+
+        if let Some(art_box) = self.page_settings.art_box() {
+            page.art_box(to_pdf_rect(art_box));
+        }
+
+        if self.page_settings.rotation() != 0 {
+            page.rotate(self.page_settings.rotation());
+        }
 
         if let Some(struct_parent) = self.struct_parent {
             page.struct_parents(struct_parent);
@@ -232,11 +485,11 @@ impl InternalPage {
             }
         }
 
-        page.parent(sc.page_tree_ref());
+        page.parent(sc.page_parent_ref(self.page_index));
         page.contents(self.stream_ref);
 
         if !annotation_refs.is_empty() {
-            page.annotations(annotation_refs.iter().copied());
+            page.annotations(annotation_refs.iter().chain(popup_refs.iter()).copied());
         }
 
         // Populate the refs for each annotation in page infos.
@@ -252,11 +505,65 @@ impl InternalPage {
     }
 }
 
+/// A numbering style for page labels that is not natively supported by PDF.
+///
+/// PDF only supports decimal, roman and alphabetic numbering styles for page labels. Labels
+/// with one of these styles are instead written by giving each page an individual label that
+/// consists of the prefix and the already formatted number, which means that they are
+/// displayed correctly in all viewers. Styles that can only represent a limited range of
+/// numbers fall back to decimal numbers for numbers outside of that range.
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Copy)]
+pub enum CustomNumberingStyle {
+    /// Decimal numbers using CJK ideographic digits, e.g. "一〇" for 10.
+    CjkDecimal,
+    /// Decimal numbers using full-width digits, e.g. "１０" for 10.
+    FullWidthDecimal,
+    /// The ten heavenly stems ("甲", "乙", "丙", ...). Only supports the numbers 1 to 10.
+    CjkHeavenlyStem,
+    /// The twelve earthly branches ("子", "丑", "寅", ...). Only supports the numbers 1 to 12.
+    CjkEarthlyBranch,
+}
+
+impl CustomNumberingStyle {
+    pub(crate) fn format(&self, num: usize) -> String {
+        const CJK_DIGITS: [char; 10] = ['〇', '一', '二', '三', '四', '五', '六', '七', '八', '九'];
+        const HEAVENLY_STEMS: [char; 10] =
+            ['甲', '乙', '丙', '丁', '戊', '己', '庚', '辛', '壬', '癸'];
+        const EARTHLY_BRANCHES: [char; 12] = [
+            '子', '丑', '寅', '卯', '辰', '巳', '午', '未', '申', '酉', '戌', '亥',
+        ];
+
+        let map_digits = |f: fn(u32) -> char| -> String {
+            num.to_string()
+                .chars()
+                .map(|c| f(c.to_digit(10).unwrap()))
+                .collect()
+        };
+        let from_list = |list: &[char]| {
+            num.checked_sub(1)
+                .and_then(|i| list.get(i))
+                .map(|c| c.to_string())
+                .unwrap_or_else(|| num.to_string())
+        };
+
+        match self {
+            CustomNumberingStyle::CjkDecimal => map_digits(|d| CJK_DIGITS[d as usize]),
+            CustomNumberingStyle::FullWidthDecimal => {
+                map_digits(|d| char::from_u32('０' as u32 + d).unwrap())
+            }
+            CustomNumberingStyle::CjkHeavenlyStem => from_list(&HEAVENLY_STEMS),
+            CustomNumberingStyle::CjkEarthlyBranch => from_list(&EARTHLY_BRANCHES),
+        }
+    }
+}
+
 /// A page label.
 #[derive(Debug, Hash, Eq, PartialEq, Default, Clone)]
 pub struct PageLabel {
     /// The numbering style of the page label.
     pub(crate) style: Option<NumberingStyle>,
+    /// The custom numbering style of the page label, which takes precedence over `style`.
+    pub(crate) custom_style: Option<CustomNumberingStyle>,
     /// The prefix of the page label.
     pub(crate) prefix: Option<String>,
     /// The numeric value of the page label.
@@ -272,13 +579,35 @@ impl PageLabel {
     ) -> Self {
         Self {
             style,
+            custom_style: None,
+            prefix,
+            offset,
+        }
+    }
+
+    /// Create a new page label with a numbering style that is not natively
+    /// supported by PDF.
+    ///
+    /// See [`CustomNumberingStyle`] for more information on how such
+    /// labels are written.
+    pub fn new_custom(
+        style: CustomNumberingStyle,
+        prefix: Option<String>,
+        offset: Option<NonZeroUsize>,
+    ) -> Self {
+        Self {
+            style: None,
+            custom_style: Some(style),
             prefix,
             offset,
         }
     }
 
     pub(crate) fn is_empty(&self) -> bool {
-        self.style.is_none() && self.prefix.is_none() && self.offset.is_none()
+        self.style.is_none()
+            && self.custom_style.is_none()
+            && self.prefix.is_none()
+            && self.offset.is_none()
     }
 
     pub(crate) fn serialize(&self, root_ref: Ref) -> Chunk {
@@ -306,28 +635,28 @@ impl PageLabel {
 }
 
 #[derive(Hash)]
-pub(crate) struct PageLabelContainer<'a> {
-    labels: &'a [PageLabel],
+pub(crate) struct PageLabelContainer {
+    /// The page index at which each label range starts, sorted by the page index.
+    entries: Vec<(usize, PageLabel)>,
+    /// The total number of pages in the document.
+    page_count: usize,
 }
 
-impl<'a> PageLabelContainer<'a> {
-    pub(crate) fn new(labels: &'a [PageLabel]) -> Option<Self> {
+impl PageLabelContainer {
+    pub(crate) fn new(labels: &[PageLabel]) -> Option<Self> {
         if labels.iter().all(|f| f.is_empty()) {
-            None
-        } else {
-            Some(PageLabelContainer { labels })
+            return None;
         }
-    }
 
-    pub(crate) fn serialize(&self, sc: &mut SerializeContext, root_ref: Ref) -> Chunk {
         // Will always contain at least one entry, since we ensured that a PageLabelContainer cannot
         // be empty
         let mut filtered_entries = vec![];
         let mut prev: Option<PageLabel> = None;
 
-        for (i, label) in self.labels.iter().enumerate() {
+        for (i, label) in labels.iter().enumerate() {
             if let Some(n_prev) = &prev {
                 if n_prev.style != label.style
+                    || n_prev.custom_style != label.custom_style
                     || n_prev.prefix != label.prefix
                     || n_prev.offset.map(|n| n.get()) != label.offset.map(|n| n.get() + 1)
                 {
@@ -340,11 +669,84 @@ impl<'a> PageLabelContainer<'a> {
             }
         }
 
+        Some(PageLabelContainer {
+            entries: filtered_entries,
+            page_count: labels.len(),
+        })
+    }
+
+    /// Create a new container from explicit label ranges, where each entry consists of the
+    /// index of the first page of the range and the label of the range.
+    pub(crate) fn from_ranges(
+        ranges: &[(usize, PageLabel)],
+        page_count: usize,
+    ) -> KrillaResult<Option<Self>> {
+        if ranges.is_empty() || page_count == 0 {
+            return Ok(None);
+        }
+
+        for window in ranges.windows(2) {
+            if window[0].0 >= window[1].0 {
+                return Err(KrillaError::UserError(
+                    "page label ranges must be sorted by their start index and must not overlap"
+                        .to_string(),
+                ));
+            }
+        }
+
+        if let Some((start, _)) = ranges.iter().find(|(start, _)| *start >= page_count) {
+            return Err(KrillaError::UserError(format!(
+                "page label range starts at page {}, but document only has {} pages",
+                start + 1,
+                page_count
+            )));
+        }
+
+        let mut entries = ranges.to_vec();
+
+        // The page label tree must always contain an entry for the first page.
+        if entries[0].0 != 0 {
+            entries.insert(0, (0, PageLabel::default()));
+        }
+
+        Ok(Some(PageLabelContainer {
+            entries,
+            page_count,
+        }))
+    }
+
+    pub(crate) fn serialize(&self, sc: &mut SerializeContext, root_ref: Ref) -> Chunk {
+        // PDF has no support for custom numbering styles, so we need to write a separate
+        // label with the formatted number for each page in the range.
+        let mut entries = vec![];
+        for (index, (start, label)) in self.entries.iter().enumerate() {
+            match label.custom_style {
+                Some(style) => {
+                    let end = self
+                        .entries
+                        .get(index + 1)
+                        .map(|(next, _)| *next)
+                        .unwrap_or(self.page_count);
+                    let offset = label.offset.map(|o| o.get()).unwrap_or(1);
+
+                    for (num, page_num) in (offset..).zip(*start..end) {
+                        let prefix = format!(
+                            "{}{}",
+                            label.prefix.as_deref().unwrap_or(""),
+                            style.format(num)
+                        );
+                        entries.push((page_num, PageLabel::new(None, Some(prefix), None)));
+                    }
+                }
+                None => entries.push((*start, label.clone())),
+            }
+        }
+
         let mut chunk = Chunk::new();
         let mut num_tree = chunk.indirect(root_ref).start::<NumberTree<Ref>>();
         let mut nums = num_tree.nums();
 
-        for (page_num, label) in filtered_entries {
+        for (page_num, label) in entries {
             let label_ref = sc.register_page_label(label);
             nums.insert(page_num as i32, label_ref);
         }
@@ -360,16 +762,24 @@ impl<'a> PageLabelContainer<'a> {
 mod tests {
 
     use crate::document::{Document, PageSettings};
-    use crate::object::page::{InternalPage, PageLabel};
+    use crate::error::KrillaError;
+    use crate::object::page::{CustomNumberingStyle, InternalPage, PageLabel, ResourceUsage};
     use crate::serialize::SerializeContext;
     use crate::stream::StreamBuilder;
+    use crate::surface::TextDirection;
 
+    use crate::font::Font;
     use crate::path::Fill;
-    use crate::tests::{blue_fill, green_fill, purple_fill, rect_to_path, red_fill};
+    use crate::tests::{
+        blue_fill, green_fill, load_png_image, purple_fill, rect_to_path, red_fill, NOTO_SANS,
+    };
+    use crate::SerializeSettings;
     use krilla_macros::{snapshot, visreg};
     use pdf_writer::types::NumberingStyle;
+    use std::cell::RefCell;
     use std::num::NonZeroUsize;
-    use tiny_skia_path::{PathBuilder, Rect};
+    use std::rc::Rc;
+    use tiny_skia_path::{PathBuilder, Point, Rect, Size};
 
     #[snapshot]
     fn page_simple(sc: &mut SerializeContext) {
@@ -430,6 +840,95 @@ mod tests {
         d.start_page_with(settings);
     }
 
+    #[snapshot(document)]
+    fn page_label_cjk(d: &mut Document) {
+        d.start_page_with(PageSettings::new(200.0, 200.0));
+
+        for _ in 0..3 {
+            let settings = PageSettings::new(200.0, 200.0).with_page_label(PageLabel::new_custom(
+                CustomNumberingStyle::CjkDecimal,
+                Some("第".to_string()),
+                None,
+            ));
+
+            d.start_page_with(settings);
+        }
+    }
+
+    #[test]
+    fn page_label_ranges() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        for _ in 0..6 {
+            d.start_page().finish();
+        }
+
+        d.set_page_labels(vec![
+            (
+                0,
+                PageLabel::new(Some(NumberingStyle::LowerRoman), None, None),
+            ),
+            (
+                3,
+                PageLabel::new(Some(NumberingStyle::Arabic), Some("A-".to_string()), None),
+            ),
+        ]);
+
+        let pdf = d.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.contains("/PageLabels"));
+        assert!(pdf.contains("/Nums [0 "));
+        assert!(pdf.contains("/S /r"));
+        assert!(pdf.contains("/S /D"));
+        assert!(pdf.contains("/P (A-)"));
+    }
+
+    #[test]
+    fn page_label_ranges_invalid() {
+        let mut d = Document::new();
+        d.start_page().finish();
+        d.start_page().finish();
+        d.set_page_labels(vec![
+            (1, PageLabel::new(Some(NumberingStyle::Arabic), None, None)),
+            (
+                0,
+                PageLabel::new(Some(NumberingStyle::LowerRoman), None, None),
+            ),
+        ]);
+
+        assert_eq!(
+            d.finish(),
+            Err(KrillaError::UserError(
+                "page label ranges must be sorted by their start index and must not overlap"
+                    .to_string()
+            ))
+        );
+
+        let mut d = Document::new();
+        d.start_page().finish();
+        d.set_page_labels(vec![(
+            2,
+            PageLabel::new(Some(NumberingStyle::Arabic), None, None),
+        )]);
+
+        assert_eq!(
+            d.finish(),
+            Err(KrillaError::UserError(
+                "page label range starts at page 3, but document only has 1 pages".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn page_label_custom_numbering_styles() {
+        assert_eq!(CustomNumberingStyle::CjkDecimal.format(9), "九");
+        assert_eq!(CustomNumberingStyle::CjkDecimal.format(105), "一〇五");
+        assert_eq!(CustomNumberingStyle::FullWidthDecimal.format(12), "１２");
+        assert_eq!(CustomNumberingStyle::CjkHeavenlyStem.format(3), "丙");
+        assert_eq!(CustomNumberingStyle::CjkHeavenlyStem.format(11), "11");
+        assert_eq!(CustomNumberingStyle::CjkEarthlyBranch.format(12), "亥");
+    }
+
     fn media_box_impl(d: &mut Document, media_box: Rect) {
         let mut page =
             d.start_page_with(PageSettings::new(200.0, 200.0).with_media_box(Some(media_box)));
@@ -464,4 +963,168 @@ mod tests {
     fn custom_media_box_zoomed_out(d: &mut Document) {
         media_box_impl(d, Rect::from_xywh(-150.0, -200.0, 500.0, 500.0).unwrap())
     }
+
+    #[snapshot(document)]
+    fn page_boxes(d: &mut Document) {
+        let settings = PageSettings::new(200.0, 200.0)
+            .with_crop_box(Rect::from_xywh(5.0, 5.0, 190.0, 190.0).unwrap())
+            .with_bleed_box(Rect::from_xywh(10.0, 10.0, 180.0, 180.0).unwrap())
+            .with_trim_box(Rect::from_xywh(20.0, 20.0, 160.0, 160.0).unwrap())
+            .with_art_box(Rect::from_xywh(30.0, 40.0, 120.0, 100.0).unwrap());
+        let mut page = d.start_page_with(settings);
+        let mut surface = page.surface();
+        surface.fill_path(&rect_to_path(30.0, 40.0, 150.0, 140.0), green_fill(1.0));
+        surface.finish();
+        page.finish();
+    }
+
+    #[test]
+    fn page_box_outside_media_box() {
+        let mut d = Document::new();
+        d.start_page_with(
+            PageSettings::new(200.0, 200.0)
+                .with_trim_box(Rect::from_xywh(20.0, 20.0, 200.0, 160.0).unwrap()),
+        )
+        .finish();
+
+        assert_eq!(
+            d.finish(),
+            Err(KrillaError::UserError(
+                "the trim box of page 1 is not contained within its media box".to_string()
+            ))
+        );
+    }
+
+    #[visreg(document)]
+    fn page_rotated_90(d: &mut Document) {
+        let mut page =
+            d.start_page_with(PageSettings::new(200.0, 100.0).with_rotation(90).unwrap());
+        let mut surface = page.surface();
+        surface.fill_path(&rect_to_path(0.0, 0.0, 200.0, 100.0), green_fill(0.5));
+        surface.fill_text(
+            Point::from_xy(10.0, 60.0),
+            Fill::default(),
+            Font::new(NOTO_SANS.clone(), 0, true).unwrap(),
+            24.0,
+            &[],
+            "Rotated text",
+            false,
+            TextDirection::Auto,
+        );
+        surface.finish();
+        page.finish();
+    }
+
+    #[test]
+    fn page_rotation() {
+        assert_eq!(
+            PageSettings::default()
+                .with_rotation(-90)
+                .unwrap()
+                .rotation(),
+            270
+        );
+        assert_eq!(
+            PageSettings::default()
+                .with_rotation(450)
+                .unwrap()
+                .rotation(),
+            90
+        );
+        assert!(PageSettings::default().with_rotation(45).is_err());
+
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        d.start_page_with(PageSettings::default().with_rotation(180).unwrap())
+            .finish();
+        let pdf = d.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.contains("/Rotate 180"));
+    }
+
+    fn deferred_page_numbers_impl(d: &mut Document) -> Rc<RefCell<Vec<String>>> {
+        let font = Font::new(NOTO_SANS.clone(), 0, true).unwrap();
+        let drawn = Rc::new(RefCell::new(vec![]));
+
+        for _ in 0..3 {
+            let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+            let mut surface = page.surface();
+            surface.fill_path(&rect_to_path(20.0, 20.0, 180.0, 150.0), green_fill(1.0));
+            surface.finish();
+
+            let drawn = drawn.clone();
+            page.add_deferred_text(
+                Point::from_xy(20.0, 185.0),
+                font.clone(),
+                16.0,
+                move |index, count| {
+                    let text = format!("Page {} of {}", index + 1, count);
+                    drawn.borrow_mut().push(text.clone());
+                    text
+                },
+            );
+        }
+
+        drawn
+    }
+
+    #[visreg(document)]
+    fn page_deferred_page_numbers(d: &mut Document) {
+        deferred_page_numbers_impl(d);
+    }
+
+    #[test]
+    fn page_deferred_text_knows_page_count() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let drawn = deferred_page_numbers_impl(&mut d);
+        let pdf = d.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert_eq!(
+            *drawn.borrow(),
+            vec!["Page 1 of 3", "Page 2 of 3", "Page 3 of 3"]
+        );
+        assert_eq!(pdf.matches("/x0 Do").count(), 3);
+        // Tagging is enabled in the settings, so the texts should be marked as artifacts.
+        assert_eq!(pdf.matches("/Type /Pagination").count(), 3);
+    }
+
+    #[test]
+    fn page_resource_summary() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let mut page = d.start_page();
+        let font = Font::new(NOTO_SANS.clone(), 0, true).unwrap();
+
+        let mut surface = page.surface();
+
+        for y in [50.0, 100.0] {
+            surface.fill_text(
+                Point::from_xy(0.0, y),
+                Fill::default(),
+                font.clone(),
+                16.0,
+                &[],
+                "hello",
+                false,
+                TextDirection::Auto,
+            );
+        }
+
+        for name in ["rgb8.png", "luma8.png"] {
+            let image = load_png_image(name);
+            let size = Size::from_wh(image.size().0 as f32, image.size().1 as f32).unwrap();
+            surface.draw_image(image, size);
+        }
+
+        surface.finish();
+
+        let summary = page.resource_summary();
+        let usage = |name: &str, count| ResourceUsage {
+            name: name.to_string(),
+            count,
+        };
+
+        assert_eq!(summary.fonts, vec![usage("f0", 2)]);
+        assert_eq!(summary.x_objects, vec![usage("x0", 1), usage("x1", 1)]);
+    }
 }
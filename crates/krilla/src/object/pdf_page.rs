@@ -0,0 +1,301 @@
+//! Pages imported from existing PDF documents.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::ops::DerefMut;
+use std::sync::Arc;
+
+use lopdf::{Dictionary, Object, ObjectId};
+use pdf_writer::{Chunk, Finish, Name, Obj, Ref, Str};
+use tiny_skia_path::{Rect, Size, Transform};
+
+use crate::error::{KrillaError, KrillaResult};
+use crate::object::{Cacheable, ChunkContainerFn, Resourceable};
+use crate::resource;
+use crate::serialize::SerializeContext;
+use crate::stream::FilterStreamBuilder;
+use crate::util::{RectExt, SipHashable};
+
+/// Keys that tie an object to the structure of the source document and
+/// therefore must not be carried over.
+const DROPPED_KEYS: &[&[u8]] = &[b"StructParent", b"StructParents", b"Parent"];
+
+struct PdfPageRepr {
+    document: Arc<lopdf::Document>,
+    page_id: ObjectId,
+    content: Vec<u8>,
+    bbox: Rect,
+    rotation: i32,
+    sip: u128,
+}
+
+/// A single page of an existing PDF document that can be embedded
+/// as a form XObject.
+#[derive(Clone)]
+pub(crate) struct PdfPage(Arc<PdfPageRepr>);
+
+impl PdfPage {
+    /// Parse the PDF and extract the page with the given (zero-based) index.
+    pub(crate) fn new(data: &[u8], page_index: usize) -> KrillaResult<Self> {
+        let document = lopdf::Document::load_mem(data)
+            .map_err(|e| KrillaError::UserError(format!("failed to parse PDF: {e}")))?;
+
+        if document.is_encrypted() {
+            return Err(KrillaError::UserError(
+                "importing pages from encrypted PDFs is not supported".to_string(),
+            ));
+        }
+
+        let page_id = document
+            .get_pages()
+            .get(&(page_index as u32 + 1))
+            .copied()
+            .ok_or_else(|| {
+                KrillaError::UserError(format!("PDF has no page with index {page_index}"))
+            })?;
+
+        let content = document
+            .get_page_content(page_id)
+            .map_err(|e| KrillaError::UserError(format!("failed to read page content: {e}")))?;
+
+        let bbox = inherited(&document, page_id, b"CropBox")
+            .or_else(|| inherited(&document, page_id, b"MediaBox"))
+            .and_then(|o| to_rect(&document, o))
+            .ok_or_else(|| KrillaError::UserError("page has an invalid media box".to_string()))?;
+
+        let rotation = inherited(&document, page_id, b"Rotate")
+            .and_then(|o| o.as_i64().ok())
+            .map(|r| r.rem_euclid(360) as i32)
+            .unwrap_or(0);
+
+        let sip = (data.sip_hash(), page_index).sip_hash();
+
+        Ok(Self(Arc::new(PdfPageRepr {
+            document: Arc::new(document),
+            page_id,
+            content,
+            bbox,
+            rotation,
+            sip,
+        })))
+    }
+
+    /// The size of the page as it is displayed, i.e. with its rotation applied.
+    pub(crate) fn size(&self) -> Size {
+        let bbox = self.0.bbox;
+
+        if self.0.rotation % 180 == 90 {
+            Size::from_wh(bbox.height(), bbox.width()).unwrap()
+        } else {
+            Size::from_wh(bbox.width(), bbox.height()).unwrap()
+        }
+    }
+
+    /// The bounding box of the page in its own coordinate system.
+    pub(crate) fn bbox(&self) -> Rect {
+        self.0.bbox
+    }
+
+    /// The transform that maps the coordinate system of the page to the coordinate
+    /// system of a krilla surface with the size of the page, where the origin is at
+    /// the top-left.
+    pub(crate) fn transform(&self) -> Transform {
+        let (x0, y0) = (self.0.bbox.left(), self.0.bbox.top());
+        let (x1, y1) = (self.0.bbox.right(), self.0.bbox.bottom());
+
+        match self.0.rotation {
+            90 => Transform::from_row(0.0, 1.0, 1.0, 0.0, -y0, -x0),
+            180 => Transform::from_row(-1.0, 0.0, 0.0, 1.0, x1, -y0),
+            270 => Transform::from_row(0.0, -1.0, -1.0, 0.0, y1, x1),
+            _ => Transform::from_row(1.0, 0.0, 0.0, -1.0, -x0, y1),
+        }
+    }
+}
+
+impl Hash for PdfPage {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.sip.hash(state);
+    }
+}
+
+impl Cacheable for PdfPage {
+    fn chunk_container(&self) -> ChunkContainerFn {
+        Box::new(|cc| &mut cc.x_objects)
+    }
+
+    fn serialize(self, sc: &mut SerializeContext, root_ref: Ref) -> Chunk {
+        let mut chunk = Chunk::new();
+        let document = &self.0.document;
+        let mut copier = ObjectCopier::new(document);
+
+        let x_object_stream =
+            FilterStreamBuilder::new_from_content_stream(&self.0.content, &sc.serialize_settings())
+                .finish(&sc.serialize_settings());
+        let mut x_object = chunk.form_xobject(root_ref, x_object_stream.encoded_data());
+        x_object_stream.write_filters(x_object.deref_mut().deref_mut());
+        x_object.bbox(self.0.bbox.to_pdf_rect());
+
+        if let Some(resources) = inherited(document, self.0.page_id, b"Resources") {
+            copier.write(x_object.insert(Name(b"Resources")), resources, sc);
+        }
+
+        x_object.finish();
+
+        copier.write_queued(&mut chunk, sc);
+
+        chunk
+    }
+}
+
+impl Resourceable for PdfPage {
+    type Resource = resource::XObject;
+}
+
+/// Look up a (potentially inherited) attribute of a page.
+fn inherited<'a>(
+    document: &'a lopdf::Document,
+    page_id: ObjectId,
+    key: &[u8],
+) -> Option<&'a Object> {
+    let mut visited = HashSet::new();
+    let mut cur = document.get_dictionary(page_id).ok()?;
+
+    loop {
+        if let Ok(object) = cur.get(key) {
+            return Some(object);
+        }
+
+        let parent = cur.get(b"Parent").and_then(Object::as_reference).ok()?;
+
+        if !visited.insert(parent) {
+            return None;
+        }
+
+        cur = document.get_dictionary(parent).ok()?;
+    }
+}
+
+fn to_rect(document: &lopdf::Document, object: &Object) -> Option<Rect> {
+    let array = resolve(document, object).as_array().ok()?;
+
+    if array.len() != 4 {
+        return None;
+    }
+
+    let mut nums = [0.0; 4];
+
+    for (num, object) in nums.iter_mut().zip(array) {
+        *num = resolve(document, object).as_float().ok()?;
+    }
+
+    Rect::from_ltrb(
+        nums[0].min(nums[2]),
+        nums[1].min(nums[3]),
+        nums[0].max(nums[2]),
+        nums[1].max(nums[3]),
+    )
+}
+
+fn resolve<'a>(document: &'a lopdf::Document, object: &'a Object) -> &'a Object {
+    match object {
+        Object::Reference(id) => document.get_object(*id).unwrap_or(&Object::Null),
+        _ => object,
+    }
+}
+
+/// Copies objects from the source document, assigning new references to all
+/// indirect objects that are reachable from them.
+struct ObjectCopier<'a> {
+    document: &'a lopdf::Document,
+    refs: HashMap<ObjectId, Ref>,
+    queue: Vec<ObjectId>,
+}
+
+impl<'a> ObjectCopier<'a> {
+    fn new(document: &'a lopdf::Document) -> Self {
+        Self {
+            document,
+            refs: HashMap::new(),
+            queue: Vec::new(),
+        }
+    }
+
+    fn map_ref(&mut self, id: ObjectId, sc: &mut SerializeContext) -> Option<Ref> {
+        if let Some(ref_) = self.refs.get(&id) {
+            return Some(*ref_);
+        }
+
+        // Never pull in other pages or the page tree.
+        if let Ok(dict) = self.document.get_dictionary(id) {
+            if matches!(
+                dict.get(b"Type").and_then(Object::as_name),
+                Ok(b"Page" | b"Pages")
+            ) {
+                return None;
+            }
+        }
+
+        let ref_ = sc.new_ref();
+        self.refs.insert(id, ref_);
+        self.queue.push(id);
+
+        Some(ref_)
+    }
+
+    fn write(&mut self, obj: Obj, object: &Object, sc: &mut SerializeContext) {
+        match object {
+            Object::Null => obj.primitive(pdf_writer::Null),
+            Object::Boolean(b) => obj.primitive(*b),
+            Object::Integer(i) => obj.primitive(*i as i32),
+            Object::Real(f) => obj.primitive(*f),
+            Object::Name(n) => obj.primitive(Name(n)),
+            Object::String(s, _) => obj.primitive(Str(s)),
+            Object::Array(items) => {
+                let mut array = obj.array();
+
+                for item in items {
+                    self.write(array.push(), item, sc);
+                }
+            }
+            Object::Dictionary(dict) => self.write_dict(&mut obj.dict(), dict, sc),
+            // Streams can only appear as indirect objects.
+            Object::Stream(_) => obj.primitive(pdf_writer::Null),
+            Object::Reference(id) => match self.map_ref(*id, sc) {
+                Some(ref_) => obj.primitive(ref_),
+                None => obj.primitive(pdf_writer::Null),
+            },
+        }
+    }
+
+    fn write_dict(
+        &mut self,
+        target: &mut pdf_writer::Dict,
+        dict: &Dictionary,
+        sc: &mut SerializeContext,
+    ) {
+        for (key, value) in dict.iter() {
+            if DROPPED_KEYS.contains(&key.as_slice()) {
+                continue;
+            }
+
+            self.write(target.insert(Name(key)), value, sc);
+        }
+    }
+
+    fn write_queued(&mut self, chunk: &mut Chunk, sc: &mut SerializeContext) {
+        while let Some(id) = self.queue.pop() {
+            let ref_ = self.refs[&id];
+
+            match self.document.get_object(id) {
+                Ok(Object::Stream(stream)) => {
+                    let mut target = chunk.stream(ref_, &stream.content);
+                    let mut dict = stream.dict.clone();
+                    dict.remove(b"Length");
+                    self.write_dict(target.deref_mut(), &dict, sc);
+                }
+                Ok(object) => self.write(chunk.indirect(ref_), object, sc),
+                Err(_) => chunk.indirect(ref_).primitive(pdf_writer::Null),
+            }
+        }
+    }
+}
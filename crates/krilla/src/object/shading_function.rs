@@ -5,7 +5,7 @@ use std::ops::DerefMut;
 use std::sync::Arc;
 
 use bumpalo::Bump;
-use pdf_writer::types::{FunctionShadingType, PostScriptOp};
+use pdf_writer::types::{FunctionShadingType, PostScriptOp, StreamShadingType};
 use pdf_writer::{Chunk, Dict, Finish, Name, Ref};
 use tiny_skia_path::{NormalizedF32, Point, Rect, Transform};
 
@@ -13,10 +13,11 @@ use crate::color::{luma, ColorSpace, DEVICE_CMYK, DEVICE_GRAY, DEVICE_RGB};
 use crate::object::color::Color;
 use crate::object::{Cacheable, ChunkContainerFn, Resourceable};
 use crate::paint::SpreadMethod;
-use crate::paint::{LinearGradient, RadialGradient, SweepGradient};
+use crate::paint::{LinearGradient, MeshGradient, RadialGradient, SweepGradient};
 use crate::resource;
 use crate::resource::Resource;
 use crate::serialize::{MaybeDeviceColorSpace, SerializeContext};
+use crate::stream::FilterStreamBuilder;
 use crate::util::{NameExt, RectExt, RectWrapper};
 use crate::validation::ValidationError;
 
@@ -76,6 +77,8 @@ impl Hash for PostScriptGradient {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.min.to_bits().hash(state);
         self.max.to_bits().hash(state);
+        self.cx.to_bits().hash(state);
+        self.cy.to_bits().hash(state);
         self.stops.hash(state);
         self.domain.hash(state);
         self.spread_method.hash(state);
@@ -84,10 +87,35 @@ impl Hash for PostScriptGradient {
     }
 }
 
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) struct MeshVertex {
+    pub(crate) point: Point,
+    pub(crate) color: Color,
+    pub(crate) opacity: NormalizedF32,
+}
+
+impl Eq for MeshVertex {}
+
+impl Hash for MeshVertex {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.point.x.to_bits().hash(state);
+        self.point.y.to_bits().hash(state);
+        self.color.hash(state);
+        self.opacity.hash(state);
+    }
+}
+
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+pub(crate) struct MeshGradientProperties {
+    pub(crate) triangles: Vec<[MeshVertex; 3]>,
+    pub(crate) anti_alias: bool,
+}
+
 #[derive(Debug, Hash, Eq, PartialEq, Clone)]
 pub(crate) enum GradientProperties {
     RadialAxialGradient(RadialAxialGradient),
     PostScriptGradient(PostScriptGradient),
+    MeshGradient(MeshGradientProperties),
 }
 
 impl GradientProperties {
@@ -104,6 +132,9 @@ impl GradientProperties {
                     return Some((psg.stops[0].color, psg.stops[0].opacity));
                 }
             }
+            // A mesh only covers the area of its triangles, so it can never be
+            // replaced by a solid fill.
+            GradientProperties::MeshGradient(_) => {}
         }
 
         None
@@ -146,7 +177,12 @@ impl GradientPropertiesExt for LinearGradient {
                 GradientProperties::RadialAxialGradient(RadialAxialGradient {
                     coords: vec![self.x1, self.y1, self.x2, self.y2],
                     shading_type: FunctionShadingType::Axial,
-                    stops: self.stops.0.into_iter().collect::<Vec<Stop>>(),
+                    stops: self
+                        .stops
+                        .0
+                        .interpolated_in(self.interpolation)
+                        .into_iter()
+                        .collect::<Vec<Stop>>(),
                     anti_alias: self.anti_alias,
                 }),
                 self.transform,
@@ -162,7 +198,12 @@ impl GradientPropertiesExt for LinearGradient {
                     max,
                     cx: 0.0,
                     cy: 0.0,
-                    stops: self.stops.0.into_iter().collect::<Vec<Stop>>(),
+                    stops: self
+                        .stops
+                        .0
+                        .interpolated_in(self.interpolation)
+                        .into_iter()
+                        .collect::<Vec<Stop>>(),
                     domain: RectWrapper(get_expanded_bbox(bbox, self.transform.pre_concat(ts))),
                     spread_method: self.spread_method,
                     gradient_type: GradientType::Linear,
@@ -179,7 +220,9 @@ impl GradientPropertiesExt for SweepGradient {
         let min = self.start_angle;
         let max = self.end_angle;
 
-        let transform = self.transform;
+        let transform =
+            self.transform
+                .pre_concat(Transform::from_rotate_at(self.rotation, self.cx, self.cy));
 
         (
             GradientProperties::PostScriptGradient(PostScriptGradient {
@@ -187,7 +230,12 @@ impl GradientPropertiesExt for SweepGradient {
                 max,
                 cx: self.cx,
                 cy: self.cy,
-                stops: self.stops.0.into_iter().collect::<Vec<Stop>>(),
+                stops: self
+                    .stops
+                    .0
+                    .interpolated_in(self.interpolation)
+                    .into_iter()
+                    .collect::<Vec<Stop>>(),
                 domain: RectWrapper(get_expanded_bbox(bbox, transform)),
                 spread_method: self.spread_method,
                 gradient_type: GradientType::Sweep,
@@ -205,7 +253,24 @@ impl GradientPropertiesExt for RadialGradient {
             GradientProperties::RadialAxialGradient(RadialAxialGradient {
                 coords: vec![self.fx, self.fy, self.fr, self.cx, self.cy, self.cr],
                 shading_type: FunctionShadingType::Radial,
-                stops: self.stops.0.into_iter().collect::<Vec<Stop>>(),
+                stops: self
+                    .stops
+                    .0
+                    .interpolated_in(self.interpolation)
+                    .into_iter()
+                    .collect::<Vec<Stop>>(),
+                anti_alias: self.anti_alias,
+            }),
+            self.transform,
+        )
+    }
+}
+
+impl GradientPropertiesExt for MeshGradient {
+    fn gradient_properties(self, _: Rect) -> (GradientProperties, Transform) {
+        (
+            GradientProperties::MeshGradient(MeshGradientProperties {
+                triangles: self.triangles.0,
                 anti_alias: self.anti_alias,
             }),
             self.transform,
@@ -244,8 +309,26 @@ impl Cacheable for ShadingFunction {
                 serialize_axial_radial_shading(sc, &mut chunk, root_ref, rag, self.0.use_opacities)
             }
             GradientProperties::PostScriptGradient(psg) => {
-                sc.register_validation_error(ValidationError::ContainsPostScript);
-                serialize_postscript_shading(sc, &mut chunk, root_ref, psg, self.0.use_opacities)
+                if psg.gradient_type == GradientType::Linear && prohibits_postscript(sc) {
+                    serialize_repeated_axial_shading(
+                        sc,
+                        &mut chunk,
+                        root_ref,
+                        psg,
+                        self.0.use_opacities,
+                    )
+                } else {
+                    serialize_postscript_shading(
+                        sc,
+                        &mut chunk,
+                        root_ref,
+                        psg,
+                        self.0.use_opacities,
+                    )
+                }
+            }
+            GradientProperties::MeshGradient(mg) => {
+                serialize_mesh_shading(sc, &mut chunk, root_ref, mg, self.0.use_opacities)
             }
         }
 
@@ -290,6 +373,170 @@ fn serialize_postscript_shading(
     shading.finish();
 }
 
+/// Serialize a linear gradient with a repeat or reflect spread method as an axial shading
+/// that spans the whole domain, by stitching together one copy of the stop function per
+/// period. This is exact and, unlike the PostScript approach, allowed in all export modes.
+fn serialize_repeated_axial_shading(
+    sc: &mut SerializeContext,
+    chunk: &mut Chunk,
+    root_ref: Ref,
+    post_script_gradient: &PostScriptGradient,
+    use_opacities: bool,
+) {
+    debug_assert_eq!(post_script_gradient.gradient_type, GradientType::Linear);
+
+    let min = post_script_gradient.min;
+    let max = post_script_gradient.max;
+    let length = max - min;
+    let domain = post_script_gradient.domain;
+
+    let stops_ref = serialize_stops_function(&post_script_gradient.stops, chunk, sc, use_opacities);
+    let cs = if use_opacities {
+        luma::Color::color_space(sc.serialize_settings().no_device_cs)
+    } else {
+        post_script_gradient.stops[0].color.color_space(sc)
+    };
+
+    // Align the start and end of the shading with the periods of the gradient.
+    let (first, last) = if length > 0.0 {
+        (
+            ((domain.left() - min) / length).floor() as i32,
+            ((domain.right() - min) / length).ceil() as i32,
+        )
+    } else {
+        (0, 1)
+    };
+    let last = last.max(first + 1);
+    let num_periods = last - first;
+
+    let function_ref = sc.new_ref();
+    let mut stitching_function = chunk.stitching_function(function_ref);
+    stitching_function.domain([0.0, 1.0]);
+    stitching_function.functions(std::iter::repeat(stops_ref).take(num_periods as usize));
+    stitching_function.bounds((1..num_periods).map(|i| i as f32 / num_periods as f32));
+    stitching_function.encode((first..last).flat_map(|i| {
+        if post_script_gradient.spread_method == SpreadMethod::Reflect && i.abs() % 2 > 0 {
+            [1.0, 0.0]
+        } else {
+            [0.0, 1.0]
+        }
+    }));
+    stitching_function.finish();
+
+    let mut shading = chunk.function_shading(root_ref);
+    shading.shading_type(FunctionShadingType::Axial);
+
+    set_colorspace(sc, cs, shading.deref_mut());
+
+    shading.anti_alias(post_script_gradient.anti_alias);
+    shading.function(function_ref);
+    shading.coords([
+        min + first as f32 * length,
+        0.0,
+        min + last as f32 * length,
+        0.0,
+    ]);
+    shading.extend([true, true]);
+    shading.finish();
+}
+
+/// Serialize a mesh gradient as a free-form Gouraud-shaded triangle mesh (type 4 shading).
+fn serialize_mesh_shading(
+    sc: &mut SerializeContext,
+    chunk: &mut Chunk,
+    root_ref: Ref,
+    mesh: &MeshGradientProperties,
+    use_opacities: bool,
+) {
+    let first = mesh.triangles.first().map(|t| t[0]);
+
+    // An empty mesh doesn't paint anything, so the color space doesn't matter.
+    let (cs, range) = match first {
+        Some(first) if !use_opacities => (first.color.color_space(sc), first.color.ranges()),
+        _ => (
+            luma::Color::color_space(sc.serialize_settings().no_device_cs),
+            vec![0.0, 1.0],
+        ),
+    };
+
+    let vertices = || mesh.triangles.iter().flatten();
+
+    let (x_min, x_max) = vertices()
+        .map(|v| v.point.x)
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), x| {
+            (min.min(x), max.max(x))
+        });
+    let (y_min, y_max) = vertices()
+        .map(|v| v.point.y)
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), y| {
+            (min.min(y), max.max(y))
+        });
+
+    // Make sure the decode ranges are valid even if the mesh is empty or degenerate.
+    let valid_range = |min: f32, max: f32| {
+        if !min.is_finite() {
+            (0.0, 1.0)
+        } else if min < max {
+            (min, max)
+        } else {
+            (min, min + 1.0)
+        }
+    };
+
+    let (x_min, x_max) = valid_range(x_min, x_max);
+    let (y_min, y_max) = valid_range(y_min, y_max);
+
+    let encode_coordinate = |val: f32, min: f32, max: f32| -> [u8; 4] {
+        let normalized = (val as f64 - min as f64) / (max as f64 - min as f64);
+        ((normalized.clamp(0.0, 1.0) * u32::MAX as f64).round() as u32).to_be_bytes()
+    };
+
+    let mut data = vec![];
+
+    for vertex in vertices() {
+        // All triangles are independent of each other, so each vertex has the flag 0.
+        data.push(0);
+        data.extend(encode_coordinate(vertex.point.x, x_min, x_max));
+        data.extend(encode_coordinate(vertex.point.y, y_min, y_max));
+
+        let components = if use_opacities {
+            vec![vertex.opacity.get()]
+        } else {
+            vertex.color.to_pdf_color()
+        };
+
+        for (c, r) in components.iter().zip(range.chunks_exact(2)) {
+            let normalized = ((c - r[0]) / (r[1] - r[0])).clamp(0.0, 1.0);
+            data.extend(((normalized * u16::MAX as f32).round() as u16).to_be_bytes());
+        }
+    }
+
+    let mesh_stream = FilterStreamBuilder::new_from_binary_data(&data, &sc.serialize_settings())
+        .finish(&sc.serialize_settings());
+
+    let mut shading = chunk.stream_shading(root_ref, mesh_stream.encoded_data());
+    mesh_stream.write_filters(shading.deref_mut().deref_mut());
+    shading.shading_type(StreamShadingType::FreeformGouraud);
+
+    set_colorspace(sc, cs, shading.deref_mut().deref_mut());
+
+    shading.anti_alias(mesh.anti_alias);
+    shading.bits_per_coordinate(32);
+    shading.bits_per_component(16);
+    shading.bits_per_flag(8);
+    shading.decode([x_min, x_max, y_min, y_max].into_iter().chain(range));
+    shading.finish();
+}
+
+/// The output range of a function that computes the colors (or opacities) of the stops.
+fn function_range(stops: &[Stop], use_opacities: bool) -> Vec<f32> {
+    if use_opacities {
+        vec![0.0, 1.0]
+    } else {
+        stops[0].color.ranges()
+    }
+}
+
 fn set_colorspace(sc: &mut SerializeContext, cs: ColorSpace, target: &mut Dict) {
     let pdf_cs = target.insert(Name(b"ColorSpace"));
 
@@ -341,9 +588,19 @@ fn select_axial_radial_function(
     sc: &mut SerializeContext,
     use_opacities: bool,
 ) -> Ref {
-    debug_assert!(properties.stops.len() > 1);
+    serialize_stops_function(&properties.stops, chunk, sc, use_opacities)
+}
 
-    let mut stops = properties.stops.clone();
+/// Serialize a function that maps the domain [0, 1] to the interpolated stop colors.
+fn serialize_stops_function(
+    stops: &[Stop],
+    chunk: &mut Chunk,
+    sc: &mut SerializeContext,
+    use_opacities: bool,
+) -> Ref {
+    debug_assert!(stops.len() > 1);
+
+    let mut stops = stops.to_vec();
 
     if let Some(first) = stops.first() {
         if first.offset != 0.0 {
@@ -366,6 +623,7 @@ fn select_axial_radial_function(
             serialize_exponential(
                 vec![stops[0].opacity.get()],
                 vec![stops[1].opacity.get()],
+                function_range(&stops, use_opacities),
                 chunk,
                 sc,
             )
@@ -381,6 +639,7 @@ fn select_axial_radial_function(
                     .to_pdf_color()
                     .into_iter()
                     .collect::<Vec<_>>(),
+                function_range(&stops, use_opacities),
                 chunk,
                 sc,
             )
@@ -401,6 +660,8 @@ fn select_postscript_function(
 
     if properties.gradient_type == GradientType::Linear {
         serialize_linear_postscript(properties, chunk, sc, use_opacities)
+    } else if properties.gradient_type == GradientType::Sweep && prohibits_postscript(sc) {
+        serialize_sweep_sampled(properties, chunk, sc, use_opacities)
     } else if properties.gradient_type == GradientType::Sweep {
         serialize_sweep_postscript(properties, chunk, sc, bump, use_opacities)
     } else {
@@ -447,6 +708,7 @@ fn serialize_sweep_postscript(
 ) -> Ref {
     use pdf_writer::types::PostScriptOp::*;
 
+    sc.register_validation_error(ValidationError::ContainsPostScript);
     let root_ref = sc.new_ref();
 
     let min: f32 = properties.min;
@@ -490,15 +752,143 @@ fn serialize_sweep_postscript(
         properties.domain.bottom(),
     ]);
 
-    if use_opacities {
-        postscript_function.range([0.0, 1.0]);
-    } else {
-        postscript_function.range([0.0, 1.0, 0.0, 1.0, 0.0, 1.0]);
+    postscript_function.range(function_range(&properties.stops, use_opacities));
+
+    root_ref
+}
+
+/// The number of samples per dimension we use when approximating a sweep gradient
+/// with a sampled function.
+const SWEEP_SAMPLES: usize = 128;
+
+/// Whether PostScript functions are forbidden in the current export mode, in which case
+/// we need to fall back to approximations or other function types.
+fn prohibits_postscript(sc: &SerializeContext) -> bool {
+    sc.serialize_settings()
+        .validator
+        .prohibits(&ValidationError::ContainsPostScript)
+}
+
+fn serialize_sweep_sampled(
+    properties: &PostScriptGradient,
+    chunk: &mut Chunk,
+    sc: &mut SerializeContext,
+    use_opacities: bool,
+) -> Ref {
+    let root_ref = sc.new_ref();
+    let domain = properties.domain;
+
+    let mut stops = properties.stops.clone();
+
+    if let Some(first) = stops.first() {
+        let mut first = *first;
+        first.offset = NormalizedF32::ZERO;
+        stops.insert(0, first);
     }
 
+    if let Some(last) = stops.last() {
+        let mut last = *last;
+        last.offset = NormalizedF32::ONE;
+        stops.push(last);
+    }
+
+    let components = |stop: &Stop| -> Vec<f32> {
+        if use_opacities {
+            vec![stop.opacity.get()]
+        } else {
+            stop.color.to_pdf_color().into_iter().collect()
+        }
+    };
+
+    let stops = stops
+        .iter()
+        .map(|s| (s.offset.get(), components(s)))
+        .collect::<Vec<_>>();
+    let num_components = stops[0].1.len();
+    let range = function_range(&properties.stops, use_opacities);
+
+    let step =
+        |min: f32, max: f32, i: usize| min + (max - min) * i as f32 / (SWEEP_SAMPLES - 1) as f32;
+
+    // The first input dimension varies fastest.
+    let mut samples = Vec::with_capacity(SWEEP_SAMPLES * SWEEP_SAMPLES * num_components);
+    for y in 0..SWEEP_SAMPLES {
+        for x in 0..SWEEP_SAMPLES {
+            let x = step(domain.left(), domain.right(), x);
+            let y = step(domain.top(), domain.bottom(), y);
+
+            let color = sweep_color_at(properties, &stops, x, y);
+            samples.extend(color.iter().zip(range.chunks_exact(2)).map(|(c, r)| {
+                (((c - r[0]) / (r[1] - r[0])).clamp(0.0, 1.0) * 255.0).round() as u8
+            }));
+        }
+    }
+
+    let sample_stream =
+        FilterStreamBuilder::new_from_binary_data(&samples, &sc.serialize_settings())
+            .finish(&sc.serialize_settings());
+
+    let mut sampled_function = chunk.sampled_function(root_ref, sample_stream.encoded_data());
+    sample_stream.write_filters(sampled_function.deref_mut().deref_mut());
+    sampled_function.domain([domain.left(), domain.right(), domain.top(), domain.bottom()]);
+    sampled_function.range(range);
+    sampled_function.size([SWEEP_SAMPLES as i32; 2]);
+    sampled_function.bits_per_sample(8);
+    sampled_function.finish();
+
     root_ref
 }
 
+/// Evaluate the color of a sweep gradient at a specific point, mirroring what
+/// `serialize_sweep_postscript` does. `stops` need to be padded and already converted
+/// into their PDF components.
+fn sweep_color_at(
+    properties: &PostScriptGradient,
+    stops: &[(f32, Vec<f32>)],
+    x: f32,
+    y: f32,
+) -> Vec<f32> {
+    let min = properties.min;
+    let max = properties.max;
+    let length = max - min;
+
+    let mut angle = (y - properties.cy).atan2(x - properties.cx).to_degrees();
+
+    if angle < 0.0 {
+        angle += 360.0;
+    }
+
+    if properties.spread_method != SpreadMethod::Pad && length != 0.0 {
+        let n = angle - min;
+        let i = (n / length).floor();
+        let mut o = n - i * length;
+
+        if properties.spread_method == SpreadMethod::Reflect && (i.abs() as i32) % 2 > 0 {
+            o = length - o;
+        }
+
+        angle = min + o;
+    }
+
+    for window in stops.windows(2) {
+        let (o0, c0) = &window[0];
+        let (o1, c1) = &window[1];
+        let stops_min = min + length * o0;
+        let stops_max = min + length * o1;
+
+        if angle <= stops_max {
+            if stops_min == stops_max {
+                return c0.clone();
+            }
+
+            let t = ((angle - stops_min) / (stops_max - stops_min)).clamp(0.0, 1.0);
+            return c0.iter().zip(c1).map(|(a, b)| a + t * (b - a)).collect();
+        }
+    }
+
+    stops[stops.len() - 1].1.clone()
+}
+
 const MAX_POSTSCRIPT_STOPS: usize = 97;
 
 fn trim_stops(stops: &[Stop]) -> Vec<Stop> {
@@ -527,6 +917,7 @@ fn serialize_linear_postscript(
     use pdf_writer::types::PostScriptOp::*;
 
     let bump = Bump::new();
+    sc.register_validation_error(ValidationError::ContainsPostScript);
     let root_ref = sc.new_ref();
 
     let min: f32 = properties.min;
@@ -553,11 +944,7 @@ fn serialize_linear_postscript(
         properties.domain.bottom(),
     ]);
 
-    if use_opacities {
-        postscript_function.range([0.0, 1.0]);
-    } else {
-        postscript_function.range([0.0, 1.0, 0.0, 1.0, 0.0, 1.0]);
-    }
+    postscript_function.range(function_range(&properties.stops, use_opacities));
 
     root_ref
 }
@@ -791,7 +1178,7 @@ fn serialize_stitching(
     let mut functions = vec![];
     let mut bounds = vec![];
     let mut encode = vec![];
-    let mut count = 0;
+    let range = function_range(stops, use_opacities);
 
     for window in stops.windows(2) {
         let (first, second) = (&window[0], &window[1]);
@@ -806,9 +1193,8 @@ fn serialize_stitching(
             )
         };
         debug_assert!(c0_components.len() == c1_components.len());
-        count = c0_components.len();
 
-        let exp_ref = serialize_exponential(c0_components, c1_components, chunk, sc);
+        let exp_ref = serialize_exponential(c0_components, c1_components, range.clone(), chunk, sc);
 
         functions.push(exp_ref);
         encode.extend([0.0, 1.0]);
@@ -817,7 +1203,7 @@ fn serialize_stitching(
     bounds.pop();
     let mut stitching_function = chunk.stitching_function(root_ref);
     stitching_function.domain([0.0, 1.0]);
-    stitching_function.range([0.0, 1.0].repeat(count));
+    stitching_function.range(range);
     stitching_function.functions(functions);
     stitching_function.bounds(bounds);
     stitching_function.encode(encode);
@@ -828,16 +1214,17 @@ fn serialize_stitching(
 fn serialize_exponential(
     first_comps: Vec<f32>,
     second_comps: Vec<f32>,
+    range: Vec<f32>,
     chunk: &mut Chunk,
     sc: &mut SerializeContext,
 ) -> Ref {
     let root_ref = sc.new_ref();
     debug_assert_eq!(first_comps.len(), second_comps.len());
-    let num_components = first_comps.len();
+    debug_assert_eq!(first_comps.len() * 2, range.len());
 
     let mut exp = chunk.exponential_function(root_ref);
 
-    exp.range([0.0, 1.0].repeat(num_components));
+    exp.range(range);
     exp.c0(first_comps);
     exp.c1(second_comps);
     exp.domain([0.0, 1.0]);
@@ -65,18 +65,31 @@ impl Resourceable for ShadingPattern {
 
 #[cfg(test)]
 mod tests {
+    use crate::color::rgb;
     use crate::object::shading_function::GradientPropertiesExt;
     use crate::object::shading_pattern::ShadingPattern;
     use crate::page::Page;
-    use crate::paint::{LinearGradient, RadialGradient, SpreadMethod, SweepGradient};
+    use crate::paint::{
+        InterpolationColorSpace, LinearGradient, MeshGradient, MeshTriangles, MeshVertex, Paint,
+        RadialGradient, SpreadMethod, SweepGradient,
+    };
     use crate::path::Fill;
     use crate::serialize::SerializeContext;
     use crate::surface::Surface;
     use crate::tests::{
         rect_to_path, stops_with_1_solid, stops_with_2_solid_1, stops_with_3_solid_1,
     };
+    use crate::{Document, SerializeSettings};
     use krilla_macros::{snapshot, visreg};
-    use tiny_skia_path::{NormalizedF32, Rect};
+    use tiny_skia_path::{NormalizedF32, Point, Rect};
+
+    fn gradient_fill(paint: impl Into<Paint>) -> Fill {
+        Fill {
+            paint: paint.into(),
+            opacity: NormalizedF32::ONE,
+            rule: Default::default(),
+        }
+    }
 
     #[snapshot]
     fn linear_gradient_pad(sc: &mut SerializeContext) {
@@ -89,6 +102,7 @@ mod tests {
             spread_method: SpreadMethod::Pad,
             stops: stops_with_2_solid_1(),
             anti_alias: false,
+            interpolation: Default::default(),
         };
 
         let (props, transform) =
@@ -97,6 +111,63 @@ mod tests {
         sc.register_cacheable(shading_pattern);
     }
 
+    #[snapshot]
+    fn linear_gradient_lab_interpolation(sc: &mut SerializeContext) {
+        let gradient = LinearGradient {
+            x1: 50.0,
+            y1: 0.0,
+            x2: 150.0,
+            y2: 0.0,
+            transform: Default::default(),
+            spread_method: SpreadMethod::Pad,
+            stops: stops_with_2_solid_1(),
+            anti_alias: false,
+            interpolation: InterpolationColorSpace::Lab,
+        };
+
+        let (props, transform) =
+            gradient.gradient_properties(Rect::from_ltrb(50.0, 50.0, 150.0, 150.0).unwrap());
+        let shading_pattern = ShadingPattern::new(props, transform);
+        sc.register_cacheable(shading_pattern);
+    }
+
+    fn mesh_triangles(opacity: NormalizedF32) -> MeshTriangles {
+        let vertex = |x: f32, y: f32, color: rgb::Color| MeshVertex {
+            point: Point::from_xy(x, y),
+            color,
+            opacity,
+        };
+
+        let red = rgb::Color::new(255, 0, 0);
+        let green = rgb::Color::new(0, 255, 0);
+        let blue = rgb::Color::new(0, 0, 255);
+        let yellow = rgb::Color::new(255, 255, 0);
+
+        vec![
+            [
+                vertex(20.0, 20.0, red),
+                vertex(180.0, 20.0, green),
+                vertex(20.0, 180.0, blue),
+            ],
+            [
+                vertex(180.0, 20.0, green),
+                vertex(180.0, 180.0, yellow),
+                vertex(20.0, 180.0, blue),
+            ],
+        ]
+        .into()
+    }
+
+    #[snapshot]
+    fn mesh_gradient(sc: &mut SerializeContext) {
+        let gradient = MeshGradient::new(mesh_triangles(NormalizedF32::ONE));
+
+        let (props, transform) =
+            gradient.gradient_properties(Rect::from_ltrb(20.0, 20.0, 180.0, 180.0).unwrap());
+        let shading_pattern = ShadingPattern::new(props, transform);
+        sc.register_cacheable(shading_pattern);
+    }
+
     #[snapshot]
     fn linear_gradient_repeat(sc: &mut SerializeContext) {
         let gradient = LinearGradient {
@@ -108,6 +179,7 @@ mod tests {
             spread_method: SpreadMethod::Repeat,
             stops: stops_with_2_solid_1(),
             anti_alias: false,
+            interpolation: Default::default(),
         };
 
         let (props, transform) =
@@ -128,6 +200,7 @@ mod tests {
             spread_method: SpreadMethod::Pad,
             stops: stops_with_2_solid_1(),
             anti_alias: false,
+            interpolation: Default::default(),
         };
 
         surface.fill_path(
@@ -140,6 +213,20 @@ mod tests {
         );
     }
 
+    #[visreg(all)]
+    fn mesh_gradient(surface: &mut Surface) {
+        let path = rect_to_path(20.0, 20.0, 180.0, 180.0);
+        let gradient = MeshGradient::new(mesh_triangles(NormalizedF32::ONE));
+        surface.fill_path(&path, gradient_fill(gradient));
+    }
+
+    #[visreg(all)]
+    fn mesh_gradient_with_opacity(surface: &mut Surface) {
+        let path = rect_to_path(20.0, 20.0, 180.0, 180.0);
+        let gradient = MeshGradient::new(mesh_triangles(NormalizedF32::new(0.5).unwrap()));
+        surface.fill_path(&path, gradient_fill(gradient));
+    }
+
     #[visreg(all)]
     fn linear_gradient_repeat(surface: &mut Surface) {
         let path = rect_to_path(20.0, 20.0, 180.0, 180.0);
@@ -152,6 +239,7 @@ mod tests {
             spread_method: SpreadMethod::Repeat,
             stops: stops_with_2_solid_1(),
             anti_alias: false,
+            interpolation: Default::default(),
         };
 
         surface.fill_path(
@@ -171,10 +259,12 @@ mod tests {
             cy: 100.0,
             start_angle: 0.0,
             end_angle: 90.0,
+            rotation: 0.0,
             transform: Default::default(),
             spread_method: SpreadMethod::Pad,
             stops: stops_with_2_solid_1(),
             anti_alias: false,
+            interpolation: Default::default(),
         };
 
         let (props, transform) =
@@ -190,10 +280,12 @@ mod tests {
             cy: 100.0,
             start_angle: 0.0,
             end_angle: 90.0,
+            rotation: 0.0,
             transform: Default::default(),
             spread_method: SpreadMethod::Repeat,
             stops: stops_with_2_solid_1(),
             anti_alias: false,
+            interpolation: Default::default(),
         };
 
         let (props, transform) =
@@ -210,10 +302,12 @@ mod tests {
             cy: 100.0,
             start_angle: 0.0,
             end_angle: 90.0,
+            rotation: 0.0,
             transform: Default::default(),
             spread_method: SpreadMethod::Pad,
             stops: stops_with_2_solid_1(),
             anti_alias: false,
+            interpolation: Default::default(),
         };
 
         surface.fill_path(
@@ -226,6 +320,28 @@ mod tests {
         );
     }
 
+    #[visreg(all)]
+    fn sweep_gradient_rotated(surface: &mut Surface) {
+        let path = rect_to_path(20.0, 20.0, 180.0, 180.0);
+        let mut gradient =
+            SweepGradient::new(100.0, 100.0, 0.0, 90.0, stops_with_2_solid_1()).unwrap();
+        gradient.rotation = 45.0;
+
+        surface.fill_path(
+            &path,
+            Fill {
+                paint: gradient.into(),
+                opacity: NormalizedF32::ONE,
+                rule: Default::default(),
+            },
+        );
+    }
+
+    #[test]
+    fn sweep_gradient_same_angles() {
+        assert!(SweepGradient::new(100.0, 100.0, 45.0, 45.0, stops_with_2_solid_1()).is_none());
+    }
+
     #[visreg(all)]
     fn sweep_gradient_repeat(surface: &mut Surface) {
         let path = rect_to_path(20.0, 20.0, 180.0, 180.0);
@@ -234,10 +350,12 @@ mod tests {
             cy: 100.0,
             start_angle: 0.0,
             end_angle: 90.0,
+            rotation: 0.0,
             transform: Default::default(),
             spread_method: SpreadMethod::Repeat,
             stops: stops_with_2_solid_1(),
             anti_alias: false,
+            interpolation: Default::default(),
         };
 
         surface.fill_path(
@@ -263,6 +381,7 @@ mod tests {
             spread_method: SpreadMethod::Pad,
             stops: stops_with_2_solid_1(),
             anti_alias: false,
+            interpolation: Default::default(),
         };
 
         let (props, transform) =
@@ -288,6 +407,7 @@ mod tests {
             spread_method: SpreadMethod::Pad,
             stops: stops_with_1_solid(),
             anti_alias: false,
+            interpolation: Default::default(),
         };
 
         surface.fill_path(
@@ -314,6 +434,7 @@ mod tests {
             spread_method: SpreadMethod::Pad,
             stops: stops_with_3_solid_1(),
             anti_alias: false,
+            interpolation: Default::default(),
         };
 
         surface.fill_path(
@@ -325,4 +446,85 @@ mod tests {
             },
         );
     }
+
+    #[test]
+    fn gradient_shared_between_shapes() {
+        let linear = LinearGradient {
+            x1: 50.0,
+            y1: 0.0,
+            x2: 150.0,
+            y2: 0.0,
+            transform: Default::default(),
+            spread_method: SpreadMethod::Pad,
+            stops: stops_with_2_solid_1(),
+            anti_alias: false,
+            interpolation: Default::default(),
+        };
+
+        let radial = RadialGradient {
+            cx: 100.0,
+            cy: 100.0,
+            cr: 50.0,
+            fx: 120.0,
+            fy: 120.0,
+            fr: 50.0,
+            transform: Default::default(),
+            spread_method: SpreadMethod::Pad,
+            stops: stops_with_3_solid_1(),
+            anti_alias: false,
+            interpolation: Default::default(),
+        };
+
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+
+        for (i, size) in [20.0, 50.0, 80.0].into_iter().enumerate() {
+            let offset = i as f32 * 60.0;
+            let path = rect_to_path(offset, 0.0, offset + size, size);
+            surface.fill_path(&path, gradient_fill(linear.clone()));
+
+            let path = rect_to_path(offset, 100.0, offset + size, 100.0 + size);
+            surface.fill_path(&path, gradient_fill(radial.clone()));
+        }
+
+        surface.finish();
+        page.finish();
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert_eq!(pdf.matches("/ShadingType 2").count(), 1);
+        assert_eq!(pdf.matches("/ShadingType 3").count(), 1);
+    }
+
+    #[test]
+    fn sweep_gradients_with_different_centers_not_shared() {
+        let sweep = |cx: f32| SweepGradient {
+            cx,
+            cy: 100.0,
+            start_angle: 0.0,
+            end_angle: 90.0,
+            rotation: 0.0,
+            transform: Default::default(),
+            spread_method: SpreadMethod::Pad,
+            stops: stops_with_2_solid_1(),
+            anti_alias: false,
+            interpolation: Default::default(),
+        };
+
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        let path = rect_to_path(20.0, 20.0, 180.0, 180.0);
+        surface.fill_path(&path, gradient_fill(sweep(60.0)));
+        surface.fill_path(&path, gradient_fill(sweep(140.0)));
+        surface.finish();
+        page.finish();
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert_eq!(pdf.matches("/ShadingType 1").count(), 2);
+    }
 }
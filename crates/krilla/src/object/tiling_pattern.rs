@@ -22,6 +22,8 @@ pub(crate) struct TilingPattern {
     base_opacity: NormalizedF32,
     width: f32,
     height: f32,
+    x_step: f32,
+    y_step: f32,
 }
 
 impl Eq for TilingPattern {}
@@ -33,6 +35,8 @@ impl Hash for TilingPattern {
         self.base_opacity.hash(state);
         self.width.to_bits().hash(state);
         self.height.to_bits().hash(state);
+        self.x_step.to_bits().hash(state);
+        self.y_step.to_bits().hash(state);
     }
 }
 
@@ -43,6 +47,8 @@ impl TilingPattern {
         base_opacity: NormalizedF32,
         width: f32,
         height: f32,
+        x_step: f32,
+        y_step: f32,
         serializer_context: &mut SerializeContext,
     ) -> Self {
         // stroke/fill opacity doesn't work consistently across different viewers for patterns,
@@ -67,6 +73,8 @@ impl TilingPattern {
             base_opacity,
             width,
             height,
+            x_step,
+            y_step,
         }
     }
 }
@@ -102,8 +110,8 @@ impl Cacheable for TilingPattern {
             .paint_type(PaintType::Colored)
             .bbox(final_bbox)
             .matrix(self.transform.to_pdf_transform())
-            .x_step(final_bbox.x2 - final_bbox.x1)
-            .y_step(final_bbox.y2 - final_bbox.y1);
+            .x_step(self.x_step)
+            .y_step(self.y_step);
 
         tiling_pattern.finish();
 
@@ -119,15 +127,16 @@ impl Resourceable for TilingPattern {
 mod tests {
 
     use crate::paint::Pattern;
-    use crate::path::Fill;
+    use crate::path::{Fill, Stroke};
     use crate::serialize::SerializeContext;
     use crate::stream::StreamBuilder;
     use crate::surface::Surface;
     use crate::tests::{basic_pattern_stream, rect_to_path};
     use crate::tiling_pattern::TilingPattern;
+    use crate::{Document, SerializeSettings};
 
     use krilla_macros::{snapshot, visreg};
-    use tiny_skia_path::{NormalizedF32, Transform};
+    use tiny_skia_path::{NormalizedF32, PathBuilder, Point, Transform};
 
     #[snapshot]
     fn tiling_pattern_basic(sc: &mut SerializeContext) {
@@ -140,6 +149,8 @@ mod tests {
             NormalizedF32::ONE,
             20.0,
             20.0,
+            20.0,
+            20.0,
             sc,
         );
 
@@ -152,12 +163,7 @@ mod tests {
         let stream_builder = surface.stream_builder();
         let pattern_stream = basic_pattern_stream(stream_builder);
 
-        let pattern = Pattern {
-            stream: pattern_stream,
-            transform: Default::default(),
-            width: 20.0,
-            height: 20.0,
-        };
+        let pattern = Pattern::new(pattern_stream, Default::default(), 20.0, 20.0);
 
         surface.fill_path(
             &path,
@@ -168,4 +174,81 @@ mod tests {
             },
         )
     }
+
+    #[visreg(all)]
+    fn tiling_pattern_overlapping_hatch(surface: &mut Surface) {
+        let path = rect_to_path(20.0, 20.0, 180.0, 180.0);
+        let mut hatch = PathBuilder::new();
+        hatch.move_to(0.0, 0.0);
+        hatch.line_to(30.0, 30.0);
+        let hatch = hatch.finish().unwrap();
+
+        let mut stream_builder = surface.stream_builder();
+        let mut pattern_surface = stream_builder.surface();
+        pattern_surface.stroke_path(
+            &hatch,
+            Stroke {
+                width: 2.0,
+                ..Stroke::default()
+            },
+        );
+        pattern_surface.finish();
+        let pattern_stream = stream_builder.finish();
+
+        // The cells are larger than the step, so neighboring hatch lines overlap.
+        let pattern = Pattern::new_tiling(
+            pattern_stream,
+            30.0,
+            30.0,
+            10.0,
+            20.0,
+            Transform::identity(),
+        )
+        .with_phase(Point::from_xy(5.0, 0.0));
+
+        surface.fill_path(
+            &path,
+            Fill {
+                paint: pattern.into(),
+                opacity: NormalizedF32::ONE,
+                rule: Default::default(),
+            },
+        )
+    }
+
+    #[test]
+    fn tiling_pattern_custom_step() {
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        let pattern_stream = basic_pattern_stream(surface.stream_builder());
+        let pattern = Pattern::new_tiling(
+            pattern_stream,
+            20.0,
+            20.0,
+            15.0,
+            25.0,
+            Transform::identity(),
+        )
+        .with_phase(Point::from_xy(3.0, 4.0));
+        surface.fill_path(
+            &rect_to_path(20.0, 20.0, 180.0, 180.0),
+            Fill {
+                paint: pattern.into(),
+                opacity: NormalizedF32::ONE,
+                rule: Default::default(),
+            },
+        );
+        surface.finish();
+        page.finish();
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.contains("/BBox [0 0 20 20]"));
+        assert!(pdf.contains("/XStep 15"));
+        assert!(pdf.contains("/YStep 25"));
+        // The phase is applied before the page transform, which flips the y axis.
+        assert!(pdf.contains("/Matrix [1 0 0 -1 3 196]"));
+    }
 }
@@ -19,6 +19,10 @@ pub(crate) struct XObject {
     isolated: bool,
     transparency_group_color_space: bool,
     custom_bbox: Option<RectWrapper>,
+    /// Whether a transparency group should be written even if it's neither isolated
+    /// nor has a color space.
+    transparency_group: bool,
+    knockout: bool,
 }
 
 impl XObject {
@@ -33,9 +37,19 @@ impl XObject {
             isolated,
             transparency_group_color_space,
             custom_bbox: custom_bbox.map(RectWrapper),
+            transparency_group: false,
+            knockout: false,
         }
     }
 
+    /// Turn the XObject into a transparency group, regardless of whether it's isolated,
+    /// and set whether it is a knockout group.
+    pub(crate) fn transparency_group(mut self, knockout: bool) -> Self {
+        self.transparency_group = true;
+        self.knockout = knockout;
+        self
+    }
+
     pub(crate) fn is_empty(&self) -> bool {
         self.stream.is_empty()
     }
@@ -75,7 +89,7 @@ impl Cacheable for XObject {
                 .to_pdf_rect(),
         );
 
-        if self.isolated || self.transparency_group_color_space {
+        if self.transparency_group || self.isolated || self.transparency_group_color_space {
             sc.register_validation_error(ValidationError::Transparency);
 
             let mut group = x_object.group();
@@ -85,6 +99,10 @@ impl Cacheable for XObject {
                 transparency.isolated(self.isolated);
             }
 
+            if self.knockout {
+                transparency.knockout(self.knockout);
+            }
+
             if self.transparency_group_color_space {
                 let cs = rgb::Color::rgb_color_space(sc.serialize_settings().no_device_cs);
                 let pdf_cs = transparency.insert(Name(b"CS"));
@@ -0,0 +1,297 @@
+//! Writing the final PDF to a byte sink.
+//!
+//! By default, the whole file is assembled in memory with pdf-writer, which also takes
+//! care of the cross-reference table and the trailer. Alternatively, the chunks can be
+//! renumbered and written to the sink one by one. In that case, only the byte offset
+//! of each object is kept around, so that the cross-reference table can be written in
+//! the end.
+
+use std::fmt::Write as _;
+use std::hash::Hasher;
+use std::io::Write;
+
+use base64::Engine;
+use pdf_writer::writers::{Catalog, DocumentInfo};
+use pdf_writer::{Chunk, Limits, Pdf, Ref};
+use siphasher::sip128::{Hasher128, SipHasher13};
+
+use crate::error::{KrillaError, KrillaResult};
+use crate::rewrite::object_offsets;
+use crate::version::PdfVersion;
+
+/// A PDF that is written to a sink as it is being assembled.
+pub(crate) struct PdfOutput<W: Write> {
+    target: Target<W>,
+    limits: Limits,
+    /// Hashes all bytes written so far, which the instance ID is derived from.
+    hasher: SipHasher13,
+}
+
+enum Target<W: Write> {
+    /// The file is assembled in memory and only written to the sink in the end.
+    Buffered { pdf: Pdf, sink: W },
+    /// Each chunk is written to the sink right away.
+    Streamed(StreamedPdf<W>),
+}
+
+struct StreamedPdf<W: Write> {
+    sink: W,
+    /// The number of bytes written so far.
+    len: usize,
+    /// The number and byte offset of each indirect object.
+    offsets: Vec<(u32, usize)>,
+    catalog_ref: Option<Ref>,
+    info_ref: Option<Ref>,
+    file_id: Option<(String, String)>,
+}
+
+impl<W: Write> PdfOutput<W> {
+    /// Create a new output and write the file header to it.
+    ///
+    /// If `streamed` is true, objects are written to the sink as soon as they are
+    /// added instead of when the output is finished.
+    pub(crate) fn new(
+        sink: W,
+        pdf_version: PdfVersion,
+        ascii_header: bool,
+        streamed: bool,
+    ) -> KrillaResult<Self> {
+        // Let pdf-writer produce the header in both cases, so that it's exactly the same.
+        let mut pdf = Pdf::new();
+        pdf_version.set_version(&mut pdf);
+
+        if ascii_header {
+            pdf.set_binary_marker(b"AAAA");
+        }
+
+        let mut hasher = SipHasher13::new();
+        hasher.write(pdf.as_bytes());
+
+        let target = if streamed {
+            let mut streamed = StreamedPdf {
+                sink,
+                len: 0,
+                offsets: vec![],
+                catalog_ref: None,
+                info_ref: None,
+                file_id: None,
+            };
+            streamed.write_bytes(pdf.as_bytes())?;
+
+            Target::Streamed(streamed)
+        } else {
+            Target::Buffered { pdf, sink }
+        };
+
+        Ok(Self {
+            target,
+            limits: Limits::new(),
+            hasher,
+        })
+    }
+
+    /// Renumber the objects of a chunk with the given mapping and write them.
+    pub(crate) fn write_chunk(
+        &mut self,
+        chunk: &Chunk,
+        mapping: impl FnMut(Ref) -> Ref,
+    ) -> KrillaResult<()> {
+        self.limits.merge(chunk.limits());
+
+        match &mut self.target {
+            Target::Buffered { pdf, .. } => {
+                let base = pdf.len();
+                chunk.renumber_into(pdf, mapping);
+                self.hasher.write(&pdf.as_bytes()[base..]);
+
+                Ok(())
+            }
+            Target::Streamed(streamed) => {
+                let mut renumbered = Chunk::with_capacity(chunk.len());
+                chunk.renumber_into(&mut renumbered, mapping);
+                self.hasher.write(renumbered.as_bytes());
+
+                streamed.write_chunk(&renumbered)
+            }
+        }
+    }
+
+    /// Write a chunk whose objects already have their final numbers.
+    pub(crate) fn write_renumbered(&mut self, chunk: &Chunk) -> KrillaResult<()> {
+        self.limits.merge(chunk.limits());
+        self.hasher.write(chunk.as_bytes());
+
+        match &mut self.target {
+            Target::Buffered { pdf, .. } => {
+                pdf.extend(chunk);
+
+                Ok(())
+            }
+            Target::Streamed(streamed) => streamed.write_chunk(chunk),
+        }
+    }
+
+    /// Write the document catalog and register it with the trailer.
+    pub(crate) fn write_catalog(
+        &mut self,
+        ref_: Ref,
+        write: impl FnOnce(Catalog<'_>) -> KrillaResult<()>,
+    ) -> KrillaResult<()> {
+        match &mut self.target {
+            Target::Buffered { pdf, .. } => {
+                let base = pdf.len();
+                write(pdf.catalog(ref_))?;
+                self.limits.merge(pdf.limits());
+                self.hasher.write(&pdf.as_bytes()[base..]);
+
+                Ok(())
+            }
+            Target::Streamed(streamed) => {
+                streamed.catalog_ref = Some(ref_);
+
+                let mut chunk = Chunk::new();
+                write(chunk.indirect(ref_).start())?;
+                self.write_renumbered(&chunk)
+            }
+        }
+    }
+
+    /// Write the document information dictionary and register it with the trailer.
+    pub(crate) fn write_document_info(
+        &mut self,
+        ref_: Ref,
+        write: impl FnOnce(DocumentInfo<'_>),
+    ) -> KrillaResult<()> {
+        match &mut self.target {
+            Target::Buffered { pdf, .. } => {
+                let base = pdf.len();
+                write(pdf.document_info(ref_));
+                self.limits.merge(pdf.limits());
+                self.hasher.write(&pdf.as_bytes()[base..]);
+
+                Ok(())
+            }
+            Target::Streamed(streamed) => {
+                streamed.info_ref = Some(ref_);
+
+                let mut chunk = Chunk::new();
+                write(chunk.indirect(ref_).start());
+                self.write_renumbered(&chunk)
+            }
+        }
+    }
+
+    /// Set the file identifier. Both parts need to be base64-encoded.
+    pub(crate) fn set_file_id(&mut self, id: (String, String)) {
+        match &mut self.target {
+            Target::Buffered { pdf, .. } => {
+                pdf.set_file_id((id.0.into_bytes(), id.1.into_bytes()));
+            }
+            Target::Streamed(streamed) => streamed.file_id = Some(id),
+        }
+    }
+
+    /// A hash of everything that has been written so far.
+    pub(crate) fn instance_id(&self) -> String {
+        let hash = self.hasher.clone().finish128().as_u128();
+        base64::engine::general_purpose::STANDARD.encode(hash.to_be_bytes())
+    }
+
+    pub(crate) fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Write the cross-reference table and the trailer and return the sink.
+    pub(crate) fn finish(self) -> KrillaResult<W> {
+        match self.target {
+            Target::Buffered { pdf, mut sink } => {
+                sink.write_all(&pdf.finish()).map_err(io_error)?;
+                sink.flush().map_err(io_error)?;
+
+                Ok(sink)
+            }
+            Target::Streamed(streamed) => streamed.finish(),
+        }
+    }
+}
+
+impl<W: Write> StreamedPdf<W> {
+    fn write_chunk(&mut self, chunk: &Chunk) -> KrillaResult<()> {
+        let base = self.len;
+
+        // The offsets of the objects in a chunk are not exposed by pdf-writer. If there is
+        // only one, we know where it starts, otherwise we need to look for them ourselves.
+        let mut refs = chunk.refs();
+        match (refs.next(), refs.next()) {
+            (Some(ref_), None) => self.offsets.push((ref_.get() as u32, base)),
+            (None, _) => {}
+            _ => self.offsets.extend(
+                object_offsets(chunk.as_bytes())?
+                    .into_iter()
+                    .map(|(num, offset)| (num, base + offset)),
+            ),
+        }
+
+        self.write_bytes(chunk.as_bytes())
+    }
+
+    fn finish(mut self) -> KrillaResult<W> {
+        self.offsets.sort();
+
+        let size = self.offsets.last().map_or(0, |(num, _)| *num) + 1;
+        let xref_offset = self.len;
+
+        let mut entries = vec![None; size as usize];
+        for (num, offset) in &self.offsets {
+            debug_assert!(entries[*num as usize].is_none());
+            entries[*num as usize] = Some(*offset);
+        }
+
+        // Link each free entry to the next one, the last one links back to 0.
+        let free = (0..size).filter(|num| entries[*num as usize].is_none());
+        let mut next_free = free.skip(1);
+
+        let mut xref = format!("xref\n0 {size}\n");
+        for (num, entry) in entries.iter().enumerate() {
+            match entry {
+                Some(offset) => write!(xref, "{offset:010} 00000 n\r\n").unwrap(),
+                None => {
+                    let next = next_free.next().unwrap_or(0);
+                    let gen = if num == 0 { 65535 } else { 0 };
+                    write!(xref, "{next:010} {gen:05} f\r\n").unwrap();
+                }
+            }
+        }
+
+        // Mirrors the trailer written by pdf-writer.
+        writeln!(xref, "trailer\n<<\n  /Size {size}").unwrap();
+
+        if let Some(catalog_ref) = self.catalog_ref {
+            writeln!(xref, "  /Root {} 0 R", catalog_ref.get()).unwrap();
+        }
+
+        if let Some(info_ref) = self.info_ref {
+            writeln!(xref, "  /Info {} 0 R", info_ref.get()).unwrap();
+        }
+
+        if let Some((document_id, instance_id)) = &self.file_id {
+            writeln!(xref, "  /ID [({document_id}) ({instance_id})]").unwrap();
+        }
+
+        write!(xref, ">>\nstartxref\n{xref_offset}\n%%EOF").unwrap();
+
+        self.write_bytes(xref.as_bytes())?;
+        self.sink.flush().map_err(io_error)?;
+
+        Ok(self.sink)
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> KrillaResult<()> {
+        self.len += bytes.len();
+        self.sink.write_all(bytes).map_err(io_error)
+    }
+}
+
+fn io_error(error: std::io::Error) -> KrillaError {
+    KrillaError::IoError(error.to_string())
+}
@@ -3,9 +3,9 @@
 use std::hash::Hash;
 use std::sync::Arc;
 
-use tiny_skia_path::{NormalizedF32, Transform};
+use tiny_skia_path::{NormalizedF32, Point, Transform};
 
-use crate::color::{cmyk, luma, rgb, Color};
+use crate::color::{cmyk, lab, luma, rgb, separation, Color};
 use crate::stream::Stream;
 use crate::util::HashExt;
 
@@ -13,6 +13,7 @@ use crate::util::HashExt;
 pub(crate) enum InnerStops {
     RgbStops(Vec<Stop<rgb::Color>>),
     CmykStops(Vec<Stop<cmyk::Color>>),
+    LabStops(Vec<Stop<lab::Color>>),
 }
 
 impl InnerStops {
@@ -22,8 +23,41 @@ impl InnerStops {
         match self {
             InnerStops::RgbStops(r) => Box::new(r.into_iter().map(|c| c.into())),
             InnerStops::CmykStops(c) => Box::new(c.into_iter().map(|c| c.into())),
+            InnerStops::LabStops(l) => Box::new(l.into_iter().map(|l| l.into())),
         }
     }
+
+    /// Convert the stops into the color space they should be interpolated in.
+    pub(crate) fn interpolated_in(self, interpolation: InterpolationColorSpace) -> Self {
+        match (self, interpolation) {
+            (InnerStops::RgbStops(r), InterpolationColorSpace::Lab) => InnerStops::LabStops(
+                r.into_iter()
+                    .map(|s| Stop {
+                        offset: s.offset,
+                        color: s.color.to_lab(),
+                        opacity: s.opacity,
+                    })
+                    .collect(),
+            ),
+            (stops, _) => stops,
+        }
+    }
+}
+
+/// The color space in which the colors of a gradient are interpolated.
+#[derive(Debug, Default, Hash, Eq, PartialEq, Clone, Copy)]
+pub enum InterpolationColorSpace {
+    /// Interpolate in the color space of the stops, i.e. RGB for RGB stops,
+    /// CMYK for CMYK stops and Lab for Lab stops.
+    #[default]
+    Native,
+    /// Interpolate in the CIE L*a*b* color space, which usually results in cleaner
+    /// transitions between very different colors, for example from red to green.
+    ///
+    /// RGB stops are assumed to be sRGB colors and converted to Lab colors with the
+    /// D65 white point. This has no effect on CMYK stops, since they can't be converted
+    /// without a color profile, and Lab stops are always interpolated in Lab anyway.
+    Lab,
 }
 
 /// The color stops of a gradient.
@@ -42,6 +76,12 @@ impl From<Vec<Stop<cmyk::Color>>> for Stops {
     }
 }
 
+impl From<Vec<Stop<lab::Color>>> for Stops {
+    fn from(value: Vec<Stop<lab::Color>>) -> Self {
+        Stops(InnerStops::LabStops(value))
+    }
+}
+
 /// A linear gradient.
 #[derive(Debug, Clone, PartialEq)]
 pub struct LinearGradient {
@@ -61,6 +101,8 @@ pub struct LinearGradient {
     pub stops: Stops,
     /// Whether the gradient should be anti-aliased.
     pub anti_alias: bool,
+    /// The color space in which the stops are interpolated.
+    pub interpolation: InterpolationColorSpace,
 }
 
 impl Eq for LinearGradient {}
@@ -75,6 +117,7 @@ impl Hash for LinearGradient {
         self.spread_method.hash(state);
         self.stops.hash(state);
         self.anti_alias.hash(state);
+        self.interpolation.hash(state);
     }
 }
 
@@ -104,6 +147,8 @@ pub struct RadialGradient {
     pub stops: Stops,
     /// Whether the gradient should be anti-aliased.
     pub anti_alias: bool,
+    /// The color space in which the stops are interpolated.
+    pub interpolation: InterpolationColorSpace,
 }
 
 impl Eq for RadialGradient {}
@@ -120,22 +165,31 @@ impl Hash for RadialGradient {
         self.spread_method.hash(state);
         self.stops.hash(state);
         self.anti_alias.hash(state);
+        self.interpolation.hash(state);
     }
 }
 
 /// A sweep gradient.
 ///
-/// Angles start from the right and go counter-clockwise with increasing values.
+/// Angles are given in degrees. They start from the right and go counter-clockwise
+/// with increasing values.
+///
+/// The gradient is first rotated around its center by `rotation`, and the result is then
+/// transformed by `transform`. Rotating the gradient is therefore equivalent to pre-concatenating
+/// a rotation around the center to the transform.
 #[derive(Debug, Clone, PartialEq)]
 pub struct SweepGradient {
     /// The x coordinate of the center.
     pub cx: f32,
     /// The y coordinate of the center.
     pub cy: f32,
-    /// The start angle.
+    /// The start angle, in degrees.
     pub start_angle: f32,
-    /// The end angle.
+    /// The end angle, in degrees.
     pub end_angle: f32,
+    /// A rotation of the gradient around its center, in degrees. Positive values
+    /// rotate in the same direction as increasing angles.
+    pub rotation: f32,
     /// A transform that should be applied to the sweep gradient.
     pub transform: Transform,
     /// The spread method of the sweep gradient.
@@ -144,6 +198,40 @@ pub struct SweepGradient {
     pub stops: Stops,
     /// Whether the gradient should be anti-aliased.
     pub anti_alias: bool,
+    /// The color space in which the stops are interpolated.
+    pub interpolation: InterpolationColorSpace,
+}
+
+impl SweepGradient {
+    /// Create a new sweep gradient that isn't rotated or transformed, uses the
+    /// `Pad` spread method and isn't anti-aliased.
+    ///
+    /// Returns `None` if the start and end angle are the same, since the gradient
+    /// would be undefined in that case.
+    pub fn new(
+        cx: f32,
+        cy: f32,
+        start_angle: f32,
+        end_angle: f32,
+        stops: impl Into<Stops>,
+    ) -> Option<Self> {
+        if start_angle == end_angle {
+            return None;
+        }
+
+        Some(Self {
+            cx,
+            cy,
+            start_angle,
+            end_angle,
+            rotation: 0.0,
+            transform: Transform::identity(),
+            spread_method: SpreadMethod::Pad,
+            stops: stops.into(),
+            anti_alias: false,
+            interpolation: InterpolationColorSpace::Native,
+        })
+    }
 }
 
 impl Eq for SweepGradient {}
@@ -154,10 +242,117 @@ impl Hash for SweepGradient {
         self.cy.to_bits().hash(state);
         self.start_angle.to_bits().hash(state);
         self.end_angle.to_bits().hash(state);
+        self.rotation.to_bits().hash(state);
         self.transform.hash(state);
         self.spread_method.hash(state);
         self.stops.hash(state);
         self.anti_alias.hash(state);
+        self.interpolation.hash(state);
+    }
+}
+
+/// A vertex of a triangle in a mesh gradient.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[allow(private_bounds)]
+pub struct MeshVertex<C>
+where
+    C: Into<Color>,
+{
+    /// The position of the vertex.
+    pub point: Point,
+    /// The color of the vertex.
+    pub color: C,
+    /// The opacity of the vertex.
+    pub opacity: NormalizedF32,
+}
+
+impl<C> From<MeshVertex<C>> for crate::object::shading_function::MeshVertex
+where
+    C: Into<Color>,
+{
+    fn from(val: MeshVertex<C>) -> Self {
+        crate::object::shading_function::MeshVertex {
+            point: val.point,
+            color: val.color.into(),
+            opacity: val.opacity,
+        }
+    }
+}
+
+/// The triangles of a mesh gradient.
+///
+/// You cannot construct this type directly, but instead can convert
+/// into it by calling `into` on a vector of triangles with RGB, CMYK
+/// or Lab vertices.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MeshTriangles(pub(crate) Vec<[crate::object::shading_function::MeshVertex; 3]>);
+
+impl MeshTriangles {
+    fn from_triangles<C>(triangles: Vec<[MeshVertex<C>; 3]>) -> Self
+    where
+        C: Into<Color>,
+    {
+        MeshTriangles(
+            triangles
+                .into_iter()
+                .map(|triangle| triangle.map(|vertex| vertex.into()))
+                .collect(),
+        )
+    }
+}
+
+impl From<Vec<[MeshVertex<rgb::Color>; 3]>> for MeshTriangles {
+    fn from(value: Vec<[MeshVertex<rgb::Color>; 3]>) -> Self {
+        MeshTriangles::from_triangles(value)
+    }
+}
+
+impl From<Vec<[MeshVertex<cmyk::Color>; 3]>> for MeshTriangles {
+    fn from(value: Vec<[MeshVertex<cmyk::Color>; 3]>) -> Self {
+        MeshTriangles::from_triangles(value)
+    }
+}
+
+impl From<Vec<[MeshVertex<lab::Color>; 3]>> for MeshTriangles {
+    fn from(value: Vec<[MeshVertex<lab::Color>; 3]>) -> Self {
+        MeshTriangles::from_triangles(value)
+    }
+}
+
+/// A mesh gradient.
+///
+/// The gradient consists of triangles, where each vertex has its own color. The colors
+/// are interpolated smoothly across each triangle (also known as Gouraud shading).
+/// Only the area covered by the triangles is painted, and where triangles overlap,
+/// later ones are painted on top of earlier ones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshGradient {
+    /// The triangles of the mesh gradient.
+    pub triangles: MeshTriangles,
+    /// A transform that should be applied to the mesh gradient.
+    pub transform: Transform,
+    /// Whether the gradient should be anti-aliased.
+    pub anti_alias: bool,
+}
+
+impl MeshGradient {
+    /// Create a new mesh gradient that isn't transformed and isn't anti-aliased.
+    pub fn new(triangles: impl Into<MeshTriangles>) -> Self {
+        Self {
+            triangles: triangles.into(),
+            transform: Transform::identity(),
+            anti_alias: false,
+        }
+    }
+}
+
+impl Eq for MeshGradient {}
+
+impl Hash for MeshGradient {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.triangles.hash(state);
+        self.transform.hash(state);
+        self.anti_alias.hash(state);
     }
 }
 
@@ -172,6 +367,50 @@ pub struct Pattern {
     pub width: f32,
     /// The height of the pattern.
     pub height: f32,
+    /// The horizontal distance between the origins of two neighboring pattern cells.
+    pub x_step: f32,
+    /// The vertical distance between the origins of two neighboring pattern cells.
+    pub y_step: f32,
+    /// An offset in pattern space by which all pattern cells are shifted.
+    pub phase: Point,
+}
+
+impl Pattern {
+    /// Create a new pattern whose cells are placed right next to each other.
+    pub fn new(stream: Stream, transform: Transform, width: f32, height: f32) -> Self {
+        Self::new_tiling(stream, width, height, width, height, transform)
+    }
+
+    /// Create a new pattern with a custom spacing between the pattern cells.
+    ///
+    /// Each cell has the given width and height, while `x_step` and `y_step` determine
+    /// the distance between neighboring cells. They can be smaller than the size of
+    /// a cell, in which case cells will overlap, or larger, in which case there will
+    /// be a gap between them. Both steps must not be zero.
+    pub fn new_tiling(
+        stream: Stream,
+        width: f32,
+        height: f32,
+        x_step: f32,
+        y_step: f32,
+        transform: Transform,
+    ) -> Self {
+        Self {
+            stream,
+            transform,
+            width,
+            height,
+            x_step,
+            y_step,
+            phase: Point::zero(),
+        }
+    }
+
+    /// Shift all pattern cells by the given offset in pattern space.
+    pub fn with_phase(mut self, phase: Point) -> Self {
+        self.phase = phase;
+        self
+    }
 }
 
 impl Eq for Pattern {}
@@ -182,15 +421,21 @@ impl Hash for Pattern {
         self.transform.hash(state);
         self.width.to_bits().hash(state);
         self.height.to_bits().hash(state);
+        self.x_step.to_bits().hash(state);
+        self.y_step.to_bits().hash(state);
+        self.phase.x.to_bits().hash(state);
+        self.phase.y.to_bits().hash(state);
     }
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub(crate) enum InnerPaint {
     Color(Color),
+    Separation(separation::Color),
     LinearGradient(LinearGradient),
     RadialGradient(RadialGradient),
     SweepGradient(SweepGradient),
+    MeshGradient(MeshGradient),
     Pattern(Arc<Pattern>),
 }
 
@@ -208,7 +453,7 @@ impl Paint {
             InnerPaint::Color(c) => match c {
                 Color::Rgb(rgb) => Some(rgb),
                 Color::Luma(l) => Some(rgb::Color::new(l.0, l.0, l.0)),
-                Color::Cmyk(_) => None,
+                Color::Cmyk(_) | Color::Lab(_) => None,
             },
             _ => None,
         }
@@ -233,6 +478,18 @@ impl From<cmyk::Color> for Paint {
     }
 }
 
+impl From<lab::Color> for Paint {
+    fn from(value: lab::Color) -> Self {
+        Paint(InnerPaint::Color(value.into()))
+    }
+}
+
+impl From<separation::Color> for Paint {
+    fn from(value: separation::Color) -> Self {
+        Paint(InnerPaint::Separation(value))
+    }
+}
+
 impl From<LinearGradient> for Paint {
     fn from(value: LinearGradient) -> Self {
         Paint(InnerPaint::LinearGradient(value))
@@ -251,6 +508,12 @@ impl From<SweepGradient> for Paint {
     }
 }
 
+impl From<MeshGradient> for Paint {
+    fn from(value: MeshGradient) -> Self {
+        Paint(InnerPaint::MeshGradient(value))
+    }
+}
+
 impl From<Pattern> for Paint {
     fn from(value: Pattern) -> Self {
         Paint(InnerPaint::Pattern(Arc::new(value)))
@@ -54,14 +54,32 @@ impl From<LineJoin> for tiny_skia_path::LineJoin {
 }
 
 /// A stroke dash.
+///
+/// The dash array alternately specifies the lengths of the dashes and the gaps between
+/// them, starting with a dash. If it has an odd number of entries, it is repeated once
+/// to get an even number of entries. Line caps are applied to both ends of each dash.
+///
+/// If the dash array is empty, contains negative or non-finite values or only consists
+/// of zeros, it doesn't describe a valid pattern and the stroke is drawn as a solid line.
 #[derive(Debug, Clone, PartialEq)]
 pub struct StrokeDash {
-    /// The dash array.
+    /// The lengths of the dashes and gaps.
     pub array: Vec<f32>,
-    /// The offset of the dash.
+    /// The dash phase, i.e. the distance into the dash pattern at which
+    /// the pattern starts. It's written as the phase of the `d` operator.
     pub offset: f32,
 }
 
+impl StrokeDash {
+    /// Whether the dash array describes a valid dash pattern.
+    pub(crate) fn is_valid(&self) -> bool {
+        self.offset.is_finite()
+            && !self.array.is_empty()
+            && self.array.iter().all(|d| d.is_finite() && *d >= 0.0)
+            && self.array.iter().any(|d| *d > 0.0)
+    }
+}
+
 impl Eq for StrokeDash {}
 
 impl Hash for StrokeDash {
@@ -81,7 +99,12 @@ pub struct Stroke {
     pub paint: Paint,
     /// The width of the stroke.
     pub width: f32,
-    /// The miter limit of the stroke.
+    /// The miter limit of the stroke, which defaults to 10.0.
+    ///
+    /// If the ratio of the miter length to the stroke width exceeds this limit at a
+    /// miter join, a bevel join is used instead. Lower values avoid long spikes at
+    /// acute angles. Values smaller than 1.0 aren't allowed by the PDF specification
+    /// and will be treated as 1.0.
     pub miter_limit: f32,
     /// The line cap of the stroke.
     pub line_cap: LineCap,
@@ -122,10 +145,15 @@ impl Default for Stroke {
 }
 
 impl Stroke {
+    pub(crate) fn valid_miter_limit(&self) -> f32 {
+        // `max` also maps NaN to 1.0.
+        self.miter_limit.max(1.0)
+    }
+
     pub(crate) fn into_tiny_skia(self) -> tiny_skia_path::Stroke {
         let mut stroke = tiny_skia_path::Stroke {
             width: self.width,
-            miter_limit: self.miter_limit,
+            miter_limit: self.valid_miter_limit(),
             line_cap: match self.line_cap {
                 LineCap::Butt => tiny_skia_path::LineCap::Butt,
                 LineCap::Round => tiny_skia_path::LineCap::Round,
@@ -139,8 +167,15 @@ impl Stroke {
             dash: None,
         };
 
-        if let Some(stroke_dash) = self.dash {
-            stroke.dash = tiny_skia_path::StrokeDash::new(stroke_dash.array, stroke_dash.offset);
+        if let Some(stroke_dash) = self.dash.filter(StrokeDash::is_valid) {
+            let mut array = stroke_dash.array;
+
+            // Repeat odd dash arrays, just like PDF viewers do.
+            if array.len() % 2 != 0 {
+                array.extend_from_within(..);
+            }
+
+            stroke.dash = tiny_skia_path::StrokeDash::new(array, stroke_dash.offset);
         }
 
         stroke
@@ -7,7 +7,7 @@ use std::marker::PhantomData;
 
 use pdf_writer::types::ProcSet;
 use pdf_writer::writers;
-use pdf_writer::{Dict, Finish, Ref};
+use pdf_writer::{Dict, Finish, Name, Ref};
 
 use crate::util::NameExt;
 
@@ -169,6 +169,31 @@ impl Resource for Font {
     }
 }
 
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub(crate) struct Properties(Ref);
+
+impl Resource for Properties {
+    fn new(ref_: Ref) -> Self {
+        Self(ref_)
+    }
+
+    fn get_ref(&self) -> Ref {
+        self.0
+    }
+
+    fn get_dict<'a>(resources: &'a mut writers::Resources) -> Dict<'a> {
+        resources.insert(Name(b"Properties")).dict()
+    }
+
+    fn get_prefix() -> &'static str {
+        "pr"
+    }
+
+    fn get_mapper(b: &mut ResourceDictionaryBuilder) -> &mut ResourceMapper<Properties> {
+        &mut b.properties
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct ResourceDictionaryBuilder {
     pub(crate) color_spaces: ResourceMapper<ColorSpace>,
@@ -177,6 +202,7 @@ pub(crate) struct ResourceDictionaryBuilder {
     pub(crate) x_objects: ResourceMapper<XObject>,
     pub(crate) shadings: ResourceMapper<Shading>,
     pub(crate) fonts: ResourceMapper<Font>,
+    pub(crate) properties: ResourceMapper<Properties>,
 }
 
 impl ResourceDictionaryBuilder {
@@ -188,6 +214,7 @@ impl ResourceDictionaryBuilder {
             x_objects: ResourceMapper::new(),
             shadings: ResourceMapper::new(),
             fonts: ResourceMapper::new(),
+            properties: ResourceMapper::new(),
         }
     }
 
@@ -206,6 +233,7 @@ impl ResourceDictionaryBuilder {
             x_objects: self.x_objects.into_resource_list(),
             shadings: self.shadings.into_resource_list(),
             fonts: self.fonts.into_resource_list(),
+            properties: self.properties.into_resource_list(),
         }
     }
 }
@@ -218,6 +246,7 @@ pub(crate) struct ResourceDictionary {
     pub(crate) x_objects: ResourceList<XObject>,
     pub(crate) shadings: ResourceList<Shading>,
     pub(crate) fonts: ResourceList<Font>,
+    pub(crate) properties: ResourceList<Properties>,
 }
 
 impl Default for ResourceDictionary {
@@ -229,6 +258,7 @@ impl Default for ResourceDictionary {
             x_objects: ResourceList::empty(),
             shadings: ResourceList::empty(),
             fonts: ResourceList::empty(),
+            properties: ResourceList::empty(),
         }
     }
 }
@@ -253,6 +283,7 @@ impl ResourceDictionary {
         write_resource_type::<XObject>(resources, &self.x_objects);
         write_resource_type::<Shading>(resources, &self.shadings);
         write_resource_type::<Font>(resources, &self.fonts);
+        write_resource_type::<Properties>(resources, &self.properties);
     }
 }
 
@@ -274,6 +305,8 @@ where
 #[derive(Debug, Eq, PartialEq, Hash, Clone, Default)]
 pub(crate) struct ResourceList<V> {
     entries: Vec<Ref>,
+    /// How many times each entry is referenced in the corresponding content stream.
+    counts: Vec<usize>,
     phantom: PhantomData<V>,
 }
 
@@ -284,6 +317,7 @@ where
     pub(crate) fn empty() -> ResourceList<T> {
         Self {
             entries: vec![],
+            counts: vec![],
             phantom: Default::default(),
         }
     }
@@ -296,18 +330,36 @@ where
         format!("{}{}", T::get_prefix(), num)
     }
 
+    /// Add a new entry to an already finished resource list and return its name.
+    #[cfg(feature = "simple-text")]
+    pub(crate) fn push(&mut self, ref_: Ref) -> String {
+        let name = Self::name_from_number(self.len());
+        self.entries.push(ref_);
+        self.counts.push(1);
+        name
+    }
+
     pub(crate) fn get_entries(&self) -> impl Iterator<Item = (String, Ref)> + '_ {
         self.entries
             .iter()
             .enumerate()
             .map(|(i, r)| (Self::name_from_number(i as ResourceNumber), *r))
     }
+
+    /// Return the name of each entry together with the number of times it is used.
+    pub(crate) fn get_usages(&self) -> impl Iterator<Item = (String, usize)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (Self::name_from_number(i as ResourceNumber), *c))
+    }
 }
 
 #[derive(Debug, Eq, PartialEq)]
 pub(crate) struct ResourceMapper<T: ?Sized> {
     forward: Vec<Ref>,
     backward: HashMap<Ref, ResourceNumber>,
+    counts: Vec<usize>,
     phantom: PhantomData<T>,
 }
 
@@ -319,6 +371,7 @@ where
         Self {
             forward: Vec::new(),
             backward: HashMap::new(),
+            counts: Vec::new(),
             phantom: PhantomData,
         }
     }
@@ -326,12 +379,17 @@ where
     pub(crate) fn remap(&mut self, ref_: Ref) -> ResourceNumber {
         let forward = &mut self.forward;
         let backward = &mut self.backward;
+        let counts = &mut self.counts;
 
-        *backward.entry(ref_).or_insert_with(|| {
+        let num = *backward.entry(ref_).or_insert_with(|| {
             let old = forward.len();
             forward.push(ref_);
+            counts.push(0);
             old as ResourceNumber
-        })
+        });
+
+        counts[num as usize] += 1;
+        num
     }
 
     pub(crate) fn remap_with_name(&mut self, ref_: Ref) -> String {
@@ -345,6 +403,7 @@ where
     pub(crate) fn into_resource_list(self) -> ResourceList<T> {
         ResourceList {
             entries: self.forward,
+            counts: self.counts,
             phantom: Default::default(),
         }
     }
@@ -0,0 +1,722 @@
+//! Linearizing documents, also known as "fast web view".
+//!
+//! A linearized PDF is organized in a way that allows a viewer to display the first
+//! page before the whole file has been downloaded. The layout is described in Annex F
+//! of the PDF specification and looks as follows:
+//!
+//! 1. The header.
+//! 2. The linearization parameter dictionary.
+//! 3. The cross-reference table and trailer of the first page.
+//! 4. The catalog and the encryption dictionary.
+//! 5. The primary hint stream.
+//! 6. The first page and all objects it uses.
+//! 7. The remaining pages, each followed by the objects only used by that page.
+//! 8. Objects that are shared between multiple pages.
+//! 9. All other objects.
+//! 10. The main cross-reference table and trailer.
+//!
+//! The objects in parts 2-6 are numbered after all other objects, so that both
+//! cross-reference tables consist of a single subsection.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use super::{
+    stream_data, write_trailer_entries, Lexer, Object, SecurityHandler, Token, TokenKind, Trailer,
+    Writer,
+};
+use crate::error::{KrillaError, KrillaResult};
+use crate::stream::deflate_encode_with_level;
+
+/// A token, together with the nesting level of the dictionaries and arrays it is part of.
+type LeveledToken<'a> = (usize, Token<'a>);
+
+/// Linearize a PDF that has been split into its objects.
+pub(super) fn linearize(
+    header: &[u8],
+    objects: Vec<Object>,
+    trailer: &Trailer,
+    handler: Option<SecurityHandler>,
+    compression_level: Option<u8>,
+) -> KrillaResult<Vec<u8>> {
+    let objects = objects
+        .into_iter()
+        .map(|object| (object.num, object))
+        .collect::<HashMap<_, _>>();
+    let tokens = objects
+        .values()
+        .map(|object| Ok((object.num, tokenize(&object.body)?)))
+        .collect::<KrillaResult<HashMap<_, _>>>()?;
+
+    let catalog = trailer
+        .root
+        .ok_or_else(|| error("the document has no catalog"))?;
+    let page_tree = tokens
+        .get(&catalog)
+        .and_then(|tokens| dict_entry(tokens, b"/Pages"))
+        .and_then(|value| refs(value).next())
+        .map(|(_, num)| num)
+        .ok_or_else(|| error("the catalog has no page tree"))?;
+
+    let mut pages = vec![];
+    let mut nodes = HashSet::new();
+    collect_pages(page_tree, &tokens, &mut pages, &mut nodes);
+
+    if pages.is_empty() {
+        return Err(error("the document has no pages"));
+    }
+
+    // Find out which objects are used by which pages. Other pages, the page tree and
+    // the catalog are never considered to be used by a page.
+    let mut stop = nodes;
+    stop.extend(pages.iter().copied());
+    stop.insert(catalog);
+
+    let mut users: HashMap<u32, Vec<usize>> = HashMap::new();
+    let mut reached = vec![];
+
+    for (index, page) in pages.iter().enumerate() {
+        let mut seen = HashSet::from([*page]);
+        let mut queue = vec![*page];
+        let mut i = 0;
+
+        while let Some(num) = queue.get(i) {
+            for (_, referenced) in refs(&tokens[num]) {
+                if !stop.contains(&referenced)
+                    && tokens.contains_key(&referenced)
+                    && seen.insert(referenced)
+                {
+                    users.entry(referenced).or_default().push(index);
+                    queue.push(referenced);
+                }
+            }
+
+            i += 1;
+        }
+
+        reached.push(queue);
+    }
+
+    // The first page section contains everything that is used by the first page.
+    let first_page = reached[0].clone();
+    let in_first_page = first_page.iter().copied().collect::<HashSet<_>>();
+    let is_shared = |num: &u32| users.get(num).is_some_and(|u| u.len() > 1);
+
+    let mut other_pages = vec![];
+    let mut shared = vec![];
+    let mut placed = in_first_page.clone();
+    placed.insert(catalog);
+
+    for page in &reached[1..] {
+        let private = page
+            .iter()
+            .copied()
+            .filter(|num| *num == page[0] || !is_shared(num))
+            .collect::<Vec<_>>();
+        placed.extend(private.iter().copied());
+        other_pages.push(private);
+    }
+
+    for num in reached[1..].iter().flatten() {
+        if placed.insert(*num) {
+            shared.push(*num);
+        }
+    }
+
+    let mut other = objects
+        .keys()
+        .copied()
+        .filter(|num| !placed.contains(num))
+        .collect::<Vec<_>>();
+    other.sort();
+
+    // Assign the new object numbers.
+    let mut map = HashMap::new();
+    for num in other_pages.iter().flatten().chain(&shared).chain(&other) {
+        map.insert(*num, map.len() as u32 + 1);
+    }
+
+    let linearization_num = map.len() as u32 + 1;
+    let catalog_num = linearization_num + 1;
+    let encrypt_num = handler.is_some().then_some(catalog_num + 1);
+    let hint_num = encrypt_num.unwrap_or(catalog_num) + 1;
+    map.insert(catalog, catalog_num);
+
+    for (i, num) in first_page.iter().enumerate() {
+        map.insert(*num, hint_num + 1 + i as u32);
+    }
+
+    let size = hint_num + 1 + first_page.len() as u32;
+
+    let mut writer = Writer {
+        handler,
+        compression_level,
+        buf: vec![],
+        entries: vec![],
+        next_num: encrypt_num.unwrap_or(0),
+    };
+
+    let write = |writer: &mut Writer, num: u32| -> KrillaResult<usize> {
+        let object = &objects[&num];
+        let offset = writer.buf.len();
+        writer.write_object(&Object {
+            num: map[&num],
+            gen: 0,
+            body: Cow::Owned(renumber(&object.body, &tokens[&num], &map)),
+            is_stream: object.is_stream,
+        })?;
+
+        Ok(offset)
+    };
+
+    // Parts 4 and 6-9, the hint stream is inserted afterwards.
+    write(&mut writer, catalog)?;
+    writer.write_encrypt_dict();
+    let hint_offset = writer.buf.len();
+
+    let mut first_page_starts = vec![];
+    for num in &first_page {
+        first_page_starts.push(write(&mut writer, *num)?);
+    }
+    let first_page_end = writer.buf.len();
+
+    let mut page_starts = vec![first_page_starts[0]];
+    for page in &other_pages {
+        page_starts.push(writer.buf.len());
+        for num in page {
+            write(&mut writer, *num)?;
+        }
+    }
+    let other_pages_end = writer.buf.len();
+
+    let mut shared_starts = vec![];
+    for num in &shared {
+        shared_starts.push(write(&mut writer, *num)?);
+    }
+    let shared_end = writer.buf.len();
+
+    for num in &other {
+        write(&mut writer, *num)?;
+    }
+    let body_end = writer.buf.len();
+
+    let renumbered_trailer = Trailer {
+        root: Some(catalog_num),
+        info: trailer.info.and_then(|info| map.get(&info).copied()),
+        id: trailer.id,
+        first_id: trailer.first_id.clone(),
+    };
+
+    // Lay out the first part with placeholders for the values we don't know yet.
+    let mut part1 = Part1 {
+        linearization_num,
+        first_page_num: map[&pages[0]],
+        page_count: pages.len() as u32,
+        encrypt_num,
+        entries: vec![0; (size - catalog_num) as usize],
+        size,
+        ..Part1::default()
+    };
+    let part1_len = write_part1(header, &renumbered_trailer, &part1).len();
+
+    // The index of each object in the shared object hint table.
+    let group_indices = first_page
+        .iter()
+        .chain(&shared)
+        .enumerate()
+        .map(|(index, num)| (*num, index as u32))
+        .collect::<HashMap<_, _>>();
+
+    let mut page_entries = vec![];
+    for (index, start) in page_starts.iter().enumerate() {
+        let (nobjects, end, shared_ids) = if index == 0 {
+            (first_page.len(), first_page_end, vec![])
+        } else {
+            let page = &reached[index];
+            let shared_ids = page
+                .iter()
+                .filter(|num| is_shared(num))
+                .map(|num| group_indices[num])
+                .collect::<Vec<_>>();
+            let end = page_starts
+                .get(index + 1)
+                .copied()
+                .unwrap_or(other_pages_end);
+
+            (other_pages[index - 1].len(), end, shared_ids)
+        };
+
+        page_entries.push(PageEntry {
+            nobjects: nobjects as u32,
+            length: (end - start) as u32,
+            shared_ids,
+        });
+    }
+
+    // All objects are laid out contiguously, so each object ends where the next one starts.
+    let lengths = |starts: &[usize], end: usize| {
+        starts
+            .iter()
+            .enumerate()
+            .map(|(index, start)| (starts.get(index + 1).unwrap_or(&end) - start) as u32)
+            .collect::<Vec<_>>()
+    };
+    let mut group_lengths = lengths(&first_page_starts, first_page_end);
+    group_lengths.extend(lengths(&shared_starts, shared_end));
+
+    // All offsets in the hint tables are given as if the hint stream didn't exist.
+    let hints = HintTables {
+        first_page_offset: (part1_len + page_starts[0]) as u32,
+        pages: page_entries,
+        first_shared_num: shared.first().map(|num| map[num]).unwrap_or(0),
+        first_shared_offset: if shared.is_empty() {
+            0
+        } else {
+            (part1_len + other_pages_end) as u32
+        },
+        first_page_groups: first_page.len() as u32,
+        group_lengths,
+    };
+
+    let (hint_data, shared_table_offset) = hints.encode();
+    let (hint_data, filter) = if let Some(level) = compression_level {
+        (
+            deflate_encode_with_level(&hint_data, level),
+            "  /Filter /FlateDecode\n",
+        )
+    } else {
+        (hint_data, "")
+    };
+
+    let mut hint_body = format!(
+        "\n<<\n  /S {shared_table_offset}\n{filter}  /Length {}\n>>\nstream\n",
+        hint_data.len()
+    )
+    .into_bytes();
+    hint_body.extend_from_slice(&hint_data);
+    hint_body.extend_from_slice(b"\nendstream\n");
+
+    writer.write_object(&Object {
+        num: hint_num,
+        gen: 0,
+        body: Cow::Owned(hint_body),
+        is_stream: true,
+    })?;
+
+    let hint_len = writer.buf.len() - body_end;
+    let offsets = writer
+        .entries
+        .iter()
+        .map(|(num, entry)| match entry {
+            super::XRefEntry::Direct(offset) => (*num, *offset),
+            super::XRefEntry::Compressed(..) => unreachable!(),
+        })
+        .collect::<HashMap<_, _>>();
+
+    // Translate an offset in the writer to the final offset.
+    let actual = |offset: usize| {
+        if offset >= body_end {
+            part1_len + hint_offset + (offset - body_end)
+        } else if offset >= hint_offset {
+            part1_len + offset + hint_len
+        } else {
+            part1_len + offset
+        }
+    };
+
+    let main_xref_offset = part1_len + writer.buf.len();
+    let mut main_xref = format!("xref\n0 {linearization_num}");
+    let main_xref_first_entry = main_xref_offset + main_xref.len();
+    main_xref.push_str("\n0000000000 65535 f\r\n");
+
+    for num in 1..linearization_num {
+        writeln!(main_xref, "{:010} 00000 n\r", actual(offsets[&num])).unwrap();
+    }
+
+    // The last `startxref` points to the cross-reference table of the first page.
+    // The main trailer repeats the entries of the first page trailer (except for `Prev`),
+    // since some standards like PDF/A require the file ID to be present in both.
+    main_xref.push_str("trailer\n<<\n");
+    write_trailer_entries(
+        &mut main_xref,
+        &renumbered_trailer,
+        linearization_num,
+        encrypt_num,
+    );
+
+    let mut linearization_dict = vec![];
+    write_linearization_dict(&mut linearization_dict, &part1);
+    write!(
+        main_xref,
+        ">>\nstartxref\n{}\n%%EOF",
+        header.len() + linearization_dict.len()
+    )
+    .unwrap();
+
+    part1.file_len = main_xref_offset + main_xref.len();
+    part1.hint_offset = part1_len + hint_offset;
+    part1.hint_len = hint_len;
+    part1.first_page_end = actual(first_page_end - 1) + 1;
+    part1.main_xref_first_entry = main_xref_first_entry;
+    part1.main_xref_offset = main_xref_offset;
+    part1.entries = (catalog_num..size)
+        .map(|num| actual(offsets[&num]))
+        .collect();
+
+    let mut pdf = write_part1(header, &renumbered_trailer, &part1);
+    debug_assert_eq!(pdf.len(), part1_len);
+
+    pdf.reserve(writer.buf.len() + main_xref.len());
+    pdf.extend_from_slice(&writer.buf[..hint_offset]);
+    pdf.extend_from_slice(&writer.buf[body_end..]);
+    pdf.extend_from_slice(&writer.buf[hint_offset..body_end]);
+    pdf.extend_from_slice(main_xref.as_bytes());
+
+    Ok(pdf)
+}
+
+fn error(message: &str) -> KrillaError {
+    KrillaError::UserError(format!("failed to linearize document: {message}"))
+}
+
+/// Collect all pages of the page tree in order, as well as the intermediate nodes.
+fn collect_pages(
+    node: u32,
+    tokens: &HashMap<u32, Vec<LeveledToken>>,
+    pages: &mut Vec<u32>,
+    nodes: &mut HashSet<u32>,
+) {
+    let Some(node_tokens) = tokens.get(&node) else {
+        return;
+    };
+
+    match dict_entry(node_tokens, b"/Kids") {
+        Some(kids) => {
+            // Guard against cycles in the page tree.
+            if nodes.insert(node) {
+                for (_, kid) in refs(kids) {
+                    collect_pages(kid, tokens, pages, nodes);
+                }
+            }
+        }
+        None => pages.push(node),
+    }
+}
+
+/// The values of the first part of the file that are only known after
+/// everything else has been laid out.
+#[derive(Default)]
+struct Part1 {
+    linearization_num: u32,
+    first_page_num: u32,
+    page_count: u32,
+    encrypt_num: Option<u32>,
+    file_len: usize,
+    hint_offset: usize,
+    hint_len: usize,
+    first_page_end: usize,
+    main_xref_first_entry: usize,
+    main_xref_offset: usize,
+    /// The offsets of all objects after the linearization dictionary.
+    entries: Vec<usize>,
+    size: u32,
+}
+
+fn write_linearization_dict(buf: &mut Vec<u8>, part1: &Part1) {
+    // Values that depend on the final layout are padded to a fixed width, so
+    // that the length of the first part doesn't change once they are known.
+    let dict = format!(
+        "{} 0 obj\n<<\n  /Linearized 1\n  /L {:010}\n  /H [{:010} {:010}]\n  /O {:010}\n  /E {:010}\n  /N {:010}\n  /T {:010}\n>>\nendobj\n\n",
+        part1.linearization_num,
+        part1.file_len,
+        part1.hint_offset,
+        part1.hint_len,
+        part1.first_page_num,
+        part1.first_page_end,
+        part1.page_count,
+        part1.main_xref_first_entry,
+    );
+    buf.extend_from_slice(dict.as_bytes());
+}
+
+/// Write the header, the linearization dictionary and the cross-reference table of
+/// the first page.
+fn write_part1(header: &[u8], trailer: &Trailer, part1: &Part1) -> Vec<u8> {
+    let mut buf = header.to_vec();
+    let linearization_offset = buf.len();
+    write_linearization_dict(&mut buf, part1);
+
+    let count = part1.entries.len() + 1;
+    let mut xref = format!(
+        "xref\n{} {count}\n{linearization_offset:010} 00000 n\r\n",
+        part1.linearization_num
+    );
+
+    for offset in &part1.entries {
+        write!(xref, "{offset:010} 00000 n\r\n").unwrap();
+    }
+
+    xref.push_str("trailer\n<<\n");
+    write_trailer_entries(&mut xref, trailer, part1.size, part1.encrypt_num);
+    write!(
+        xref,
+        "  /Prev {:010}\n>>\nstartxref\n0\n%%EOF\n\n",
+        part1.main_xref_offset
+    )
+    .unwrap();
+
+    buf.extend_from_slice(xref.as_bytes());
+    buf
+}
+
+/// Split the body of an object into tokens, skipping the data of streams.
+fn tokenize(body: &[u8]) -> KrillaResult<Vec<LeveledToken<'_>>> {
+    let mut lexer = Lexer::new(body, 0);
+    let mut tokens: Vec<LeveledToken> = vec![];
+    let mut level = 0;
+    let mut length = None;
+
+    while let Some(token) = lexer.next_token() {
+        if let Some((1, prev)) = tokens.last() {
+            if prev.raw == b"/Length" {
+                length = parse_int::<usize>(token.raw);
+            }
+        }
+
+        match (&token.kind, token.raw) {
+            (TokenKind::Other, b"<<" | b"[") => {
+                tokens.push((level, token));
+                level += 1;
+                continue;
+            }
+            (TokenKind::Other, b">>" | b"]") => level = usize::saturating_sub(level, 1),
+            (TokenKind::Other, b"stream") => {
+                let len = length
+                    .take()
+                    .ok_or_else(|| error("a stream has no direct length"))?;
+                lexer.pos = stream_data(body, lexer.pos, len).end;
+            }
+            _ => {}
+        }
+
+        tokens.push((level, token));
+    }
+
+    Ok(tokens)
+}
+
+/// Return the indirect references in the tokens, with the index of their first token.
+fn refs<'a>(tokens: &'a [LeveledToken]) -> impl Iterator<Item = (usize, u32)> + 'a {
+    tokens.windows(3).enumerate().filter_map(|(index, window)| {
+        if window[2].1.raw != b"R" {
+            return None;
+        }
+
+        parse_int::<u16>(window[1].1.raw)?;
+        Some((index, parse_int(window[0].1.raw)?))
+    })
+}
+
+/// Return the tokens of the value of the given key in the top-level dictionary.
+fn dict_entry<'a, 'b>(
+    tokens: &'b [LeveledToken<'a>],
+    key: &[u8],
+) -> Option<&'b [LeveledToken<'a>]> {
+    let start = tokens
+        .iter()
+        .position(|(level, token)| *level == 1 && token.raw == key)?
+        + 1;
+
+    // The first token always belongs to the value, even if it's a name.
+    let len = tokens
+        .get(start + 1..)?
+        .iter()
+        .position(|(level, token)| {
+            *level == 0 || (*level == 1 && matches!(token.kind, TokenKind::Name))
+        })
+        .map_or(tokens.len() - start, |len| len + 1);
+
+    Some(&tokens[start..start + len])
+}
+
+fn parse_int<T: std::str::FromStr>(raw: &[u8]) -> Option<T> {
+    std::str::from_utf8(raw).ok()?.parse().ok()
+}
+
+/// Replace the object numbers of all indirect references in the body.
+fn renumber(body: &[u8], tokens: &[LeveledToken], map: &HashMap<u32, u32>) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len());
+    let mut pos = 0;
+
+    for (index, num) in refs(tokens) {
+        let Some(new) = map.get(&num) else {
+            continue;
+        };
+
+        let token = &tokens[index].1;
+        out.extend_from_slice(&body[pos..token.start]);
+        out.extend_from_slice(new.to_string().as_bytes());
+        pos = token.start + token.raw.len();
+    }
+
+    out.extend_from_slice(&body[pos..]);
+    out
+}
+
+struct PageEntry {
+    nobjects: u32,
+    length: u32,
+    /// The indices of the shared object groups used by the page.
+    shared_ids: Vec<u32>,
+}
+
+/// The page offset and shared object hint tables, as described in
+/// section F.4 of the PDF specification.
+struct HintTables {
+    first_page_offset: u32,
+    pages: Vec<PageEntry>,
+    first_shared_num: u32,
+    first_shared_offset: u32,
+    /// The number of groups that belong to the first page section.
+    first_page_groups: u32,
+    /// The lengths of all groups. Each group consists of exactly one object.
+    group_lengths: Vec<u32>,
+}
+
+impl HintTables {
+    /// Encode the hint tables and return them, along with the offset of the
+    /// shared object hint table.
+    fn encode(&self) -> (Vec<u8>, usize) {
+        let mut w = BitWriter::default();
+
+        let min_nobjects = self.pages.iter().map(|p| p.nobjects).min().unwrap_or(0);
+        let max_nobjects = self.pages.iter().map(|p| p.nobjects).max().unwrap_or(0);
+        let min_length = self.pages.iter().map(|p| p.length).min().unwrap_or(0);
+        let max_length = self.pages.iter().map(|p| p.length).max().unwrap_or(0);
+        let max_shared = self.pages.iter().map(|p| p.shared_ids.len() as u32).max();
+        let max_id = self.pages.iter().flat_map(|p| &p.shared_ids).max();
+
+        let nbits_nobjects = nbits(max_nobjects - min_nobjects);
+        let nbits_length = nbits(max_length - min_length);
+        let nbits_nshared = nbits(max_shared.unwrap_or(0));
+        let nbits_id = nbits(max_id.copied().unwrap_or(0));
+
+        // Like other writers, we don't provide separate information about the
+        // content streams, but treat the whole page as the content stream.
+        w.write(min_nobjects, 32);
+        w.write(self.first_page_offset, 32);
+        w.write(nbits_nobjects, 16);
+        w.write(min_length, 32);
+        w.write(nbits_length, 16);
+        w.write(0, 32);
+        w.write(0, 16);
+        w.write(min_length, 32);
+        w.write(nbits_length, 16);
+        w.write(nbits_nshared, 16);
+        w.write(nbits_id, 16);
+        w.write(0, 16);
+        w.write(1, 16);
+
+        // Each column of the per-page entries starts at a byte boundary.
+        let column = |w: &mut BitWriter, values: &mut dyn Iterator<Item = u32>, bits| {
+            for value in values {
+                w.write(value, bits);
+            }
+            w.flush();
+        };
+
+        let pages = &self.pages;
+        column(
+            &mut w,
+            &mut pages.iter().map(|p| p.nobjects - min_nobjects),
+            nbits_nobjects,
+        );
+        column(
+            &mut w,
+            &mut pages.iter().map(|p| p.length - min_length),
+            nbits_length,
+        );
+        column(
+            &mut w,
+            &mut pages.iter().map(|p| p.shared_ids.len() as u32),
+            nbits_nshared,
+        );
+        column(
+            &mut w,
+            &mut pages.iter().flat_map(|p| p.shared_ids.iter().copied()),
+            nbits_id,
+        );
+        // Numerators of the fractional positions, content stream offsets and lengths.
+        column(&mut w, &mut std::iter::empty(), 0);
+        column(&mut w, &mut pages.iter().map(|_| 0), 0);
+        column(
+            &mut w,
+            &mut pages.iter().map(|p| p.length - min_length),
+            nbits_length,
+        );
+
+        let shared_table_offset = w.buf.len();
+
+        let min_group = self.group_lengths.iter().copied().min().unwrap_or(0);
+        let max_group = self.group_lengths.iter().copied().max().unwrap_or(0);
+        let nbits_group = nbits(max_group - min_group);
+
+        w.write(self.first_shared_num, 32);
+        w.write(self.first_shared_offset, 32);
+        w.write(self.first_page_groups, 32);
+        w.write(self.group_lengths.len() as u32, 32);
+        // Each group consists of exactly one object.
+        w.write(0, 16);
+        w.write(min_group, 32);
+        w.write(nbits_group, 16);
+
+        let groups = &self.group_lengths;
+        column(
+            &mut w,
+            &mut groups.iter().map(|l| l - min_group),
+            nbits_group,
+        );
+        // We never provide MD5 signatures.
+        column(&mut w, &mut groups.iter().map(|_| 0), 1);
+        column(&mut w, &mut groups.iter().map(|_| 0), 0);
+
+        (w.buf, shared_table_offset)
+    }
+}
+
+/// The number of bits that are needed to represent the value.
+fn nbits(value: u32) -> u32 {
+    u32::BITS - value.leading_zeros()
+}
+
+#[derive(Default)]
+struct BitWriter {
+    buf: Vec<u8>,
+    /// The bits that haven't been written to the buffer yet.
+    cur: u8,
+    len: u32,
+}
+
+impl BitWriter {
+    /// Write the lowest `bits` bits of the value, most significant bit first.
+    fn write(&mut self, value: u32, bits: u32) {
+        for i in (0..bits).rev() {
+            self.cur = (self.cur << 1) | ((value >> i) & 1) as u8;
+            self.len += 1;
+
+            if self.len == 8 {
+                self.buf.push(self.cur);
+                self.cur = 0;
+                self.len = 0;
+            }
+        }
+    }
+
+    /// Pad the remaining bits with zeroes.
+    fn flush(&mut self) {
+        if self.len > 0 {
+            self.write(0, 8 - self.len);
+        }
+    }
+}
@@ -0,0 +1,1049 @@
+//! Rewriting the file structure of a finished PDF.
+//!
+//! pdf-writer always writes each object as a separate indirect object followed by a classic
+//! cross-reference table. Some features, like encryption, object streams and linearization,
+//! require changing that structure, so we parse the objects of the written PDF again and
+//! write them back out in the desired form.
+
+mod linearize;
+
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::ops::Range;
+
+#[cfg(feature = "encryption")]
+use crate::encryption::{EncryptionSettings, SecurityHandler};
+use crate::error::{KrillaError, KrillaResult};
+use crate::stream::deflate_encode_with_level;
+use crate::util::hex;
+
+/// Without the `encryption` feature, there never is a security handler.
+#[cfg(not(feature = "encryption"))]
+enum SecurityHandler {}
+
+#[cfg(not(feature = "encryption"))]
+impl SecurityHandler {
+    fn encrypt(&mut self, _: &[u8], _: u32, _: u16) -> Vec<u8> {
+        match *self {}
+    }
+
+    fn write_dict(&self, _: &mut String) {
+        match *self {}
+    }
+}
+
+/// The maximum number of objects that are packed into a single object stream.
+const OBJECTS_PER_STREAM: usize = 100;
+
+/// The settings that determine how the PDF should be rewritten.
+pub(crate) struct RewriteSettings {
+    pub(crate) object_streams: bool,
+    pub(crate) linearize: bool,
+    /// The flate compression level, or `None` if nothing should be compressed.
+    pub(crate) compression_level: Option<u8>,
+    #[cfg(feature = "encryption")]
+    pub(crate) encryption: Option<EncryptionSettings>,
+}
+
+/// Rewrite a PDF written by pdf-writer according to the given settings.
+pub(crate) fn rewrite(pdf: &[u8], settings: RewriteSettings) -> KrillaResult<Vec<u8>> {
+    let trailer = Trailer::parse(pdf)?;
+    let (header, objects) = parse_objects(pdf)?;
+
+    #[cfg(feature = "encryption")]
+    let handler = settings
+        .encryption
+        .as_ref()
+        .map(|encryption| SecurityHandler::new(encryption, &trailer.first_id))
+        .transpose()?;
+    #[cfg(not(feature = "encryption"))]
+    let handler = None;
+
+    if settings.linearize {
+        return linearize::linearize(
+            header,
+            objects,
+            &trailer,
+            handler,
+            settings.compression_level,
+        );
+    }
+
+    let mut writer = Writer {
+        handler,
+        compression_level: settings.compression_level,
+        // Rewriting barely changes the size of the document.
+        buf: Vec::with_capacity(pdf.len() + pdf.len() / 10),
+        entries: vec![],
+        next_num: objects.iter().map(|o| o.num).max().unwrap_or(0) + 1,
+    };
+
+    writer.buf.extend_from_slice(header);
+
+    if settings.object_streams {
+        // Streams can't be part of object streams and neither can objects with a
+        // non-zero generation number.
+        let (packed, direct): (Vec<_>, Vec<_>) = objects
+            .into_iter()
+            .partition(|object| !object.is_stream && object.gen == 0);
+
+        for object in &direct {
+            writer.write_object(object)?;
+        }
+
+        for chunk in packed.chunks(OBJECTS_PER_STREAM) {
+            writer.write_object_stream(chunk);
+        }
+
+        Ok(writer.finish_with_xref_stream(&trailer))
+    } else {
+        for object in &objects {
+            writer.write_object(object)?;
+        }
+
+        Ok(writer.finish_with_xref_table(&trailer))
+    }
+}
+
+/// An indirect object of the original PDF.
+struct Object<'a> {
+    num: u32,
+    gen: u16,
+    /// Everything between the `obj` and `endobj` keywords.
+    body: Cow<'a, [u8]>,
+    is_stream: bool,
+}
+
+/// Split the PDF into the header and its indirect objects.
+fn parse_objects(pdf: &[u8]) -> KrillaResult<(&[u8], Vec<Object<'_>>)> {
+    let mut lexer = Lexer::new(pdf, 0);
+    // Skip the header comments.
+    let header_end = lexer.skip_whitespace();
+    let objects = parse_object_list(pdf, header_end)?
+        .into_iter()
+        .map(|(_, object)| object)
+        .collect();
+
+    Ok((&pdf[..header_end], objects))
+}
+
+/// Return the number and byte offset of each indirect object in a chunk written by
+/// pdf-writer.
+pub(crate) fn object_offsets(chunk: &[u8]) -> KrillaResult<Vec<(u32, usize)>> {
+    Ok(parse_object_list(chunk, 0)?
+        .into_iter()
+        .map(|(offset, object)| (object.num, offset))
+        .collect())
+}
+
+/// Parse the indirect objects starting at `start`, together with their byte offsets,
+/// until the cross-reference table or the end of the data is reached.
+fn parse_object_list(pdf: &[u8], start: usize) -> KrillaResult<Vec<(usize, Object<'_>)>> {
+    let mut objects = vec![];
+    let mut lexer = Lexer::new(pdf, start);
+
+    loop {
+        let start = lexer.skip_whitespace();
+
+        if start == pdf.len() || pdf[start..].starts_with(b"xref") {
+            return Ok(objects);
+        }
+
+        let num = lexer.next_number()?;
+        let gen = lexer.next_number()? as u16;
+        lexer.next_token();
+
+        let body_start = lexer.pos;
+        let mut depth = 0;
+        let mut length = None;
+        // The range of an indirect `Length` value, together with the actual length of
+        // the stream data, so that we can replace it with a direct value.
+        let mut indirect_length = None;
+        let mut is_stream = false;
+
+        loop {
+            let Some(token) = lexer.next_token() else {
+                return Err(KrillaError::UserError(format!(
+                    "object {num} is not terminated"
+                )));
+            };
+
+            match token.kind {
+                TokenKind::Name if depth == 1 && token.raw == b"/Length" => {
+                    let value_start = lexer.skip_whitespace();
+                    let value = lexer.next_number()? as usize;
+
+                    let mut reference = Lexer::new(pdf, lexer.pos);
+                    if reference.next_number().is_ok()
+                        && reference.next_token().is_some_and(|t| t.raw == b"R")
+                    {
+                        lexer.pos = reference.pos;
+                        indirect_length = Some((value_start..lexer.pos, None));
+                    } else {
+                        length = Some(value);
+                    }
+                }
+                TokenKind::Other if token.raw == b"stream" => {
+                    let data = match (length.take(), &mut indirect_length) {
+                        (Some(len), _) => stream_data(pdf, lexer.pos, len),
+                        (None, Some((_, actual))) => {
+                            let data =
+                                stream_data_without_length(pdf, lexer.pos).ok_or_else(|| {
+                                    KrillaError::UserError(format!(
+                                        "the stream of object {num} is not terminated"
+                                    ))
+                                })?;
+                            *actual = Some(data.len());
+                            data
+                        }
+                        (None, None) => {
+                            return Err(KrillaError::UserError(
+                                "a stream without a length cannot be rewritten".to_string(),
+                            ))
+                        }
+                    };
+
+                    lexer.pos = data.end;
+                    is_stream = true;
+                }
+                TokenKind::Other if token.raw == b"endobj" => {
+                    let body = match &indirect_length {
+                        Some((range, Some(actual))) => Cow::Owned(
+                            [
+                                &pdf[body_start..range.start],
+                                actual.to_string().as_bytes(),
+                                &pdf[range.end..token.start],
+                            ]
+                            .concat(),
+                        ),
+                        _ => Cow::Borrowed(&pdf[body_start..token.start]),
+                    };
+
+                    objects.push((
+                        start,
+                        Object {
+                            num,
+                            gen,
+                            body,
+                            is_stream,
+                        },
+                    ));
+                    break;
+                }
+                TokenKind::Other if token.raw == b"<<" => depth += 1,
+                TokenKind::Other if token.raw == b">>" => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Return the range of the data of a stream, given the position after the `stream` keyword.
+fn stream_data(data: &[u8], mut start: usize, len: usize) -> Range<usize> {
+    if data[start..].starts_with(b"\r\n") {
+        start += 2;
+    } else if data[start..].starts_with(b"\n") {
+        start += 1;
+    }
+
+    start..start + len
+}
+
+/// Return the range of the data of a stream whose length is stored in another object,
+/// given the position after the `stream` keyword.
+///
+/// Since the objects can appear in any order, we don't resolve the length and instead
+/// look for the `endstream` keyword.
+fn stream_data_without_length(data: &[u8], start: usize) -> Option<Range<usize>> {
+    let start = stream_data(data, start, 0).start;
+    let mut end = start + data[start..].windows(9).position(|w| w == b"endstream")?;
+
+    // The data is followed by an end-of-line marker.
+    if data[start..end].ends_with(b"\r\n") {
+        end -= 2;
+    } else if data[start..end].ends_with(b"\n") || data[start..end].ends_with(b"\r") {
+        end -= 1;
+    }
+
+    Some(start..end)
+}
+
+/// An entry in the cross-reference table or stream.
+#[derive(Copy, Clone)]
+enum XRefEntry {
+    /// An object at the given byte offset.
+    Direct(usize),
+    /// An object that is part of the object stream with the given number, at the given index.
+    Compressed(u32, usize),
+}
+
+struct Writer {
+    handler: Option<SecurityHandler>,
+    compression_level: Option<u8>,
+    buf: Vec<u8>,
+    entries: Vec<(u32, XRefEntry)>,
+    next_num: u32,
+}
+
+impl Writer {
+    fn write_object(&mut self, object: &Object) -> KrillaResult<()> {
+        self.entries
+            .push((object.num, XRefEntry::Direct(self.buf.len())));
+        self.buf
+            .extend_from_slice(format!("{} {} obj", object.num, object.gen).as_bytes());
+
+        if self.handler.is_some() {
+            self.write_encrypted_body(object)?;
+        } else {
+            self.buf.extend_from_slice(&object.body);
+        }
+
+        self.buf.extend_from_slice(b"endobj\n\n");
+
+        Ok(())
+    }
+
+    /// Copy the body of an object, while encrypting its strings and streams.
+    fn write_encrypted_body(&mut self, object: &Object) -> KrillaResult<()> {
+        let handler = self.handler.as_mut().unwrap();
+        let body = &object.body;
+        let mut pos = 0;
+        let mut lexer = Lexer::new(body, pos);
+        let mut depth = 0;
+        // The range of the value of the `Length` entry in the output, as well as the value.
+        let mut length: Option<(Range<usize>, usize)> = None;
+
+        while let Some(token) = lexer.next_token() {
+            self.buf.extend_from_slice(&body[pos..token.start]);
+
+            match token.kind {
+                TokenKind::String(s) => {
+                    let encrypted = handler.encrypt(&s, object.num, object.gen);
+                    self.buf.push(b'<');
+                    self.buf.extend_from_slice(hex(&encrypted).as_bytes());
+                    self.buf.push(b'>');
+                }
+                TokenKind::Name if depth == 1 && token.raw == b"/Length" => {
+                    self.buf.extend_from_slice(token.raw);
+                    let value_start = lexer.skip_whitespace();
+                    self.buf
+                        .extend_from_slice(&body[token.start + token.raw.len()..value_start]);
+
+                    let value = lexer.next_number()?;
+                    let range = self.buf.len()..self.buf.len() + (lexer.pos - value_start);
+                    self.buf.extend_from_slice(&body[value_start..lexer.pos]);
+                    length = Some((range, value as usize));
+                }
+                TokenKind::Other if token.raw == b"stream" => {
+                    // The length has already been validated when parsing the objects.
+                    let (range, len) = length.take().unwrap();
+                    let data = stream_data(body, lexer.pos, len);
+                    let encrypted = handler.encrypt(&body[data.clone()], object.num, object.gen);
+                    self.buf
+                        .splice(range, encrypted.len().to_string().into_bytes());
+
+                    self.buf.extend_from_slice(b"stream\n");
+                    self.buf.extend_from_slice(&encrypted);
+                    lexer.pos = data.end;
+                }
+                _ => {
+                    if token.raw == b"<<" {
+                        depth += 1;
+                    } else if token.raw == b">>" {
+                        depth -= 1;
+                    }
+
+                    self.buf.extend_from_slice(token.raw);
+                }
+            }
+
+            pos = lexer.pos;
+        }
+
+        self.buf.extend_from_slice(&body[pos..]);
+
+        Ok(())
+    }
+
+    /// Pack the given objects into a new object stream.
+    fn write_object_stream(&mut self, objects: &[Object]) {
+        let num = self.next_num;
+        self.next_num += 1;
+
+        let mut offsets = String::new();
+        let mut data = vec![];
+
+        for (index, object) in objects.iter().enumerate() {
+            self.entries
+                .push((object.num, XRefEntry::Compressed(num, index)));
+            write!(offsets, "{} {} ", object.num, data.len()).unwrap();
+            data.extend_from_slice(object.body.trim_ascii());
+            data.push(b'\n');
+        }
+
+        offsets.pop();
+        offsets.push('\n');
+        let first = offsets.len();
+        let mut data = [offsets.as_bytes(), &data].concat();
+
+        let filter = if let Some(level) = self.compression_level {
+            data = deflate_encode_with_level(&data, level);
+            "\n  /Filter /FlateDecode"
+        } else {
+            ""
+        };
+
+        if let Some(handler) = &mut self.handler {
+            data = handler.encrypt(&data, num, 0);
+        }
+
+        self.entries.push((num, XRefEntry::Direct(self.buf.len())));
+        let dict = format!(
+            "{num} 0 obj\n<<\n  /Type /ObjStm\n  /N {}\n  /First {first}{filter}\n  /Length {}\n>>\nstream\n",
+            objects.len(),
+            data.len(),
+        );
+        self.buf.extend_from_slice(dict.as_bytes());
+        self.buf.extend_from_slice(&data);
+        self.buf.extend_from_slice(b"\nendstream\nendobj\n\n");
+    }
+
+    /// Write the encryption dictionary, if necessary, and return its number.
+    fn write_encrypt_dict(&mut self) -> Option<u32> {
+        let handler = self.handler.as_ref()?;
+        let num = self.next_num;
+        self.next_num += 1;
+
+        let mut dict = String::new();
+        handler.write_dict(&mut dict);
+        self.entries.push((num, XRefEntry::Direct(self.buf.len())));
+        self.buf
+            .extend_from_slice(format!("{num} 0 obj\n{dict}endobj\n\n").as_bytes());
+
+        Some(num)
+    }
+
+    /// Return the entries indexed by object number, as well as the free object numbers.
+    fn sorted_entries(&self, size: u32) -> (Vec<Option<XRefEntry>>, Vec<u32>) {
+        let mut entries = vec![None; size as usize];
+        for (num, entry) in &self.entries {
+            entries[*num as usize] = Some(*entry);
+        }
+
+        let free = (0..size)
+            .filter(|num| entries[*num as usize].is_none())
+            .collect();
+
+        (entries, free)
+    }
+
+    /// Write a classic cross-reference table and the trailer.
+    fn finish_with_xref_table(mut self, trailer: &Trailer) -> Vec<u8> {
+        let encrypt = self.write_encrypt_dict();
+        let size = self.next_num;
+        let xref_offset = self.buf.len();
+        let (entries, free) = self.sorted_entries(size);
+        // Link each free entry to the next one, the last one links back to 0.
+        let mut next_free = free.iter().skip(1);
+
+        let mut xref = format!("xref\n0 {size}\n");
+        for (num, entry) in entries.iter().enumerate() {
+            match entry {
+                Some(XRefEntry::Direct(offset)) => {
+                    write!(xref, "{offset:010} 00000 n\r\n").unwrap()
+                }
+                Some(XRefEntry::Compressed(..)) => unreachable!(),
+                None => {
+                    let next = next_free.next().copied().unwrap_or(0);
+                    let gen = if num == 0 { 65535 } else { 0 };
+                    write!(xref, "{next:010} {gen:05} f\r\n").unwrap();
+                }
+            }
+        }
+
+        xref.push_str("trailer\n<<\n");
+        write_trailer_entries(&mut xref, trailer, size, encrypt);
+        write!(xref, ">>\nstartxref\n{xref_offset}\n%%EOF").unwrap();
+
+        self.buf.extend_from_slice(xref.as_bytes());
+        self.buf
+    }
+
+    /// Write a cross-reference stream, which also takes over the role of the trailer.
+    fn finish_with_xref_stream(mut self, trailer: &Trailer) -> Vec<u8> {
+        let encrypt = self.write_encrypt_dict();
+        let xref_num = self.next_num;
+        let size = xref_num + 1;
+        let xref_offset = self.buf.len();
+        self.entries
+            .push((xref_num, XRefEntry::Direct(xref_offset)));
+        let (entries, free) = self.sorted_entries(size);
+        let mut next_free = free.iter().skip(1);
+
+        // The second field needs to be wide enough for the largest offset and
+        // object stream number.
+        let max = xref_offset.max(size as usize);
+        let width = (usize::BITS - max.leading_zeros()).div_ceil(8).max(1) as usize;
+
+        let mut data = vec![];
+        let mut push_entry = |ty: u8, field2: usize, field3: usize| {
+            data.push(ty);
+            let field2 = field2.to_be_bytes();
+            data.extend_from_slice(&field2[field2.len() - width..]);
+            data.extend_from_slice(&(field3 as u16).to_be_bytes());
+        };
+
+        for (num, entry) in entries.iter().enumerate() {
+            match entry {
+                Some(XRefEntry::Direct(offset)) => push_entry(1, *offset, 0),
+                Some(XRefEntry::Compressed(stream, index)) => {
+                    push_entry(2, *stream as usize, *index)
+                }
+                None => {
+                    let next = next_free.next().copied().unwrap_or(0);
+                    let gen = if num == 0 { 65535 } else { 0 };
+                    push_entry(0, next as usize, gen);
+                }
+            }
+        }
+
+        let filter = if let Some(level) = self.compression_level {
+            data = deflate_encode_with_level(&data, level);
+            "  /Filter /FlateDecode\n"
+        } else {
+            ""
+        };
+
+        let mut dict = format!("{xref_num} 0 obj\n<<\n  /Type /XRef\n");
+        write_trailer_entries(&mut dict, trailer, size, encrypt);
+        write!(
+            dict,
+            "  /W [1 {width} 2]\n{filter}  /Length {}\n>>\nstream\n",
+            data.len()
+        )
+        .unwrap();
+
+        // Note that cross-reference streams are never encrypted.
+        self.buf.extend_from_slice(dict.as_bytes());
+        self.buf.extend_from_slice(&data);
+        self.buf.extend_from_slice(
+            format!("\nendstream\nendobj\n\nstartxref\n{xref_offset}\n%%EOF").as_bytes(),
+        );
+
+        self.buf
+    }
+}
+
+/// Write the entries of the trailer dictionary that are shared between the
+/// trailer and the cross-reference stream.
+fn write_trailer_entries(out: &mut String, trailer: &Trailer, size: u32, encrypt: Option<u32>) {
+    writeln!(out, "  /Size {size}").unwrap();
+
+    if let Some(root) = trailer.root {
+        writeln!(out, "  /Root {root} 0 R").unwrap();
+    }
+
+    if let Some(info) = trailer.info {
+        writeln!(out, "  /Info {info} 0 R").unwrap();
+    }
+
+    writeln!(out, "  /ID {}", String::from_utf8_lossy(trailer.id)).unwrap();
+
+    if let Some(encrypt) = encrypt {
+        writeln!(out, "  /Encrypt {encrypt} 0 R").unwrap();
+    }
+}
+
+/// The entries of the trailer of the unencrypted PDF that we need to carry over.
+struct Trailer<'a> {
+    /// The object number of the catalog.
+    root: Option<u32>,
+    /// The object number of the document information dictionary.
+    info: Option<u32>,
+    /// The raw ID array.
+    id: &'a [u8],
+    /// The first string of the ID array, which is used for deriving keys.
+    first_id: Vec<u8>,
+}
+
+impl<'a> Trailer<'a> {
+    fn parse(pdf: &'a [u8]) -> KrillaResult<Self> {
+        let error = |message: &str| KrillaError::UserError(format!("invalid trailer: {message}"));
+        let start = pdf
+            .windows(8)
+            .rposition(|w| w == b"trailer\n")
+            .ok_or_else(|| error("the document has no trailer"))?;
+
+        let mut lexer = Lexer::new(pdf, start + 8);
+        let mut root = None;
+        let mut info = None;
+        let mut id = None;
+
+        while let Some(token) = lexer.next_token() {
+            match token.kind {
+                TokenKind::Name if token.raw == b"/Root" || token.raw == b"/Info" => {
+                    // pdf-writer always uses a generation number of zero.
+                    let value = lexer.next_number().ok();
+                    lexer.next_token();
+                    lexer.next_token();
+
+                    if token.raw == b"/Root" {
+                        root = value;
+                    } else {
+                        info = value;
+                    }
+                }
+                TokenKind::Name if token.raw == b"/ID" => {
+                    let start = lexer.skip_whitespace();
+                    lexer.next_token();
+                    let first_id = match lexer.next_token().map(|t| t.kind) {
+                        Some(TokenKind::String(s)) => s,
+                        _ => return Err(error("the file ID doesn't contain a string")),
+                    };
+
+                    while lexer.next_token().is_some_and(|t| t.raw != b"]") {}
+
+                    id = Some((&pdf[start..lexer.pos], first_id));
+                }
+                TokenKind::Other if token.raw == b">>" => break,
+                _ => {}
+            }
+        }
+
+        let (id, first_id) = id.ok_or_else(|| error("the document has no file ID"))?;
+
+        Ok(Self {
+            root,
+            info,
+            id,
+            first_id,
+        })
+    }
+}
+
+enum TokenKind {
+    /// A literal or hex string, with its decoded bytes.
+    String(Vec<u8>),
+    Name,
+    /// Delimiters, numbers and keywords.
+    Other,
+}
+
+struct Token<'a> {
+    kind: TokenKind,
+    raw: &'a [u8],
+    start: usize,
+}
+
+/// A minimal lexer for the PDF syntax that pdf-writer produces.
+struct Lexer<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    /// Skip whitespace and comments and return the new position.
+    fn skip_whitespace(&mut self) -> usize {
+        while let Some(b) = self.peek() {
+            if is_whitespace(b) {
+                self.pos += 1;
+            } else if b == b'%' {
+                while self.peek().is_some_and(|b| b != b'\n' && b != b'\r') {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+
+        self.pos
+    }
+
+    fn next_number(&mut self) -> KrillaResult<u32> {
+        self.next_token()
+            .and_then(|t| std::str::from_utf8(t.raw).ok()?.parse().ok())
+            .ok_or_else(|| KrillaError::UserError("expected a number".to_string()))
+    }
+
+    fn next_token(&mut self) -> Option<Token<'a>> {
+        let start = self.skip_whitespace();
+        let first = self.peek()?;
+        self.pos += 1;
+
+        let kind = match first {
+            b'(' => TokenKind::String(self.literal_string()),
+            b'<' if self.peek() == Some(b'<') => {
+                self.pos += 1;
+                TokenKind::Other
+            }
+            b'<' => TokenKind::String(self.hex_string()),
+            b'>' if self.peek() == Some(b'>') => {
+                self.pos += 1;
+                TokenKind::Other
+            }
+            b'[' | b']' | b'{' | b'}' => TokenKind::Other,
+            b'/' => {
+                self.regular();
+                TokenKind::Name
+            }
+            _ => {
+                self.regular();
+                TokenKind::Other
+            }
+        };
+
+        Some(Token {
+            kind,
+            raw: &self.data[start..self.pos],
+            start,
+        })
+    }
+
+    fn regular(&mut self) {
+        while self
+            .peek()
+            .is_some_and(|b| !is_whitespace(b) && !is_delimiter(b))
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn literal_string(&mut self) -> Vec<u8> {
+        let mut out = vec![];
+        let mut depth = 1;
+
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+
+            match b {
+                b'(' => {
+                    depth += 1;
+                    out.push(b);
+                }
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                    out.push(b);
+                }
+                b'\\' => {
+                    let Some(escaped) = self.peek() else {
+                        break;
+                    };
+                    self.pos += 1;
+
+                    match escaped {
+                        b'n' => out.push(b'\n'),
+                        b'r' => out.push(b'\r'),
+                        b't' => out.push(b'\t'),
+                        b'b' => out.push(0x08),
+                        b'f' => out.push(0x0C),
+                        b'0'..=b'7' => {
+                            let mut value = (escaped - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.peek() {
+                                    Some(d @ b'0'..=b'7') => {
+                                        self.pos += 1;
+                                        value = value * 8 + (d - b'0') as u32;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            out.push(value as u8);
+                        }
+                        // A line continuation.
+                        b'\r' => {
+                            if self.peek() == Some(b'\n') {
+                                self.pos += 1;
+                            }
+                        }
+                        b'\n' => {}
+                        _ => out.push(escaped),
+                    }
+                }
+                _ => out.push(b),
+            }
+        }
+
+        out
+    }
+
+    fn hex_string(&mut self) -> Vec<u8> {
+        let mut nibbles = vec![];
+
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+
+            if b == b'>' {
+                break;
+            }
+
+            if let Some(nibble) = (b as char).to_digit(16) {
+                nibbles.push(nibble as u8);
+            }
+        }
+
+        // An odd number of digits is padded with a zero.
+        if nibbles.len() % 2 == 1 {
+            nibbles.push(0);
+        }
+
+        nibbles
+            .chunks_exact(2)
+            .map(|c| (c[0] << 4) | c[1])
+            .collect()
+    }
+}
+
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, b' ' | b'\n' | b'\r' | b'\t' | b'\x0C' | b'\0')
+}
+
+fn is_delimiter(b: u8) -> bool {
+    matches!(
+        b,
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "encryption")]
+    use crate::encryption::{EncryptionAlgorithm, EncryptionSettings, Permissions};
+    #[cfg(feature = "encryption")]
+    use crate::error::KrillaError;
+    use crate::metadata::Metadata;
+    #[cfg(feature = "encryption")]
+    use crate::rewrite::{rewrite, RewriteSettings};
+    use crate::rewrite::{Lexer, TokenKind};
+    use crate::validation::Validator;
+    use crate::version::PdfVersion;
+    use crate::{Document, SerializeSettings};
+
+    fn document_with_object_streams(settings: SerializeSettings) -> Vec<u8> {
+        let mut document = Document::new_with(SerializeSettings {
+            use_object_streams: true,
+            ..settings
+        });
+        document.set_metadata(Metadata::new().title("A title".to_string()));
+        document.start_page();
+        document.finish().unwrap()
+    }
+
+    #[test]
+    fn rewrite_lexer_literal_string() {
+        let mut lexer = Lexer::new(
+            br"(a\(b\) (c) \101\n\
+d)",
+            0,
+        );
+
+        match lexer.next_token().unwrap().kind {
+            TokenKind::String(s) => assert_eq!(s, b"a(b) (c) A\nd"),
+            _ => panic!("expected a string"),
+        }
+    }
+
+    #[test]
+    fn rewrite_object_streams() {
+        let pdf = document_with_object_streams(SerializeSettings::settings_1());
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.contains("/Type /ObjStm"));
+        assert!(pdf.contains("/Type /XRef"));
+        assert!(pdf.contains("(A title)"));
+        assert!(!pdf.contains("\nxref\n"));
+        assert!(!pdf.contains("trailer"));
+    }
+
+    #[test]
+    fn rewrite_object_streams_disabled_for_pdf14() {
+        let pdf = document_with_object_streams(SerializeSettings {
+            pdf_version: PdfVersion::Pdf14,
+            ..SerializeSettings::settings_1()
+        });
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(!pdf.contains("/Type /ObjStm"));
+        assert!(pdf.contains("\nxref\n"));
+    }
+
+    #[test]
+    fn rewrite_object_streams_disabled_for_pdfa1() {
+        // PDF/A-1 forces PDF 1.4, even though PDF 1.7 is requested here.
+        let pdf = document_with_object_streams(SerializeSettings {
+            validator: Validator::A1_B,
+            ..SerializeSettings::settings_1()
+        });
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(!pdf.contains("/Type /ObjStm"));
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn rewrite_object_streams_with_encryption() {
+        let pdf = document_with_object_streams(SerializeSettings {
+            encryption: Some(encryption()),
+            ..SerializeSettings::settings_1()
+        });
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.contains("/Type /ObjStm"));
+        assert!(pdf.contains("/Type /XRef"));
+        assert!(pdf.contains("/Encrypt "));
+        assert!(!pdf.contains("A title"));
+    }
+
+    #[cfg(feature = "encryption")]
+    fn encryption() -> EncryptionSettings {
+        EncryptionSettings {
+            user_password: "user".to_string(),
+            owner_password: None,
+            permissions: Permissions::default(),
+            algorithm: EncryptionAlgorithm::Aes128,
+        }
+    }
+
+    /// A PDF with a single page whose content stream stores its length in a separate
+    /// object, which pdf-writer itself never does.
+    #[cfg(feature = "encryption")]
+    fn pdf_with_indirect_length() -> Vec<u8> {
+        let content = "0 0 1 rg 20 20 160 160 re f";
+        let stream = format!("<< /Length 5 0 R >>\nstream\n{content}\nendstream");
+        let length = content.len().to_string();
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>",
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Contents 4 0 R >>",
+            stream.as_str(),
+            length.as_str(),
+        ];
+
+        let mut pdf = b"%PDF-1.7\n".to_vec();
+        let mut offsets = vec![];
+
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend(format!("{} 0 obj\n{object}\nendobj\n\n", i + 1).as_bytes());
+        }
+
+        let xref_offset = pdf.len();
+        let size = objects.len() + 1;
+        pdf.extend(format!("xref\n0 {size}\n0000000000 65535 f\r\n").as_bytes());
+
+        for offset in offsets {
+            pdf.extend(format!("{offset:010} 00000 n\r\n").as_bytes());
+        }
+
+        pdf.extend(
+            format!(
+                "trailer\n<<\n  /Size {size}\n  /Root 1 0 R\n  /ID [(abc) (abc)]\n>>\nstartxref\n{xref_offset}\n%%EOF"
+            )
+            .as_bytes(),
+        );
+
+        pdf
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn rewrite_indirect_length_with_encryption() {
+        let pdf = rewrite(
+            &pdf_with_indirect_length(),
+            RewriteSettings {
+                object_streams: false,
+                linearize: false,
+                compression_level: None,
+                encryption: Some(encryption()),
+            },
+        )
+        .unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.contains("/Encrypt "));
+        assert!(!pdf.contains("/Length 5 0 R"));
+        assert!(!pdf.contains("160 160 re"));
+    }
+
+    #[cfg(all(feature = "encryption", feature = "pdf"))]
+    #[test]
+    fn rewrite_imported_page_with_indirect_length() {
+        let mut document = Document::new_with(SerializeSettings {
+            encryption: Some(encryption()),
+            ..SerializeSettings::settings_1()
+        });
+        document
+            .import_page(&pdf_with_indirect_length(), 0)
+            .unwrap();
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.contains("/Encrypt "));
+        assert!(!pdf.contains("160 160 re"));
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn rewrite_without_trailer() {
+        let result = rewrite(
+            b"%PDF-1.7\n",
+            RewriteSettings {
+                object_streams: false,
+                linearize: false,
+                compression_level: None,
+                encryption: Some(encryption()),
+            },
+        );
+
+        assert!(matches!(result, Err(KrillaError::UserError(_))));
+    }
+
+    fn linearized_pdf(settings: SerializeSettings) -> Vec<u8> {
+        let mut document = Document::new_with(SerializeSettings {
+            linearize: true,
+            ..settings
+        });
+        document.set_metadata(Metadata::new().title("A title".to_string()));
+
+        for _ in 0..3 {
+            document.start_page();
+        }
+
+        document.finish().unwrap()
+    }
+
+    #[test]
+    fn rewrite_linearize() {
+        let pdf = linearized_pdf(SerializeSettings::settings_1());
+        let header = String::from_utf8_lossy(&pdf[..1024]);
+
+        assert!(header.contains("/Linearized 1"));
+        assert!(header.contains(&format!("/L {:010}", pdf.len())));
+        assert!(header.contains("/N 0000000003"));
+        // The first page cross-reference table refers to the main one.
+        assert!(header.contains("/Prev "));
+        assert!(pdf.ends_with(b"%%EOF"));
+    }
+
+    #[test]
+    fn rewrite_linearize_disables_object_streams() {
+        let pdf = linearized_pdf(SerializeSettings {
+            use_object_streams: true,
+            ..SerializeSettings::settings_1()
+        });
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.contains("/Linearized 1"));
+        assert!(!pdf.contains("/Type /ObjStm"));
+    }
+}
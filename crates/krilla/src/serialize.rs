@@ -1,37 +1,61 @@
 //! Serializing PDF documents.
 
 use std::cell::RefCell;
-use std::collections::{BTreeMap, HashMap};
-use std::ops::{Deref, DerefMut};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::{Debug, Formatter};
+use std::io::Write;
+use std::ops::{Deref, DerefMut, Range};
 use std::rc::Rc;
 use std::sync::Arc;
 
 #[cfg(feature = "fontdb")]
 use fontdb::{Database, ID};
 use pdf_writer::types::StructRole;
-use pdf_writer::writers::{NameTree, NumberTree, OutputIntent, RoleMap};
-use pdf_writer::{Chunk, Dict, Finish, Limits, Name, Pdf, Ref, Str, TextStr};
+use pdf_writer::writers::{NameTree, NumberTree, RoleMap};
+use pdf_writer::{Chunk, Dict, Finish, Limits, Name, Ref, Str, TextStr};
 use skrifa::raw::TableProvider;
+use skrifa::GlyphId;
 use tiny_skia_path::Size;
+#[cfg(feature = "simple-text")]
+use tiny_skia_path::Transform;
 
+use crate::action::Action;
 use crate::chunk_container::ChunkContainer;
 use crate::color::{ColorSpace, ICCBasedColorSpace, ICCProfile};
-use crate::destination::{NamedDestination, XyzDestination};
+#[cfg(feature = "simple-text")]
+use crate::content::ContentBuilder;
+use crate::destination::{NamedDestination, PageDestination};
+#[cfg(feature = "encryption")]
+use crate::encryption::{EncryptionAlgorithm, EncryptionSettings};
 use crate::error::{KrillaError, KrillaResult};
 use crate::font::{Font, FontInfo};
 #[cfg(feature = "raster-images")]
 use crate::image::Image;
-use crate::metadata::Metadata;
+use crate::metadata::{Metadata, XmpHook};
 use crate::object::font::cid_font::CIDFont;
 use crate::object::font::type3_font::Type3FontMapper;
 use crate::object::font::{FontContainer, FontIdentifier};
+use crate::object::form::FieldFont;
 use crate::object::outline::Outline;
+use crate::object::output_intent::{OutputIntent, OutputIntentSubtype};
+use crate::object::page::AnnotationWarningHook;
+#[cfg(feature = "simple-text")]
+use crate::object::page::DeferredText;
 use crate::object::page::{InternalPage, PageLabelContainer};
+#[cfg(feature = "simple-text")]
+use crate::object::xobject::XObject;
 use crate::object::{Cacheable, Resourceable};
 use crate::page::PageLabel;
+#[cfg(feature = "simple-text")]
+use crate::path::Fill;
 use crate::resource;
 use crate::resource::Resource;
-use crate::tagging::{AnnotationIdentifier, IdentifierType, PageTagIdentifier, TagTree};
+use crate::stream::DEFAULT_COMPRESSION_LEVEL;
+#[cfg(feature = "simple-text")]
+use crate::surface::{Surface, TextDirection};
+use crate::tagging::{AnnotationIdentifier, IdentifierType, PageTagIdentifier, TagGroup, TagTree};
+#[cfg(feature = "simple-text")]
+use crate::tagging::{ArtifactType, ContentTag};
 use crate::util::SipHashable;
 use crate::validation::{ValidationError, Validator};
 use crate::version::PdfVersion;
@@ -39,9 +63,20 @@ use crate::version::PdfVersion;
 /// Settings that should be applied when creating a PDF document.
 #[derive(Clone, Debug)]
 pub struct SerializeSettings {
-    /// Whether content streams should be compressed. Leads to significantly smaller file sizes,
+    /// Whether streams should be compressed. Leads to significantly smaller file sizes,
     /// but also longer running times. It is highly recommended that you set this to true.
+    ///
+    /// This is only kept for backwards compatibility: Setting it to false is the same as
+    /// setting [`SerializeSettings::compression`] to [`Compression::None`].
+    #[deprecated = "use `compression` instead"]
     pub compress_content_streams: bool,
+    /// How strongly streams should be compressed with flate. This applies to all streams
+    /// krilla writes, including content streams, fonts and images.
+    ///
+    /// Images and ICC profiles are already compressed with the default level when they are
+    /// created, so choosing a different level means that they need to be decompressed and
+    /// compressed again when writing the document.
+    pub compression: Compression,
     /// Whether device-independent colors should be used instead of
     /// device-dependent ones.
     ///
@@ -71,6 +106,20 @@ pub struct SerializeSettings {
     /// This is usually not required, but it is for example required when exporting
     /// to PDF/A and using a CMYK color, since they have to be device-independent.
     pub cmyk_profile: Option<ICCProfile<4>>,
+    /// Whether CMYK colors should always use the `cmyk_profile`, even if `no_device_cs`
+    /// is disabled.
+    ///
+    /// This allows you to use color-managed CMYK colors while still writing all other
+    /// colors (like RGB or grayscale colors) in device-dependent color spaces. Has no
+    /// effect if no `cmyk_profile` was provided.
+    pub always_use_cmyk_profile: bool,
+    /// The ICC profile that should be used for luma colors when `no_device_cs`
+    /// is enabled.
+    ///
+    /// If not provided, krilla uses a built-in sgray profile, which is usually
+    /// what you want. Providing a different profile can be useful if the document
+    /// is for example printed with a specific dot gain.
+    pub gray_profile: Option<ICCProfile<1>>,
     /// A validator that allows for exporting to a specific substandard of PDF.
     ///
     /// In case validation fails, export will fail, and a list of validation errors that
@@ -105,6 +154,214 @@ pub struct SerializeSettings {
     pub enable_tagging: bool,
     /// The PDF version that should be used for export.
     pub pdf_version: PdfVersion,
+    /// The maximum number of bytes all content streams of the document are allowed to
+    /// take up in total (before compression). If this budget is exceeded, exporting the
+    /// document will fail with [`KrillaError::ContentBudgetExceeded`].
+    ///
+    /// This is mainly useful as a safety net when the drawn content is driven by untrusted
+    /// input. Once the budget is exceeded, all further drawing operations are skipped.
+    /// Since the exact size of a content stream is only known once it's finished, the
+    /// content that is still being drawn is estimated, so the check can be off by a
+    /// few operations.
+    pub max_content_bytes: Option<usize>,
+    /// Whether annotations that are added via [`Page::add_annotation`] should automatically
+    /// be included in the tag tree.
+    ///
+    /// If enabled, each such annotation is wrapped in a tag group with the tag
+    /// [`Tag::Link`] (for link annotations), [`Tag::Form`] (for form fields) or [`Tag::Annot`]
+    /// (for all other annotations),
+    /// which is appended to the end of the tag tree. This makes it impossible to
+    /// accidentally forget to tag an annotation, which is for example required by PDF/UA.
+    /// If you need control over where in the tag tree an annotation appears, use
+    /// [`Page::add_tagged_annotation`] instead, which is never affected by this setting.
+    ///
+    /// Has no effect if tagging is disabled or no tag tree was set.
+    ///
+    /// [`Page::add_annotation`]: crate::page::Page::add_annotation
+    /// [`Page::add_tagged_annotation`]: crate::page::Page::add_tagged_annotation
+    /// [`Tag::Link`]: crate::tagging::Tag::Link
+    /// [`Tag::Form`]: crate::tagging::Tag::Form
+    /// [`Tag::Annot`]: crate::tagging::Tag::Annot
+    pub auto_tag_annotations: bool,
+    /// The maximum number of kids a node in the page tree is allowed to have.
+    ///
+    /// By default, all pages are written as direct kids of a single page tree node,
+    /// which is the most compact representation. For documents with a very large number
+    /// of pages, this can make it slow for viewers to look up individual pages. If this is
+    /// set and the document has more pages than the given number, the page tree will instead
+    /// be split into nested intermediate nodes, such that no node has more than the given
+    /// number of kids. Values smaller than 2 are treated as 2.
+    pub max_page_tree_kids: Option<usize>,
+    /// Whether fonts should be subsetted, i.e. only the glyphs that are actually used
+    /// in the document are embedded.
+    ///
+    /// Disabling this will embed the full font program of each font, which can lead to
+    /// much bigger file sizes, but might be useful if you want to post-process the PDF
+    /// with a different tool. Note that this does not apply to fonts that need to be
+    /// embedded as Type3 fonts (e.g. color fonts).
+    pub subset_fonts: bool,
+    /// Whether the document should be encrypted, and if so, with which passwords,
+    /// permissions and algorithm. See the [`encryption`] module for more information.
+    ///
+    /// Note that encryption is forbidden by PDF/A, so exporting will fail if you
+    /// combine it with a PDF/A validator. Requires the `encryption` feature.
+    ///
+    /// [`encryption`]: crate::encryption
+    #[cfg(feature = "encryption")]
+    pub encryption: Option<EncryptionSettings>,
+    /// Whether non-stream objects should be packed into object streams, and whether
+    /// a cross-reference stream should be written instead of a cross-reference table.
+    /// This usually leads to noticeably smaller file sizes.
+    ///
+    /// Note that object streams are only supported since PDF 1.5, so this value will
+    /// be ignored for older PDF versions. Since PDF/A-1 is always exported as PDF 1.4,
+    /// this also means that it has no effect when exporting to PDF/A-1.
+    pub use_object_streams: bool,
+    /// Whether the document should be linearized, also known as "fast web view".
+    /// A linearized document is laid out such that a viewer can display the first page
+    /// before the whole file has been downloaded, which is useful when serving PDFs over
+    /// the web.
+    ///
+    /// Note that linearization and object streams are mutually exclusive, so
+    /// `use_object_streams` will be ignored if this is enabled. Since all objects are
+    /// renumbered, it also can't be combined with [`Document::reserve_object`].
+    ///
+    /// [`Document::reserve_object`]: crate::Document::reserve_object
+    pub linearize: bool,
+    /// Whether resources with byte-identical content should only be written once.
+    ///
+    /// krilla always reuses objects that were created from the same input, for example
+    /// when drawing the same image multiple times. However, objects created from different
+    /// inputs can still end up being identical, for example when the same logo is imported
+    /// from two different PDF files. If this is enabled, krilla compares the finished
+    /// XObjects, images, masks, patterns, shadings, graphics states and color spaces
+    /// (including everything they reference) and writes each distinct one only once.
+    ///
+    /// This requires hashing all of these objects, so finishing the document takes longer.
+    pub deduplicate_streams: bool,
+    /// Whether independent work during [`Document::finish`] should be spread across
+    /// multiple threads. Currently, this means that all fonts are subsetted in parallel,
+    /// which can considerably speed up exporting documents that use many large fonts.
+    ///
+    /// The resulting PDF is exactly the same as without this setting, regardless of the
+    /// number of threads. This has no effect unless the `rayon` feature is enabled.
+    ///
+    /// [`Document::finish`]: crate::Document::finish
+    pub parallel: bool,
+    /// The first part of the file identifier of the document.
+    ///
+    /// If not set, krilla derives it from the document ID in the [`Metadata`], or from the
+    /// title and authors of the document, and otherwise falls back to a hash of the whole
+    /// file.
+    ///
+    /// Apart from the file identifier, krilla's output only depends on its input. In
+    /// particular, the current time is never written to the document unless you set it
+    /// via [`Metadata::creation_date`] or [`Metadata::modification_date`]. So exporting the
+    /// same document twice always yields byte-identical files, unless encryption is
+    /// enabled, which always requires random data.
+    ///
+    /// [`Metadata::creation_date`]: crate::metadata::Metadata::creation_date
+    /// [`Metadata::modification_date`]: crate::metadata::Metadata::modification_date
+    pub document_id: Option<[u8; 16]>,
+    /// What should be drawn for characters that the font doesn't have a glyph for
+    /// when drawing text with [`Surface::fill_text`] or [`Surface::stroke_text`].
+    ///
+    /// By default, the `.notdef` glyph of the font is drawn, which is forbidden by some
+    /// export formats, like PDF/A. See [`NotdefFallback`] for the alternatives.
+    ///
+    /// [`Surface::fill_text`]: crate::surface::Surface::fill_text
+    /// [`Surface::stroke_text`]: crate::surface::Surface::stroke_text
+    pub notdef_fallback: NotdefFallback,
+    /// Don't compress content streams, but all other streams. This keeps the content
+    /// streams in the snapshot tests readable.
+    #[cfg(test)]
+    pub(crate) plain_content_streams: bool,
+}
+
+impl SerializeSettings {
+    /// The compression that should actually be used, taking the deprecated
+    /// `compress_content_streams` into account.
+    #[allow(deprecated)]
+    pub(crate) fn effective_compression(&self) -> Compression {
+        if self.compress_content_streams {
+            self.compression
+        } else {
+            Compression::None
+        }
+    }
+
+    /// The compression level of content streams, or `None` if they shouldn't be compressed.
+    pub(crate) fn content_compression_level(&self) -> Option<u8> {
+        #[cfg(test)]
+        if self.plain_content_streams {
+            return None;
+        }
+
+        self.effective_compression().level()
+    }
+}
+
+/// How strongly streams should be compressed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Compression {
+    /// Don't compress streams at all.
+    None,
+    /// Compress streams as fast as possible, at the cost of bigger files.
+    Fast,
+    /// A good trade-off between speed and file size.
+    #[default]
+    Default,
+    /// Compress streams as much as possible, at the cost of longer running times.
+    Best,
+}
+
+impl Compression {
+    /// The flate compression level, or `None` if streams shouldn't be compressed.
+    pub(crate) fn level(self) -> Option<u8> {
+        match self {
+            Compression::None => None,
+            Compression::Fast => Some(1),
+            Compression::Default => Some(DEFAULT_COMPRESSION_LEVEL),
+            Compression::Best => Some(9),
+        }
+    }
+}
+
+/// A callback that returns a replacement glyph for a character, see
+/// [`NotdefFallback::Callback`].
+pub type NotdefCallback = Arc<dyn Fn(char) -> Option<(Font, GlyphId)> + Send + Sync>;
+
+/// What should be drawn for characters that the font doesn't have a glyph for.
+#[derive(Clone, Default)]
+pub enum NotdefFallback {
+    /// Draw the `.notdef` glyph of the font.
+    #[default]
+    Notdef,
+    /// Don't draw anything, but keep the advance of the `.notdef` glyph.
+    ///
+    /// The text of the character is still attached to a neighboring glyph, so that
+    /// it can be copied, unless the text doesn't contain any other glyphs.
+    Nothing,
+    /// Draw a hollow box (also known as "tofu") in place of the `.notdef` glyph.
+    ///
+    /// As with [`NotdefFallback::Nothing`], the text of the character is attached
+    /// to a neighboring glyph.
+    Tofu,
+    /// Ask the callback for a replacement glyph for each affected character, which
+    /// allows using a glyph from a different font. The `.notdef` glyph is only drawn
+    /// if the callback returns `None` for one of the characters.
+    Callback(NotdefCallback),
+}
+
+impl Debug for NotdefFallback {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotdefFallback::Notdef => write!(f, "Notdef"),
+            NotdefFallback::Nothing => write!(f, "Nothing"),
+            NotdefFallback::Tofu => write!(f, "Tofu"),
+            NotdefFallback::Callback(_) => write!(f, "Callback(..)"),
+        }
+    }
 }
 
 /// Settings that should be applied when converting a SVG.
@@ -128,16 +385,34 @@ impl Default for SvgSettings {
 }
 
 impl Default for SerializeSettings {
+    #[allow(deprecated)]
     fn default() -> Self {
         Self {
             ascii_compatible: false,
             compress_content_streams: true,
+            compression: Compression::Default,
             no_device_cs: false,
             xmp_metadata: true,
             cmyk_profile: None,
+            always_use_cmyk_profile: false,
+            gray_profile: None,
             validator: Validator::None,
             enable_tagging: true,
             pdf_version: PdfVersion::Pdf17,
+            max_content_bytes: None,
+            auto_tag_annotations: false,
+            max_page_tree_kids: None,
+            subset_fonts: true,
+            #[cfg(feature = "encryption")]
+            encryption: None,
+            use_object_streams: false,
+            linearize: false,
+            deduplicate_streams: false,
+            parallel: false,
+            document_id: None,
+            notdef_fallback: NotdefFallback::Notdef,
+            #[cfg(test)]
+            plain_content_streams: false,
         }
     }
 }
@@ -156,6 +431,7 @@ pub(crate) struct PageInfo {
     pub(crate) annotations: Vec<Ref>,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 enum StructParentElement {
     /// The index of the page and the number of marked content IDs present on that page.
     Page(usize, i32),
@@ -164,6 +440,37 @@ enum StructParentElement {
     Annotation(usize, usize),
 }
 
+/// Check that each page and each annotation was assigned at most one
+/// struct parent key, so that the entries of the parent tree are unambiguous.
+fn check_struct_parents(struct_parents: &[StructParentElement]) -> KrillaResult<()> {
+    let mut seen = HashSet::new();
+
+    for struct_parent in struct_parents {
+        let key = match *struct_parent {
+            StructParentElement::Page(page_index, _) => (page_index, None),
+            StructParentElement::Annotation(page_index, annot_index) => {
+                (page_index, Some(annot_index))
+            }
+        };
+
+        if !seen.insert(key) {
+            return Err(KrillaError::UserError(match key {
+                (page_index, None) => format!(
+                    "page {} was assigned more than one struct parent key",
+                    page_index + 1
+                ),
+                (page_index, Some(annot_index)) => format!(
+                    "annotation {} on page {} was assigned more than one struct parent key",
+                    annot_index + 1,
+                    page_index + 1
+                ),
+            }));
+        }
+    }
+
+    Ok(())
+}
+
 pub(crate) enum MaybeDeviceColorSpace {
     DeviceRgb,
     DeviceGray,
@@ -183,8 +490,15 @@ pub(crate) struct SerializeContext {
     /// speed up SVG conversion, so that if we convert many SVGs with the same font,
     /// we can cache the font.
     font_cache: HashMap<Arc<FontInfo>, Font>,
+    /// A cache for the bitmap strike that is selected for a font at a certain number
+    /// of pixels per em, since the selection happens for each glyph run that is drawn.
+    #[cfg(feature = "raster-images")]
+    strike_cache: HashMap<(Font, u32), Font>,
     /// The ref of the page tree.
     page_tree_ref: Option<Ref>,
+    /// The ref of the page tree node that is the direct parent of each page, indexed
+    /// by the page index. Only populated once the page tree has been serialized.
+    page_parents: Vec<Ref>,
     /// All global objects, such as PDF fonts, that are populated over time.
     pub(crate) global_objects: GlobalObjects,
     /// Information for each page written so far, index by the page index.
@@ -208,6 +522,24 @@ pub(crate) struct SerializeContext {
     /// need to merge limits from postscript functions, which are not directly accessible
     /// from the chunk they are written to.
     limits: Limits,
+    /// The total number of bytes of all content streams written so far.
+    content_bytes: usize,
+    /// Whether any content was skipped because it would have exceeded the content budget.
+    content_skipped: bool,
+    /// The number of object numbers that have been reserved for external use. Reserved
+    /// object numbers always occupy the range `1..=reserved_refs` in the final PDF.
+    reserved_refs: i32,
+    /// The description of the output intent, if it was overridden by the user.
+    output_intents: Vec<OutputIntent>,
+    /// The page label ranges of the document, if they were set by the user.
+    page_label_ranges: Option<Vec<(usize, PageLabel)>>,
+    /// Whether any content was marked as a page number artifact.
+    page_number_artifacts: bool,
+    /// The threshold for the number of annotations on a single page, as well as
+    /// the hook that should be called once a page reaches it.
+    annotation_warning: Option<(usize, AnnotationWarningHook)>,
+    /// The names of all form fields written so far.
+    form_field_names: HashSet<String>,
 }
 
 impl SerializeContext {
@@ -224,20 +556,116 @@ impl SerializeContext {
             serialize_settings.pdf_version = serialize_settings.validator.recommended_version();
         }
 
+        // Object streams were only introduced in PDF 1.5. Since PDF/A-1 is always
+        // exported as PDF 1.4, this also covers it.
+        serialize_settings.use_object_streams &=
+            serialize_settings.pdf_version >= PdfVersion::Pdf15;
+        serialize_settings.use_object_streams &= !serialize_settings.linearize;
+
         Self {
             cached_mappings: HashMap::new(),
             font_cache: HashMap::new(),
+            #[cfg(feature = "raster-images")]
+            strike_cache: HashMap::new(),
             global_objects: GlobalObjects::default(),
             cur_ref: Ref::new(1),
             chunk_container: ChunkContainer::new(),
             page_tree_ref: None,
+            page_parents: vec![],
             page_infos: vec![],
             validation_errors: vec![],
             serialize_settings: Arc::new(serialize_settings),
             limits: Limits::new(),
+            content_bytes: 0,
+            content_skipped: false,
+            reserved_refs: 0,
+            output_intents: vec![],
+            page_label_ranges: None,
+            page_number_artifacts: false,
+            annotation_warning: None,
+            form_field_names: HashSet::new(),
         }
     }
 
+    pub(crate) fn reserve_ref(&mut self) -> Ref {
+        self.reserved_refs += 1;
+        Ref::new(self.reserved_refs)
+    }
+
+    pub(crate) fn reserved_refs(&self) -> i32 {
+        self.reserved_refs
+    }
+
+    pub(crate) fn register_raw_object(&mut self, write: impl FnOnce(&mut Chunk, Ref)) -> Ref {
+        let ref_ = self.new_ref();
+        let mut chunk = Chunk::new();
+        write(&mut chunk, ref_);
+        assert!(
+            chunk.refs().eq([ref_]),
+            "a raw object must consist of exactly one object with the given reference"
+        );
+        self.chunk_container.raw_objects.push(chunk);
+
+        ref_
+    }
+
+    pub(crate) fn set_xmp_hook(&mut self, hook: XmpHook) {
+        self.chunk_container.xmp_hook = Some(hook);
+    }
+
+    pub(crate) fn set_annotation_warning(&mut self, threshold: usize, hook: AnnotationWarningHook) {
+        self.annotation_warning = Some((threshold, hook));
+    }
+
+    /// Call the annotation warning hook if the number of annotations of a page
+    /// went from below the threshold to at least the threshold.
+    pub(crate) fn check_annotation_count(&self, page_index: usize, before: usize, after: usize) {
+        if let Some((threshold, hook)) = &self.annotation_warning {
+            if before < *threshold && after >= *threshold {
+                hook(page_index, after);
+            }
+        }
+    }
+
+    pub(crate) fn set_catalog_entry(&mut self, key: String, value: Ref) {
+        self.chunk_container.catalog_entries.push((key, value));
+    }
+
+    pub(crate) fn register_layer(&mut self, name: &str, visible: bool) -> Ref {
+        let ref_ = self.new_ref();
+        let mut chunk = Chunk::new();
+        chunk
+            .indirect(ref_)
+            .dict()
+            .pair(Name(b"Type"), Name(b"OCG"))
+            .pair(Name(b"Name"), TextStr(name));
+
+        self.chunk_container.layers.push(chunk);
+        self.chunk_container.layer_states.push((ref_, visible));
+
+        ref_
+    }
+
+    pub(crate) fn register_form_field(&mut self, name: &str, ref_: Ref) -> KrillaResult<()> {
+        if !self.form_field_names.insert(name.to_string()) {
+            return Err(KrillaError::UserError(format!(
+                "the form field name `{name}` is used more than once"
+            )));
+        }
+
+        self.chunk_container.form_fields.push(ref_);
+
+        Ok(())
+    }
+
+    pub(crate) fn register_form_font(&mut self, font: FieldFont) {
+        self.chunk_container.form_fonts.insert(font);
+    }
+
+    pub(crate) fn set_need_appearances(&mut self, need_appearances: bool) {
+        self.chunk_container.need_appearances = need_appearances;
+    }
+
     pub(crate) fn page_infos(&self) -> &[PageInfo] {
         &self.page_infos
     }
@@ -259,10 +687,38 @@ impl SerializeContext {
         }
     }
 
+    pub(crate) fn set_open_action(&mut self, action: Action) {
+        self.global_objects.open_action = MaybeTaken::new(Some(action));
+    }
+
     pub(crate) fn set_metadata(&mut self, metadata: Metadata) {
         self.chunk_container.metadata = Some(metadata);
     }
 
+    pub(crate) fn set_output_intent(&mut self, output_intent: OutputIntent) {
+        self.output_intents = vec![output_intent];
+    }
+
+    pub(crate) fn add_output_intent(&mut self, output_intent: OutputIntent) {
+        self.output_intents.push(output_intent);
+    }
+
+    pub(crate) fn used_glyphs(&self) -> HashMap<Font, BTreeSet<GlyphId>> {
+        self.global_objects
+            .font_map
+            .iter()
+            .map(|(font, container)| (font.clone(), container.borrow().used_glyphs()))
+            .collect()
+    }
+
+    pub(crate) fn set_page_labels(&mut self, ranges: Vec<(usize, PageLabel)>) {
+        self.page_label_ranges = Some(ranges);
+    }
+
+    pub(crate) fn register_page_number_artifact(&mut self) {
+        self.page_number_artifacts = true;
+    }
+
     pub(crate) fn set_tag_tree(&mut self, root: TagTree) {
         // Only set the tag tree if the user actually enabled tagging.
         if self.serialize_settings.enable_tagging {
@@ -284,45 +740,92 @@ impl SerializeContext {
             .get_or_insert_with(|| self.cur_ref.bump())
     }
 
+    /// Return the ref of the page tree node the page with the given index belongs to.
+    pub(crate) fn page_parent_ref(&mut self, page_index: usize) -> Ref {
+        match self.page_parents.get(page_index) {
+            Some(parent) => *parent,
+            None => self.page_tree_ref(),
+        }
+    }
+
+    /// Return the font that should be used to draw glyphs with the given number of
+    /// pixels per em.
+    ///
+    /// Unless a strike size was set explicitly, bitmap fonts use the strike that fits
+    /// the size best, see [`Font::with_bitmap_strike_size`].
+    pub(crate) fn font_for_ppem(&mut self, font: Font, ppem: f32) -> Font {
+        #[cfg(feature = "raster-images")]
+        if font.allow_color() && font.bitmap_strike_size().is_none() {
+            // Strikes always have an integer size, so rounding up doesn't change
+            // which one is selected.
+            let key = (font, ppem.ceil() as u32);
+
+            if let Some(font) = self.strike_cache.get(&key) {
+                return font.clone();
+            }
+
+            let font = match crate::font::bitmap::strike_size(&key.0, key.1 as f32) {
+                Some(size) => key.0.clone().with_bitmap_strike_size(size),
+                None => key.0.clone(),
+            };
+            self.strike_cache.insert(key, font.clone());
+
+            return font;
+        }
+
+        #[cfg(not(feature = "raster-images"))]
+        let _ = ppem;
+
+        font
+    }
+
     pub(crate) fn register_font_container(&mut self, font: Font) -> Rc<RefCell<FontContainer>> {
-        self.global_objects
-            .font_map
-            .entry(font.clone())
-            .or_insert_with(|| {
-                self.font_cache
-                    .insert(font.font_info().clone(), font.clone());
-
-                // Right now, we decide whether to embed a font as a Type3 font
-                // solely based on whether one of these tables exist (or if
-                // the settings tell us to force it). This is not the most "efficient"
-                // method, because it is possible a font has a `COLR` table, but
-                // there are still some glyphs which are not in COLR but in `glyf`
-                // or `CFF`. In this case, we would still choose a Type3 font for
-                // the outlines, even though they could be embedded as a CID font.
-                // For now, we make the simplifying assumption that a font is either mapped
-                // to a series of Type3 fonts or to a single CID font, but not a mix of both.
-                let font_ref = font.font_ref();
-                let use_type3 = if !font.allow_color() {
-                    false
-                } else {
-                    font_ref.svg().is_ok()
-                        || font_ref.colr().is_ok()
-                        || font_ref.sbix().is_ok()
-                        || font_ref.cbdt().is_ok()
-                        || font_ref.ebdt().is_ok()
-                };
-
-                if use_type3 {
-                    Rc::new(RefCell::new(FontContainer::Type3(Type3FontMapper::new(
-                        font.clone(),
-                    ))))
-                } else {
-                    Rc::new(RefCell::new(FontContainer::CIDFont(CIDFont::new(
-                        font.clone(),
-                    ))))
-                }
-            })
-            .clone()
+        if let Some(container) = self.global_objects.font_map.get(&font) {
+            return container.clone();
+        }
+
+        self.font_cache
+            .insert(font.font_info().clone(), font.clone());
+
+        // Right now, we decide whether to embed a font as a Type3 font
+        // solely based on whether one of these tables exist (or if
+        // the settings tell us to force it). This is not the most "efficient"
+        // method, because it is possible a font has a `COLR` table, but
+        // there are still some glyphs which are not in COLR but in `glyf`
+        // or `CFF`. In this case, we would still choose a Type3 font for
+        // the outlines, even though they could be embedded as a CID font.
+        // For now, we make the simplifying assumption that a font is either mapped
+        // to a series of Type3 fonts or to a single CID font, but not a mix of both.
+        let font_ref = font.font_ref();
+        let use_type3 = if !font.allow_color() {
+            false
+        } else {
+            font_ref.svg().is_ok()
+                || font_ref.colr().is_ok()
+                || font_ref.sbix().is_ok()
+                || font_ref.cbdt().is_ok()
+                || font_ref.ebdt().is_ok()
+        };
+
+        let container = if use_type3 {
+            Rc::new(RefCell::new(FontContainer::Type3(Type3FontMapper::new(
+                font.clone(),
+            ))))
+        } else if font.allow_color() {
+            // The `allow_color` flag doesn't make a difference for fonts that are
+            // embedded as a CID font, so we share the container with the monochrome
+            // version of the font, so that the font program is only embedded once.
+            self.register_font_container(font.clone().with_allow_color(false))
+        } else {
+            Rc::new(RefCell::new(FontContainer::CIDFont(CIDFont::new(
+                font.clone(),
+                self.serialize_settings.subset_fonts,
+            ))))
+        };
+
+        self.global_objects.font_map.insert(font, container.clone());
+
+        container
     }
 
     #[cfg(feature = "fontdb")]
@@ -357,30 +860,77 @@ impl SerializeContext {
         map
     }
 
-    pub(crate) fn finish(mut self) -> KrillaResult<Pdf> {
+    #[cfg(feature = "encryption")]
+    fn check_encryption(&mut self) -> KrillaResult<()> {
+        if let Some(encryption) = &self.serialize_settings.encryption {
+            self.register_validation_error(ValidationError::Encryption);
+
+            let min_version = match encryption.algorithm {
+                EncryptionAlgorithm::Rc4_128 => None,
+                EncryptionAlgorithm::Aes128 => Some(("AES-128", PdfVersion::Pdf16)),
+                EncryptionAlgorithm::Aes256 => Some(("AES-256", PdfVersion::Pdf17)),
+            };
+
+            if let Some((name, min_version)) = min_version {
+                if self.serialize_settings.pdf_version < min_version {
+                    return Err(KrillaError::UserError(format!(
+                        "{name} encryption requires at least {}",
+                        min_version.as_str()
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn finish<W: Write>(mut self, sink: W, streamed: bool) -> KrillaResult<W> {
         // We need to be careful here that we serialize the objects in the right order,
         // as in some cases we use MaybeTake::take to remove an object, which means that
         // no object that is serialized afterwards must depend on it.
 
-        // Serialize all objects that can only be written in the end.
+        if let Some(max) = self.serialize_settings.max_content_bytes {
+            if self.content_skipped || self.content_bytes > max {
+                return Err(KrillaError::ContentBudgetExceeded(max));
+            }
+        }
+
+        #[cfg(feature = "encryption")]
+        self.check_encryption()?;
+
+        // Linearization renumbers all objects, so reserved object
+        // numbers wouldn't be stable anymore.
+        if self.serialize_settings.linearize && self.reserved_refs > 0 {
+            return Err(KrillaError::UserError(
+                "reserved objects cannot be used in a linearized document".to_string(),
+            ));
+        }
+
+        // Serialize all objects that can only be written in the end. Deferred texts
+        // need to come first, since they might still add new glyphs to the fonts.
+        #[cfg(feature = "simple-text")]
+        self.serialize_deferred_texts();
         self.serialize_destination_profiles();
-        self.serialize_page_label_tree();
+        self.serialize_page_label_tree()?;
         self.serialize_outline()?;
+        self.serialize_open_action()?;
         self.serialize_fonts()?;
-        self.serialize_pages()?;
+        // The page tree needs to be serialized before the pages, so that we know the
+        // parent node of each page.
         self.serialize_page_tree();
-        self.serialize_xyz_destinations()?;
+        self.serialize_pages()?;
+        self.serialize_page_destinations()?;
         // It is important that we serialize the tags AFTER we have serialized the pages,
         // because page serialization will update the annotation refs of the page infos,
         // and when serializing the parent tree map we need to know the refs of the annotations
         self.serialize_tag_tree()?;
 
-        // Create the final PDF.
-        let pdf = {
+        // Write the final PDF.
+        let output = {
             let chunk_container = std::mem::take(&mut self.chunk_container);
-            chunk_container.finish(&mut self)?
+            chunk_container.finish(&mut self, sink, streamed)?
         };
-        self.register_limits(pdf.limits());
+        self.register_limits(output.limits());
 
         self.check_limits();
 
@@ -391,7 +941,7 @@ impl SerializeContext {
         // Just a sanity check that we've actually processed all items.
         self.global_objects.assert_all_taken();
 
-        Ok(pdf)
+        output.finish()
     }
 }
 
@@ -407,6 +957,25 @@ impl SerializeContext {
         self.limits.merge(limits);
     }
 
+    pub(crate) fn register_content_bytes(&mut self, len: usize) {
+        self.content_bytes = self.content_bytes.saturating_add(len);
+    }
+
+    /// Whether the content budget set in the serialize settings has been exceeded,
+    /// including `pending` bytes of content streams that are still being written.
+    ///
+    /// If it has, the content that is about to be drawn is considered to be skipped,
+    /// so that exporting the document fails.
+    pub(crate) fn content_budget_exceeded(&mut self, pending: usize) -> bool {
+        let exceeded = self
+            .serialize_settings
+            .max_content_bytes
+            .is_some_and(|max| self.content_bytes.saturating_add(pending) > max);
+        self.content_skipped |= exceeded;
+
+        exceeded
+    }
+
     pub(crate) fn register_page_struct_parent(
         &mut self,
         page_index: usize,
@@ -447,10 +1016,26 @@ impl SerializeContext {
     }
 
     pub(crate) fn register_named_destination(&mut self, nd: NamedDestination) {
-        let dest_ref = self.register_xyz_destination((*nd.xyz_dest).clone());
+        let dest_ref = self.register_page_destination(PageDestination::Xyz((*nd.xyz_dest).clone()));
         self.global_objects.named_destinations.insert(nd, dest_ref);
     }
 
+    #[cfg(feature = "simple-text")]
+    pub(crate) fn register_deferred_texts(
+        &mut self,
+        x_object_ref: Ref,
+        page_index: usize,
+        texts: Vec<DeferredText>,
+    ) {
+        self.global_objects
+            .deferred_texts
+            .push((x_object_ref, page_index, texts));
+    }
+
+    pub(crate) fn register_annotation_tag_group(&mut self, group: TagGroup) {
+        self.global_objects.annotation_tag_groups.push(group);
+    }
+
     pub(crate) fn register_page(&mut self, page: InternalPage) {
         let ref_ = self.new_ref();
         self.page_infos.push(PageInfo {
@@ -504,9 +1089,9 @@ impl SerializeContext {
         })
     }
 
-    pub(crate) fn register_xyz_destination(&mut self, dest: XyzDestination) -> Ref {
+    pub(crate) fn register_page_destination(&mut self, dest: PageDestination) -> Ref {
         self.register_cached(dest, |sc, object, root_ref| {
-            sc.global_objects.xyz_destinations.push((root_ref, object));
+            sc.global_objects.page_destinations.push((root_ref, object));
         })
     }
 
@@ -533,12 +1118,22 @@ impl SerializeContext {
             ColorSpace::Srgb => MaybeDeviceColorSpace::ColorSpace(self.register_resourceable(
                 ICCBasedColorSpace(self.serialize_settings.pdf_version.rgb_icc()),
             )),
-            ColorSpace::Luma => MaybeDeviceColorSpace::ColorSpace(self.register_resourceable(
-                ICCBasedColorSpace(self.serialize_settings.pdf_version.grey_icc()),
-            )),
+            ColorSpace::Luma => {
+                let profile = self
+                    .serialize_settings
+                    .gray_profile
+                    .clone()
+                    .unwrap_or_else(|| self.serialize_settings.pdf_version.grey_icc());
+                MaybeDeviceColorSpace::ColorSpace(
+                    self.register_resourceable(ICCBasedColorSpace(profile)),
+                )
+            }
             ColorSpace::Cmyk(cs) => {
                 MaybeDeviceColorSpace::ColorSpace(self.register_resourceable(cs))
             }
+            ColorSpace::Lab(cs) => {
+                MaybeDeviceColorSpace::ColorSpace(self.register_resourceable(cs))
+            }
             ColorSpace::DeviceGray => MaybeDeviceColorSpace::DeviceGray,
             ColorSpace::DeviceRgb => MaybeDeviceColorSpace::DeviceRgb,
             ColorSpace::DeviceCmyk => MaybeDeviceColorSpace::DeviceCMYK,
@@ -550,51 +1145,116 @@ impl SerializeContext {
 /// All methods are supposed to only be called once in `SerializeContext::finish`!
 impl SerializeContext {
     fn serialize_destination_profiles(&mut self) {
-        let validator = self.serialize_settings.validator;
-        self.chunk_container.destination_profiles = validator.output_intent().map(|subtype| {
-            let root_ref = self.new_ref();
-            let mut chunk = Chunk::new();
+        let required_subtype = self.serialize_settings.validator.output_intent();
+        let default_subtype = required_subtype
+            .clone()
+            .unwrap_or(OutputIntentSubtype::PdfX);
+        let icc_profile = self.serialize_settings.pdf_version.rgb_icc();
+        let mut output_intents = std::mem::take(&mut self.output_intents);
+
+        // If the validator requires an output intent with a specific subtype and the user
+        // didn't provide one, we fall back to an sRGB output intent.
+        if let Some(required_subtype) = &required_subtype {
+            let has_required = output_intents
+                .iter()
+                .any(|oi| oi.subtype.as_ref().unwrap_or(&default_subtype) == required_subtype);
+
+            if !has_required {
+                output_intents.push(
+                    OutputIntent::new("Custom".to_string())
+                        .with_output_condition("sRGB".to_string())
+                        .with_registry_name(String::new())
+                        .with_info(format!(
+                            "sRGB v{}.{}",
+                            icc_profile.metadata().major,
+                            icc_profile.metadata().minor
+                        )),
+                );
+            }
+        }
+
+        if output_intents.is_empty() {
+            return;
+        }
+
+        let root_ref = self.new_ref();
+        let mut chunk = Chunk::new();
+        let mut oi_refs = vec![];
+        let mut dest_profile_refs = HashSet::new();
+
+        for output_intent in &output_intents {
+            // ICC profiles are cached by their content, so if the same profile is used
+            // by an image or a color space, it will only be embedded once.
+            let dest_profile_ref = match &output_intent.dest_profile {
+                Some(profile) => self.register_cacheable(profile.clone()),
+                None => {
+                    self.register_validation_error(ValidationError::MissingOutputIntentProfile);
+                    self.register_cacheable(icc_profile.clone())
+                }
+            };
+            dest_profile_refs.insert(dest_profile_ref);
 
             let oi_ref = self.new_ref();
-            let mut oi = chunk.indirect(oi_ref).start::<OutputIntent>();
-            let icc_profile = self.serialize_settings.pdf_version.rgb_icc();
-
-            oi.dest_output_profile(self.register_cacheable(icc_profile.clone()))
-                .subtype(subtype)
-                .output_condition_identifier(TextStr("Custom"))
-                .output_condition(TextStr("sRGB"))
-                .registry_name(TextStr(""))
-                .info(TextStr(
-                    format!(
-                        "sRGB v{}.{}",
-                        icc_profile.metadata().major,
-                        icc_profile.metadata().minor
-                    )
-                    .as_str(),
-                ));
+            let mut oi = chunk
+                .indirect(oi_ref)
+                .start::<pdf_writer::writers::OutputIntent>();
+            oi.dest_output_profile(dest_profile_ref).subtype(
+                output_intent
+                    .subtype
+                    .as_ref()
+                    .unwrap_or(&default_subtype)
+                    .to_pdf(),
+            );
+            output_intent.write(&mut oi);
             oi.finish();
 
-            let mut array = chunk.indirect(root_ref).array();
-            array.item(oi_ref);
-            array.finish();
+            oi_refs.push(oi_ref);
+        }
 
-            (root_ref, chunk)
-        });
+        if dest_profile_refs.len() > 1 {
+            self.register_validation_error(ValidationError::ConflictingOutputIntentProfiles);
+        }
+
+        chunk.indirect(root_ref).array().items(oi_refs);
+
+        self.chunk_container.destination_profiles = Some((root_ref, chunk));
     }
 
-    fn serialize_page_label_tree(&mut self) {
-        if let Some(container) = PageLabelContainer::new(
-            &self
-                .global_objects
-                .pages
-                .iter()
-                .map(|(_, p)| p.page_settings.page_label().clone())
-                .collect::<Vec<_>>(),
-        ) {
+    fn serialize_page_label_tree(&mut self) -> KrillaResult<()> {
+        // Page label ranges set on the document take precedence over the labels
+        // of the individual pages.
+        let container = match &self.page_label_ranges {
+            Some(ranges) => PageLabelContainer::from_ranges(ranges, self.page_infos.len())?,
+            None => PageLabelContainer::new(
+                &self
+                    .global_objects
+                    .pages
+                    .iter()
+                    .map(|(_, p)| p.page_settings.page_label().clone())
+                    .collect::<Vec<_>>(),
+            ),
+        };
+
+        if let Some(container) = container {
             let page_label_tree_ref = self.new_ref();
             let chunk = container.serialize(self, page_label_tree_ref);
             self.chunk_container.page_label_tree = Some((page_label_tree_ref, chunk));
+        } else if self.page_number_artifacts {
+            self.register_validation_error(ValidationError::MissingPageLabels);
         }
+
+        Ok(())
+    }
+
+    fn serialize_open_action(&mut self) -> KrillaResult<()> {
+        if let Some(action) = self.global_objects.open_action.take() {
+            let mut chunk = Chunk::new();
+            let action_ref = self.new_ref();
+            action.serialize(self, chunk.indirect(action_ref).start())?;
+            self.chunk_container.open_action = Some((action_ref, chunk));
+        }
+
+        Ok(())
     }
 
     fn serialize_outline(&mut self) -> KrillaResult<()> {
@@ -610,9 +1270,77 @@ impl SerializeContext {
         Ok(())
     }
 
+    #[cfg(feature = "simple-text")]
+    fn serialize_deferred_texts(&mut self) {
+        let deferred_texts = self.global_objects.deferred_texts.take();
+        let page_count = self.page_infos.len();
+
+        for (x_object_ref, page_index, texts) in deferred_texts {
+            let mut stream = None;
+            let page_identifier = self
+                .serialize_settings
+                .enable_tagging
+                .then(|| PageTagIdentifier::new(page_index, 0));
+
+            let mut surface = Surface::new(
+                self,
+                ContentBuilder::new(Transform::identity()),
+                page_identifier,
+                Box::new(|s, _| stream = Some(s)),
+            );
+
+            surface.start_tagged(ContentTag::Artifact(ArtifactType::Footer));
+            for text in &texts {
+                surface.fill_text(
+                    text.start,
+                    Fill::default(),
+                    text.font.clone(),
+                    text.font_size,
+                    &[],
+                    &(text.text)(page_index, page_count),
+                    false,
+                    TextDirection::Auto,
+                );
+            }
+            surface.end_tagged();
+            surface.finish();
+
+            let x_object = XObject::new(stream.unwrap(), false, false, None);
+            let chunk = x_object.serialize(self, x_object_ref);
+            self.chunk_container.x_objects.push(chunk);
+        }
+    }
+
     fn serialize_fonts(&mut self) -> KrillaResult<()> {
         let fonts = self.global_objects.font_map.take();
-        for font_container in fonts.values() {
+
+        // Subsetting is by far the most expensive part of serializing a font and doesn't
+        // depend on anything else, so if requested, it's done for all fonts in parallel
+        // up front. The subsets are in the same order as the fonts below.
+        #[cfg(feature = "rayon")]
+        let mut subsets = self.serialize_settings.parallel.then(|| {
+            use rayon::prelude::*;
+
+            let cid_fonts = fonts
+                .iter()
+                .filter_map(|(font, font_container)| match &*font_container.borrow() {
+                    FontContainer::CIDFont(cid_font) if cid_font.font() == *font => {
+                        Some(cid_font.clone())
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+
+            cid_fonts
+                .par_iter()
+                .map(CIDFont::subset)
+                .collect::<Vec<_>>()
+                .into_iter()
+        });
+        #[cfg(not(feature = "rayon"))]
+        let mut subsets: Option<std::vec::IntoIter<KrillaResult<Vec<u8>>>> = None;
+
+        for (font, font_container) in fonts.iter() {
             match &*font_container.borrow() {
                 FontContainer::Type3(font_mapper) => {
                     for t3_font in font_mapper.fonts() {
@@ -621,9 +1349,16 @@ impl SerializeContext {
                         self.chunk_container.fonts.push(chunk);
                     }
                 }
+                // Fonts that share their container with a different font are
+                // serialized as part of the entry of that font.
+                FontContainer::CIDFont(cid_font) if cid_font.font() != *font => {}
                 FontContainer::CIDFont(cid_font) => {
+                    let subsetted = match &mut subsets {
+                        Some(subsets) => subsets.next().unwrap()?,
+                        None => cid_font.subset()?,
+                    };
                     let f = self.register_font_identifier(cid_font.identifier());
-                    let chunk = cid_font.serialize(self, f.get_ref())?;
+                    let chunk = cid_font.serialize(self, f.get_ref(), subsetted)?;
                     self.chunk_container.fonts.push(chunk);
                 }
             }
@@ -643,19 +1378,87 @@ impl SerializeContext {
     }
 
     fn serialize_page_tree(&mut self) {
-        if let Some(page_tree_ref) = self.page_tree_ref {
-            let mut page_tree_chunk = Chunk::new();
-            page_tree_chunk
-                .pages(page_tree_ref)
-                .count(self.page_infos.len() as i32)
-                .kids(self.page_infos.iter().map(|i| i.ref_));
-            self.chunk_container.page_tree = Some((page_tree_ref, page_tree_chunk));
+        if self.page_infos.is_empty() {
+            return;
         }
+
+        let page_tree_ref = self.page_tree_ref();
+        let max_kids = self
+            .serialize_settings
+            .max_page_tree_kids
+            .unwrap_or(usize::MAX)
+            .max(2);
+
+        let mut page_tree_chunk = Chunk::new();
+        self.page_parents = vec![page_tree_ref; self.page_infos.len()];
+        self.serialize_page_tree_node(
+            &mut page_tree_chunk,
+            page_tree_ref,
+            None,
+            0..self.page_infos.len(),
+            max_kids,
+        );
+        self.chunk_container.page_tree = Some((page_tree_ref, page_tree_chunk));
     }
 
-    fn serialize_xyz_destinations(&mut self) -> KrillaResult<()> {
-        let xyz_destinations = self.global_objects.xyz_destinations.take();
-        for (ref_, dest) in &xyz_destinations {
+    fn serialize_page_tree_node(
+        &mut self,
+        chunk: &mut Chunk,
+        ref_: Ref,
+        parent: Option<Ref>,
+        pages: Range<usize>,
+        max_kids: usize,
+    ) {
+        let mut node = chunk.pages(ref_);
+
+        if let Some(parent) = parent {
+            node.parent(parent);
+        }
+
+        node.count(pages.len() as i32);
+
+        if pages.len() <= max_kids {
+            node.kids(
+                pages
+                    .clone()
+                    .map(|page_index| self.page_infos[page_index].ref_),
+            );
+            node.finish();
+
+            for page_index in pages {
+                self.page_parents[page_index] = ref_;
+            }
+        } else {
+            // Choose the number of pages per kid such that the kids are as full as possible,
+            // which ensures that all pages end up at (roughly) the same depth.
+            let mut capacity = max_kids;
+            while capacity.saturating_mul(max_kids) < pages.len() {
+                capacity *= max_kids;
+            }
+
+            let kids = pages
+                .clone()
+                .step_by(capacity)
+                .map(|start| {
+                    (
+                        self.cur_ref.bump(),
+                        start..(start + capacity).min(pages.end),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            node.kids(kids.iter().map(|(kid_ref, _)| *kid_ref));
+            node.finish();
+
+            for (kid_ref, kid_pages) in kids {
+                self.serialize_page_tree_node(chunk, kid_ref, Some(ref_), kid_pages, max_kids);
+            }
+        }
+    }
+
+    fn serialize_page_destinations(&mut self) -> KrillaResult<()> {
+        let page_destinations = self.global_objects.page_destinations.take();
+        for (ref_, dest) in &page_destinations {
             let chunk = dest.serialize(self, *ref_)?;
             self.chunk_container.destinations.push(chunk);
         }
@@ -664,9 +1467,19 @@ impl SerializeContext {
     }
 
     fn serialize_tag_tree(&mut self) -> KrillaResult<()> {
-        let tag_tree = self.global_objects.tag_tree.take();
+        let mut tag_tree = self.global_objects.tag_tree.take();
         let struct_parents = self.global_objects.struct_parents.take();
+        let annotation_tag_groups = self.global_objects.annotation_tag_groups.take();
+
+        if let Some(root) = &mut tag_tree {
+            for group in annotation_tag_groups {
+                root.push(group);
+            }
+        }
+
         if let Some(root) = &tag_tree {
+            check_struct_parents(&struct_parents)?;
+
             let mut parent_tree_map = HashMap::new();
             let mut id_tree_map = BTreeMap::new();
             let struct_tree_root_ref = self.new_ref();
@@ -681,6 +1494,9 @@ impl SerializeContext {
             let mut chunk = Chunk::new();
             let mut tree = chunk.indirect(struct_tree_root_ref).start::<Dict>();
             tree.pair(Name(b"Type"), Name(b"StructTreeRoot"));
+            // Map the custom tags to standard structure types of PDF 1.7. Since we
+            // don't write structure namespaces, this is not enough for PDF 2.0 based
+            // standards like PDF/UA-2.
             let mut role_map = tree.insert(Name(b"RoleMap")).start::<RoleMap>();
             role_map.insert(Name(b"Datetime"), StructRole::Span);
             role_map.insert(Name(b"Terms"), StructRole::Part);
@@ -692,16 +1508,19 @@ impl SerializeContext {
             let mut parent_tree = tree.insert(Name(b"ParentTree")).start::<NumberTree<Ref>>();
             let mut tree_nums = parent_tree.nums();
 
+            // The key of each entry in the parent tree is the index of the element in
+            // `struct_parents`, since this is also how the keys were assigned to
+            // the pages and annotations.
             for (index, struct_parent) in struct_parents.iter().enumerate() {
                 match *struct_parent {
-                    StructParentElement::Page(index, num_mcids) => {
+                    StructParentElement::Page(page_index, num_mcids) => {
                         let mut list_chunk = Chunk::new();
                         let list_ref = self.new_ref();
 
                         let mut refs = list_chunk.indirect(list_ref).array();
 
                         for mcid in 0..num_mcids {
-                            let rci = PageTagIdentifier::new(index, mcid);
+                            let rci = PageTagIdentifier::new(page_index, mcid);
                             refs.item(parent_tree_map.get(&rci.into()).ok_or(
                                 KrillaError::UserError(
                                     "an identifier doesn't appear in the tag tree".to_string(),
@@ -719,7 +1538,9 @@ impl SerializeContext {
                             page_index,
                             annot_index,
                         ));
-                        let ref_ = parent_tree_map.get(&it).unwrap();
+                        let ref_ = parent_tree_map.get(&it).ok_or(KrillaError::UserError(
+                            "a tagged annotation doesn't appear in the tag tree".to_string(),
+                        ))?;
                         tree_nums.insert(index as i32, *ref_);
                     }
                 }
@@ -750,6 +1571,20 @@ impl SerializeContext {
         Ok(())
     }
 
+    /// Return all validation errors that were registered so far, including those
+    /// caused by exceeding the limits of the file format.
+    pub(crate) fn check_validation(&mut self) -> KrillaResult<()> {
+        self.check_limits();
+
+        if self.validation_errors.is_empty() {
+            Ok(())
+        } else {
+            Err(KrillaError::ValidationError(std::mem::take(
+                &mut self.validation_errors,
+            )))
+        }
+    }
+
     fn check_limits(&mut self) {
         const STR_LEN: usize = 32767;
         const NAME_LEN: usize = 127;
@@ -760,27 +1595,35 @@ impl SerializeContext {
         const ARRAY_LEN: usize = 8191;
 
         if self.cur_ref > Ref::new(8388607) {
-            self.register_validation_error(ValidationError::TooManyIndirectObjects)
+            self.register_limit_error(ValidationError::TooManyIndirectObjects)
         }
 
         if self.limits.str_len() > STR_LEN {
-            self.register_validation_error(ValidationError::TooLongString);
+            self.register_limit_error(ValidationError::TooLongString);
         }
 
         if self.limits.name_len() > NAME_LEN {
-            self.register_validation_error(ValidationError::TooLongName);
+            self.register_limit_error(ValidationError::TooLongName);
         }
 
         if self.limits.real() > MAX_FLOAT {
-            self.register_validation_error(ValidationError::TooLargeFloat);
+            self.register_limit_error(ValidationError::TooLargeFloat);
         }
 
         if self.limits.array_len() > ARRAY_LEN {
-            self.register_validation_error(ValidationError::TooLongArray);
+            self.register_limit_error(ValidationError::TooLongArray);
         }
 
         if self.limits.dict_entries() > DICT_LEN {
-            self.register_validation_error(ValidationError::TooLongDictionary);
+            self.register_limit_error(ValidationError::TooLongDictionary);
+        }
+    }
+
+    /// Like `register_validation_error`, but doesn't register the same error twice,
+    /// since the limits are checked both before and after writing the file.
+    fn register_limit_error(&mut self, error: ValidationError) {
+        if !self.validation_errors.contains(&error) {
+            self.register_validation_error(error);
         }
     }
 }
@@ -840,10 +1683,10 @@ pub(crate) struct GlobalObjects {
     pub(crate) named_destinations: MaybeTaken<HashMap<NamedDestination, Ref>>,
     /// A map from fonts to font container.
     font_map: MaybeTaken<HashMap<Font, Rc<RefCell<FontContainer>>>>,
-    /// All XYZ destinations used in the document. The reason we need to store them
+    /// All page destinations used in the document. The reason we need to store them
     /// separately is that we can only serialize them in the very end, once all pages
     /// have been written, so that we know the Ref of the page they belong to.
-    xyz_destinations: MaybeTaken<Vec<(Ref, XyzDestination)>>,
+    page_destinations: MaybeTaken<Vec<(Ref, PageDestination)>>,
     /// All pages and their corresponding chunks. Similarly to destinations, they need
     /// to be written in the very end, because pages might contain annotations which in turn
     /// depend on future pages (not written yet), so pages must also only be written in the
@@ -853,18 +1696,31 @@ pub(crate) struct GlobalObjects {
     struct_parents: MaybeTaken<Vec<StructParentElement>>,
     /// Stores the document outline.
     outline: MaybeTaken<Option<Outline>>,
+    /// Stores the action that should be executed when opening the document.
+    open_action: MaybeTaken<Option<Action>>,
     /// Stores the tag tree.
     tag_tree: MaybeTaken<Option<TagTree>>,
+    /// Stores the tag groups of annotations that were tagged automatically
+    /// and need to be appended to the tag tree.
+    annotation_tag_groups: MaybeTaken<Vec<TagGroup>>,
+    /// Stores the deferred texts of each page, as well as the ref of the XObject
+    /// they should be written to and the index of the page.
+    #[cfg(feature = "simple-text")]
+    deferred_texts: MaybeTaken<Vec<(Ref, usize, Vec<DeferredText>)>>,
 }
 
 impl GlobalObjects {
     pub(crate) fn assert_all_taken(&self) {
         assert!(self.named_destinations.is_taken());
         assert!(self.font_map.is_taken());
-        assert!(self.xyz_destinations.is_taken());
+        assert!(self.page_destinations.is_taken());
         assert!(self.pages.is_taken());
         assert!(self.struct_parents.is_taken());
         assert!(self.outline.is_taken());
+        assert!(self.open_action.is_taken());
         assert!(self.tag_tree.is_taken());
+        assert!(self.annotation_tag_groups.is_taken());
+        #[cfg(feature = "simple-text")]
+        assert!(self.deferred_texts.is_taken());
     }
 }
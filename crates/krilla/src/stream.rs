@@ -31,7 +31,7 @@ use tiny_skia_path::{Rect, Transform};
 
 use crate::content::ContentBuilder;
 use crate::resource::{ResourceDictionary, ResourceDictionaryBuilder};
-use crate::serialize::SerializeContext;
+use crate::serialize::{Compression, SerializeContext};
 use crate::surface::Surface;
 use crate::util::RectWrapper;
 use crate::validation::ValidationError;
@@ -122,7 +122,6 @@ impl<'a> StreamBuilder<'a> {
 #[derive(Debug, Copy, Clone)]
 pub(crate) enum StreamFilter {
     Flate,
-    FlateMemoized,
     AsciiHex,
     Dct,
 }
@@ -132,7 +131,6 @@ impl StreamFilter {
         match self {
             Self::AsciiHex => Name(b"ASCIIHexDecode"),
             Self::Flate => Name(b"FlateDecode"),
-            Self::FlateMemoized => Name(b"FlateDecode"),
             Self::Dct => Name(b"DCTDecode"),
         }
     }
@@ -140,7 +138,6 @@ impl StreamFilter {
     pub(crate) fn is_binary(&self) -> bool {
         match self {
             StreamFilter::Flate => true,
-            StreamFilter::FlateMemoized => true,
             StreamFilter::AsciiHex => false,
             StreamFilter::Dct => true,
         }
@@ -151,7 +148,6 @@ impl StreamFilter {
     pub(crate) fn apply(&self, content: &[u8]) -> Vec<u8> {
         match self {
             StreamFilter::Flate => deflate_encode(content),
-            StreamFilter::FlateMemoized => deflate_encode_memoized(content),
             StreamFilter::AsciiHex => hex_encode(content),
             // Note: We don't actually encode manually with DCT, because
             // this is only used for JPEG images which are already encoded,
@@ -209,23 +205,55 @@ impl<'a> FilterStreamBuilder<'a> {
     ) -> Self {
         let mut filter_stream = Self::empty(content);
 
-        if serialize_settings.compress_content_streams {
-            filter_stream.add_filter(StreamFilter::FlateMemoized);
+        if let Some(level) = serialize_settings.content_compression_level() {
+            filter_stream.content = Cow::Owned(deflate_encode_memoized(content, level));
+            filter_stream.add_unapplied_filter(StreamFilter::Flate);
         }
 
         filter_stream
     }
 
-    pub(crate) fn new_from_deflated(content: &'a [u8]) -> Self {
+    /// Create a new stream from data that was compressed with [`deflate_encode`].
+    pub(crate) fn new_from_deflated(
+        content: &'a [u8],
+        serialize_settings: &SerializeSettings,
+    ) -> Self {
         let mut filter_stream = Self::empty(content);
-        filter_stream.add_unapplied_filter(StreamFilter::Flate);
+
+        let compression = serialize_settings.effective_compression();
+
+        if compression == Compression::Default {
+            filter_stream.add_unapplied_filter(StreamFilter::Flate);
+            return filter_stream;
+        }
+
+        // The data was compressed with the default level, so we need to compress it again.
+        match miniz_oxide::inflate::decompress_to_vec_zlib(content) {
+            Ok(decompressed) => {
+                if let Some(level) = compression.level() {
+                    filter_stream.content =
+                        Cow::Owned(deflate_encode_with_level(&decompressed, level));
+                    filter_stream.add_unapplied_filter(StreamFilter::Flate);
+                } else {
+                    filter_stream.content = Cow::Owned(decompressed);
+                }
+            }
+            Err(_) => filter_stream.add_unapplied_filter(StreamFilter::Flate),
+        }
 
         filter_stream
     }
 
-    pub(crate) fn new_from_binary_data(content: &'a [u8]) -> Self {
+    pub(crate) fn new_from_binary_data(
+        content: &'a [u8],
+        serialize_settings: &SerializeSettings,
+    ) -> Self {
         let mut filter_stream = Self::empty(content);
-        filter_stream.add_filter(StreamFilter::Flate);
+
+        if let Some(level) = serialize_settings.effective_compression().level() {
+            filter_stream.content = Cow::Owned(deflate_encode_with_level(content, level));
+            filter_stream.add_unapplied_filter(StreamFilter::Flate);
+        }
 
         filter_stream
     }
@@ -288,14 +316,20 @@ impl FilterStream<'_> {
     }
 }
 
+/// The flate compression level that is used by default.
+pub(crate) const DEFAULT_COMPRESSION_LEVEL: u8 = 6;
+
 #[cfg_attr(feature = "comemo", comemo::memoize)]
-fn deflate_encode_memoized(data: &[u8]) -> Vec<u8> {
-    deflate_encode(data)
+fn deflate_encode_memoized(data: &[u8], level: u8) -> Vec<u8> {
+    deflate_encode_with_level(data, level)
 }
 
 pub(crate) fn deflate_encode(data: &[u8]) -> Vec<u8> {
-    const COMPRESSION_LEVEL: u8 = 6;
-    miniz_oxide::deflate::compress_to_vec_zlib(data, COMPRESSION_LEVEL)
+    deflate_encode_with_level(data, DEFAULT_COMPRESSION_LEVEL)
+}
+
+pub(crate) fn deflate_encode_with_level(data: &[u8], level: u8) -> Vec<u8> {
+    miniz_oxide::deflate::compress_to_vec_zlib(data, level)
 }
 
 fn hex_encode(data: &[u8]) -> Vec<u8> {
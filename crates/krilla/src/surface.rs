@@ -13,24 +13,35 @@ use rustybuzz::{Direction, Feature, UnicodeBuffer};
 use skrifa::GlyphId;
 #[cfg(feature = "fontdb")]
 use std::collections::HashMap;
+#[cfg(feature = "simple-text")]
+use std::ops::Range;
 #[cfg(feature = "raster-images")]
 use tiny_skia_path::Size;
 use tiny_skia_path::{NormalizedF32, Rect};
-use tiny_skia_path::{Path, Point, Transform};
+use tiny_skia_path::{Path, PathBuilder, Point, Transform};
 
 use crate::content::{unit_normalize, ContentBuilder};
-use crate::font::{draw_glyph, Font, Glyph, GlyphUnits, KrillaGlyph};
-use crate::object::font::PaintMode;
+use crate::document::Layer;
+#[cfg(feature = "pdf")]
+use crate::error::KrillaResult;
+use crate::font::outline::glyph_path;
+use crate::font::{draw_glyph, ppem, Font, Glyph, GlyphUnits, KrillaGlyph, PositionedGlyph};
+use crate::object::font::{FontContainer, PaintMode};
 #[cfg(feature = "raster-images")]
 use crate::object::image::Image;
 use crate::object::mask::Mask;
+#[cfg(feature = "pdf")]
+use crate::object::pdf_page::PdfPage;
 use crate::object::shading_function::ShadingFunction;
+use crate::paint::Paint;
 use crate::path::{Fill, FillRule, Stroke};
+#[cfg(feature = "simple-text")]
+use crate::serialize::NotdefFallback;
 use crate::serialize::SerializeContext;
 use crate::stream::{Stream, StreamBuilder};
 #[cfg(feature = "svg")]
 use crate::svg;
-use crate::tagging::{ContentTag, Identifier, PageTagIdentifier};
+use crate::tagging::{ArtifactType, ContentTag, Identifier, PageTagIdentifier};
 use crate::util::RectExt;
 use crate::SvgSettings;
 
@@ -42,7 +53,12 @@ pub(crate) enum PushInstruction {
     ClipPath,
     BlendMode,
     Mask(Box<Mask>),
-    Isolated,
+    TransparencyGroup {
+        isolated: bool,
+        knockout: bool,
+    },
+    /// Stores whether a tagged section was already active when the layer was pushed.
+    Layer(bool),
 }
 
 /// A surface.
@@ -106,16 +122,71 @@ impl<'a> Surface<'a> {
 
     /// Fill a path.
     pub fn fill_path(&mut self, path: &Path, fill: Fill) {
+        if self.content_budget_exceeded() {
+            return;
+        }
+
         Self::cur_builder_mut(&mut self.root_builder, &mut self.sub_builders)
             .fill_path(path, fill, self.sc);
     }
 
     /// Stroke a path.
     pub fn stroke_path(&mut self, path: &Path, stroke: Stroke) {
+        if self.content_budget_exceeded() {
+            return;
+        }
+
         Self::cur_builder_mut(&mut self.root_builder, &mut self.sub_builders)
             .stroke_path(path, stroke, self.sc)
     }
 
+    /// Stroke a number of independent line segments.
+    ///
+    /// All segments are combined into a single path, which leads to much smaller
+    /// content streams than stroking each segment separately, for example when
+    /// drawing the grid lines of a table. If you want dashed lines, you can
+    /// simply use a stroke with a dash pattern.
+    pub fn draw_lines(&mut self, lines: &[(Point, Point)], stroke: Stroke) {
+        let mut builder = PathBuilder::new();
+
+        for (start, end) in lines {
+            builder.move_to(start.x, start.y);
+            builder.line_to(end.x, end.y);
+        }
+
+        if let Some(path) = builder.finish() {
+            self.stroke_path(&path, stroke);
+        }
+    }
+
+    /// Stroke a grid that divides a rectangle into `rows` rows and `cols` columns
+    /// of equal size, including the border of the rectangle.
+    ///
+    /// Like [`Surface::draw_lines`], the whole grid is drawn as a single path.
+    pub fn draw_grid(&mut self, rect: Rect, rows: u16, cols: u16, stroke: Stroke) {
+        let rows = rows.max(1);
+        let cols = cols.max(1);
+        let mut lines = Vec::with_capacity(rows as usize + cols as usize + 2);
+
+        for row in 0..=rows {
+            let y = rect.top() + rect.height() * row as f32 / rows as f32;
+            lines.push((
+                Point::from_xy(rect.left(), y),
+                Point::from_xy(rect.right(), y),
+            ));
+        }
+
+        for col in 0..=cols {
+            let x = rect.left() + rect.width() * col as f32 / cols as f32;
+            lines.push((
+                Point::from_xy(x, rect.top()),
+                Point::from_xy(x, rect.bottom()),
+            ));
+        }
+
+        self.draw_lines(&lines, stroke);
+    }
+
     /// Start a new tagged content section.
     ///
     /// # Panics
@@ -129,6 +200,10 @@ impl<'a> Surface<'a> {
                 // for the sake of simplicity. But the user of the library does not need to know
                 // about this.
                 ContentTag::Artifact(at) => {
+                    if at == ArtifactType::PageNumber {
+                        self.sc.register_page_number_artifact();
+                    }
+
                     if at.requires_properties() {
                         Self::cur_builder_mut(&mut self.root_builder, &mut self.sub_builders)
                             .start_marked_content_with_properties(self.sc, None, tag);
@@ -170,6 +245,8 @@ impl<'a> Surface<'a> {
         glyph_units: GlyphUnits,
         paint_mode: PaintMode,
     ) {
+        let ppem = ppem(font_size, self.cur_transform());
+        let font = self.sc.font_for_ppem(font, ppem);
         let normalize = |val| unit_normalize(glyph_units, font.units_per_em(), font_size, val);
         let (mut cur_x, y) = (start.x, start.y);
 
@@ -212,6 +289,10 @@ impl<'a> Surface<'a> {
         glyph_units: GlyphUnits,
         outlined: bool,
     ) {
+        if self.content_budget_exceeded() {
+            return;
+        }
+
         if outlined {
             self.outline_glyphs(
                 glyphs,
@@ -227,6 +308,71 @@ impl<'a> Surface<'a> {
                 self.sc,
                 fill,
                 glyphs,
+                &[],
+                font,
+                text,
+                font_size,
+                glyph_units,
+            );
+        }
+    }
+
+    /// Draw a sequence of glyphs with a fill, where some glyphs have a different paint.
+    ///
+    /// This works the same as [`Surface::fill_glyphs`], but `glyph_paints` allows you to
+    /// override the paint of the fill for each glyph, where the paint at index `i` belongs
+    /// to the glyph at index `i`. Glyphs with a paint of `None` (or without a corresponding
+    /// entry) use the paint of `fill`. In contrast to splitting the glyphs into multiple runs,
+    /// this keeps all glyphs in a single text object, which is useful for example for
+    /// syntax-highlighted code.
+    #[allow(clippy::too_many_arguments)]
+    pub fn fill_glyphs_with_paints(
+        &mut self,
+        start: Point,
+        fill: Fill,
+        glyphs: &[impl Glyph],
+        glyph_paints: &[Option<Paint>],
+        font: Font,
+        text: &str,
+        font_size: f32,
+        glyph_units: GlyphUnits,
+        outlined: bool,
+    ) {
+        if self.content_budget_exceeded() {
+            return;
+        }
+
+        if outlined {
+            let normalize = |val| unit_normalize(glyph_units, font.units_per_em(), font_size, val);
+            let mut cur_x = start.x;
+
+            for (i, glyph) in glyphs.iter().enumerate() {
+                let fill = match glyph_paints.get(i) {
+                    Some(Some(paint)) => Fill {
+                        paint: paint.clone(),
+                        ..fill.clone()
+                    },
+                    _ => fill.clone(),
+                };
+
+                self.outline_glyphs(
+                    std::slice::from_ref(glyph),
+                    Point::from_xy(cur_x, start.y),
+                    font.clone(),
+                    font_size,
+                    glyph_units,
+                    PaintMode::Fill(&fill),
+                );
+
+                cur_x += normalize(glyph.x_advance()) * font_size;
+            }
+        } else {
+            Self::cur_builder_mut(&mut self.root_builder, &mut self.sub_builders).fill_glyphs(
+                start,
+                self.sc,
+                fill,
+                glyphs,
+                glyph_paints,
                 font,
                 text,
                 font_size,
@@ -235,6 +381,234 @@ impl<'a> Surface<'a> {
         }
     }
 
+    /// Draw a sequence of glyphs with the given text render mode.
+    ///
+    /// This works the same as [`Surface::fill_glyphs`] and [`Surface::stroke_glyphs`], but
+    /// allows you to choose how the glyphs are rendered. `fill` is only used by the modes that
+    /// fill the glyphs and `stroke` only by the ones that stroke them.
+    ///
+    /// The clipping modes additionally intersect the current clip path with the outlines
+    /// of the glyphs, which stays in place until the corresponding call to [`Surface::pop`].
+    /// Glyphs that don't have an outline (for example bitmap glyphs) don't contribute
+    /// to the clip path.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_glyphs(
+        &mut self,
+        start: Point,
+        render_mode: TextRenderMode,
+        fill: Fill,
+        stroke: Stroke,
+        glyphs: &[impl Glyph],
+        font: Font,
+        text: &str,
+        font_size: f32,
+        glyph_units: GlyphUnits,
+    ) {
+        if self.content_budget_exceeded() {
+            return;
+        }
+
+        match render_mode {
+            TextRenderMode::Fill | TextRenderMode::FillClip => self.fill_glyphs(
+                start,
+                fill,
+                glyphs,
+                font.clone(),
+                text,
+                font_size,
+                glyph_units,
+                false,
+            ),
+            TextRenderMode::Stroke | TextRenderMode::StrokeClip => self.stroke_glyphs(
+                start,
+                stroke,
+                glyphs,
+                font.clone(),
+                text,
+                font_size,
+                glyph_units,
+                false,
+            ),
+            TextRenderMode::FillStroke | TextRenderMode::FillStrokeClip => {
+                let is_type3 = matches!(
+                    *self.sc.register_font_container(font.clone()).borrow(),
+                    FontContainer::Type3(_)
+                );
+
+                if is_type3 {
+                    // The glyphs of Type3 fonts already contain either the fill or
+                    // the stroke, so we need to draw them twice.
+                    self.fill_glyphs(
+                        start,
+                        fill,
+                        glyphs,
+                        font.clone(),
+                        text,
+                        font_size,
+                        glyph_units,
+                        false,
+                    );
+                    self.stroke_glyphs(
+                        start,
+                        stroke,
+                        glyphs,
+                        font.clone(),
+                        text,
+                        font_size,
+                        glyph_units,
+                        false,
+                    );
+                } else {
+                    Self::cur_builder_mut(&mut self.root_builder, &mut self.sub_builders)
+                        .fill_stroke_glyphs(
+                            start,
+                            self.sc,
+                            fill,
+                            stroke,
+                            glyphs,
+                            font.clone(),
+                            text,
+                            font_size,
+                            glyph_units,
+                        );
+                }
+            }
+            TextRenderMode::Invisible | TextRenderMode::Clip => {
+                Self::cur_builder_mut(&mut self.root_builder, &mut self.sub_builders)
+                    .invisible_glyphs(
+                        start,
+                        self.sc,
+                        glyphs,
+                        font.clone(),
+                        text,
+                        font_size,
+                        glyph_units,
+                    )
+            }
+        }
+
+        if render_mode.clips() {
+            let path = glyphs_clip_path(glyphs, start, font, font_size, glyph_units);
+            self.push_clip_path(&path, &FillRule::NonZero);
+        }
+    }
+
+    /// Draw multiple consecutive runs of glyphs with a fill.
+    ///
+    /// This is a convenience method on top of [`Surface::fill_glyphs`] for the common case
+    /// where a line of text is split into several runs with different styles, for example
+    /// different fills or fonts. The runs are drawn one after another, with each run
+    /// starting where the previous one ended.
+    pub fn fill_glyph_runs(&mut self, start: Point, runs: &[GlyphRun<impl Glyph>]) {
+        let mut cur_x = start.x;
+
+        for run in runs {
+            self.fill_glyphs(
+                Point::from_xy(cur_x, start.y),
+                run.fill.clone(),
+                run.glyphs,
+                run.font.clone(),
+                run.text,
+                run.font_size,
+                run.glyph_units,
+                run.outlined,
+            );
+
+            let upem = run.font.units_per_em();
+            cur_x += run
+                .glyphs
+                .iter()
+                .map(|g| {
+                    unit_normalize(run.glyph_units, upem, run.font_size, g.x_advance())
+                        * run.font_size
+                })
+                .sum::<f32>();
+        }
+    }
+
+    /// Draw a run of glyphs with explicit positions.
+    ///
+    /// This is an alternative to [`Surface::fill_glyphs`] and [`Surface::stroke_glyphs`] for
+    /// callers that do their own text layout and thus already know the position of each glyph,
+    /// so that they don't have to convert the positions back into advances. The position of each
+    /// glyph is relative to `origin`, which is the start of the baseline of the run.
+    ///
+    /// Consecutive glyphs on the same baseline are still encoded with a single text showing
+    /// operator, where differences between the positions and the advances in the font are
+    /// expressed as adjustments.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_text_run(
+        &mut self,
+        origin: Point,
+        font: Font,
+        font_size: f32,
+        glyphs: &[PositionedGlyph],
+        text: &str,
+        paint: TextPaint,
+    ) {
+        let Some((start, converted)) = convert_positioned_glyphs(origin, &font, font_size, glyphs)
+        else {
+            return;
+        };
+
+        match paint {
+            TextPaint::Fill(fill) => self.fill_glyphs(
+                start,
+                fill,
+                &converted,
+                font,
+                text,
+                font_size,
+                GlyphUnits::UserSpace,
+                false,
+            ),
+            TextPaint::Stroke(stroke) => self.stroke_glyphs(
+                start,
+                stroke,
+                &converted,
+                font,
+                text,
+                font_size,
+                GlyphUnits::UserSpace,
+                false,
+            ),
+        }
+    }
+
+    /// Draw invisible text on top of a scanned image.
+    ///
+    /// This is meant for OCR overlays: The text is neither filled nor stroked, but it can
+    /// still be selected, copied and searched in PDF viewers. The glyphs are positioned
+    /// in the same way as in [`Surface::draw_text_run`], so that you can directly use the
+    /// positions of the recognized glyphs in the coordinate system of the image.
+    pub fn draw_ocr_text(
+        &mut self,
+        origin: Point,
+        font: Font,
+        font_size: f32,
+        glyphs: &[PositionedGlyph],
+        text: &str,
+    ) {
+        if self.content_budget_exceeded() {
+            return;
+        }
+
+        let Some((start, converted)) = convert_positioned_glyphs(origin, &font, font_size, glyphs)
+        else {
+            return;
+        };
+
+        Self::cur_builder_mut(&mut self.root_builder, &mut self.sub_builders).invisible_glyphs(
+            start,
+            self.sc,
+            &converted,
+            font,
+            text,
+            font_size,
+            GlyphUnits::UserSpace,
+        );
+    }
+
     /// Draw some text with a fill.
     ///
     /// This is a high-level method which allows you to just provide some text, which will
@@ -243,7 +617,8 @@ impl<'a> Surface<'a> {
     /// - It will not perform BIDI resolution and only supports a single script, meaning that you
     ///   must ensure that your text does not contain multiple scripts.
     /// - It will only use the single font you provided to draw the text, no font fallback will
-    ///   be performed.
+    ///   be performed. Characters that the font doesn't have a glyph for are handled according
+    ///   to [`SerializeSettings::notdef_fallback`](crate::SerializeSettings::notdef_fallback).
     ///
     /// If you need more advanced control over how your text looks, but you don't want to
     /// implement your own text processing solution, so you can use the `fill_glyphs` method,
@@ -263,18 +638,52 @@ impl<'a> Surface<'a> {
     ) {
         let glyphs = naive_shape(text, font.clone(), features, font_size, direction);
 
-        self.fill_glyphs(
+        self.draw_shaped_text(
             start,
-            fill,
+            PaintMode::Fill(&fill),
             &glyphs,
             font,
             text,
             font_size,
-            GlyphUnits::UserSpace,
             outlined,
         );
     }
 
+    /// Draw some text with a fill, applying OpenType features to specific ranges of the text.
+    ///
+    /// This works the same as [`Surface::fill_text`], but instead of applying the features
+    /// to the whole text, each list of features is only applied to the given byte range
+    /// of the text. This allows you to, for example, only enable small caps for a single
+    /// word. Passing an empty slice behaves the same as not applying any features.
+    #[cfg(feature = "simple-text")]
+    #[allow(clippy::too_many_arguments)]
+    pub fn fill_text_with_features(
+        &mut self,
+        start: Point,
+        fill: Fill,
+        font: Font,
+        font_size: f32,
+        text: &str,
+        features: &[(Range<usize>, Vec<Feature>)],
+        outlined: bool,
+        direction: TextDirection,
+    ) {
+        let features = features
+            .iter()
+            .flat_map(|(range, features)| {
+                features.iter().map(|f| Feature {
+                    start: range.start as u32,
+                    end: range.end as u32,
+                    ..*f
+                })
+            })
+            .collect::<Vec<_>>();
+
+        self.fill_text(
+            start, fill, font, font_size, &features, text, outlined, direction,
+        );
+    }
+
     /// Draw a sequence of glyphs with a stroke.
     ///
     /// This is a very low-level method, which gives you full control over how to place
@@ -292,6 +701,10 @@ impl<'a> Surface<'a> {
         glyph_units: GlyphUnits,
         outlined: bool,
     ) {
+        if self.content_budget_exceeded() {
+            return;
+        }
+
         if outlined {
             self.outline_glyphs(
                 glyphs,
@@ -323,7 +736,8 @@ impl<'a> Surface<'a> {
     /// - It will not perform BIDI resolution and only supports a single script, meaning that you
     ///   must ensure that your text does not contain multiple scripts.
     /// - It will only use the single font you provided to draw the text, no font fallback will
-    ///   be performed.
+    ///   be performed. Characters that the font doesn't have a glyph for are handled according
+    ///   to [`SerializeSettings::notdef_fallback`](crate::SerializeSettings::notdef_fallback).
     ///
     /// If you need more advanced control over how your text looks, but you don't want to
     /// implement your own text processing solution, so you can use the `stroke_glyphs` method,
@@ -343,18 +757,270 @@ impl<'a> Surface<'a> {
     ) {
         let glyphs = naive_shape(text, font.clone(), features, font_size, direction);
 
-        self.stroke_glyphs(
+        self.draw_shaped_text(
             start,
-            stroke,
+            PaintMode::Stroke(&stroke),
             &glyphs,
             font,
             text,
             font_size,
-            GlyphUnits::UserSpace,
             outlined,
         );
     }
 
+    /// Draw the result of `naive_shape`, replacing `.notdef` glyphs according to the
+    /// `notdef_fallback` of the serialize settings.
+    #[cfg(feature = "simple-text")]
+    #[allow(clippy::too_many_arguments)]
+    fn draw_shaped_text(
+        &mut self,
+        start: Point,
+        paint_mode: PaintMode,
+        glyphs: &[KrillaGlyph],
+        font: Font,
+        text: &str,
+        font_size: f32,
+        outlined: bool,
+    ) {
+        let fallback = self.sc.serialize_settings().notdef_fallback.clone();
+
+        if matches!(fallback, NotdefFallback::Notdef) || !glyphs.iter().any(is_notdef) {
+            self.draw_shaped_run(start, paint_mode, glyphs, font, text, font_size, outlined);
+            return;
+        }
+
+        let glyphs = match fallback {
+            NotdefFallback::Nothing | NotdefFallback::Tofu => preserve_notdef_text(glyphs),
+            _ => glyphs.to_vec(),
+        };
+
+        let mut run_start = 0;
+        let mut run_origin = start;
+        let mut cur = start;
+
+        for (i, glyph) in glyphs.iter().enumerate() {
+            if !is_notdef(glyph) {
+                cur = advance(cur, glyph);
+                continue;
+            }
+
+            self.draw_shaped_run(
+                run_origin,
+                paint_mode,
+                &glyphs[run_start..i],
+                font.clone(),
+                text,
+                font_size,
+                outlined,
+            );
+
+            cur = self.draw_notdef_fallback(
+                cur,
+                paint_mode,
+                glyph,
+                font.clone(),
+                text,
+                font_size,
+                outlined,
+                &fallback,
+            );
+
+            run_start = i + 1;
+            run_origin = cur;
+        }
+
+        self.draw_shaped_run(
+            run_origin,
+            paint_mode,
+            &glyphs[run_start..],
+            font,
+            text,
+            font_size,
+            outlined,
+        );
+    }
+
+    /// Draw a replacement for a `.notdef` glyph and return the position of the next glyph.
+    #[cfg(feature = "simple-text")]
+    #[allow(clippy::too_many_arguments)]
+    fn draw_notdef_fallback(
+        &mut self,
+        origin: Point,
+        paint_mode: PaintMode,
+        glyph: &KrillaGlyph,
+        font: Font,
+        text: &str,
+        font_size: f32,
+        outlined: bool,
+        fallback: &NotdefFallback,
+    ) -> Point {
+        // Glyphs in vertical text only advance along the y axis.
+        let vertical = glyph.y_advance != 0.0;
+
+        match fallback {
+            NotdefFallback::Notdef => {}
+            NotdefFallback::Nothing => return advance(origin, glyph),
+            NotdefFallback::Tofu => {
+                let width = if glyph.x_advance > 0.0 {
+                    glyph.x_advance
+                } else {
+                    font_size * 0.5
+                };
+                let inset = font_size * 0.05;
+                let outer = Rect::from_xywh(
+                    origin.x + glyph.x_offset + inset,
+                    origin.y - glyph.y_offset - font_size * 0.7,
+                    width - 2.0 * inset,
+                    font_size * 0.7,
+                );
+
+                if let Some(outer) = outer {
+                    match paint_mode {
+                        PaintMode::Fill(fill) => {
+                            // Cut out the inner part of the box, so that only
+                            // the border remains.
+                            let mut builder = PathBuilder::new();
+                            builder.push_rect(outer);
+
+                            if let Some(inner) = Rect::from_ltrb(
+                                outer.left() + inset,
+                                outer.top() + inset,
+                                outer.right() - inset,
+                                outer.bottom() - inset,
+                            ) {
+                                builder.push_rect(inner);
+                            }
+
+                            if let Some(path) = builder.finish() {
+                                let fill = Fill {
+                                    rule: FillRule::EvenOdd,
+                                    ..fill.clone()
+                                };
+                                self.fill_path(&path, fill);
+                            }
+                        }
+                        PaintMode::Stroke(stroke) => {
+                            self.stroke_path(&PathBuilder::from_rect(outer), stroke.clone());
+                        }
+                    }
+                }
+
+                return if vertical || glyph.x_advance > 0.0 {
+                    advance(origin, glyph)
+                } else {
+                    Point::from_xy(origin.x + width, origin.y)
+                };
+            }
+            NotdefFallback::Callback(callback) => {
+                let cluster = &text[glyph.text_range.clone()];
+                let num_chars = cluster.chars().count() as f32;
+                let replacements = cluster
+                    .char_indices()
+                    .map(|(offset, c)| {
+                        let (font, glyph_id) = callback(c)?;
+                        let start = glyph.text_range.start + offset;
+                        let range = start..start + c.len_utf8();
+
+                        // We don't know the vertical metrics of the replacement, so
+                        // in vertical text, the characters of the cluster evenly
+                        // share the space of the `.notdef` glyph.
+                        let replacement = if vertical {
+                            KrillaGlyph::new(
+                                glyph_id,
+                                0.0,
+                                glyph.x_offset,
+                                glyph.y_offset,
+                                glyph.y_advance / num_chars,
+                                range,
+                            )
+                        } else {
+                            let x_advance = font.advance_width(glyph_id).unwrap_or(0.0)
+                                / font.units_per_em()
+                                * font_size;
+
+                            KrillaGlyph::new(glyph_id, x_advance, 0.0, 0.0, 0.0, range)
+                        };
+
+                        Some((font, replacement))
+                    })
+                    .collect::<Option<Vec<_>>>();
+
+                if let Some(replacements) = replacements.filter(|r| !r.is_empty()) {
+                    let mut cur = origin;
+
+                    for (font, replacement) in replacements {
+                        let next = advance(cur, &replacement);
+                        self.draw_shaped_run(
+                            cur,
+                            paint_mode,
+                            &[replacement],
+                            font,
+                            text,
+                            font_size,
+                            outlined,
+                        );
+                        cur = next;
+                    }
+
+                    return cur;
+                }
+            }
+        }
+
+        // Fall back to the `.notdef` glyph itself.
+        self.draw_shaped_run(
+            origin,
+            paint_mode,
+            std::slice::from_ref(glyph),
+            font,
+            text,
+            font_size,
+            outlined,
+        );
+
+        advance(origin, glyph)
+    }
+
+    #[cfg(feature = "simple-text")]
+    #[allow(clippy::too_many_arguments)]
+    fn draw_shaped_run(
+        &mut self,
+        start: Point,
+        paint_mode: PaintMode,
+        glyphs: &[KrillaGlyph],
+        font: Font,
+        text: &str,
+        font_size: f32,
+        outlined: bool,
+    ) {
+        if glyphs.is_empty() {
+            return;
+        }
+
+        match paint_mode {
+            PaintMode::Fill(fill) => self.fill_glyphs(
+                start,
+                fill.clone(),
+                glyphs,
+                font,
+                text,
+                font_size,
+                GlyphUnits::UserSpace,
+                outlined,
+            ),
+            PaintMode::Stroke(stroke) => self.stroke_glyphs(
+                start,
+                stroke.clone(),
+                glyphs,
+                font,
+                text,
+                font_size,
+                GlyphUnits::UserSpace,
+                outlined,
+            ),
+        }
+    }
+
     /// Return the current transformation matrix.
     pub fn ctm(&self) -> Transform {
         Self::cur_builder(&self.root_builder, &self.sub_builders).cur_transform()
@@ -384,6 +1050,13 @@ impl<'a> Surface<'a> {
     }
 
     /// Push a new mask.
+    ///
+    /// All subsequent content until the corresponding `pop` is drawn into a
+    /// non-isolated transparency group, which is then composited with the mask applied
+    /// as a soft mask. Since the group is not isolated, blend modes used inside of it
+    /// also interact with content drawn before the mask was pushed. If that is not what
+    /// you want, push an isolated group (for example via [`Surface::push_isolated`])
+    /// right after pushing the mask.
     pub fn push_mask(&mut self, mask: Mask) {
         self.push_instructions
             .push(PushInstruction::Mask(Box::new(mask)));
@@ -396,6 +1069,11 @@ impl<'a> Surface<'a> {
     ///
     /// This stacks, meaning that if you do `push_opacity(0.5)` twice, the resulting
     /// base opacity will be 0.25.
+    ///
+    /// Unless the opacity is 1, the content is drawn into an isolated transparency
+    /// group, so that overlapping objects are composited with each other first. If you
+    /// only want to draw a single path with some opacity, it is cheaper to set the
+    /// opacity on its fill or stroke instead, which doesn't require a group.
     pub fn push_opacity(&mut self, opacity: NormalizedF32) {
         self.push_instructions
             .push(PushInstruction::Opacity(opacity));
@@ -407,12 +1085,70 @@ impl<'a> Surface<'a> {
     }
 
     /// Push a new isolated layer.
+    ///
+    /// The content is drawn into an isolated transparency group, meaning that blend
+    /// modes inside of the layer only apply to content within the layer.
     pub fn push_isolated(&mut self) {
-        self.push_instructions.push(PushInstruction::Isolated);
-        self.sub_builders
+        self.push_transparency_group(true, false);
+    }
+
+    /// Push a new transparency group.
+    ///
+    /// All content until the corresponding `pop` is first composited within the group,
+    /// and the result is then composited with the backdrop as a whole.
+    ///
+    /// If the group is isolated, its content is composited onto a fully transparent
+    /// backdrop instead of the content below the group, so blend modes inside of the
+    /// group only apply to content within the group. If the group is a knockout group,
+    /// each element of the group is composited onto the initial backdrop of the group
+    /// instead of onto the elements before it, so overlapping translucent shapes
+    /// replace each other instead of shining through.
+    pub fn push_transparency_group(&mut self, isolated: bool, knockout: bool) {
+        self.push_instructions
+            .push(PushInstruction::TransparencyGroup { isolated, knockout });
+        self.sub_builders
             .push(ContentBuilder::new(Transform::identity()));
     }
 
+    /// Push a new layer.
+    ///
+    /// All subsequent content until the corresponding `pop` belongs to the given layer,
+    /// meaning that viewers will only show it if the layer is enabled. Layers can be
+    /// nested, in which case the content is only shown if all of them are enabled.
+    /// See [`Document::add_layer`] for how to create a layer.
+    ///
+    /// Tagged sections can be used inside of a layer, but the two must be properly nested:
+    /// A tagged section that was started inside of a layer must be ended before popping
+    /// the layer, and a layer that was pushed inside of a tagged section must be popped
+    /// before ending the tagged section.
+    ///
+    /// [`Document::add_layer`]: crate::Document::add_layer
+    pub fn push_layer(&mut self, layer: &Layer) {
+        let builder = Self::cur_builder_mut(&mut self.root_builder, &mut self.sub_builders);
+        self.push_instructions
+            .push(PushInstruction::Layer(builder.active_marked_content));
+        builder.start_layer(layer);
+    }
+
+    /// Pop the last layer.
+    ///
+    /// This is the same as calling [`Surface::pop`], but additionally checks that
+    /// the last `push` instruction actually was a layer.
+    ///
+    /// # Panics
+    /// Panics if the last `push` instruction wasn't a [`Surface::push_layer`].
+    pub fn pop_layer(&mut self) {
+        assert!(
+            matches!(
+                self.push_instructions.last(),
+                Some(PushInstruction::Layer(_))
+            ),
+            "the last push instruction is not a layer"
+        );
+
+        self.pop();
+    }
+
     /// Pop the last `push` instruction.
     ///
     /// # Panics
@@ -443,10 +1179,19 @@ impl<'a> Surface<'a> {
                 Self::cur_builder_mut(&mut self.root_builder, &mut self.sub_builders)
                     .draw_masked(self.sc, *mask, stream)
             }
-            PushInstruction::Isolated => {
+            PushInstruction::TransparencyGroup { isolated, knockout } => {
                 let stream = self.sub_builders.pop().unwrap().finish(self.sc);
                 Self::cur_builder_mut(&mut self.root_builder, &mut self.sub_builders)
-                    .draw_isolated(self.sc, stream);
+                    .draw_transparency_group(self.sc, stream, isolated, knockout);
+            }
+            PushInstruction::Layer(tagged) => {
+                let builder = Self::cur_builder_mut(&mut self.root_builder, &mut self.sub_builders);
+                assert_eq!(
+                    tagged, builder.active_marked_content,
+                    "tagged sections and layers must be properly nested"
+                );
+
+                builder.end_layer();
             }
         }
     }
@@ -454,8 +1199,103 @@ impl<'a> Surface<'a> {
     #[cfg(feature = "raster-images")]
     /// Draw a new bitmap image.
     pub fn draw_image(&mut self, image: Image, size: Size) {
+        if self.content_budget_exceeded() {
+            return;
+        }
+
+        Self::cur_builder_mut(&mut self.root_builder, &mut self.sub_builders).draw_image(
+            image,
+            size,
+            NormalizedF32::ONE,
+            BlendMode::Normal,
+            self.sc,
+        );
+    }
+
+    #[cfg(feature = "raster-images")]
+    /// Draw a new bitmap image such that it exactly fills the given rectangle.
+    ///
+    /// Just like all other coordinates on a surface, the rectangle is given in the current
+    /// user space, where the origin is at the top-left and the y-axis points down. The
+    /// top-left corner of the image is placed at the top-left corner of the rectangle, and
+    /// the image is stretched to the size of the rectangle, without preserving its aspect
+    /// ratio.
+    pub fn draw_image_with_rect(&mut self, image: Image, rect: Rect) {
+        // Nothing would be visible anyway.
+        let Some(size) = Size::from_wh(rect.width(), rect.height()) else {
+            return;
+        };
+
+        self.push_transform(&Transform::from_translate(rect.x(), rect.y()));
+        self.draw_image(image, size);
+        self.pop();
+    }
+
+    #[cfg(feature = "raster-images")]
+    /// Draw a new bitmap image with an arbitrary transform.
+    ///
+    /// The image is drawn into the unit square from `(0, 0)` to `(1, 1)`, with the
+    /// top-left corner of the image at `(0, 0)`, and the transform maps this square
+    /// into the current user space. For example, a transform that scales by the
+    /// width and height of a rectangle and then translates to its top-left corner
+    /// is equivalent to [`Surface::draw_image_with_rect`].
+    pub fn draw_image_with_transform(&mut self, image: Image, transform: &Transform) {
+        self.push_transform(transform);
+        self.draw_image(image, Size::from_wh(1.0, 1.0).unwrap());
+        self.pop();
+    }
+
+    #[cfg(feature = "raster-images")]
+    /// Draw a new bitmap image with an opacity.
+    ///
+    /// In contrast to wrapping the image in [`Surface::push_opacity`], this only sets the
+    /// alpha of the graphics state for the duration of the image draw, without creating a
+    /// transparency group.
+    pub fn draw_image_with_opacity(&mut self, image: Image, size: Size, opacity: NormalizedF32) {
+        if self.content_budget_exceeded() {
+            return;
+        }
+
+        Self::cur_builder_mut(&mut self.root_builder, &mut self.sub_builders).draw_image(
+            image,
+            size,
+            opacity,
+            BlendMode::Normal,
+            self.sc,
+        );
+    }
+
+    #[cfg(feature = "raster-images")]
+    /// Draw a new bitmap image with a blend mode.
+    ///
+    /// This is a shorthand for pushing a blend mode via [`Surface::push_blend_mode`],
+    /// drawing the image and popping the blend mode again.
+    pub fn draw_image_with_blend(&mut self, image: Image, size: Size, blend_mode: BlendMode) {
+        if self.content_budget_exceeded() {
+            return;
+        }
+
+        Self::cur_builder_mut(&mut self.root_builder, &mut self.sub_builders).draw_image(
+            image,
+            size,
+            NormalizedF32::ONE,
+            blend_mode,
+            self.sc,
+        );
+    }
+
+    #[cfg(feature = "raster-images")]
+    /// Draw a stencil mask created with [`Image::stencil`], painting its
+    /// marked pixels with the given fill.
+    ///
+    /// The fill rule of the fill is ignored.
+    pub fn draw_stencil(&mut self, image: Image, size: Size, fill: Fill) {
+        if self.content_budget_exceeded() {
+            return;
+        }
+
         Self::cur_builder_mut(&mut self.root_builder, &mut self.sub_builders)
-            .draw_image(image, size, self.sc);
+            .draw_stencil(image, size, fill, self.sc);
     }
 
     #[cfg(feature = "svg")]
@@ -484,11 +1324,65 @@ impl<'a> Surface<'a> {
         Some(())
     }
 
+    /// Draw a page of an existing PDF onto the surface.
+    ///
+    /// The page with the (zero-based) index `page_index` is parsed from `data` and
+    /// embedded as a form XObject, which can be reused by drawing the same page again.
+    /// Without any transform, the page is drawn at its original size with its top-left
+    /// corner at the origin of the surface, and the transform allows you to scale and
+    /// position it. The resources of the page stay separate from the ones of the surface,
+    /// so there can be no conflicts between their names. Note that the content of the page
+    /// is copied as is, so krilla cannot ensure that it conforms to the validator of the
+    /// document.
+    ///
+    /// Returns an error if the source PDF is malformed, encrypted or does not contain a
+    /// page with the given index.
+    #[cfg(feature = "pdf")]
+    pub fn draw_pdf_page(
+        &mut self,
+        data: &[u8],
+        page_index: usize,
+        transform: &Transform,
+    ) -> KrillaResult<()> {
+        let page = PdfPage::new(data, page_index)?;
+        self.draw_imported_page(page, transform);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "pdf")]
+    pub(crate) fn draw_imported_page(&mut self, page: PdfPage, transform: &Transform) {
+        if self.content_budget_exceeded() {
+            return;
+        }
+
+        Self::cur_builder_mut(&mut self.root_builder, &mut self.sub_builders)
+            .draw_pdf_page(page, transform, self.sc);
+    }
+
     pub(crate) fn draw_shading(&mut self, shading: &ShadingFunction) {
+        if self.content_budget_exceeded() {
+            return;
+        }
+
         Self::cur_builder_mut(&mut self.root_builder, &mut self.sub_builders)
             .draw_shading(shading, self.sc);
     }
 
+    /// Whether the content budget has been exceeded, taking into account the
+    /// content that has been drawn on this surface so far. If it has, the caller
+    /// must skip the drawing operation.
+    fn content_budget_exceeded(&mut self) -> bool {
+        let pending = self.root_builder.estimated_len()
+            + self
+                .sub_builders
+                .iter()
+                .map(ContentBuilder::estimated_len)
+                .sum::<usize>();
+
+        self.sc.content_budget_exceeded(pending)
+    }
+
     /// Convert a `fontdb` into `krilla` `Font` objects. This is a convenience method,
     /// which makes it easier to integrate `cosmic-text` with this library.
     #[cfg(feature = "fontdb")]
@@ -507,6 +1401,11 @@ impl<'a> Surface<'a> {
             .draw_opacified(self.sc, opacity, stream)
     }
 
+    pub(crate) fn draw_stream(&mut self, stream: Stream) {
+        Self::cur_builder_mut(&mut self.root_builder, &mut self.sub_builders)
+            .draw_stream(self.sc, stream)
+    }
+
     pub(crate) fn cur_transform(&self) -> Transform {
         Self::cur_builder(&self.root_builder, &self.sub_builders).cur_transform()
     }
@@ -543,6 +1442,148 @@ impl Drop for Surface<'_> {
     }
 }
 
+/// Whether a text run should be filled or stroked.
+///
+/// See [`Surface::draw_text_run`] for more information.
+#[derive(Debug, Clone)]
+pub enum TextPaint {
+    /// Fill the glyphs.
+    Fill(Fill),
+    /// Stroke the glyphs.
+    Stroke(Stroke),
+}
+
+/// Convert glyphs with explicit positions into glyphs with advances, and return
+/// them together with the start of the run. Returns `None` if there are no glyphs.
+fn convert_positioned_glyphs(
+    origin: Point,
+    font: &Font,
+    font_size: f32,
+    glyphs: &[PositionedGlyph],
+) -> Option<(Point, Vec<KrillaGlyph>)> {
+    let first = glyphs.first()?;
+
+    let converted = glyphs
+        .iter()
+        .enumerate()
+        .map(|(i, glyph)| {
+            // The last glyph has nothing to be positioned against, so we just use
+            // its actual advance.
+            let x_advance = match glyphs.get(i + 1) {
+                Some(next) => next.x - glyph.x,
+                None => font
+                    .advance_width(glyph.glyph_id)
+                    .map(|a| a / font.units_per_em() * font_size)
+                    .unwrap_or(0.0),
+            };
+
+            // A positive y offset moves the glyph up, while a positive y coordinate
+            // moves it down.
+            KrillaGlyph::new(
+                glyph.glyph_id,
+                x_advance,
+                0.0,
+                -glyph.y,
+                0.0,
+                glyph.text_range.clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    Some((Point::from_xy(origin.x + first.x, origin.y), converted))
+}
+
+/// How the glyphs of a text should be rendered.
+///
+/// See [`Surface::draw_glyphs`] for more information.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextRenderMode {
+    /// Fill the glyphs.
+    #[default]
+    Fill,
+    /// Stroke the glyphs.
+    Stroke,
+    /// Fill the glyphs and then stroke them.
+    FillStroke,
+    /// Neither fill nor stroke the glyphs. The text is still selectable and searchable,
+    /// which is useful for example for OCR layers on top of scanned images.
+    Invisible,
+    /// Fill the glyphs and add them to the clip path.
+    FillClip,
+    /// Stroke the glyphs and add them to the clip path.
+    StrokeClip,
+    /// Fill and stroke the glyphs and add them to the clip path.
+    FillStrokeClip,
+    /// Only add the glyphs to the clip path, without painting them.
+    Clip,
+}
+
+impl TextRenderMode {
+    fn clips(self) -> bool {
+        matches!(
+            self,
+            Self::FillClip | Self::StrokeClip | Self::FillStrokeClip | Self::Clip
+        )
+    }
+}
+
+/// Build a clip path from the outlines of the given glyphs.
+fn glyphs_clip_path(
+    glyphs: &[impl Glyph],
+    start: Point,
+    font: Font,
+    font_size: f32,
+    glyph_units: GlyphUnits,
+) -> Path {
+    let normalize = |val| unit_normalize(glyph_units, font.units_per_em(), font_size, val);
+    let (mut cur_x, y) = (start.x, start.y);
+    let mut builder = PathBuilder::new();
+
+    for glyph in glyphs {
+        let transform = Transform::from_translate(
+            cur_x + normalize(glyph.x_offset()) * font_size,
+            y - normalize(glyph.y_offset()) * font_size,
+        )
+        .pre_scale(
+            font_size / font.units_per_em(),
+            -font_size / font.units_per_em(),
+        );
+
+        if let Some(path) =
+            glyph_path(font.clone(), glyph.glyph_id()).and_then(|path| path.transform(transform))
+        {
+            builder.push_path(&path);
+        }
+
+        cur_x += normalize(glyph.x_advance()) * font_size;
+    }
+
+    // If no glyph has an outline, everything is clipped away.
+    builder
+        .finish()
+        .unwrap_or_else(|| PathBuilder::from_rect(Rect::from_xywh(0.0, 0.0, 0.0, 0.0).unwrap()))
+}
+
+/// A run of glyphs that share the same style.
+///
+/// See [`Surface::fill_glyph_runs`] for more information.
+pub struct GlyphRun<'a, G: Glyph> {
+    /// The fill of the glyphs.
+    pub fill: Fill,
+    /// The font of the glyphs.
+    pub font: Font,
+    /// The font size of the glyphs.
+    pub font_size: f32,
+    /// The glyphs of the run.
+    pub glyphs: &'a [G],
+    /// The text the text ranges of the glyphs refer to.
+    pub text: &'a str,
+    /// The units in which the glyph metrics are given.
+    pub glyph_units: GlyphUnits,
+    /// Whether the glyphs should be drawn as outlines instead of text.
+    pub outlined: bool,
+}
+
 #[cfg(feature = "simple-text")]
 /// The direction of a text.
 pub enum TextDirection {
@@ -558,6 +1599,49 @@ pub enum TextDirection {
     BottomToTop,
 }
 
+#[cfg(feature = "simple-text")]
+fn is_notdef(glyph: &KrillaGlyph) -> bool {
+    glyph.glyph_id == GlyphId::new(0)
+}
+
+/// The position of the glyph after the given one.
+#[cfg(feature = "simple-text")]
+fn advance(origin: Point, glyph: &KrillaGlyph) -> Point {
+    Point::from_xy(origin.x + glyph.x_advance, origin.y - glyph.y_advance)
+}
+
+/// Assign the text of `.notdef` glyphs that won't be drawn to a neighboring glyph, so
+/// that it still ends up in the PDF. The text is preferably attached to the preceding
+/// glyph, and to the following one if the `.notdef` glyph comes first.
+#[cfg(feature = "simple-text")]
+fn preserve_notdef_text(glyphs: &[KrillaGlyph]) -> Vec<KrillaGlyph> {
+    let mut preserved = glyphs.to_vec();
+
+    for (i, glyph) in glyphs.iter().enumerate().filter(|(_, g)| is_notdef(g)) {
+        let neighbor = glyphs[..i]
+            .iter()
+            .rev()
+            .chain(&glyphs[i + 1..])
+            .find(|g| !is_notdef(g));
+
+        // If no glyph of the text can be drawn, there is nothing to attach the text to.
+        let Some(neighbor) = neighbor else {
+            continue;
+        };
+
+        // All glyphs of the neighboring cluster need to keep the same range, otherwise
+        // its text would be duplicated.
+        for (other, original) in preserved.iter_mut().zip(glyphs) {
+            if !is_notdef(original) && original.text_range == neighbor.text_range {
+                other.text_range = other.text_range.start.min(glyph.text_range.start)
+                    ..other.text_range.end.max(glyph.text_range.end);
+            }
+        }
+    }
+
+    preserved
+}
+
 /// Shape some text with a single font.
 #[cfg(feature = "simple-text")]
 fn naive_shape(
@@ -645,22 +1729,697 @@ fn naive_shape(
 
 #[cfg(test)]
 mod tests {
-    use crate::font::Font;
+    use std::sync::Arc;
+
+    use crate::error::KrillaError;
+    use crate::font::{Font, GlyphUnits, KrillaGlyph, PositionedGlyph};
+    use crate::image::Image;
     use crate::mask::MaskType;
     use crate::page::Page;
     use crate::paint::{LinearGradient, Paint, SpreadMethod};
-    use crate::path::Fill;
+    use crate::path::{Fill, LineCap, StrokeDash};
     use crate::surface::Surface;
-    use crate::surface::{Stroke, TextDirection};
+    use crate::surface::{naive_shape, GlyphRun, Stroke, TextDirection, TextPaint, TextRenderMode};
+    use crate::tagging::ContentTag;
     use crate::tests::{
         basic_mask, blue_fill, blue_stroke, cmyk_fill, gray_fill, green_fill, load_png_image,
         rect_to_path, red_fill, red_stroke, stops_with_3_solid_1, FONTDB, NOTO_COLOR_EMOJI_COLR,
         NOTO_SANS, NOTO_SANS_CJK, NOTO_SANS_DEVANAGARI, SVGS_PATH,
     };
-    use crate::SvgSettings;
+    use crate::{
+        Compression, Document, NotdefFallback, PageSettings, SerializeSettings, SvgSettings,
+    };
     use krilla_macros::{snapshot, visreg};
     use pdf_writer::types::BlendMode;
-    use tiny_skia_path::{Point, Size, Transform};
+    use skrifa::{GlyphId, MetadataProvider};
+    use tiny_skia_path::{NormalizedF32, PathBuilder, Point, Rect, Size, Transform};
+
+    /// Return the reference to the layer with the given name.
+    fn layer_ref(pdf: &str, name: &str) -> String {
+        let pos = pdf.find(&format!("/Name ({name})")).unwrap();
+        let end = pdf[..pos].rfind(" 0 obj").unwrap();
+        let start = pdf[..end].rfind('\n').unwrap() + 1;
+
+        format!("{} 0 R", &pdf[start..end])
+    }
+
+    #[test]
+    fn layer_nested() {
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        let annotations = document.add_layer("Annotations", true);
+        let dimensions = document.add_layer("Dimensions", false);
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        surface.push_layer(&annotations);
+        surface.fill_path(&rect_to_path(20.0, 20.0, 100.0, 100.0), red_fill(1.0));
+        surface.push_layer(&dimensions);
+        surface.fill_path(&rect_to_path(100.0, 100.0, 180.0, 180.0), green_fill(1.0));
+        surface.pop_layer();
+        surface.pop_layer();
+        surface.finish();
+        page.finish();
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        let annotations = layer_ref(&pdf, "Annotations");
+        let dimensions = layer_ref(&pdf, "Dimensions");
+
+        let outer = pdf.find("/OC /pr0 BDC").unwrap();
+        let inner = pdf.find("/OC /pr1 BDC").unwrap();
+        assert!(outer < inner);
+        assert_eq!(pdf.matches("EMC").count(), 2);
+        assert!(pdf.contains(&format!("/pr0 {annotations}")));
+        assert!(pdf.contains(&format!("/pr1 {dimensions}")));
+        assert!(pdf.contains(&format!("/OCGs [{annotations} {dimensions}]")));
+        assert!(pdf.contains(&format!("/ON [{annotations}]")));
+        assert!(pdf.contains(&format!("/OFF [{dimensions}]")));
+    }
+
+    #[test]
+    fn layer_with_tagged_content() {
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        let layer = document.add_layer("Layer", true);
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        surface.push_layer(&layer);
+        surface.start_tagged(ContentTag::Other);
+        surface.fill_path(&rect_to_path(20.0, 20.0, 180.0, 180.0), red_fill(1.0));
+        surface.end_tagged();
+        surface.pop_layer();
+        surface.finish();
+        page.finish();
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        let layer = pdf.find("/OC /pr0 BDC").unwrap();
+        let mcid = pdf.find("/MCID 0").unwrap();
+        assert!(layer < mcid);
+    }
+
+    #[cfg(feature = "pdf")]
+    fn pdf_page_source() -> Vec<u8> {
+        let mut document = Document::new();
+        let mut page = document.start_page_with(PageSettings::new(200.0, 100.0));
+        let mut surface = page.surface();
+        surface.fill_path(&rect_to_path(0.0, 0.0, 200.0, 100.0), green_fill(1.0));
+        surface.fill_path(&rect_to_path(20.0, 20.0, 80.0, 80.0), red_fill(1.0));
+        surface.finish();
+        page.finish();
+
+        document.finish().unwrap()
+    }
+
+    #[cfg(feature = "pdf")]
+    #[visreg]
+    fn pdf_page_with_overlay(surface: &mut Surface) {
+        let source = pdf_page_source();
+        surface
+            .draw_pdf_page(&source, 0, &Transform::from_translate(0.0, 50.0))
+            .unwrap();
+        surface
+            .draw_pdf_page(
+                &source,
+                0,
+                &Transform::from_row(0.5, 0.0, 0.0, 0.5, 100.0, 150.0),
+            )
+            .unwrap();
+        surface.fill_text(
+            Point::from_xy(10.0, 100.0),
+            blue_fill(1.0),
+            NOTO_SANS.clone(),
+            20.0,
+            &[],
+            "Overlay",
+            false,
+            TextDirection::Auto,
+        );
+    }
+
+    #[cfg(feature = "pdf")]
+    #[test]
+    fn pdf_page_reused() {
+        let source = pdf_page_source();
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let mut page = d.start_page();
+        let mut surface = page.surface();
+        surface
+            .draw_pdf_page(&source, 0, &Transform::identity())
+            .unwrap();
+        surface
+            .draw_pdf_page(&source, 0, &Transform::from_scale(0.5, 0.5))
+            .unwrap();
+        assert!(matches!(
+            surface.draw_pdf_page(&source, 1, &Transform::identity()),
+            Err(KrillaError::UserError(_))
+        ));
+        surface.finish();
+        page.finish();
+
+        let pdf = d.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert_eq!(pdf.matches("/BBox [0 0 200 100]").count(), 1);
+    }
+
+    fn masked_group_over_backdrop(
+        surface: &mut Surface,
+        blend_mode: Option<BlendMode>,
+        isolated: bool,
+    ) {
+        surface.fill_path(&rect_to_path(0.0, 0.0, 200.0, 100.0), green_fill(1.0));
+        surface.fill_path(&rect_to_path(0.0, 100.0, 200.0, 200.0), blue_fill(1.0));
+
+        let mask = basic_mask(surface, MaskType::Luminosity);
+        surface.push_mask(mask);
+
+        if isolated {
+            surface.push_isolated();
+        }
+
+        if let Some(blend_mode) = blend_mode {
+            surface.push_blend_mode(blend_mode);
+        }
+
+        surface.fill_path(&rect_to_path(20.0, 20.0, 180.0, 180.0), red_fill(1.0));
+
+        if blend_mode.is_some() {
+            surface.pop();
+        }
+
+        if isolated {
+            surface.pop();
+        }
+
+        surface.pop();
+    }
+
+    #[visreg]
+    fn masked_group_normal_over_backdrop(surface: &mut Surface) {
+        masked_group_over_backdrop(surface, None, false);
+    }
+
+    #[visreg]
+    fn masked_group_difference_over_backdrop(surface: &mut Surface) {
+        masked_group_over_backdrop(surface, Some(BlendMode::Difference), false);
+    }
+
+    #[visreg]
+    fn masked_group_difference_isolated_over_backdrop(surface: &mut Surface) {
+        masked_group_over_backdrop(surface, Some(BlendMode::Difference), true);
+    }
+
+    #[test]
+    fn masked_group_isolation() {
+        let pdf = |isolated| {
+            let mut d = Document::new_with(SerializeSettings::settings_1());
+            let mut page = d.start_page();
+            let mut surface = page.surface();
+            masked_group_over_backdrop(&mut surface, None, isolated);
+            surface.finish();
+            page.finish();
+
+            String::from_utf8_lossy(&d.finish().unwrap()).into_owned()
+        };
+
+        let non_isolated = pdf(false);
+        assert!(non_isolated.contains("/SMask"));
+        assert!(!non_isolated.contains("/I true"));
+
+        let isolated = pdf(true);
+        assert!(isolated.contains("/SMask"));
+        assert!(isolated.contains("/I true"));
+    }
+
+    fn transparency_group_over_backdrop(surface: &mut Surface, isolated: bool, knockout: bool) {
+        surface.fill_path(&rect_to_path(0.0, 0.0, 200.0, 100.0), green_fill(1.0));
+        surface.fill_path(&rect_to_path(0.0, 100.0, 200.0, 200.0), blue_fill(1.0));
+
+        surface.push_transparency_group(isolated, knockout);
+        surface.push_blend_mode(BlendMode::Multiply);
+        surface.fill_path(&rect_to_path(20.0, 20.0, 130.0, 130.0), red_fill(0.6));
+        surface.fill_path(&rect_to_path(70.0, 70.0, 180.0, 180.0), gray_fill(0.6));
+        surface.pop();
+        surface.pop();
+    }
+
+    #[visreg]
+    fn transparency_group_non_isolated(surface: &mut Surface) {
+        transparency_group_over_backdrop(surface, false, false);
+    }
+
+    #[visreg]
+    fn transparency_group_isolated(surface: &mut Surface) {
+        transparency_group_over_backdrop(surface, true, false);
+    }
+
+    #[visreg]
+    fn transparency_group_isolated_knockout(surface: &mut Surface) {
+        transparency_group_over_backdrop(surface, true, true);
+    }
+
+    #[test]
+    fn transparency_group_dict() {
+        let pdf = |isolated, knockout| {
+            let mut d = Document::new_with(SerializeSettings::settings_1());
+            let mut page = d.start_page();
+            let mut surface = page.surface();
+            transparency_group_over_backdrop(&mut surface, isolated, knockout);
+            surface.finish();
+            page.finish();
+
+            String::from_utf8_lossy(&d.finish().unwrap()).into_owned()
+        };
+
+        let non_isolated = pdf(false, false);
+        assert!(non_isolated.contains("/S /Transparency"));
+        assert!(!non_isolated.contains("/I true") && !non_isolated.contains("/K true"));
+
+        let knockout = pdf(true, true);
+        assert!(knockout.contains("/I true") && knockout.contains("/K true"));
+    }
+
+    #[test]
+    fn glyph_runs_with_different_fills() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let font = Font::new(NOTO_SANS.clone(), 0, true).unwrap();
+        let text = "red blue";
+        let glyphs = vec![
+            KrillaGlyph::new(GlyphId::new(85), 0.32, 0.0, 0.0, 0.0, 0..1),
+            KrillaGlyph::new(GlyphId::new(72), 0.56, 0.0, 0.0, 0.0, 1..2),
+            KrillaGlyph::new(GlyphId::new(71), 0.62, 0.0, 0.0, 0.0, 2..3),
+            KrillaGlyph::new(GlyphId::new(3), 0.26, 0.0, 0.0, 0.0, 3..4),
+            KrillaGlyph::new(GlyphId::new(69), 0.62, 0.0, 0.0, 0.0, 4..5),
+            KrillaGlyph::new(GlyphId::new(79), 0.26, 0.0, 0.0, 0.0, 5..6),
+            KrillaGlyph::new(GlyphId::new(88), 0.62, 0.0, 0.0, 0.0, 6..7),
+            KrillaGlyph::new(GlyphId::new(72), 0.56, 0.0, 0.0, 0.0, 7..8),
+        ];
+
+        let mut page = d.start_page();
+        let mut surface = page.surface();
+        surface.fill_glyph_runs(
+            Point::from_xy(0.0, 100.0),
+            &[
+                GlyphRun {
+                    fill: red_fill(1.0),
+                    font: font.clone(),
+                    font_size: 20.0,
+                    glyphs: &glyphs[0..4],
+                    text,
+                    glyph_units: GlyphUnits::Normalized,
+                    outlined: false,
+                },
+                GlyphRun {
+                    fill: blue_fill(1.0),
+                    font,
+                    font_size: 20.0,
+                    glyphs: &glyphs[4..],
+                    text,
+                    glyph_units: GlyphUnits::Normalized,
+                    outlined: false,
+                },
+            ],
+        );
+        surface.finish();
+        page.finish();
+
+        let pdf = d.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert_eq!(pdf.lines().filter(|l| *l == "BT").count(), 2);
+        assert!(pdf.lines().any(|l| l == "1 0 0 scn"));
+        assert!(pdf.lines().any(|l| l == "0 0 1 scn"));
+    }
+
+    #[test]
+    fn text_run_single_operator() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let font = Font::new(NOTO_SANS.clone(), 0, true).unwrap();
+        let text = "AVA";
+        let glyphs = vec![
+            PositionedGlyph::new(GlyphId::new(36), 0.0, 0.0, 0..1),
+            PositionedGlyph::new(GlyphId::new(57), 12.0, 0.0, 1..2),
+            PositionedGlyph::new(GlyphId::new(36), 30.0, 0.0, 2..3),
+        ];
+
+        let mut page = d.start_page();
+        let mut surface = page.surface();
+        surface.draw_text_run(
+            Point::from_xy(20.0, 100.0),
+            font,
+            20.0,
+            &glyphs,
+            text,
+            TextPaint::Fill(red_fill(1.0)),
+        );
+        surface.finish();
+        page.finish();
+
+        let pdf = d.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert_eq!(pdf.lines().filter(|l| *l == "BT").count(), 1);
+        assert_eq!(pdf.lines().filter(|l| l.ends_with("TJ")).count(), 1);
+    }
+
+    fn ocr_text(surface: &mut Surface) {
+        let font = Font::new(NOTO_SANS.clone(), 0, true).unwrap();
+        let text = "AVA";
+        let glyphs = vec![
+            PositionedGlyph::new(GlyphId::new(36), 0.0, 0.0, 0..1),
+            PositionedGlyph::new(GlyphId::new(57), 12.0, 0.0, 1..2),
+            PositionedGlyph::new(GlyphId::new(36), 30.0, 0.0, 2..3),
+        ];
+
+        surface.draw_ocr_text(Point::from_xy(20.0, 100.0), font, 20.0, &glyphs, text);
+    }
+
+    #[snapshot(stream)]
+    fn stream_ocr_text(surface: &mut Surface) {
+        ocr_text(surface);
+    }
+
+    #[test]
+    fn ocr_text_is_invisible() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let mut page = d.start_page();
+        let mut surface = page.surface();
+        ocr_text(&mut surface);
+        surface.finish();
+        page.finish();
+
+        let pdf = d.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.lines().any(|l| l == "3 Tr"));
+        assert!(pdf.lines().any(|l| l == "1 0 0 -1 20 100 Tm"));
+        // No colors are set for invisible text.
+        assert!(!pdf
+            .lines()
+            .any(|l| l.ends_with(" scn") || l.ends_with(" cs")));
+    }
+
+    fn notdef_fallback_pdf(notdef_fallback: NotdefFallback, direction: TextDirection) -> String {
+        let mut d = Document::new_with(SerializeSettings {
+            notdef_fallback,
+            // Keep the `ToUnicode` CMap readable.
+            compression: Compression::None,
+            ..SerializeSettings::settings_1()
+        });
+        let font = Font::new(NOTO_SANS.clone(), 0, true).unwrap();
+        let mut page = d.start_page();
+        let mut surface = page.surface();
+        surface.fill_text(
+            Point::from_xy(0.0, 80.0),
+            Fill::default(),
+            font,
+            20.0,
+            &[],
+            "Hi 你 there",
+            false,
+            direction,
+        );
+        surface.finish();
+        page.finish();
+
+        String::from_utf8_lossy(&d.finish().unwrap()).into_owned()
+    }
+
+    fn text_objects(pdf: &str) -> usize {
+        pdf.lines().filter(|l| *l == "BT").count()
+    }
+
+    /// The y coordinate of the first text matrix in each text object.
+    fn text_object_ys(pdf: &str) -> Vec<f32> {
+        let mut ys = vec![];
+        let mut in_text = false;
+
+        for line in pdf.lines() {
+            if line == "BT" {
+                in_text = true;
+            } else if in_text && line.ends_with(" Tm") {
+                ys.push(line.split(' ').nth(5).unwrap().parse().unwrap());
+                in_text = false;
+            }
+        }
+
+        ys
+    }
+
+    // The space before the CJK character has to be mapped to both characters.
+    const SPACE_AND_CJK: &str = "00204F60>";
+
+    #[test]
+    fn notdef_fallback_notdef() {
+        let pdf = notdef_fallback_pdf(NotdefFallback::Notdef, TextDirection::Auto);
+        assert_eq!(text_objects(&pdf), 1);
+    }
+
+    #[test]
+    fn notdef_fallback_nothing() {
+        let pdf = notdef_fallback_pdf(NotdefFallback::Nothing, TextDirection::Auto);
+        assert_eq!(text_objects(&pdf), 2);
+        assert!(!pdf.lines().any(|l| l == "f*"));
+        assert!(pdf.contains(SPACE_AND_CJK));
+    }
+
+    #[test]
+    fn notdef_fallback_nothing_vertical() {
+        let pdf = notdef_fallback_pdf(NotdefFallback::Nothing, TextDirection::TopToBottom);
+        let ys = text_object_ys(&pdf);
+
+        // The second run needs to continue below the first one.
+        assert_eq!(ys.len(), 2);
+        assert!(ys[1] > ys[0] + 20.0);
+    }
+
+    #[test]
+    fn notdef_fallback_tofu() {
+        let pdf = notdef_fallback_pdf(NotdefFallback::Tofu, TextDirection::Auto);
+        assert_eq!(text_objects(&pdf), 2);
+        assert!(pdf.lines().any(|l| l == "f*"));
+        assert!(pdf.contains(SPACE_AND_CJK));
+    }
+
+    #[test]
+    fn notdef_fallback_callback() {
+        let cjk = Font::new(NOTO_SANS_CJK.clone(), 0, true).unwrap();
+        let pdf = notdef_fallback_pdf(
+            NotdefFallback::Callback(Arc::new(move |c| {
+                let glyph_id = cjk.font_ref().charmap().map(c)?;
+                Some((cjk.clone(), glyph_id))
+            })),
+            TextDirection::Auto,
+        );
+
+        assert_eq!(text_objects(&pdf), 3);
+        assert!(pdf.contains("NotoSansCJKsc-Regular"));
+    }
+
+    #[test]
+    fn notdef_fallback_callback_without_glyph() {
+        let pdf = notdef_fallback_pdf(
+            NotdefFallback::Callback(Arc::new(|_| None)),
+            TextDirection::Auto,
+        );
+        assert_eq!(text_objects(&pdf), 3);
+        assert!(!pdf.contains("NotoSansCJKsc-Regular"));
+    }
+
+    #[test]
+    fn text_run_with_baseline_shift() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let font = Font::new(NOTO_SANS.clone(), 0, true).unwrap();
+        let text = "x2";
+        let glyphs = vec![
+            PositionedGlyph::new(GlyphId::new(91), 0.0, 0.0, 0..1),
+            PositionedGlyph::new(GlyphId::new(21), 11.0, -8.0, 1..2),
+        ];
+
+        let mut page = d.start_page();
+        let mut surface = page.surface();
+        surface.draw_text_run(
+            Point::from_xy(20.0, 100.0),
+            font,
+            20.0,
+            &glyphs,
+            text,
+            TextPaint::Stroke(red_stroke(1.0, 1.0)),
+        );
+        surface.finish();
+        page.finish();
+
+        let pdf = d.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        // The shifted glyph needs its own text matrix.
+        assert!(pdf.lines().any(|l| l == "1 0 0 -1 20 100 Tm"));
+        assert!(pdf.lines().any(|l| l == "1 0 0 -1 31 92 Tm"));
+    }
+
+    fn glyphs_with_paints_impl(surface: &mut Surface) {
+        let font = Font::new(NOTO_SANS.clone(), 0, true).unwrap();
+        let text = "red blue";
+        let glyphs = vec![
+            KrillaGlyph::new(GlyphId::new(85), 0.32, 0.0, 0.0, 0.0, 0..1),
+            KrillaGlyph::new(GlyphId::new(72), 0.56, 0.0, 0.0, 0.0, 1..2),
+            KrillaGlyph::new(GlyphId::new(71), 0.62, 0.0, 0.0, 0.0, 2..3),
+            KrillaGlyph::new(GlyphId::new(3), 0.26, 0.0, 0.0, 0.0, 3..4),
+            KrillaGlyph::new(GlyphId::new(69), 0.62, 0.0, 0.0, 0.0, 4..5),
+            KrillaGlyph::new(GlyphId::new(79), 0.26, 0.0, 0.0, 0.0, 5..6),
+            KrillaGlyph::new(GlyphId::new(88), 0.62, 0.0, 0.0, 0.0, 6..7),
+            KrillaGlyph::new(GlyphId::new(72), 0.56, 0.0, 0.0, 0.0, 7..8),
+        ];
+        let red = Some(red_fill(1.0).paint);
+        let blue = Some(blue_fill(1.0).paint);
+        let glyph_paints = vec![
+            red.clone(),
+            red.clone(),
+            red,
+            None,
+            blue.clone(),
+            blue.clone(),
+            blue.clone(),
+            blue,
+        ];
+
+        surface.fill_glyphs_with_paints(
+            Point::from_xy(0.0, 100.0),
+            green_fill(1.0),
+            &glyphs,
+            &glyph_paints,
+            font,
+            text,
+            20.0,
+            GlyphUnits::Normalized,
+            false,
+        );
+    }
+
+    #[snapshot(stream)]
+    fn stream_fill_glyphs_with_paints(surface: &mut Surface) {
+        glyphs_with_paints_impl(surface);
+    }
+
+    #[test]
+    fn fill_glyphs_with_paints_single_text_object() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let mut page = d.start_page();
+        let mut surface = page.surface();
+        glyphs_with_paints_impl(&mut surface);
+        surface.finish();
+        page.finish();
+
+        let pdf = d.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert_eq!(pdf.lines().filter(|l| *l == "BT").count(), 1);
+        assert!(pdf.lines().any(|l| l == "1 0 0 scn"));
+        assert!(pdf.lines().any(|l| l == "0 0 1 scn"));
+        assert!(pdf.lines().any(|l| l == "0 1 0 scn"));
+    }
+
+    #[test]
+    fn grid_single_stroked_path() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let mut page = d.start_page();
+        let mut surface = page.surface();
+        surface.draw_grid(
+            Rect::from_xywh(20.0, 20.0, 160.0, 160.0).unwrap(),
+            10,
+            4,
+            red_stroke(1.0, 1.0),
+        );
+        surface.finish();
+        page.finish();
+
+        let pdf = d.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert_eq!(pdf.lines().filter(|l| *l == "S").count(), 1);
+        assert_eq!(pdf.lines().filter(|l| l.ends_with(" m")).count(), 11 + 5);
+    }
+
+    #[test]
+    fn content_budget_exceeded() {
+        let mut d = Document::new_with(SerializeSettings {
+            max_content_bytes: Some(10_000),
+            ..SerializeSettings::settings_1()
+        });
+
+        for _ in 0..3 {
+            let mut page = d.start_page();
+            let mut surface = page.surface();
+
+            for i in 0..1000 {
+                let offset = (i % 100) as f32;
+                surface.fill_path(&rect_to_path(offset, offset, 100.0, 100.0), red_fill(0.5));
+            }
+
+            surface.finish();
+            page.finish();
+        }
+
+        assert_eq!(d.finish(), Err(KrillaError::ContentBudgetExceeded(10_000)));
+    }
+
+    #[test]
+    fn content_budget_exceeded_single_page() {
+        let mut d = Document::new_with(SerializeSettings {
+            max_content_bytes: Some(10_000),
+            ..SerializeSettings::settings_1()
+        });
+        let font = Font::new(NOTO_SANS.clone(), 0, true).unwrap();
+        let image = load_png_image("rgb8.png");
+
+        let mut page = d.start_page();
+        let mut surface = page.surface();
+
+        for i in 0..1000 {
+            let offset = (i % 100) as f32;
+            surface.fill_text(
+                Point::from_xy(offset, offset),
+                Fill::default(),
+                font.clone(),
+                12.0,
+                &[],
+                "Some text that is drawn over and over again.",
+                false,
+                TextDirection::Auto,
+            );
+            surface.draw_image(image.clone(), Size::from_wh(offset + 1.0, 10.0).unwrap());
+        }
+
+        // Drawing stops once the budget is exceeded, even before the
+        // content stream of the page is finished.
+        assert!(surface.root_builder.estimated_len() < 20_000);
+
+        surface.finish();
+        page.finish();
+
+        assert_eq!(d.finish(), Err(KrillaError::ContentBudgetExceeded(10_000)));
+    }
+
+    #[test]
+    fn content_budget_exceeded_by_estimate() {
+        let mut d = Document::new_with(SerializeSettings {
+            max_content_bytes: Some(1000),
+            ..SerializeSettings::settings_1()
+        });
+
+        // Each segment is estimated to be much longer than it actually is.
+        let mut builder = PathBuilder::new();
+        builder.move_to(0.0, 0.0);
+        for i in 1..30 {
+            builder.line_to(i as f32, (i % 2) as f32);
+        }
+        builder.close();
+        let path = builder.finish().unwrap();
+
+        let mut page = d.start_page();
+        let mut surface = page.surface();
+        surface.fill_path(&path, Fill::default());
+        surface.fill_path(&path, Fill::default());
+        surface.finish();
+        page.finish();
+
+        // The second path was skipped, even though the content that was actually
+        // written stays below the budget.
+        assert_eq!(d.finish(), Err(KrillaError::ContentBudgetExceeded(1000)));
+    }
 
     #[visreg]
     fn text_direction_ltr(surface: &mut Surface) {
@@ -722,6 +2481,21 @@ mod tests {
         );
     }
 
+    #[snapshot(stream)]
+    fn stream_fill_text_small_caps_range(surface: &mut Surface) {
+        let font = Font::new(NOTO_SANS.clone(), 0, true).unwrap();
+        surface.fill_text_with_features(
+            Point::from_xy(0.0, 100.0),
+            Fill::default(),
+            font,
+            16.0,
+            "Some small caps",
+            &[(5..10, vec!["smcp".parse().unwrap()])],
+            false,
+            TextDirection::Auto,
+        );
+    }
+
     #[snapshot(stream)]
     fn stream_path_single_with_rgb(surface: &mut Surface) {
         let path = rect_to_path(20.0, 20.0, 180.0, 180.0);
@@ -761,6 +2535,80 @@ mod tests {
         surface.fill_path(&path3, blue_fill(1.0));
     }
 
+    fn dashed_curve(surface: &mut Surface, dash: StrokeDash) {
+        let mut builder = PathBuilder::new();
+        builder.move_to(20.0, 150.0);
+        builder.cubic_to(50.0, 20.0, 150.0, 20.0, 180.0, 150.0);
+        let path = builder.finish().unwrap();
+
+        surface.stroke_path(
+            &path,
+            Stroke {
+                line_cap: LineCap::Round,
+                dash: Some(dash),
+                ..red_stroke(1.0, 10.0)
+            },
+        );
+    }
+
+    #[snapshot(stream)]
+    fn stream_stroke_miter_limit(surface: &mut Surface) {
+        let mut builder = PathBuilder::new();
+        builder.move_to(20.0, 180.0);
+        builder.line_to(100.0, 20.0);
+        builder.line_to(180.0, 180.0);
+        let path = builder.finish().unwrap();
+
+        surface.stroke_path(
+            &path,
+            Stroke {
+                miter_limit: 2.0,
+                ..red_stroke(1.0, 10.0)
+            },
+        );
+    }
+
+    #[snapshot(stream)]
+    fn stream_stroke_dash_phase(surface: &mut Surface) {
+        dashed_curve(
+            surface,
+            StrokeDash {
+                array: vec![20.0, 15.0],
+                offset: 5.0,
+            },
+        );
+    }
+
+    #[visreg(all)]
+    fn stroke_dash_round_cap_curve(surface: &mut Surface) {
+        dashed_curve(
+            surface,
+            StrokeDash {
+                array: vec![0.0, 20.0, 30.0],
+                offset: 10.0,
+            },
+        );
+    }
+
+    #[test]
+    fn stroke_dash_all_zero_is_solid() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let mut page = d.start_page();
+        let mut surface = page.surface();
+        dashed_curve(
+            &mut surface,
+            StrokeDash {
+                array: vec![0.0, 0.0],
+                offset: 0.0,
+            },
+        );
+        surface.finish();
+        page.finish();
+
+        let pdf = String::from_utf8_lossy(&d.finish().unwrap()).into_owned();
+        assert!(!pdf.contains(" d\n"));
+    }
+
     #[snapshot(stream)]
     fn stream_nested_transforms(surface: &mut Surface) {
         let path1 = rect_to_path(0.0, 0.0, 100.0, 100.0);
@@ -812,6 +2660,49 @@ mod tests {
         );
     }
 
+    fn glyphs_with_render_mode(surface: &mut Surface, render_mode: TextRenderMode) {
+        let font = Font::new(NOTO_SANS.clone(), 0, true).unwrap();
+        let text = "Hello";
+        let glyphs = naive_shape(text, font.clone(), &[], 60.0, TextDirection::Auto);
+
+        surface.draw_glyphs(
+            Point::from_xy(10.0, 120.0),
+            render_mode,
+            red_fill(1.0),
+            Stroke {
+                width: 2.0,
+                ..blue_stroke(1.0)
+            },
+            &glyphs,
+            font,
+            text,
+            60.0,
+            GlyphUnits::UserSpace,
+        );
+    }
+
+    #[snapshot(stream)]
+    fn stream_text_render_mode_fill_stroke(surface: &mut Surface) {
+        glyphs_with_render_mode(surface, TextRenderMode::FillStroke);
+    }
+
+    #[snapshot(stream)]
+    fn stream_text_render_mode_invisible(surface: &mut Surface) {
+        glyphs_with_render_mode(surface, TextRenderMode::Invisible);
+    }
+
+    #[visreg]
+    fn text_render_mode_fill_stroke(surface: &mut Surface) {
+        glyphs_with_render_mode(surface, TextRenderMode::FillStroke);
+    }
+
+    #[visreg]
+    fn text_render_mode_clip(surface: &mut Surface) {
+        glyphs_with_render_mode(surface, TextRenderMode::Clip);
+        surface.fill_path(&rect_to_path(0.0, 0.0, 200.0, 200.0), green_fill(1.0));
+        surface.pop();
+    }
+
     #[snapshot(single_page)]
     fn complex_text(page: &mut Page) {
         let mut surface = page.surface();
@@ -879,6 +2770,87 @@ mod tests {
         surface.draw_image(image, size);
     }
 
+    #[visreg(all)]
+    fn image_with_opacity_over_background(surface: &mut Surface) {
+        surface.fill_path(&rect_to_path(0.0, 0.0, 200.0, 100.0), red_fill(1.0));
+        surface.fill_path(&rect_to_path(0.0, 100.0, 200.0, 200.0), blue_fill(1.0));
+
+        let image = load_png_image("rgb8.png");
+        let size = Size::from_wh(image.size().0 as f32, image.size().1 as f32).unwrap();
+        surface.draw_image_with_opacity(image, size, NormalizedF32::new(0.5).unwrap());
+    }
+
+    #[visreg]
+    fn image_with_blend_mode(surface: &mut Surface) {
+        surface.fill_path(&rect_to_path(0.0, 0.0, 200.0, 200.0), green_fill(1.0));
+
+        let image = load_png_image("rgb8.png");
+        let size = Size::from_wh(image.size().0 as f32, image.size().1 as f32).unwrap();
+        surface.draw_image_with_blend(image, size, BlendMode::Multiply);
+    }
+
+    fn image_with_rect_pdf(draw: impl FnOnce(&mut Surface, Image)) -> String {
+        // A non-square image.
+        let mut data = vec![];
+        image::RgbImage::from_pixel(200, 100, image::Rgb([255, 0, 0]))
+            .write_to(
+                &mut std::io::Cursor::new(&mut data),
+                image::ImageFormat::Png,
+            )
+            .unwrap();
+        let image = Image::from_png(Arc::new(data)).unwrap();
+
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let mut page = d.start_page_with(PageSettings::new(200.0, 200.0));
+        let mut surface = page.surface();
+        draw(&mut surface, image);
+        surface.finish();
+        page.finish();
+
+        String::from_utf8_lossy(&d.finish().unwrap()).into_owned()
+    }
+
+    #[test]
+    fn image_with_rect() {
+        let pdf = image_with_rect_pdf(|surface, image| {
+            surface.draw_image_with_rect(image, Rect::from_xywh(20.0, 30.0, 100.0, 100.0).unwrap());
+        });
+
+        // The unit square of the image is mapped to x 20..120 and (in PDF coordinates,
+        // with the page height of 200) y 70..170, which is y 30..130 in surface coordinates.
+        assert!(pdf.contains("100 0 0 100 20 70 cm"));
+
+        let pdf_with_transform = image_with_rect_pdf(|surface, image| {
+            surface.draw_image_with_transform(
+                image,
+                &Transform::from_row(100.0, 0.0, 0.0, 100.0, 20.0, 30.0),
+            );
+        });
+
+        assert!(pdf_with_transform.contains("100 0 0 100 20 70 cm"));
+    }
+
+    #[test]
+    fn image_with_opacity_dedupes_ext_g_state() {
+        let mut d = Document::new_with(SerializeSettings::settings_1());
+        let mut page = d.start_page();
+        let mut surface = page.surface();
+
+        for _ in 0..2 {
+            let image = load_png_image("rgb8.png");
+            let size = Size::from_wh(image.size().0 as f32, image.size().1 as f32).unwrap();
+            surface.draw_image_with_opacity(image, size, NormalizedF32::new(0.5).unwrap());
+        }
+
+        surface.finish();
+        page.finish();
+
+        let pdf = d.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert_eq!(pdf.matches("/Type /ExtGState").count(), 1);
+        assert_eq!(pdf.lines().filter(|l| *l == "/g0 gs").count(), 2);
+    }
+
     #[snapshot(stream)]
     fn stream_mask(surface: &mut Surface) {
         let mask = basic_mask(surface, MaskType::Alpha);
@@ -962,6 +2934,7 @@ mod tests {
             spread_method,
             stops: stops_with_3_solid_1(),
             anti_alias: false,
+            interpolation: Default::default(),
         }
     }
 
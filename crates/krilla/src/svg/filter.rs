@@ -76,16 +76,7 @@ pub(crate) fn render(
         &mut pixmap.as_mut(),
     );
 
-    let demultiplied = pixmap
-        .pixels()
-        .iter()
-        .flat_map(|p| {
-            let c = p.demultiply();
-            [c.red(), c.green(), c.blue(), c.alpha()]
-        })
-        .collect::<Vec<_>>();
-
-    let image = Image::from_rgba8(demultiplied, width, height);
+    let image = Image::from_pixmap(&pixmap);
     let size = Size::from_wh(layer_bbox.width(), layer_bbox.height())?;
 
     surface.push_transform(&Transform::from_translate(layer_bbox.x(), layer_bbox.y()));
@@ -75,6 +75,7 @@ pub(crate) fn convert_paint(
                 .collect::<Vec<_>>()
                 .into(),
             anti_alias: false,
+            interpolation: Default::default(),
         }
         .into(),
         usvg::Paint::RadialGradient(rg) => RadialGradient {
@@ -93,6 +94,7 @@ pub(crate) fn convert_paint(
                 .collect::<Vec<_>>()
                 .into(),
             anti_alias: false,
+            interpolation: Default::default(),
         }
         .into(),
         usvg::Paint::Pattern(pat) => {
@@ -101,14 +103,14 @@ pub(crate) fn convert_paint(
             surface.finish();
             let stream = stream_builder.finish();
 
-            Pattern {
+            Pattern::new(
                 stream,
-                transform: additional_transform
+                additional_transform
                     .pre_concat(pat.transform())
                     .pre_concat(Transform::from_translate(pat.rect().x(), pat.rect().y())),
-                width: pat.rect().width(),
-                height: pat.rect().height(),
-            }
+                pat.rect().width(),
+                pat.rect().height(),
+            )
             .into()
         }
     }
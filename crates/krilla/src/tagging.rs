@@ -72,7 +72,9 @@
 //!      annotations to the content they correspond to. Currently, krilla only supports link
 //!      annotations, and a link annotation should always be a child in a tag group with the
 //!      [Tag] `Link`, with its sibling being an identifier or another tag group that is
-//!      to be associated with the link.
+//!      to be associated with the link. Alternatively, you can enable the
+//!      `auto_tag_annotations` attribute in [`SerializeSettings`], in which case all
+//!      annotations added via `add_annotation` will automatically be appended to the tag tree.
 //!    - Use the `start_tagged` command on [`Surface`], which returns an [Identifier], and
 //!      indicates that all content drawn on the surface should be associated with that
 //!      identifier, until you call the `end_tagged` method. *Important*: Note that you cannot
@@ -86,6 +88,10 @@
 //!
 //! 5) Once you have built your tag tree, simply call `set_tag_tree` on [`Document`]. That's it!
 //!
+//! If your document contains a huge number of groups, for example because of very large tables,
+//! consider building the tree in a [`TagArena`] instead, which stores all groups in a few flat
+//! buffers and thus needs considerably less memory.
+//!
 //! # Other notes
 //!
 //! Make sure that you carefully read the documentation of the other parts of this module, as
@@ -106,7 +112,8 @@
 //! - Word breaks in text should be represented explicitly with spaces, instead of implicitly
 //!   by not including them, but instead positioning text in a way that "simulates" the spaces.
 //! - Hyphenation should be represented as a soft hyphen character (U+00AD) instead
-//!   of a hard hyphen (U+002D).
+//!   of a hard hyphen (U+002D). If you do your own line breaking, you can use
+//!   [`paragraph_actual_texts`] to derive the `ActualText` of each line of a hyphenated paragraph.
 //! - Tag groups should follow the best-practice of what kind of children they contain. See
 //!   [Tag] for more information.
 //! - You should provide "Alt" descriptions for formulas and images.
@@ -121,9 +128,7 @@
 use std::cmp::PartialEq;
 use std::collections::{BTreeMap, HashMap};
 
-use pdf_writer::types::{
-    ArtifactAttachment, ArtifactSubtype, ListNumbering, StructRole, TableHeaderScope,
-};
+use pdf_writer::types::{ArtifactAttachment, ArtifactSubtype, StructRole, TableHeaderScope};
 use pdf_writer::writers::{PropertyList, StructElement};
 use pdf_writer::{Chunk, Finish, Name, Ref, Str, TextStr};
 
@@ -132,6 +137,8 @@ use crate::serialize::SerializeContext;
 use crate::validation::ValidationError;
 use crate::version::PdfVersion;
 
+pub use pdf_writer::types::ListNumbering;
+
 /// A type of artifact.
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum ArtifactType {
@@ -139,6 +146,12 @@ pub enum ArtifactType {
     Header,
     /// The footer of the page.
     Footer,
+    /// A page number, for example as part of a header or footer.
+    ///
+    /// Note that when exporting with PDF/UA, a document that contains page number
+    /// artifacts also needs to define page labels, so that the page numbers shown
+    /// in the viewer match the ones printed on the pages.
+    PageNumber,
     /// Page artifacts, such as for example cut marks or color bars.
     Page,
     /// Any other type of artifact (e.g. table strokes).
@@ -150,6 +163,7 @@ impl ArtifactType {
         match self {
             ArtifactType::Header => true,
             ArtifactType::Footer => true,
+            ArtifactType::PageNumber => true,
             ArtifactType::Page => true,
             ArtifactType::Other => false,
         }
@@ -168,6 +182,87 @@ pub type ActualText<'a> = &'a str;
 /// The expanded form of an abbreviation.
 pub type Expanded<'a> = &'a str;
 
+/// Compute the `ActualText` of each line of a paragraph that was broken into multiple lines.
+///
+/// `paragraph` is the full, logical text of the paragraph, while `lines` are the runs of text
+/// that are actually visible on each line, including any hyphens that were inserted at line
+/// breaks. The returned strings (one per line) can be used as the `ActualText` of the
+/// [`ContentTag::Span`] wrapping each line, so that the paragraph is extracted as the
+/// original text, without any stray hyphens. Soft hyphens (U+00AD) are removed, while
+/// whitespace at line breaks is attached to the end of the previous line.
+///
+/// If the lines don't match up with the paragraph, the remaining text is attached to the
+/// last line.
+pub fn paragraph_actual_texts(paragraph: &str, lines: &[&str]) -> Vec<String> {
+    const SOFT_HYPHEN: char = '\u{AD}';
+
+    let mut remaining = paragraph;
+    let mut texts = Vec::with_capacity(lines.len());
+
+    for (i, line) in lines.iter().enumerate() {
+        if i + 1 == lines.len() {
+            texts.push(remaining.replace(SOFT_HYPHEN, ""));
+            break;
+        }
+
+        let mut end = 0;
+        let mut chars = remaining.char_indices().peekable();
+
+        for c in line.trim_end().chars() {
+            // Soft hyphens are invisible unless they are at a line break.
+            if c != SOFT_HYPHEN {
+                while chars.next_if(|&(_, p)| p == SOFT_HYPHEN).is_some() {}
+            }
+
+            // A mismatch means that the character was inserted during
+            // line breaking, i.e. it's a hyphen.
+            match chars.next_if(|&(_, p)| p == c) {
+                Some((idx, p)) => end = idx + p.len_utf8(),
+                None => break,
+            }
+        }
+
+        while let Some((idx, p)) = chars.next_if(|&(_, p)| p == SOFT_HYPHEN || p.is_whitespace()) {
+            end = idx + p.len_utf8();
+        }
+
+        texts.push(remaining[..end].replace(SOFT_HYPHEN, ""));
+        remaining = &remaining[end..];
+    }
+
+    texts
+}
+
+/// The base direction of an isolated run of text. See [`isolate_actual_text`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IsolationDirection {
+    /// The run is isolated with a left-to-right base direction.
+    LeftToRight,
+    /// The run is isolated with a right-to-left base direction.
+    RightToLeft,
+    /// The base direction is determined by the first strong character of the run.
+    FirstStrong,
+}
+
+/// Wrap some text in Unicode directional isolates, so that it can be used as the
+/// `ActualText` of a [`ContentTag::Span`] or a [`SpanTag`].
+///
+/// This is useful for numeric content in bidirectional text (for example European
+/// digits within Arabic text), where assistive technology might otherwise reorder the
+/// digits when reading the text out loud. Note that this only affects how the text is
+/// interpreted by assistive technology and when extracting text, it does not change
+/// how the text is rendered.
+pub fn isolate_actual_text(text: &str, direction: IsolationDirection) -> String {
+    let isolate = match direction {
+        IsolationDirection::LeftToRight => '\u{2066}',
+        IsolationDirection::RightToLeft => '\u{2067}',
+        IsolationDirection::FirstStrong => '\u{2068}',
+    };
+
+    // Pop directional isolate.
+    format!("{isolate}{text}\u{2069}")
+}
+
 /// A content tag associated with the content it wraps.
 #[derive(Clone, Copy, Debug)]
 pub enum ContentTag<'a> {
@@ -215,6 +310,7 @@ impl ContentTag<'_> {
                 let artifact_type = match at {
                     ArtifactType::Header => pdf_writer::types::ArtifactType::Pagination,
                     ArtifactType::Footer => pdf_writer::types::ArtifactType::Pagination,
+                    ArtifactType::PageNumber => pdf_writer::types::ArtifactType::Pagination,
                     ArtifactType::Page => pdf_writer::types::ArtifactType::Page,
                     // This method should only be called with artifacts that actually
                     // require a property.
@@ -324,7 +420,7 @@ pub(crate) enum IdentifierType {
     AnnotationIdentifier(AnnotationIdentifier),
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub(crate) enum IdentifierInner {
     Real(IdentifierType),
     Dummy,
@@ -333,7 +429,7 @@ pub(crate) enum IdentifierInner {
 /// An identifier for an annotation or certain parts of page content.
 ///
 /// Need to be used as a leaf node in a tag tree.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Identifier(pub(crate) IdentifierInner);
 
 impl Identifier {
@@ -344,6 +440,13 @@ impl Identifier {
     pub(crate) fn dummy() -> Self {
         Self(IdentifierInner::Dummy)
     }
+
+    fn reference(&self) -> Option<Reference> {
+        match self.0 {
+            IdentifierInner::Real(rci) => Some(Reference::ContentIdentifier(rci)),
+            IdentifierInner::Dummy => None,
+        }
+    }
 }
 
 /// A tag for group nodes.
@@ -405,7 +508,7 @@ pub enum Tag {
     /// A list.
     ///
     /// **Best practice**: Should consist of an optional caption followed by
-    /// list items.
+    /// list items. You can use [`ListBuilder`] to create lists with this structure.
     // List numbering is only required for PDF/UA, but we just enforce it for always.
     L(ListNumbering),
     /// A list item.
@@ -464,10 +567,15 @@ pub enum Tag {
     /// An association between an annotation and the content it belongs to. PDF
     ///
     /// **Best practice**: Should be used for all annotations, except for link annotations and
-    /// widget annotations. The first child should be the identifier of a non-link annotation,
+    /// widget annotations (see [`Tag::Form`]). The first child should be the identifier of a non-link annotation,
     /// and all other subsequent children should be content identifiers associated with that
     /// annotation.
     Annot,
+    /// A form field.
+    ///
+    /// **Best practice**: Should be used for all widget annotations of form fields. The
+    /// first child should be the identifier of the widget annotation.
+    Form,
     /// Item of graphical content, with some optional alt text.
     ///
     /// Providing the alt text is required in some export modes, like for example PDF/UA1.
@@ -476,6 +584,12 @@ pub enum Tag {
     ///
     /// Providing the alt text is required in some export modes, like for example PDF/UA1.
     Formula(Option<String>),
+    /// An inline portion of text with properties that differ from the surrounding text,
+    /// like its language or its actual text.
+    ///
+    /// In contrast to [`ContentTag::Span`], the properties are stored in the structure
+    /// element, so they apply to all of its children.
+    Span(SpanTag),
     // All below are non-standard attributes.
     /// A date or time.
     Datetime,
@@ -526,8 +640,10 @@ impl Tag {
                 Tag::Code => struct_elem.kind(StructRole::Code),
                 Tag::Link => struct_elem.kind(StructRole::Link),
                 Tag::Annot => struct_elem.kind(StructRole::Annot),
+                Tag::Form => struct_elem.kind(StructRole::Form),
                 Tag::Figure(_) => struct_elem.kind(StructRole::Figure),
                 Tag::Formula(_) => struct_elem.kind(StructRole::Formula),
+                Tag::Span(_) => struct_elem.kind(StructRole::Span),
                 // Every additional tag needs to be registered in the role map!
                 Tag::Datetime => struct_elem.custom_kind(Name(b"Datetime")),
                 Tag::Terms => struct_elem.custom_kind(Name(b"Terms")),
@@ -544,6 +660,7 @@ impl Tag {
         match self {
             Tag::Figure(s) => s.as_deref(),
             Tag::Formula(s) => s.as_deref(),
+            Tag::Span(s) => s.alt.as_deref(),
             _ => None,
         }
     }
@@ -583,8 +700,10 @@ impl Tag {
             Tag::Code => PdfVersion::Pdf14,
             Tag::Link => PdfVersion::Pdf14,
             Tag::Annot => PdfVersion::Pdf15,
+            Tag::Form => PdfVersion::Pdf14,
             Tag::Figure(_) => PdfVersion::Pdf15,
             Tag::Formula(_) => PdfVersion::Pdf15,
+            Tag::Span(_) => PdfVersion::Pdf14,
             Tag::Datetime => PdfVersion::Pdf15,
             Tag::Terms => PdfVersion::Pdf15,
             Tag::Title => PdfVersion::Pdf15,
@@ -611,6 +730,41 @@ impl Tag {
     }
 }
 
+/// The properties of a [`Tag::Span`].
+#[derive(Debug, Clone, Default)]
+pub struct SpanTag {
+    pub(crate) lang: Option<String>,
+    pub(crate) alt: Option<String>,
+    pub(crate) actual_text: Option<String>,
+}
+
+impl SpanTag {
+    /// Create a new span without any properties.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The language of the text, as a BCP 47 language tag.
+    pub fn lang(mut self, lang: String) -> Self {
+        self.lang = Some(lang);
+        self
+    }
+
+    /// An alternate description of the text, for example if it consists of symbols
+    /// that should be described in natural language.
+    pub fn alt(mut self, alt: String) -> Self {
+        self.alt = Some(alt);
+        self
+    }
+
+    /// The exact replacement for the text, which is used when reading it out loud
+    /// or extracting it. See [`isolate_actual_text`] for numbers in bidirectional text.
+    pub fn actual_text(mut self, actual_text: String) -> Self {
+        self.actual_text = Some(actual_text);
+        self
+    }
+}
+
 /// A node in a tag tree.
 pub enum Node {
     /// A group node.
@@ -638,10 +792,7 @@ impl Node {
                 note_id,
                 struct_elems,
             )?)),
-            Node::Leaf(ci) => match ci.0 {
-                IdentifierInner::Real(rci) => Ok(Some(Reference::ContentIdentifier(rci))),
-                IdentifierInner::Dummy => Ok(None),
-            },
+            Node::Leaf(ci) => Ok(ci.reference()),
         }
     }
 }
@@ -686,6 +837,12 @@ impl TagGroup {
         self.children.push(child.into())
     }
 
+    /// Reorder the children of the tag group by a key. See [`TagTree::reorder_by`]
+    /// for more information.
+    pub fn reorder_by<K: Ord>(&mut self, mut key: impl FnMut(&Identifier) -> K) {
+        reorder_children(&mut self.children, &mut key);
+    }
+
     pub(crate) fn serialize(
         &self,
         sc: &mut SerializeContext,
@@ -706,53 +863,69 @@ impl TagGroup {
             }
         }
 
-        let mut chunk = Chunk::new();
-        let mut struct_elem = chunk.struct_element(root_ref);
-        self.tag
-            .write_kind(&mut struct_elem, sc.serialize_settings().pdf_version);
-        struct_elem.parent(parent);
-
-        if let Some(alt) = self.tag.alt() {
-            struct_elem.alt(TextStr(alt));
-        } else if self.tag.can_have_alt() {
-            sc.register_validation_error(ValidationError::MissingAltText);
-        }
+        serialize_group(
+            sc,
+            &self.tag,
+            root_ref,
+            parent,
+            children_refs,
+            parent_tree_map,
+            id_tree,
+            note_id,
+            struct_elems,
+        )
+    }
+}
 
-        if let Some(title) = self.tag.title() {
-            struct_elem.title(TextStr(title));
-        } else if self.tag.can_have_title() {
-            sc.register_validation_error(ValidationError::MissingHeadingTitle);
+/// A builder for creating lists with the correct structure.
+///
+/// A list in the tag tree should consist of list items (`LI`), each of which contains
+/// an optional label (`Lbl`), like a bullet point or a number, and the body of the
+/// list item (`LBody`). This builder takes care of creating this nested structure.
+pub struct ListBuilder {
+    numbering: ListNumbering,
+    items: Vec<(Option<Identifier>, Node)>,
+}
+
+impl ListBuilder {
+    /// Create a new list builder with the given numbering.
+    pub fn new(numbering: ListNumbering) -> Self {
+        Self {
+            numbering,
+            items: vec![],
         }
+    }
 
-        match self.tag {
-            Tag::L(ln) => {
-                struct_elem.attributes().push().list().list_numbering(ln);
-            }
-            Tag::TH(ths) => {
-                if sc.serialize_settings().pdf_version >= PdfVersion::Pdf15 {
-                    struct_elem.attributes().push().table().scope(ths);
-                }
-            }
-            Tag::Note => {
-                let id = format!("Note {}", note_id);
-                *note_id += 1;
-                id_tree.insert(id.clone(), root_ref);
-                struct_elem.id(Str(id.as_bytes()));
+    /// Add a new item to the list.
+    ///
+    /// The `label` should point to the content of the list label (e.g. the bullet point), if
+    /// it exists. The `body` can either be an identifier or another tag group, for example
+    /// a nested list.
+    pub fn push_item(&mut self, label: Option<Identifier>, body: impl Into<Node>) {
+        self.items.push((label, body.into()));
+    }
+
+    /// Create the tag group of the list.
+    pub fn finish(self) -> TagGroup {
+        let mut list = TagGroup::new(Tag::L(self.numbering));
+
+        for (label, body) in self.items {
+            let mut item = TagGroup::new(Tag::LI);
+
+            if let Some(label) = label {
+                let mut lbl = TagGroup::new(Tag::Lbl);
+                lbl.push(label);
+                item.push(lbl);
             }
-            _ => {}
-        }
 
-        serialize_children(
-            sc,
-            root_ref,
-            children_refs,
-            parent_tree_map,
-            &mut struct_elem,
-        )?;
-        struct_elem.finish();
-        struct_elems.push(chunk);
+            let mut l_body = TagGroup::new(Tag::LBody);
+            l_body.push(body);
+            item.push(l_body);
+
+            list.push(item);
+        }
 
-        Ok(Reference::Ref(root_ref))
+        list
     }
 }
 
@@ -760,17 +933,59 @@ impl TagGroup {
 #[derive(Default)]
 pub struct TagTree {
     children: Vec<Node>,
+    /// The arena that stores the nodes instead, if the tree was created from one.
+    arena: Option<TagArena>,
 }
 
 impl TagTree {
     /// Create a new tag tree.
     pub fn new() -> Self {
-        Self { children: vec![] }
+        Self {
+            children: vec![],
+            arena: None,
+        }
+    }
+
+    /// Create a new tag tree from an arena. The children of the root of the
+    /// arena become the children of the tag tree.
+    pub fn with_arena(arena: TagArena) -> Self {
+        Self {
+            children: vec![],
+            arena: Some(arena),
+        }
     }
 
     /// Append a new child to the tag tree.
+    ///
+    /// If the tree was created from an arena, the child is moved into the arena.
     pub fn push(&mut self, child: impl Into<Node>) {
-        self.children.push(child.into())
+        match &mut self.arena {
+            Some(arena) => arena.push(arena.root(), child),
+            None => self.children.push(child.into()),
+        }
+    }
+
+    /// Reorder the nodes of the tag tree by a key.
+    ///
+    /// The tag tree should reflect the logical reading order of the document, which is not
+    /// necessarily the same as the order in which the content was drawn, for example in
+    /// multi-column layouts. This method allows you to fix up the order after the
+    /// tree has been built by providing a sort key (e.g. the column and the vertical position
+    /// of the content) for each identifier.
+    ///
+    /// The children of each group are sorted independently, so the structure of the tree
+    /// is preserved. Groups are ordered by the smallest key among their descendants,
+    /// and groups without any identifiers are moved to the end. The sort is stable, so nodes
+    /// with equal keys keep their relative order.
+    pub fn reorder_by<K: Ord>(&mut self, mut key: impl FnMut(&Identifier) -> K) {
+        match &mut self.arena {
+            Some(arena) => {
+                arena.reorder(arena.root(), &mut key);
+            }
+            None => {
+                reorder_children(&mut self.children, &mut key);
+            }
+        }
     }
 
     pub(crate) fn serialize(
@@ -790,6 +1005,24 @@ impl TagTree {
 
         let mut children_refs = vec![];
 
+        if let Some(arena) = &self.arena {
+            for child in arena.children(arena.root()) {
+                let serialized = arena.serialize_node(
+                    *child,
+                    sc,
+                    parent_tree_map,
+                    id_tree_map,
+                    root_ref,
+                    &mut note_id,
+                    &mut struct_elems,
+                )?;
+
+                if let Some(ref_) = serialized {
+                    children_refs.push(ref_);
+                }
+            }
+        }
+
         for child in &self.children {
             let serialized = child.serialize(
                 sc,
@@ -828,6 +1061,329 @@ impl TagTree {
     }
 }
 
+/// The ID of a group in a [`TagArena`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GroupId(u32);
+
+#[derive(Clone, Copy)]
+enum ArenaNode {
+    Group(GroupId),
+    Leaf(Identifier),
+}
+
+/// An entry in the linked list of children of a group.
+struct ArenaChild {
+    node: ArenaNode,
+    /// The index of the next sibling.
+    next: Option<u32>,
+}
+
+struct ArenaGroup {
+    /// The tag of the group, which is `None` for the root.
+    tag: Option<Tag>,
+    /// The indices of the first and the last child.
+    children: Option<(u32, u32)>,
+}
+
+/// An arena for building very large tag trees.
+///
+/// With [`TagGroup`], the children of each group are stored in a separate allocation,
+/// which adds up for documents with a huge number of groups, like table-heavy documents
+/// with hundreds of thousands of cells. A tag arena instead stores all groups and their
+/// children in a few flat buffers and refers to groups by their [`GroupId`].
+///
+/// Start by pushing groups to the [root](TagArena::root) of the arena, and once the
+/// tree is complete, turn it into a tag tree with [`TagTree::with_arena`]. A tree built
+/// this way is written in exactly the same way as if it had been built from tag groups.
+pub struct TagArena {
+    groups: Vec<ArenaGroup>,
+    children: Vec<ArenaChild>,
+}
+
+impl Default for TagArena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TagArena {
+    /// Create a new, empty arena.
+    pub fn new() -> Self {
+        Self {
+            groups: vec![ArenaGroup {
+                tag: None,
+                children: None,
+            }],
+            children: vec![],
+        }
+    }
+
+    /// The ID of the root of the arena, which corresponds to the root of the tag tree.
+    pub fn root(&self) -> GroupId {
+        GroupId(0)
+    }
+
+    /// Append a new group with the given tag to a group and return its ID.
+    ///
+    /// Panics if `parent` doesn't belong to this arena.
+    pub fn push_group(&mut self, parent: GroupId, tag: Tag) -> GroupId {
+        let id = GroupId(self.groups.len() as u32);
+        self.groups.push(ArenaGroup {
+            tag: Some(tag),
+            children: None,
+        });
+        self.push_node(parent, ArenaNode::Group(id));
+
+        id
+    }
+
+    /// Append a new child to a group. Tag groups are moved into the arena.
+    ///
+    /// Panics if `parent` doesn't belong to this arena.
+    pub fn push(&mut self, parent: GroupId, child: impl Into<Node>) {
+        match child.into() {
+            Node::Group(group) => {
+                let id = self.push_group(parent, group.tag);
+
+                for child in group.children {
+                    self.push(id, child);
+                }
+            }
+            Node::Leaf(identifier) => self.push_node(parent, ArenaNode::Leaf(identifier)),
+        }
+    }
+
+    fn push_node(&mut self, parent: GroupId, node: ArenaNode) {
+        let index = self.children.len() as u32;
+        self.children.push(ArenaChild { node, next: None });
+
+        let list = &mut self.groups[parent.0 as usize].children;
+        match list {
+            Some((_, last)) => {
+                self.children[*last as usize].next = Some(index);
+                *last = index;
+            }
+            None => *list = Some((index, index)),
+        }
+    }
+
+    fn child_indices(&self, group: GroupId) -> impl Iterator<Item = u32> + '_ {
+        let mut next = self.groups[group.0 as usize]
+            .children
+            .map(|(first, _)| first);
+
+        std::iter::from_fn(move || {
+            let index = next?;
+            next = self.children[index as usize].next;
+            Some(index)
+        })
+    }
+
+    fn children(&self, group: GroupId) -> impl Iterator<Item = &ArenaNode> + '_ {
+        self.child_indices(group)
+            .map(|index| &self.children[index as usize].node)
+    }
+
+    /// Sort the children of the group by their key and return the smallest key.
+    /// Works the same way as `reorder_children`.
+    fn reorder<K: Ord>(
+        &mut self,
+        group: GroupId,
+        key: &mut impl FnMut(&Identifier) -> K,
+    ) -> Option<K> {
+        let indices = self.child_indices(group).collect::<Vec<_>>();
+
+        let mut keyed = indices
+            .into_iter()
+            .map(|index| {
+                let node_key = match self.children[index as usize].node {
+                    ArenaNode::Group(child) => self.reorder(child, key),
+                    ArenaNode::Leaf(identifier) => Some(key(&identifier)),
+                };
+
+                (node_key, index)
+            })
+            .collect::<Vec<_>>();
+
+        keyed.sort_by(|(k1, _), (k2, _)| cmp_keys(k1, k2));
+
+        for window in keyed.windows(2) {
+            self.children[window[0].1 as usize].next = Some(window[1].1);
+        }
+
+        if let (Some((_, first)), Some((_, last))) = (keyed.first(), keyed.last()) {
+            self.children[*last as usize].next = None;
+            self.groups[group.0 as usize].children = Some((*first, *last));
+        }
+
+        keyed.into_iter().next().and_then(|(node_key, _)| node_key)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn serialize_node(
+        &self,
+        node: ArenaNode,
+        sc: &mut SerializeContext,
+        parent_tree_map: &mut HashMap<IdentifierType, Ref>,
+        id_tree: &mut BTreeMap<String, Ref>,
+        parent: Ref,
+        note_id: &mut u32,
+        struct_elems: &mut Vec<Chunk>,
+    ) -> KrillaResult<Option<Reference>> {
+        let group = match node {
+            ArenaNode::Group(group) => group,
+            ArenaNode::Leaf(identifier) => return Ok(identifier.reference()),
+        };
+
+        let root_ref = sc.new_ref();
+        let mut children_refs = vec![];
+
+        for child in self.children(group) {
+            let serialized = self.serialize_node(
+                *child,
+                sc,
+                parent_tree_map,
+                id_tree,
+                parent,
+                note_id,
+                struct_elems,
+            )?;
+
+            if let Some(ref_) = serialized {
+                children_refs.push(ref_);
+            }
+        }
+
+        // Only the root doesn't have a tag, and it's never serialized as a node.
+        let tag = self.groups[group.0 as usize].tag.as_ref().unwrap();
+
+        serialize_group(
+            sc,
+            tag,
+            root_ref,
+            parent,
+            children_refs,
+            parent_tree_map,
+            id_tree,
+            note_id,
+            struct_elems,
+        )
+        .map(Some)
+    }
+}
+
+/// Sort the nodes by their key and return the smallest key.
+fn reorder_children<K: Ord>(
+    children: &mut Vec<Node>,
+    key: &mut impl FnMut(&Identifier) -> K,
+) -> Option<K> {
+    let mut keyed = std::mem::take(children)
+        .into_iter()
+        .map(|mut node| {
+            let node_key = match &mut node {
+                Node::Group(group) => reorder_children(&mut group.children, key),
+                Node::Leaf(identifier) => Some(key(identifier)),
+            };
+
+            (node_key, node)
+        })
+        .collect::<Vec<_>>();
+
+    keyed.sort_by(|(k1, _), (k2, _)| cmp_keys(k1, k2));
+
+    let mut min_key = None;
+
+    for (node_key, node) in keyed {
+        if min_key.is_none() {
+            min_key = node_key;
+        }
+
+        children.push(node);
+    }
+
+    min_key
+}
+
+/// Write the struct element of a group, given the already serialized children.
+#[allow(clippy::too_many_arguments)]
+fn serialize_group(
+    sc: &mut SerializeContext,
+    tag: &Tag,
+    root_ref: Ref,
+    parent: Ref,
+    children_refs: Vec<Reference>,
+    parent_tree_map: &mut HashMap<IdentifierType, Ref>,
+    id_tree: &mut BTreeMap<String, Ref>,
+    note_id: &mut u32,
+    struct_elems: &mut Vec<Chunk>,
+) -> KrillaResult<Reference> {
+    let mut chunk = Chunk::new();
+    let mut struct_elem = chunk.struct_element(root_ref);
+    tag.write_kind(&mut struct_elem, sc.serialize_settings().pdf_version);
+    struct_elem.parent(parent);
+
+    if let Some(alt) = tag.alt() {
+        struct_elem.alt(TextStr(alt));
+    } else if tag.can_have_alt() {
+        sc.register_validation_error(ValidationError::MissingAltText);
+    }
+
+    if let Some(title) = tag.title() {
+        struct_elem.title(TextStr(title));
+    } else if tag.can_have_title() {
+        sc.register_validation_error(ValidationError::MissingHeadingTitle);
+    }
+
+    match *tag {
+        Tag::Span(ref span) => {
+            if let Some(lang) = &span.lang {
+                struct_elem.lang(TextStr(lang));
+            }
+
+            if let Some(actual_text) = &span.actual_text {
+                struct_elem.actual_text(TextStr(actual_text));
+            }
+        }
+        Tag::L(ln) => {
+            struct_elem.attributes().push().list().list_numbering(ln);
+        }
+        Tag::TH(ths) => {
+            if sc.serialize_settings().pdf_version >= PdfVersion::Pdf15 {
+                struct_elem.attributes().push().table().scope(ths);
+            }
+        }
+        Tag::Note => {
+            let id = format!("Note {}", note_id);
+            *note_id += 1;
+            id_tree.insert(id.clone(), root_ref);
+            struct_elem.id(Str(id.as_bytes()));
+        }
+        _ => {}
+    }
+
+    serialize_children(
+        sc,
+        root_ref,
+        children_refs,
+        parent_tree_map,
+        &mut struct_elem,
+    )?;
+    struct_elem.finish();
+    struct_elems.push(chunk);
+
+    Ok(Reference::Ref(root_ref))
+}
+
+/// Compare two sort keys, where nodes without a key come last.
+fn cmp_keys<K: Ord>(k1: &Option<K>, k2: &Option<K>) -> std::cmp::Ordering {
+    match (k1, k2) {
+        (Some(k1), Some(k2)) => k1.cmp(k2),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    }
+}
+
 fn serialize_children(
     sc: &mut SerializeContext,
     root_ref: Ref,
@@ -934,12 +1490,336 @@ mod tests {
     use crate::font::Font;
     use crate::path::Fill;
     use crate::surface::{Surface, TextDirection};
-    use crate::tagging::{ArtifactType, ContentTag, Tag, TagGroup, TagTree};
+    use crate::tagging::{
+        isolate_actual_text, paragraph_actual_texts, ArtifactType, ContentTag, IsolationDirection,
+        ListBuilder, ListNumbering, Node, SpanTag, Tag, TagArena, TagGroup, TagTree,
+    };
     use crate::tests::{green_fill, load_png_image, rect_to_path, NOTO_SANS, SVGS_PATH};
-    use crate::{Document, SvgSettings};
+    use crate::{Document, SerializeSettings, SvgSettings};
     use krilla_macros::snapshot;
     use tiny_skia_path::{Rect, Size, Transform};
 
+    #[test]
+    fn isolated_numeric_span() {
+        let actual_text = isolate_actual_text("123", IsolationDirection::LeftToRight);
+        assert_eq!(actual_text, "\u{2066}123\u{2069}");
+
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        let id = surface.start_tagged(ContentTag::Span("ar", None, None, Some(&actual_text)));
+        surface.fill_text_(25.0, "123");
+        surface.end_tagged();
+        surface.finish();
+        page.finish();
+
+        let mut tag_tree = TagTree::new();
+        let mut par = TagGroup::new(Tag::P);
+        par.push(id);
+        tag_tree.push(par);
+        document.set_tag_tree(tag_tree);
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf).to_ascii_uppercase();
+        assert!(pdf.contains("/ACTUALTEXT <FEFF20660031003200332069>"));
+    }
+
+    #[test]
+    fn span_tag_properties() {
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        let id = surface.start_tagged(ContentTag::Span("", None, None, None));
+        surface.fill_text_(25.0, "123");
+        surface.end_tagged();
+        surface.finish();
+        page.finish();
+
+        let mut tag_tree = TagTree::new();
+        let mut span = TagGroup::new(Tag::Span(
+            SpanTag::new()
+                .lang("ar".to_string())
+                .alt("One hundred twenty-three".to_string())
+                .actual_text(isolate_actual_text("123", IsolationDirection::LeftToRight)),
+        ));
+        span.push(id);
+        let mut par = TagGroup::new(Tag::P);
+        par.push(span);
+        tag_tree.push(par);
+        document.set_tag_tree(tag_tree);
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/S /Span"));
+        assert!(pdf.contains("/Lang (ar)"));
+        assert!(pdf.contains("/Alt (One hundred twenty-three)"));
+        assert!(pdf
+            .to_ascii_uppercase()
+            .contains("/ACTUALTEXT <FEFF20660031003200332069>"));
+    }
+
+    #[test]
+    fn tag_tree_reorder_two_columns() {
+        let mut document = Document::new();
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+
+        // Draw the lines of a two-column layout row by row, while the
+        // reading order is column by column.
+        let mut ids = vec![];
+        for row in 0..2 {
+            for column in 0..2 {
+                let id = surface.start_tagged(ContentTag::Span("", None, None, None));
+                surface.fill_text_(row as f32 * 20.0 + 20.0, "Text");
+                surface.end_tagged();
+                ids.push((id, (column, row)));
+            }
+        }
+
+        surface.finish();
+        page.finish();
+
+        let mut tag_tree = TagTree::new();
+        for (id, _) in &ids {
+            let mut par = TagGroup::new(Tag::P);
+            par.push(*id);
+            tag_tree.push(par);
+        }
+
+        tag_tree.reorder_by(|id| ids.iter().find(|(i, _)| i == id).unwrap().1);
+
+        let order = tag_tree
+            .children
+            .iter()
+            .map(|node| match node {
+                Node::Group(g) => match g.children[0] {
+                    Node::Leaf(id) => id,
+                    Node::Group(_) => unreachable!(),
+                },
+                Node::Leaf(_) => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(order, vec![ids[0].0, ids[2].0, ids[1].0, ids[3].0]);
+
+        document.set_tag_tree(tag_tree);
+        assert!(document.finish().is_ok());
+    }
+
+    fn numbered_list_pdf(use_builder: bool) -> Vec<u8> {
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+
+        let mut ids = vec![];
+        for (i, text) in ["First", "Second", "Third"].iter().enumerate() {
+            let y = 25.0 + i as f32 * 20.0;
+            let label = surface.start_tagged(ContentTag::Span("", None, None, None));
+            surface.fill_text_(y, &format!("{}.", i + 1));
+            surface.end_tagged();
+            let body = surface.start_tagged(ContentTag::Span("", None, None, None));
+            surface.fill_text_(y, text);
+            surface.end_tagged();
+            ids.push((label, body));
+        }
+
+        surface.finish();
+        page.finish();
+
+        let list = if use_builder {
+            let mut builder = ListBuilder::new(ListNumbering::Decimal);
+            for (label, body) in ids {
+                builder.push_item(Some(label), body);
+            }
+
+            builder.finish()
+        } else {
+            let mut list = TagGroup::new(Tag::L(ListNumbering::Decimal));
+            for (label, body) in ids {
+                let mut lbl = TagGroup::new(Tag::Lbl);
+                lbl.push(label);
+                let mut l_body = TagGroup::new(Tag::LBody);
+                l_body.push(body);
+                let mut item = TagGroup::new(Tag::LI);
+                item.push(lbl);
+                item.push(l_body);
+                list.push(item);
+            }
+
+            list
+        };
+
+        let mut tag_tree = TagTree::new();
+        tag_tree.push(list);
+        document.set_tag_tree(tag_tree);
+
+        document.finish().unwrap()
+    }
+
+    #[test]
+    fn list_builder_numbered_list() {
+        assert_eq!(numbered_list_pdf(true), numbered_list_pdf(false));
+    }
+
+    fn table_pdf(use_arena: bool, reorder: bool) -> Vec<u8> {
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+
+        let mut rows = vec![];
+        for row in 0..3 {
+            let mut cells = vec![];
+            for column in 0..2 {
+                let id = surface.start_tagged(ContentTag::Span("", None, None, None));
+                surface.fill_text_(25.0 + row as f32 * 20.0, &format!("{row}/{column}"));
+                surface.end_tagged();
+                cells.push(id);
+            }
+
+            rows.push(cells);
+        }
+
+        let caption = surface.start_tagged(ContentTag::Span("", None, None, None));
+        surface.fill_text_(100.0, "Caption");
+        surface.end_tagged();
+
+        surface.finish();
+        page.finish();
+
+        let mut tag_tree = if use_arena {
+            let mut arena = TagArena::new();
+            let table = arena.push_group(arena.root(), Tag::Table);
+
+            for cells in &rows {
+                let row = arena.push_group(table, Tag::TR);
+
+                for id in cells {
+                    let cell = arena.push_group(row, Tag::TD);
+                    arena.push(cell, *id);
+                }
+            }
+
+            let mut tag_tree = TagTree::with_arena(arena);
+            let mut group = TagGroup::new(Tag::Caption);
+            group.push(caption);
+            tag_tree.push(group);
+
+            tag_tree
+        } else {
+            let mut table = TagGroup::new(Tag::Table);
+
+            for cells in &rows {
+                let mut row = TagGroup::new(Tag::TR);
+
+                for id in cells {
+                    let mut cell = TagGroup::new(Tag::TD);
+                    cell.push(*id);
+                    row.push(cell);
+                }
+
+                table.push(row);
+            }
+
+            let mut tag_tree = TagTree::new();
+            tag_tree.push(table);
+            let mut group = TagGroup::new(Tag::Caption);
+            group.push(caption);
+            tag_tree.push(group);
+
+            tag_tree
+        };
+
+        if reorder {
+            // Put the caption first and reverse the order of the rows and cells.
+            tag_tree.reorder_by(|id| {
+                if *id == caption {
+                    return None;
+                }
+
+                let position = rows.iter().flatten().position(|i| i == id).unwrap();
+                Some(std::cmp::Reverse(position))
+            });
+        }
+
+        document.set_tag_tree(tag_tree);
+        document.finish().unwrap()
+    }
+
+    #[test]
+    fn tag_arena_matches_tag_groups() {
+        assert_eq!(table_pdf(true, false), table_pdf(false, false));
+    }
+
+    #[test]
+    fn tag_arena_reorder_matches_tag_groups() {
+        let pdf = table_pdf(true, true);
+
+        assert_eq!(pdf, table_pdf(false, true));
+        assert_ne!(pdf, table_pdf(true, false));
+    }
+
+    #[test]
+    fn paragraph_actual_texts_hyphenated() {
+        let paragraph = "An extra\u{AD}ordinary para\u{AD}graph";
+        let texts = paragraph_actual_texts(paragraph, &["An extra-", "ordinary para-", "graph"]);
+
+        assert_eq!(texts, vec!["An extra", "ordinary para", "graph"]);
+        assert_eq!(texts.concat(), "An extraordinary paragraph");
+    }
+
+    #[test]
+    fn paragraph_actual_texts_two_lines() {
+        let paragraph = "The quick brown fox jumps over the lazy dog.";
+        let texts = paragraph_actual_texts(
+            paragraph,
+            &["The quick brown fox jum-", "ps over the lazy dog."],
+        );
+
+        assert_eq!(
+            texts,
+            vec!["The quick brown fox jum", "ps over the lazy dog."]
+        );
+        assert!(!texts.concat().contains('-'));
+        assert_eq!(texts.concat(), paragraph);
+    }
+
+    #[test]
+    fn paragraph_actual_texts_in_pdf() {
+        let paragraph = "The quick brown fox jumps over the lazy dog.";
+        let lines = ["The quick brown fox jum-", "ps over the lazy dog."];
+        let texts = paragraph_actual_texts(paragraph, &lines);
+
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        let mut par = TagGroup::new(Tag::P);
+
+        for (i, (line, text)) in lines.iter().zip(&texts).enumerate() {
+            let id = surface.start_tagged(ContentTag::Span("en", None, None, Some(text.as_str())));
+            surface.fill_text_(25.0 + i as f32 * 25.0, line);
+            surface.end_tagged();
+            par.push(id);
+        }
+
+        surface.finish();
+        page.finish();
+
+        let mut tag_tree = TagTree::new();
+        tag_tree.push(par);
+        document.set_tag_tree(tag_tree);
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        // This is the text that a PDF reader extracts from the tagged content.
+        let extracted = pdf
+            .split("/ActualText (")
+            .skip(1)
+            .map(|rest| &rest[..rest.find(')').unwrap()])
+            .collect::<String>();
+        assert_eq!(extracted, paragraph);
+    }
+
     pub trait SurfaceExt {
         fn fill_text_(&mut self, y: f32, content: &str);
     }
@@ -1278,4 +2158,46 @@ mod tests {
 
         assert!(matches!(document.finish(), Err(KrillaError::UserError(_))))
     }
+
+    #[test]
+    fn tagging_missing_annotation_in_tree() {
+        let mut document = Document::new();
+        let tag_tree = TagTree::new();
+
+        let mut page = document.start_page();
+        let _ = page.add_tagged_annotation(
+            LinkAnnotation::new(
+                Rect::from_xywh(0.0, 0.0, 100.0, 25.0).unwrap(),
+                Target::Action(Action::Link(LinkAction::new("www.youtube.com".to_string()))),
+            )
+            .into(),
+        );
+        page.finish();
+
+        document.set_tag_tree(tag_tree);
+
+        assert!(matches!(document.finish(), Err(KrillaError::UserError(_))))
+    }
+
+    #[test]
+    fn tagging_struct_parents_unique_keys() {
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        tagging_simple_with_link_impl(&mut document);
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.contains("/StructParent 0"));
+        assert!(pdf.contains("/StructParents 1"));
+        assert!(pdf.contains("/ParentTreeNextKey 2"));
+
+        let nums = pdf.lines().find(|l| l.contains("/Nums")).unwrap();
+        let keys = nums
+            .trim()
+            .trim_start_matches("/Nums [")
+            .trim_end_matches(']')
+            .split(' ')
+            .step_by(4)
+            .collect::<Vec<_>>();
+        assert_eq!(keys, vec!["0", "1"]);
+    }
 }
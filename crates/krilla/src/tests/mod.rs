@@ -33,7 +33,7 @@ use crate::stream::StreamBuilder;
 use crate::surface::Surface;
 use crate::validation::Validator;
 use crate::version::PdfVersion;
-use crate::{SerializeSettings, SvgSettings};
+use crate::{Compression, NotdefFallback, SerializeSettings, SvgSettings};
 
 #[allow(dead_code)]
 #[rustfmt::skip]
@@ -79,7 +79,8 @@ static STORE_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
     path
 });
 
-static FONT_PATH: LazyLock<PathBuf> = LazyLock::new(|| WORKSPACE_PATH.join("assets/fonts"));
+pub(crate) static FONT_PATH: LazyLock<PathBuf> =
+    LazyLock::new(|| WORKSPACE_PATH.join("assets/fonts"));
 
 macro_rules! lazy_font {
     ($name:ident, $path:expr) => {
@@ -105,9 +106,13 @@ lazy_font!(NOTO_COLOR_EMOJI_COLR, FONT_PATH.join("NotoColorEmoji.COLR.subset.ttf
 #[rustfmt::skip]
 lazy_font!(NOTO_COLOR_EMOJI_CBDT, FONT_PATH.join("NotoColorEmoji.CBDT.subset.ttf"));
 #[rustfmt::skip]
+lazy_font!(NOTO_COLOR_EMOJI_CBDT_STRIKES, FONT_PATH.join("NotoColorEmoji.CBDT.strikes.ttf"));
+#[rustfmt::skip]
 lazy_font!(TWITTER_COLOR_EMOJI, FONT_PATH.join("TwitterColorEmoji.subset.ttf"));
 #[rustfmt::skip]
 lazy_font!(SVG_EXTRA, FONT_PATH.join("SVG_extra.ttf"));
+#[rustfmt::skip]
+lazy_font!(NOTO_SANS_VARIABLE, FONT_PATH.join("NotoSans_variable.ttf"));
 
 #[derive(Clone)]
 struct TestImage {
@@ -292,6 +297,13 @@ pub fn load_gif_image(name: &str) -> Image {
     .unwrap()
 }
 
+pub fn load_tiff_image(name: &str) -> Image {
+    Image::from_tiff(Arc::new(
+        std::fs::read(ASSETS_PATH.join("images").join(name)).unwrap(),
+    ))
+    .unwrap()
+}
+
 pub fn load_webp_image(name: &str) -> Image {
     Image::from_webp(Arc::new(
         std::fs::read(ASSETS_PATH.join("images").join(name)).unwrap(),
@@ -752,16 +764,33 @@ fn svg_impl(name: &str, renderer: Renderer, ignore_renderer: bool) {
 
 #[cfg(test)]
 impl SerializeSettings {
+    #[allow(deprecated)]
     pub(crate) fn settings_1() -> Self {
         Self {
             ascii_compatible: true,
-            compress_content_streams: false,
+            compress_content_streams: true,
+            compression: Compression::Default,
             no_device_cs: false,
             xmp_metadata: false,
             cmyk_profile: None,
+            always_use_cmyk_profile: false,
+            gray_profile: None,
             validator: Validator::None,
             enable_tagging: true,
             pdf_version: PdfVersion::Pdf17,
+            max_content_bytes: None,
+            auto_tag_annotations: false,
+            max_page_tree_kids: None,
+            subset_fonts: true,
+            #[cfg(feature = "encryption")]
+            encryption: None,
+            use_object_streams: false,
+            linearize: false,
+            deduplicate_streams: false,
+            parallel: false,
+            document_id: None,
+            notdef_fallback: NotdefFallback::Notdef,
+            plain_content_streams: true,
         }
     }
 
@@ -915,4 +944,24 @@ impl SerializeSettings {
             ..Self::settings_1()
         }
     }
+
+    pub(crate) fn settings_23() -> Self {
+        Self {
+            always_use_cmyk_profile: true,
+            cmyk_profile: Some(
+                ICCProfile::new(
+                    &std::fs::read(crate::tests::ASSETS_PATH.join("icc/eciCMYK_v2.icc")).unwrap(),
+                )
+                .unwrap(),
+            ),
+            ..Self::settings_1()
+        }
+    }
+
+    pub(crate) fn settings_24() -> Self {
+        Self {
+            validator: Validator::X4,
+            ..Self::settings_1()
+        }
+    }
 }
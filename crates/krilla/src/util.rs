@@ -337,3 +337,13 @@ impl<T: Send + Sync + 'static> Deferred<T> {
         self.0.wait()
     }
 }
+
+/// Encode the given bytes as an uppercase hexadecimal string.
+pub(crate) fn hex(data: &[u8]) -> String {
+    use std::fmt::Write;
+
+    data.iter().fold(String::new(), |mut s, b| {
+        write!(s, "{b:02X}").unwrap();
+        s
+    })
+}
@@ -1,8 +1,8 @@
 //! Exporting with a specific PDF conformance level.
 //!
 //! PDF defines a number of additional conformance level that restrict the features of PDF that
-//! can be used to a specific subset. Currently, krilla only supports some PDF/A conformance levels,
-//! although more are planned for the future.
+//! can be used to a specific subset. Currently, krilla supports some PDF/A conformance levels,
+//! PDF/UA-1 and PDF/X-4, although more are planned for the future.
 //!
 //! You can use a [`Validator`] by setting the `validator` attribute of the [`SerializeSettings`]
 //! you create the document with. There are three important aspects that play into this:
@@ -20,15 +20,17 @@
 //!
 //! [`SerializeSettings`]: crate::SerializeSettings
 
-use pdf_writer::types::OutputIntentSubtype;
 use pdf_writer::Finish;
 use skrifa::GlyphId;
 use std::fmt::Debug;
-use xmp_writer::XmpWriter;
+use xmp_writer::{Namespace, XmpWriter};
 
 use crate::font::Font;
+use crate::object::output_intent::OutputIntentSubtype;
 use crate::version::PdfVersion;
 
+const PDFX_4: &str = "PDF/X-4";
+
 /// An error that occurred during validation
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ValidationError {
@@ -103,6 +105,59 @@ pub enum ValidationError {
     MissingAnnotationAltText,
     /// The PDF contains transparency, which is forbidden by some standards (e.g. PDF/A1).
     Transparency,
+    /// The document asks viewers to regenerate the appearances of form fields, which
+    /// is forbidden by PDF/A.
+    ///
+    /// Occurs if `NeedAppearances` was enabled via [`Document::set_need_appearances`].
+    ///
+    /// [`Document::set_need_appearances`]: crate::Document::set_need_appearances
+    NeedAppearances,
+    /// The document contains layers, which is forbidden by some standards (e.g. PDF/A1).
+    OptionalContent,
+    /// The document contains page number artifacts, but does not define any page labels.
+    MissingPageLabels,
+    /// The document is encrypted, which is forbidden by PDF/A.
+    ///
+    /// Occurs if [`SerializeSettings::encryption`] is set.
+    ///
+    /// [`SerializeSettings::encryption`]: crate::SerializeSettings::encryption
+    Encryption,
+    /// The document contains JavaScript, which is forbidden by PDF/A.
+    ///
+    /// Occurs if a [`JavaScriptAction`] was used.
+    ///
+    /// [`JavaScriptAction`]: crate::action::JavaScriptAction
+    JavaScript,
+    /// The document contains a launch action, which is forbidden by PDF/A.
+    ///
+    /// Occurs if a [`LaunchAction`] was used.
+    ///
+    /// [`LaunchAction`]: crate::action::LaunchAction
+    LaunchAction,
+    /// The document information dictionary contains custom entries that are not
+    /// mirrored in the XMP metadata, which is not allowed by PDF/A.
+    ///
+    /// Occurs if [`Metadata::custom_info`] was used.
+    ///
+    /// [`Metadata::custom_info`]: crate::metadata::Metadata::custom_info
+    CustomDocumentInfo,
+    /// No ICC profile was provided for the output intent, even though the standard
+    /// requires one that describes the intended printing condition.
+    ///
+    /// Occurs if no destination profile was set via [`OutputIntent::with_dest_profile`].
+    ///
+    /// [`OutputIntent::with_dest_profile`]: crate::output_intent::OutputIntent::with_dest_profile
+    MissingOutputIntentProfile,
+    /// The document does not specify whether it has been trapped, even though
+    /// it is required by the standard.
+    ///
+    /// Occurs if [`Metadata::trapped`] wasn't set to either `True` or `False`.
+    ///
+    /// [`Metadata::trapped`]: crate::metadata::Metadata::trapped
+    MissingTrappingStatus,
+    /// The output intents of the document use different destination profiles, which
+    /// is forbidden by PDF/A.
+    ConflictingOutputIntentProfiles,
 }
 
 /// A validator for exporting PDF documents to a specific subset of PDF.
@@ -238,6 +293,20 @@ pub enum Validator {
     ///
     /// [`Tag`]: crate::tagging::Tag
     UA1,
+    /// The validator for the PDF/X-4 standard.
+    ///
+    /// **Requirements**:
+    /// - You need to provide an output intent with an ICC profile that describes the
+    ///   intended printing condition via [`Document::set_output_intent`].
+    /// - CMYK colors are written as device colors unless a CMYK profile is set in the
+    ///   [`SerializeSettings`], so they should match the printing condition of the
+    ///   output intent.
+    /// - You should only use fonts that are legally embeddable in a file for unlimited,
+    ///   universal rendering.
+    ///
+    /// [`Document::set_output_intent`]: crate::Document::set_output_intent
+    /// [`SerializeSettings`]: crate::SerializeSettings
+    X4,
 }
 
 impl Validator {
@@ -266,6 +335,16 @@ impl Validator {
                 ValidationError::MissingDocumentOutline => false,
                 ValidationError::MissingAnnotationAltText => false,
                 ValidationError::Transparency => true,
+                ValidationError::NeedAppearances => true,
+                ValidationError::OptionalContent => true,
+                ValidationError::MissingPageLabels => false,
+                ValidationError::Encryption => true,
+                ValidationError::JavaScript => true,
+                ValidationError::LaunchAction => true,
+                ValidationError::CustomDocumentInfo => true,
+                ValidationError::MissingOutputIntentProfile => false,
+                ValidationError::MissingTrappingStatus => false,
+                ValidationError::ConflictingOutputIntentProfiles => true,
             },
             Validator::A2_A | Validator::A2_B | Validator::A2_U => match validation_error {
                 ValidationError::TooLongString => true,
@@ -289,6 +368,16 @@ impl Validator {
                 ValidationError::MissingDocumentOutline => false,
                 ValidationError::MissingAnnotationAltText => false,
                 ValidationError::Transparency => false,
+                ValidationError::NeedAppearances => true,
+                ValidationError::OptionalContent => false,
+                ValidationError::MissingPageLabels => false,
+                ValidationError::Encryption => true,
+                ValidationError::JavaScript => true,
+                ValidationError::LaunchAction => true,
+                ValidationError::CustomDocumentInfo => true,
+                ValidationError::MissingOutputIntentProfile => false,
+                ValidationError::MissingTrappingStatus => false,
+                ValidationError::ConflictingOutputIntentProfiles => true,
             },
             Validator::A3_A | Validator::A3_B | Validator::A3_U => match validation_error {
                 ValidationError::TooLongString => true,
@@ -312,6 +401,16 @@ impl Validator {
                 ValidationError::MissingDocumentOutline => false,
                 ValidationError::MissingAnnotationAltText => false,
                 ValidationError::Transparency => false,
+                ValidationError::NeedAppearances => true,
+                ValidationError::OptionalContent => false,
+                ValidationError::MissingPageLabels => false,
+                ValidationError::Encryption => true,
+                ValidationError::JavaScript => true,
+                ValidationError::LaunchAction => true,
+                ValidationError::CustomDocumentInfo => true,
+                ValidationError::MissingOutputIntentProfile => false,
+                ValidationError::MissingTrappingStatus => false,
+                ValidationError::ConflictingOutputIntentProfiles => true,
             },
             Validator::UA1 => match validation_error {
                 ValidationError::TooLongString => false,
@@ -335,6 +434,51 @@ impl Validator {
                 ValidationError::MissingDocumentOutline => true,
                 ValidationError::MissingAnnotationAltText => true,
                 ValidationError::Transparency => false,
+                ValidationError::NeedAppearances => false,
+                ValidationError::OptionalContent => false,
+                ValidationError::MissingPageLabels => true,
+                ValidationError::Encryption => false,
+                ValidationError::JavaScript => false,
+                ValidationError::LaunchAction => false,
+                ValidationError::CustomDocumentInfo => false,
+                ValidationError::MissingOutputIntentProfile => false,
+                ValidationError::MissingTrappingStatus => false,
+                ValidationError::ConflictingOutputIntentProfiles => false,
+            },
+            Validator::X4 => match validation_error {
+                ValidationError::TooLongString => false,
+                ValidationError::TooLongName => false,
+                ValidationError::TooLongArray => false,
+                ValidationError::TooLongDictionary => false,
+                ValidationError::TooLargeFloat => false,
+                ValidationError::TooManyIndirectObjects => false,
+                ValidationError::TooHighQNestingLevel => false,
+                ValidationError::ContainsPostScript => false,
+                // Device CMYK is allowed, it refers to the printing condition of
+                // the output intent.
+                ValidationError::MissingCMYKProfile => false,
+                ValidationError::ContainsNotDefGlyph => false,
+                ValidationError::InvalidCodepointMapping(_, _) => {
+                    self.requires_codepoint_mappings()
+                }
+                ValidationError::UnicodePrivateArea(_, _) => false,
+                ValidationError::NoDocumentLanguage => false,
+                ValidationError::NoDocumentTitle => true,
+                ValidationError::MissingAltText => false,
+                ValidationError::MissingHeadingTitle => false,
+                ValidationError::MissingDocumentOutline => false,
+                ValidationError::MissingAnnotationAltText => false,
+                ValidationError::Transparency => false,
+                ValidationError::NeedAppearances => false,
+                ValidationError::OptionalContent => false,
+                ValidationError::MissingPageLabels => false,
+                ValidationError::Encryption => true,
+                ValidationError::JavaScript => true,
+                ValidationError::LaunchAction => true,
+                ValidationError::CustomDocumentInfo => false,
+                ValidationError::MissingOutputIntentProfile => true,
+                ValidationError::MissingTrappingStatus => true,
+                ValidationError::ConflictingOutputIntentProfiles => false,
             },
         }
     }
@@ -347,6 +491,7 @@ impl Validator {
             Validator::A2_A | Validator::A2_B | Validator::A2_U => pdf_version <= PdfVersion::Pdf17,
             Validator::A3_A | Validator::A3_B | Validator::A3_U => pdf_version <= PdfVersion::Pdf17,
             Validator::UA1 => pdf_version <= PdfVersion::Pdf17,
+            Validator::X4 => pdf_version <= PdfVersion::Pdf16,
         }
     }
 
@@ -358,6 +503,7 @@ impl Validator {
             Validator::A2_A | Validator::A2_B | Validator::A2_U => PdfVersion::Pdf17,
             Validator::A3_A | Validator::A3_B | Validator::A3_U => PdfVersion::Pdf17,
             Validator::UA1 => PdfVersion::Pdf17,
+            Validator::X4 => PdfVersion::Pdf16,
         }
     }
 
@@ -423,6 +569,14 @@ impl Validator {
             Validator::UA1 => {
                 xmp.pdfua_part(1);
             }
+            Validator::X4 => {
+                let namespace = Namespace::Custom((
+                    "PDF/X ID Schema",
+                    "pdfxid",
+                    "http://www.npes.org/pdfx/ns/id/",
+                ));
+                xmp.element("GTS_PDFXVersion", namespace).value(PDFX_4);
+            }
         }
     }
 
@@ -433,6 +587,7 @@ impl Validator {
             Validator::A2_A | Validator::A2_B | Validator::A2_U => *self != Validator::A2_B,
             Validator::A3_A | Validator::A3_B | Validator::A3_U => *self != Validator::A3_B,
             Validator::UA1 => true,
+            Validator::X4 => false,
         }
     }
 
@@ -443,6 +598,7 @@ impl Validator {
             Validator::A2_A | Validator::A2_B | Validator::A2_U => false,
             Validator::A3_A | Validator::A3_B | Validator::A3_U => false,
             Validator::UA1 => true,
+            Validator::X4 => false,
         }
     }
 
@@ -453,6 +609,7 @@ impl Validator {
             Validator::A2_A | Validator::A2_B | Validator::A2_U => true,
             Validator::A3_A | Validator::A3_B | Validator::A3_U => true,
             Validator::UA1 => false,
+            Validator::X4 => true,
         }
     }
 
@@ -466,6 +623,7 @@ impl Validator {
             Validator::A3_A => true,
             Validator::A3_B | Validator::A3_U => false,
             Validator::UA1 => true,
+            Validator::X4 => false,
         }
     }
 
@@ -476,6 +634,7 @@ impl Validator {
             Validator::A2_A | Validator::A2_B | Validator::A2_U => true,
             Validator::A3_A | Validator::A3_B | Validator::A3_U => true,
             Validator::UA1 => true,
+            Validator::X4 => true,
         }
     }
 
@@ -486,16 +645,18 @@ impl Validator {
             Validator::A2_A | Validator::A2_B | Validator::A2_U => true,
             Validator::A3_A | Validator::A3_B | Validator::A3_U => true,
             Validator::UA1 => false,
+            Validator::X4 => true,
         }
     }
 
     pub(crate) fn output_intent(&self) -> Option<OutputIntentSubtype> {
         match self {
             Validator::None => None,
-            Validator::A1_A | Validator::A1_B => Some(OutputIntentSubtype::PDFA),
-            Validator::A2_A | Validator::A2_B | Validator::A2_U => Some(OutputIntentSubtype::PDFA),
-            Validator::A3_A | Validator::A3_B | Validator::A3_U => Some(OutputIntentSubtype::PDFA),
+            Validator::A1_A | Validator::A1_B => Some(OutputIntentSubtype::PdfA),
+            Validator::A2_A | Validator::A2_B | Validator::A2_U => Some(OutputIntentSubtype::PdfA),
+            Validator::A3_A | Validator::A3_B | Validator::A3_U => Some(OutputIntentSubtype::PdfA),
             Validator::UA1 => None,
+            Validator::X4 => Some(OutputIntentSubtype::PdfX),
         }
     }
 
@@ -512,30 +673,49 @@ impl Validator {
             Validator::A3_B => "PDF/A3-B",
             Validator::A3_U => "PDF/A3-U",
             Validator::UA1 => "PDF/UA1",
+            Validator::X4 => "PDF/X-4",
         }
     }
+
+    /// The value of the `GTS_PDFXVersion` key, if the validator is for a PDF/X standard.
+    pub(crate) fn pdfx_version(&self) -> Option<&'static str> {
+        match self {
+            Validator::X4 => Some(PDFX_4),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn requires_trim_box(&self) -> bool {
+        matches!(self, Validator::X4)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::action::LinkAction;
-    use crate::annotation::{Annotation, LinkAnnotation, Target};
+    use crate::action::{JavaScriptAction, LinkAction};
+    use crate::annotation::{
+        Annotation, LinkAnnotation, SquareAnnotation, Target, TextAnnotation, TextIcon,
+    };
+    use crate::color::{rgb, ICCProfile};
     use crate::error::KrillaError;
     use crate::font::{Font, GlyphId, GlyphUnits, KrillaGlyph};
-    use crate::metadata::Metadata;
+    use crate::metadata::{Metadata, Trapped};
     use crate::outline::Outline;
-    use crate::page::Page;
-    use crate::paint::{LinearGradient, SpreadMethod};
+    use crate::output_intent::{OutputIntent, OutputIntentSubtype};
+    use crate::page::{NumberingStyle, Page, PageLabel};
+    use crate::paint::{LinearGradient, SpreadMethod, SweepGradient};
     use crate::path::{Fill, FillRule};
-    use crate::surface::TextDirection;
+    use crate::surface::{Surface, TextDirection};
     use crate::tagging::{ArtifactType, ContentTag, Tag, TagGroup, TagTree};
     use crate::tests::{
         cmyk_fill, rect_to_path, red_fill, stops_with_2_solid_1, youtube_link, NOTO_SANS,
     };
     use crate::validation::ValidationError;
     use crate::{Document, SerializeSettings};
-    use krilla_macros::snapshot;
+    use krilla_macros::{snapshot, visreg};
     use pdf_writer::types::{ListNumbering, TableHeaderScope};
+    use std::cell::RefCell;
+    use std::rc::Rc;
     use tiny_skia_path::{Point, Rect};
 
     fn pdfa_document() -> Document {
@@ -599,6 +779,27 @@ mod tests {
         );
     }
 
+    #[test]
+    pub fn validation_pdfa_annotation_contents_length() {
+        let mut document = pdfa_document();
+        let mut page = document.start_page();
+        page.add_annotation(
+            TextAnnotation::new(
+                Rect::from_xywh(50.0, 50.0, 20.0, 20.0).unwrap(),
+                "A".repeat(32768),
+                TextIcon::Note,
+                false,
+            )
+            .into(),
+        );
+        page.finish();
+
+        assert!(matches!(
+            document.finish(),
+            Err(KrillaError::ValidationError(errors)) if errors.contains(&ValidationError::TooLongString)
+        ));
+    }
+
     #[snapshot(single_page, settings_7)]
     fn validation_pdfa_annotation(page: &mut Page) {
         page.add_annotation(
@@ -611,7 +812,7 @@ mod tests {
     }
 
     #[test]
-    fn validation_pdfa_postscript() {
+    fn validation_pdfa_linear_gradient_without_postscript() {
         let mut document = pdfa_document();
         let mut page = document.start_page();
 
@@ -624,6 +825,7 @@ mod tests {
             spread_method: SpreadMethod::Repeat,
             stops: stops_with_2_solid_1(),
             anti_alias: false,
+            interpolation: Default::default(),
         };
 
         let fill = Fill {
@@ -638,14 +840,136 @@ mod tests {
         surface.finish();
         page.finish();
 
+        let pdf = document.finish().unwrap();
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("/FunctionType 3"));
+        assert!(!text.contains("/FunctionType 4"));
+    }
+
+    fn linear_gradient_impl(surface: &mut Surface, spread_method: SpreadMethod) {
+        let gradient = LinearGradient {
+            x1: 60.0,
+            y1: 40.0,
+            x2: 100.0,
+            y2: 80.0,
+            transform: Default::default(),
+            spread_method,
+            stops: stops_with_2_solid_1(),
+            anti_alias: false,
+            interpolation: Default::default(),
+        };
+
+        let fill = Fill {
+            paint: gradient.into(),
+            ..Default::default()
+        };
+
+        surface.fill_path(&rect_to_path(20.0, 20.0, 180.0, 180.0), fill);
+    }
+
+    #[visreg(all, settings_7)]
+    fn validation_pdfa_linear_gradient_repeat(surface: &mut Surface) {
+        linear_gradient_impl(surface, SpreadMethod::Repeat);
+    }
+
+    #[visreg(all, settings_7)]
+    fn validation_pdfa_linear_gradient_reflect(surface: &mut Surface) {
+        linear_gradient_impl(surface, SpreadMethod::Reflect);
+    }
+
+    fn sweep_gradient_impl(surface: &mut Surface) {
+        let gradient = SweepGradient {
+            cx: 100.0,
+            cy: 100.0,
+            start_angle: 0.0,
+            end_angle: 270.0,
+            rotation: 0.0,
+            transform: Default::default(),
+            spread_method: SpreadMethod::Reflect,
+            stops: stops_with_2_solid_1(),
+            anti_alias: false,
+            interpolation: Default::default(),
+        };
+
+        let fill = Fill {
+            paint: gradient.into(),
+            ..Default::default()
+        };
+
+        surface.fill_path(&rect_to_path(20.0, 20.0, 180.0, 180.0), fill);
+    }
+
+    #[test]
+    fn validation_pdfa_sweep_gradient_without_postscript() {
+        let mut document = pdfa_document();
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        sweep_gradient_impl(&mut surface);
+        surface.finish();
+        page.finish();
+
+        let pdf = document.finish().unwrap();
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("/FunctionType 0"));
+        assert!(!text.contains("/FunctionType 4"));
+    }
+
+    #[visreg(all, settings_7)]
+    fn validation_pdfa_sweep_gradient(surface: &mut Surface) {
+        sweep_gradient_impl(surface);
+    }
+
+    #[test]
+    fn validation_pdfa_need_appearances() {
+        let mut document = pdfa_document();
+        document.set_need_appearances(true);
+
         assert_eq!(
             document.finish(),
             Err(KrillaError::ValidationError(vec![
-                ValidationError::ContainsPostScript
+                ValidationError::NeedAppearances
             ]))
         )
     }
 
+    #[test]
+    fn validation_pdfa_need_appearances_disabled() {
+        let mut document = pdfa_document();
+        document.set_need_appearances(false);
+
+        let pdf = document.finish().unwrap();
+        assert!(!String::from_utf8_lossy(&pdf).contains("/NeedAppearances"));
+    }
+
+    #[test]
+    fn validation_pdfa1_layer() {
+        let mut document = Document::new_with(SerializeSettings::settings_19());
+        document.add_layer("Layer", true);
+
+        assert_eq!(
+            document.finish(),
+            Err(KrillaError::ValidationError(vec![
+                ValidationError::OptionalContent
+            ]))
+        )
+    }
+
+    #[test]
+    fn validation_pdfa2_layer() {
+        let mut document = pdfa_document();
+        let layer = document.add_layer("Layer", false);
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        surface.push_layer(&layer);
+        surface.fill_path(&rect_to_path(20.0, 20.0, 180.0, 180.0), red_fill(1.0));
+        surface.pop_layer();
+        surface.finish();
+        page.finish();
+
+        let pdf = document.finish().unwrap();
+        assert!(String::from_utf8_lossy(&pdf).contains("/Name (Default)"));
+    }
+
     #[test]
     pub fn validation_disabled_q_nesting_28() {
         let document = q_nesting_impl(SerializeSettings::default());
@@ -855,6 +1179,58 @@ mod tests {
         validation_pdf_full_example(document);
     }
 
+    #[snapshot(document, settings_7)]
+    fn validation_pdfa2_b_custom_output_intent(document: &mut Document) {
+        validation_pdf_full_example(document);
+        document.set_output_intent(
+            OutputIntent::new("FOGRA39".to_string())
+                .with_output_condition("Coated FOGRA39 (ISO 12647-2:2004)".to_string())
+                .with_registry_name("http://www.color.org".to_string())
+                .with_info("Offset printing on coated paper".to_string()),
+        );
+    }
+
+    #[test]
+    fn validation_pdfa2_b_too_long_output_condition_identifier() {
+        let mut document = Document::new_with(SerializeSettings::settings_7());
+        validation_pdf_full_example(&mut document);
+        document.set_output_intent(OutputIntent::new("A".repeat(40000)));
+
+        assert!(matches!(
+            document.finish(),
+            Err(KrillaError::ValidationError(errors)) if errors.contains(&ValidationError::TooLongString)
+        ));
+    }
+
+    #[test]
+    fn validation_pdfa2_b_creator_and_producer() {
+        let mut document = Document::new_with(SerializeSettings::settings_7());
+        validation_pdf_full_example(&mut document);
+        document.set_metadata(
+            Metadata::new()
+                .title("a nice title".to_string())
+                .creator("My Tool".to_string())
+                .producer("My Producer".to_string()),
+        );
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        // The document information dictionary and the XMP metadata must agree.
+        assert_eq!(pdf.matches("/Creator (My Tool)").count(), 1);
+        assert_eq!(pdf.matches("/Producer (My Producer)").count(), 1);
+        assert_eq!(
+            pdf.matches("<xmp:CreatorTool>My Tool</xmp:CreatorTool>")
+                .count(),
+            1
+        );
+        assert_eq!(
+            pdf.matches("<pdf:Producer>My Producer</pdf:Producer>")
+                .count(),
+            1
+        );
+    }
+
     #[snapshot(document, settings_9)]
     fn validation_pdfa2_u_full_example(document: &mut Document) {
         validation_pdf_full_example(document);
@@ -922,6 +1298,112 @@ mod tests {
         document.set_outline(outline);
     }
 
+    #[test]
+    fn validation_pdfua1_auto_tagged_link() {
+        let mut document = Document::new_with(SerializeSettings {
+            auto_tag_annotations: true,
+            ..SerializeSettings::settings_15()
+        });
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+
+        let id1 = surface.start_tagged(ContentTag::Span("", None, None, None));
+        surface.fill_text(
+            Point::from_xy(0.0, 100.0),
+            Fill::default(),
+            Font::new(NOTO_SANS.clone(), 0, true).unwrap(),
+            20.0,
+            &[],
+            "This is some text",
+            false,
+            TextDirection::Auto,
+        );
+        surface.end_tagged();
+
+        surface.finish();
+
+        page.add_annotation(Annotation::new_link(
+            LinkAnnotation::new(
+                Rect::from_xywh(50.0, 50.0, 100.0, 100.0).unwrap(),
+                Target::Action(LinkAction::new("https://www.youtube.com".to_string()).into()),
+            ),
+            Some("A link to youtube".to_string()),
+        ));
+
+        page.finish();
+
+        let mut tag_tree = TagTree::new();
+        tag_tree.push(id1);
+        document.set_tag_tree(tag_tree);
+
+        let metadata = Metadata::new()
+            .language("en".to_string())
+            .title("a nice title".to_string());
+        document.set_metadata(metadata);
+        document.set_outline(Outline::new());
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.contains("/S /Link"));
+        assert!(pdf.contains("/Type /OBJR"));
+        assert!(pdf.contains("/StructParent 0"));
+    }
+
+    #[test]
+    fn validation_pdfua1_annotation_alt_text() {
+        let mut document = Document::new_with(SerializeSettings {
+            auto_tag_annotations: true,
+            ..SerializeSettings::settings_15()
+        });
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+
+        let id1 = surface.start_tagged(ContentTag::Span("", None, None, None));
+        surface.fill_text(
+            Point::from_xy(0.0, 100.0),
+            Fill::default(),
+            Font::new(NOTO_SANS.clone(), 0, true).unwrap(),
+            20.0,
+            &[],
+            "This is some text",
+            false,
+            TextDirection::Auto,
+        );
+        surface.end_tagged();
+
+        surface.finish();
+
+        let mut annotation = Annotation::from(SquareAnnotation::new(
+            Rect::from_xywh(50.0, 50.0, 100.0, 100.0).unwrap(),
+            None,
+            rgb::Color::new(255, 0, 0),
+            2.0,
+        ));
+        annotation.set_alt_text("Ein rotes Rechteck".to_string());
+        annotation.set_language("de".to_string());
+        page.add_annotation(annotation);
+
+        page.finish();
+
+        let mut tag_tree = TagTree::new();
+        tag_tree.push(id1);
+        document.set_tag_tree(tag_tree);
+
+        let metadata = Metadata::new()
+            .language("en".to_string())
+            .title("a nice title".to_string());
+        document.set_metadata(metadata);
+        document.set_outline(Outline::new());
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.contains("/Subtype /Square"));
+        assert!(pdf.contains("/Contents (Ein rotes Rechteck)"));
+        assert!(pdf.contains("/Lang (de)"));
+    }
+
     #[test]
     fn validation_pdfua1_missing_requirements() {
         let mut document = Document::new_with(SerializeSettings::settings_15());
@@ -974,6 +1456,51 @@ mod tests {
         )
     }
 
+    fn pdfua1_page_number_document(document: &mut Document) {
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        surface.start_tagged(ContentTag::Artifact(ArtifactType::PageNumber));
+        surface.fill_text(
+            Point::from_xy(0.0, 100.0),
+            Fill::default(),
+            Font::new(NOTO_SANS.clone(), 0, true).unwrap(),
+            20.0,
+            &[],
+            "1",
+            false,
+            TextDirection::Auto,
+        );
+        surface.end_tagged();
+        surface.finish();
+        page.finish();
+
+        document.set_tag_tree(TagTree::new());
+        document.set_metadata(Metadata::new().title("a nice title".to_string()));
+        document.set_outline(Outline::new());
+    }
+
+    #[test]
+    fn validation_pdfua1_missing_page_labels() {
+        let mut document = Document::new_with(SerializeSettings::settings_15());
+        pdfua1_page_number_document(&mut document);
+
+        assert_eq!(
+            document.finish(),
+            Err(KrillaError::ValidationError(vec![
+                ValidationError::MissingPageLabels
+            ]))
+        );
+
+        let mut document = Document::new_with(SerializeSettings::settings_15());
+        pdfua1_page_number_document(&mut document);
+        document.set_page_labels(vec![(
+            0,
+            PageLabel::new(Some(NumberingStyle::Arabic), None, None),
+        )]);
+
+        assert!(document.finish().is_ok());
+    }
+
     #[snapshot(document, settings_15)]
     fn validation_pdfua1_attributes(document: &mut Document) {
         let mut page = document.start_page();
@@ -1066,4 +1593,185 @@ mod tests {
             ]))
         )
     }
+
+    #[test]
+    fn validation_pdfa1_annotation_warning() {
+        let mut document = Document::new_with(SerializeSettings::settings_19());
+        let warnings = Rc::new(RefCell::new(vec![]));
+        let cloned = warnings.clone();
+        document.set_annotation_warning(8000, move |page_index, count| {
+            cloned.borrow_mut().push((page_index, count))
+        });
+
+        let mut page = document.start_page();
+
+        for _ in 0..8000 {
+            page.add_annotation(youtube_link(100.0, 100.0, 100.0, 100.0));
+        }
+
+        assert_eq!(page.annotation_count(), 8000);
+        assert_eq!(*warnings.borrow(), vec![(0, 8000)]);
+
+        for _ in 0..193 {
+            page.add_annotation(youtube_link(100.0, 100.0, 100.0, 100.0));
+        }
+
+        // The hook is only called once per page.
+        assert_eq!(warnings.borrow().len(), 1);
+        page.finish();
+
+        assert_eq!(
+            document.finish(),
+            Err(KrillaError::ValidationError(vec![
+                ValidationError::TooLongArray
+            ]))
+        )
+    }
+
+    #[test]
+    fn validation_pdfx4_missing_requirements() {
+        let mut document = Document::new_with(SerializeSettings::settings_24());
+        document.start_page().finish();
+
+        assert_eq!(
+            document.finish(),
+            Err(KrillaError::ValidationError(vec![
+                ValidationError::MissingOutputIntentProfile,
+                ValidationError::NoDocumentTitle,
+                ValidationError::MissingTrappingStatus,
+            ]))
+        )
+    }
+
+    #[test]
+    fn validation_pdfx4_javascript() {
+        let mut document = Document::new_with(SerializeSettings::settings_24());
+        pdfx4_requirements(&mut document);
+        document.set_open_action(JavaScriptAction::new("app.alert('Hi');".to_string()).into());
+
+        assert_eq!(
+            document.finish(),
+            Err(KrillaError::ValidationError(vec![
+                ValidationError::JavaScript
+            ]))
+        )
+    }
+
+    fn cmyk_icc_profile() -> ICCProfile<4> {
+        ICCProfile::new(
+            &std::fs::read(crate::tests::ASSETS_PATH.join("icc/eciCMYK_v2.icc")).unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn pdfx4_requirements(document: &mut Document) {
+        document.set_metadata(
+            Metadata::new()
+                .title("A title".to_string())
+                .trapped(Trapped::False),
+        );
+        document.set_output_intent(
+            OutputIntent::new("FOGRA59".to_string())
+                .with_registry_name("http://www.color.org".to_string())
+                .with_dest_profile(cmyk_icc_profile()),
+        );
+    }
+
+    #[test]
+    fn validation_pdfx4() {
+        let mut document = Document::new_with(SerializeSettings::settings_24());
+        pdfx4_requirements(&mut document);
+
+        let mut page = document.start_page();
+        let mut surface = page.surface();
+        surface.fill_path(&rect_to_path(0.0, 0.0, 100.0, 100.0), red_fill(0.5));
+        surface.fill_path(&rect_to_path(50.0, 50.0, 150.0, 150.0), cmyk_fill(1.0));
+        surface.finish();
+        page.finish();
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.starts_with("%PDF-1.6"));
+        assert!(pdf.contains("/GTS_PDFXVersion (PDF/X-4)"));
+        assert!(pdf.contains("<pdfxid:GTS_PDFXVersion>PDF/X-4</pdfxid:GTS_PDFXVersion>"));
+        assert!(pdf.contains("/S /GTS_PDFX"));
+        assert!(pdf.contains("/OutputConditionIdentifier (FOGRA59)"));
+        assert!(pdf.contains("/N 4"));
+        assert!(pdf.contains("/TrimBox"));
+        assert!(pdf.contains("/Trapped /False"));
+    }
+
+    #[test]
+    fn output_intent_without_validator() {
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        document.add_output_intent(
+            OutputIntent::new("FOGRA39".to_string()).with_dest_profile(cmyk_icc_profile()),
+        );
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.contains("/OutputIntents"));
+        assert!(pdf.contains("/S /GTS_PDFX"));
+        assert!(pdf.contains("/OutputConditionIdentifier (FOGRA39)"));
+    }
+
+    #[test]
+    fn output_intent_replaces_default() {
+        let mut document = Document::new_with(SerializeSettings::settings_7());
+        document.add_output_intent(
+            OutputIntent::new("FOGRA39".to_string())
+                .with_subtype(OutputIntentSubtype::PdfA)
+                .with_dest_profile(cmyk_icc_profile()),
+        );
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert_eq!(pdf.matches("/Type /OutputIntent").count(), 1);
+        assert!(pdf.contains("/S /GTS_PDFA1"));
+        assert!(pdf.contains("/OutputConditionIdentifier (FOGRA39)"));
+        assert!(!pdf.contains("(sRGB)"));
+    }
+
+    #[test]
+    fn output_intent_multiple() {
+        let mut document = Document::new_with(SerializeSettings::settings_1());
+        document.add_output_intent(
+            OutputIntent::new("FOGRA39".to_string())
+                .with_subtype(OutputIntentSubtype::PdfA)
+                .with_dest_profile(cmyk_icc_profile()),
+        );
+        document.add_output_intent(
+            OutputIntent::new("FOGRA39".to_string())
+                .with_subtype(OutputIntentSubtype::PdfX)
+                .with_dest_profile(cmyk_icc_profile()),
+        );
+
+        let pdf = document.finish().unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert_eq!(pdf.matches("/Type /OutputIntent").count(), 2);
+        // Both output intents share the same profile.
+        assert_eq!(pdf.matches("/N 4").count(), 1);
+    }
+
+    #[test]
+    fn validation_pdfa_conflicting_output_intents() {
+        let mut document = pdfa_document();
+        // The default sRGB output intent is still added for PDF/A.
+        document.add_output_intent(
+            OutputIntent::new("FOGRA39".to_string())
+                .with_subtype(OutputIntentSubtype::PdfX)
+                .with_dest_profile(cmyk_icc_profile()),
+        );
+
+        assert_eq!(
+            document.finish(),
+            Err(KrillaError::ValidationError(vec![
+                ValidationError::ConflictingOutputIntentProfiles
+            ]))
+        )
+    }
 }
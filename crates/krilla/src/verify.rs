@@ -0,0 +1,330 @@
+//! Verifying the structural integrity of PDF files.
+//!
+//! This module provides a basic sanity check for PDF files produced by krilla. It is
+//! not meant to check arbitrary PDF files, but it can be used as a safety net to catch
+//! serialization bugs, such as dangling references or corrupted cross-reference tables,
+//! before shipping a file. The file is parsed with the same parser that is used for
+//! importing pages, which is why the `verify` feature also enables the `pdf` feature.
+//!
+//! The following properties are checked:
+//! - The file has a header and ends with a trailer pointing to a cross-reference table
+//!   or a cross-reference stream. Linearized files and their chain of cross-reference
+//!   tables are supported as well.
+//! - Each in-use entry of the cross-reference table points to the start of the
+//!   object with the same object number, or to the corresponding object in an
+//!   object stream.
+//! - The `/Size` entry of the trailer matches the size of the cross-reference table.
+//! - Each indirect reference in the file points to an object that actually exists.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use lopdf::xref::XrefEntry;
+use lopdf::{Object, XrefError};
+
+/// An issue that was found while verifying a PDF file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    /// The file doesn't start with a PDF header.
+    MissingHeader,
+    /// The `startxref` keyword or the offset following it are missing or invalid.
+    MissingStartXref,
+    /// The cross-reference table couldn't be parsed.
+    MalformedXref,
+    /// The trailer dictionary is missing or doesn't contain a valid `/Size` entry.
+    MalformedTrailer,
+    /// The `/Size` entry of the trailer doesn't match the number of entries
+    /// in the cross-reference table.
+    SizeMismatch {
+        /// The size stored in the trailer.
+        trailer: u32,
+        /// The number of entries in the cross-reference table.
+        xref: u32,
+    },
+    /// The offset stored in the cross-reference table for an object doesn't point
+    /// to the start of that object.
+    InvalidOffset {
+        /// The object number of the object.
+        object: u32,
+        /// The offset stored in the cross-reference table.
+        offset: usize,
+    },
+    /// An object that is stored in an object stream according to the cross-reference
+    /// stream couldn't be found in that object stream.
+    InvalidCompressedObject {
+        /// The object number of the object.
+        object: u32,
+        /// The object number of the object stream.
+        stream: u32,
+        /// The index of the object in the object stream.
+        index: usize,
+    },
+    /// An indirect reference points to an object that doesn't exist.
+    DanglingReference {
+        /// The object number of the object that contains the reference, or `None`
+        /// if the reference appears in the trailer.
+        from: Option<u32>,
+        /// The object number the reference points to.
+        to: u32,
+    },
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::MissingHeader => write!(f, "the file doesn't start with a PDF header"),
+            Issue::MissingStartXref => write!(f, "the startxref entry is missing or invalid"),
+            Issue::MalformedXref => write!(f, "the cross-reference table is malformed"),
+            Issue::MalformedTrailer => write!(f, "the trailer is malformed"),
+            Issue::SizeMismatch { trailer, xref } => write!(
+                f,
+                "the trailer has size {trailer}, but the cross-reference table has {xref} entries"
+            ),
+            Issue::InvalidOffset { object, offset } => write!(
+                f,
+                "the offset {offset} of object {object} doesn't point to the object"
+            ),
+            Issue::InvalidCompressedObject {
+                object,
+                stream,
+                index,
+            } => write!(
+                f,
+                "object {object} is not at index {index} of the object stream {stream}"
+            ),
+            Issue::DanglingReference {
+                from: Some(from),
+                to,
+            } => {
+                write!(f, "object {from} references the non-existent object {to}")
+            }
+            Issue::DanglingReference { from: None, to } => {
+                write!(f, "the trailer references the non-existent object {to}")
+            }
+        }
+    }
+}
+
+/// Verify the basic structural integrity of a PDF file written by krilla.
+///
+/// Returns all issues that were found, or `Ok(())` if the file passed all checks.
+/// See the [module-level documentation](crate::verify) for more information.
+pub fn verify(pdf: &[u8]) -> Result<(), Vec<Issue>> {
+    if !pdf.starts_with(b"%PDF-") {
+        return Err(vec![Issue::MissingHeader]);
+    }
+
+    let document = lopdf::Document::load_mem(pdf).map_err(|err| {
+        vec![match err {
+            lopdf::Error::Xref(XrefError::Start) => Issue::MissingStartXref,
+            lopdf::Error::Trailer => Issue::MalformedTrailer,
+            _ => Issue::MalformedXref,
+        }]
+    })?;
+
+    let xref = &document.reference_table;
+    let mut issues = vec![];
+
+    // The loader silently fixes up a wrong size, so we need to compare against
+    // the original entry of the trailer.
+    let xref_size = xref.max_id() + 1;
+    match document.trailer.get(b"Size").and_then(Object::as_i64) {
+        Ok(size) if size != i64::from(xref_size) => issues.push(Issue::SizeMismatch {
+            trailer: u32::try_from(size).unwrap_or(u32::MAX),
+            xref: xref_size,
+        }),
+        Ok(_) => {}
+        Err(_) => issues.push(Issue::MalformedTrailer),
+    }
+
+    for (object, entry) in &xref.entries {
+        let object = *object;
+
+        match *entry {
+            XrefEntry::Normal { offset, generation } => {
+                // The loader skips objects it can't read, but it doesn't check whether
+                // an offset points to the start of the object.
+                let offset = offset as usize;
+                let header = format!("{object} {generation} obj");
+
+                if !pdf
+                    .get(offset..)
+                    .is_some_and(|data| data.starts_with(header.as_bytes()))
+                {
+                    issues.push(Issue::InvalidOffset { object, offset });
+                }
+            }
+            XrefEntry::Compressed { container, index } => {
+                if !document.objects.contains_key(&(object, 0)) {
+                    issues.push(Issue::InvalidCompressedObject {
+                        object,
+                        stream: container,
+                        index: usize::from(index),
+                    });
+                }
+            }
+            XrefEntry::Free | XrefEntry::UnusableFree => {}
+        }
+    }
+
+    let existing = document
+        .objects
+        .keys()
+        .map(|(num, _)| *num)
+        .collect::<BTreeSet<_>>();
+
+    for ((object, _), body) in &document.objects {
+        for to in references(body) {
+            if !existing.contains(&to) {
+                issues.push(Issue::DanglingReference {
+                    from: Some(*object),
+                    to,
+                });
+            }
+        }
+    }
+
+    for (_, value) in document.trailer.iter() {
+        for to in references(value) {
+            if !existing.contains(&to) {
+                issues.push(Issue::DanglingReference { from: None, to });
+            }
+        }
+    }
+
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(issues)
+    }
+}
+
+/// Return the object numbers of all indirect references in an object.
+fn references(object: &Object) -> Vec<u32> {
+    fn collect(object: &Object, references: &mut Vec<u32>) {
+        match object {
+            Object::Reference((num, _)) => references.push(*num),
+            Object::Array(array) => array.iter().for_each(|o| collect(o, references)),
+            Object::Dictionary(dict) => dict.iter().for_each(|(_, o)| collect(o, references)),
+            Object::Stream(stream) => stream.dict.iter().for_each(|(_, o)| collect(o, references)),
+            _ => {}
+        }
+    }
+
+    let mut references = vec![];
+    collect(object, &mut references);
+    references
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metadata::Metadata;
+    use crate::tests::{green_fill, rect_to_path};
+    use crate::verify::{verify, Issue};
+    use crate::{Document, SerializeSettings};
+
+    fn pdf_with(settings: SerializeSettings) -> Vec<u8> {
+        let mut document = Document::new_with(settings);
+        document.set_metadata(Metadata::new().title("A title".to_string()));
+
+        for _ in 0..3 {
+            let mut page = document.start_page();
+            let mut surface = page.surface();
+            surface.fill_path(&rect_to_path(20.0, 20.0, 180.0, 180.0), green_fill(1.0));
+            surface.finish();
+            page.finish();
+        }
+
+        document.finish().unwrap()
+    }
+
+    /// Replace the first occurrence of `from` in a PDF written without object streams.
+    fn corrupted_pdf(from: &str, to: &str) -> Vec<u8> {
+        let mut pdf = pdf_with(SerializeSettings::settings_1());
+        let pos = pdf
+            .windows(from.len())
+            .position(|w| w == from.as_bytes())
+            .unwrap();
+        pdf.splice(pos..pos + from.len(), to.bytes());
+        pdf
+    }
+
+    #[test]
+    fn verify_valid_pdf() {
+        assert_eq!(verify(&pdf_with(SerializeSettings::settings_1())), Ok(()));
+    }
+
+    #[test]
+    fn verify_object_streams() {
+        let pdf = pdf_with(SerializeSettings {
+            use_object_streams: true,
+            ..SerializeSettings::settings_1()
+        });
+
+        assert!(String::from_utf8_lossy(&pdf).contains("/Type /ObjStm"));
+        assert_eq!(verify(&pdf), Ok(()));
+    }
+
+    #[test]
+    fn verify_xref_stream() {
+        let mut pdf = pdf_with(SerializeSettings {
+            use_object_streams: true,
+            ..SerializeSettings::settings_1()
+        });
+
+        assert!(String::from_utf8_lossy(&pdf).contains("/Type /XRef"));
+
+        // The cross-reference stream is the last object in the file, so changing its
+        // dictionary doesn't invalidate any offsets.
+        let root = pdf.windows(6).rposition(|w| w == b"/Root ").unwrap() + 6;
+        let len = pdf[root..].iter().position(|b| *b == b' ').unwrap();
+        pdf.splice(root..root + len, b"9999".iter().copied());
+        let issues = verify(&pdf).unwrap_err();
+
+        assert!(issues.contains(&Issue::DanglingReference {
+            from: None,
+            to: 9999
+        }));
+    }
+
+    #[test]
+    fn verify_linearized() {
+        let pdf = pdf_with(SerializeSettings {
+            linearize: true,
+            ..SerializeSettings::settings_1()
+        });
+
+        assert!(String::from_utf8_lossy(&pdf[..1024]).contains("/Linearized 1"));
+        assert_eq!(verify(&pdf), Ok(()));
+    }
+
+    #[test]
+    fn verify_missing_header() {
+        let pdf = corrupted_pdf("%PDF-", "%XYZ-");
+
+        assert_eq!(verify(&pdf), Err(vec![Issue::MissingHeader]));
+    }
+
+    #[test]
+    fn verify_corrupted_offset() {
+        let pdf = corrupted_pdf("0000000016 00000 n", "0000000017 00000 n");
+        let issues = verify(&pdf).unwrap_err();
+
+        assert!(issues.contains(&Issue::InvalidOffset {
+            object: 1,
+            offset: 17
+        }));
+    }
+
+    #[test]
+    fn verify_dangling_reference() {
+        // Object 0 is always the head of the free list, so it can never be referenced.
+        let pdf = corrupted_pdf(" 1 0 R", " 0 0 R");
+        let issues = verify(&pdf).unwrap_err();
+
+        assert!(issues
+            .iter()
+            .all(|i| matches!(i, Issue::DanglingReference { to: 0, .. })));
+    }
+}